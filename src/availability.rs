@@ -0,0 +1,334 @@
+use crate::events::{Event, EventKind};
+use crate::instance_manager::InstanceService;
+use crate::web_api::ErrorResponse;
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{RwLock, broadcast};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_RANGE: &str = "30d";
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/**
+ * Status transition of an instance: it went up (started) or down (stopped,
+ * failed, or the daemon itself went away).
+ */
+pub struct AvailabilityRecord {
+    pub timestamp: DateTime<Utc>,
+    pub instance_id: Uuid,
+    pub up: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Period during which an instance was down. `end` is unset while it still is.
+ */
+pub struct Incident {
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+    pub duration_secs: i64,
+    pub reason: Option<String>,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Availability of an instance over a time range.
+ *
+ * Only the time since the instance's first recorded transition counts:
+ * `tracked_secs` may be shorter than the range, and `uptime_percent` is
+ * unset when nothing was recorded yet.
+ */
+pub struct AvailabilityReport {
+    pub instance_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub tracked_secs: i64,
+    pub uptime_secs: i64,
+    pub downtime_secs: i64,
+    pub uptime_percent: Option<f64>,
+    pub incidents: Vec<Incident>,
+}
+/**
+ * Parses a range such as `30d`, `12h`, `90m`, `2w` or `3600s`.
+ */
+pub fn parse_range(range: &str) -> Result<chrono::Duration, String> {
+    let range = range.trim();
+    let split = range.char_indices().last().map_or(0, |(index, _)| index);
+    let (value, unit) = range.split_at(split);
+    let value: i64 = value
+        .parse()
+        .ok()
+        .filter(|value| *value > 0)
+        .ok_or_else(|| format!("Invalid range {:?}, expected e.g. 30d", range))?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "Invalid range unit in {:?}, expected s, m, h, d or w",
+                range
+            ));
+        }
+    };
+    value
+        .checked_mul(seconds)
+        .and_then(chrono::Duration::try_seconds)
+        .ok_or_else(|| format!("Range {:?} is too large", range))
+}
+/**
+ * Computes the availability of an instance between `from` and `to` from its
+ * transitions, ordered by time.
+ */
+pub fn compute_report(
+    instance_id: Uuid,
+    records: &[AvailabilityRecord],
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> AvailabilityReport {
+    let mut segments: Vec<&AvailabilityRecord> = Vec::new();
+    for record in records
+        .iter()
+        .filter(|record| record.instance_id == instance_id)
+    {
+        if segments.last().is_none_or(|last| last.up != record.up) {
+            segments.push(record);
+        }
+    }
+    let mut report = AvailabilityReport {
+        instance_id,
+        from,
+        to,
+        tracked_secs: 0,
+        uptime_secs: 0,
+        downtime_secs: 0,
+        uptime_percent: None,
+        incidents: Vec::new(),
+    };
+    for (index, segment) in segments.iter().enumerate() {
+        let next = segments.get(index + 1).map(|next| next.timestamp);
+        let start = segment.timestamp.max(from);
+        let end = next.unwrap_or(to).min(to);
+        if end <= start {
+            continue;
+        }
+        let seconds = (end - start).num_seconds();
+        report.tracked_secs += seconds;
+        if segment.up {
+            report.uptime_secs += seconds;
+        } else {
+            report.downtime_secs += seconds;
+            report.incidents.push(Incident {
+                start: segment.timestamp,
+                end: next,
+                duration_secs: seconds,
+                reason: segment.reason.clone(),
+            });
+        }
+    }
+    if report.tracked_secs > 0 {
+        report.uptime_percent =
+            Some(report.uptime_secs as f64 * 100.0 / report.tracked_secs as f64);
+    }
+    report
+}
+/**
+ * Persistent log of instance status transitions, fed by lifecycle events.
+ *
+ * Transitions are appended to a JSON-lines file. A heartbeat file is
+ * refreshed every minute so that, after the daemon was stopped or crashed,
+ * the instances it left running are recorded as down from its last
+ * heartbeat.
+ */
+pub struct AvailabilityTracker {
+    path: PathBuf,
+    records: RwLock<Vec<AvailabilityRecord>>,
+    instance_service: Arc<InstanceService>,
+}
+impl AvailabilityTracker {
+    pub async fn load(path: PathBuf, instance_service: Arc<InstanceService>) -> Result<Self> {
+        let records = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect::<Result<Vec<AvailabilityRecord>, _>>()
+                .with_context(|| format!("Invalid availability log {:?}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        info!(
+            "Loaded {} availability records from {:?}",
+            records.len(),
+            path
+        );
+        let tracker = Self {
+            path,
+            records: RwLock::new(records),
+            instance_service,
+        };
+        tracker.close_dangling().await?;
+        Ok(tracker)
+    }
+    fn heartbeat_path(&self) -> PathBuf {
+        self.path.with_extension("heartbeat")
+    }
+    async fn close_dangling(&self) -> Result<()> {
+        let heartbeat = tokio::fs::read_to_string(self.heartbeat_path())
+            .await
+            .ok()
+            .and_then(|content| DateTime::parse_from_rfc3339(content.trim()).ok())
+            .map(|timestamp| timestamp.with_timezone(&Utc));
+        let mut last_state: std::collections::HashMap<Uuid, &AvailabilityRecord> =
+            std::collections::HashMap::new();
+        let records = self.records.read().await.clone();
+        for record in &records {
+            last_state.insert(record.instance_id, record);
+        }
+        for record in last_state.values().filter(|record| record.up) {
+            let timestamp = heartbeat
+                .filter(|heartbeat| *heartbeat > record.timestamp)
+                .unwrap_or(record.timestamp);
+            self.append(AvailabilityRecord {
+                timestamp,
+                instance_id: record.instance_id,
+                up: false,
+                reason: Some("daemon stopped".to_string()),
+            })
+            .await?;
+        }
+        Ok(())
+    }
+    async fn append(&self, record: AvailabilityRecord) -> Result<()> {
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        let mut records = self.records.write().await;
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("Failed to open {:?}", self.path))?;
+        file.write_all(line.as_bytes())
+            .await
+            .with_context(|| format!("Failed to write {:?}", self.path))?;
+        records.push(record);
+        Ok(())
+    }
+    pub async fn record(&self, instance_id: Uuid, up: bool, reason: Option<String>) -> Result<()> {
+        debug!(
+            "Instance {} is {}",
+            instance_id,
+            if up { "up" } else { "down" }
+        );
+        self.append(AvailabilityRecord {
+            timestamp: Utc::now(),
+            instance_id,
+            up,
+            reason,
+        })
+        .await
+    }
+    /**
+     * Records transitions from the lifecycle events received on `events` and
+     * refreshes the heartbeat.
+     */
+    pub fn start(self: Arc<Self>, mut events: broadcast::Receiver<Event>) {
+        let tracker = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let (instance_id, up, reason) = match events.recv().await {
+                    Ok(Event { kind, .. }) => match kind {
+                        EventKind::InstanceStarted { instance_id, .. } => (instance_id, true, None),
+                        EventKind::InstanceStopped { instance_id, .. } => {
+                            (instance_id, false, Some("stopped".to_string()))
+                        }
+                        EventKind::InstanceFailed { instance_id, error } => {
+                            (instance_id, false, Some(format!("failed: {}", error)))
+                        }
+                        EventKind::InstanceStatusChanged { .. }
+                        | EventKind::ConfigImported { .. } => {
+                            continue;
+                        }
+                    },
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!(
+                            "Availability tracker fell behind, {} events dropped",
+                            missed
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if let Err(e) = tracker.record(instance_id, up, reason).await {
+                    error!("Failed to record availability of {}: {}", instance_id, e);
+                }
+            }
+        });
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) =
+                    tokio::fs::write(self.heartbeat_path(), Utc::now().to_rfc3339()).await
+                {
+                    error!("Failed to write availability heartbeat: {}", e);
+                }
+            }
+        });
+    }
+    pub fn instance_service(&self) -> &Arc<InstanceService> {
+        &self.instance_service
+    }
+    pub async fn report(
+        &self,
+        instance_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> AvailabilityReport {
+        compute_report(instance_id, &self.records.read().await, from, to)
+    }
+}
+#[derive(Debug, Deserialize)]
+pub struct AvailabilityQuery {
+    pub range: Option<String>,
+}
+pub fn create_routes(tracker: Arc<AvailabilityTracker>) -> Router {
+    Router::new()
+        .route("/api/instances/:id/availability", get(get_availability))
+        .with_state(tracker)
+}
+async fn get_availability(
+    State(tracker): State<Arc<AvailabilityTracker>>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<AvailabilityQuery>,
+) -> Result<Json<AvailabilityReport>, (StatusCode, Json<ErrorResponse>)> {
+    debug!("Getting availability for instance: {}", id);
+    if tracker.instance_service.get_instance(id).await.is_none() {
+        let error_response = ErrorResponse::new(
+            "NOT_FOUND".to_string(),
+            format!("Instance {} not found", id),
+        );
+        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    }
+    let range = parse_range(query.range.as_deref().unwrap_or(DEFAULT_RANGE)).map_err(|e| {
+        let error_response = ErrorResponse::new("INVALID_RANGE".to_string(), e);
+        (StatusCode::BAD_REQUEST, Json(error_response))
+    })?;
+    let to = Utc::now();
+    Ok(Json(tracker.report(id, to - range, to).await))
+}
@@ -0,0 +1,57 @@
+use crate::backup::BackupSchedule;
+use crate::instance_manager::InstanceService;
+use anyhow::Result;
+use chrono::Utc;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/**
+ * Takes the backups of the `[backup]` schedule.
+ *
+ * The schedule is read again at every check, so changes made through the
+ * API apply without a restart. A run missed while the daemon was down is
+ * not caught up.
+ */
+pub struct BackupScheduler {
+    instance_service: Arc<InstanceService>,
+}
+impl BackupScheduler {
+    pub fn new(instance_service: Arc<InstanceService>) -> Self {
+        Self { instance_service }
+    }
+    pub fn start(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut last_check = Utc::now();
+            loop {
+                tokio::time::sleep(CHECK_INTERVAL).await;
+                let now = Utc::now();
+                let schedule = self.instance_service.backup_schedule().await;
+                if schedule.next_run(last_check).is_some_and(|due| due <= now)
+                    && let Err(e) = self.run(&schedule).await
+                {
+                    error!("Scheduled backup failed: {:#}", e);
+                }
+                last_check = now;
+            }
+        });
+    }
+    /**
+     * Takes a backup and deletes those beyond the retention of the schedule.
+     */
+    pub async fn run(&self, schedule: &BackupSchedule) -> Result<PathBuf> {
+        let backup_path = self.instance_service.create_backup().await?;
+        if schedule.keep > 0 {
+            let pruned = self.instance_service.prune_backups(schedule.keep).await?;
+            if !pruned.is_empty() {
+                info!(
+                    "Deleted {} backups beyond the retention of {}",
+                    pruned.len(),
+                    schedule.keep
+                );
+            }
+        }
+        Ok(backup_path)
+    }
+}
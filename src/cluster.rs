@@ -0,0 +1,273 @@
+use crate::instance_manager::InstanceService;
+use anyhow::Result;
+use axum::{
+    Router,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, header},
+    response::Json,
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+const NODE_HEADER: &str = "x-voidproxy-node";
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+/**
+ * Role of this node in a replicated VoidProxy cluster.
+ *
+ * A leader serves its instance configuration to followers, which poll it and
+ * apply every new version locally.
+ */
+pub enum ClusterRole {
+    Standalone,
+    Leader,
+    Follower,
+}
+#[derive(Debug, Clone)]
+/**
+ * Cluster settings taken from the command line.
+ */
+pub struct ClusterConfig {
+    pub role: ClusterRole,
+    pub leader_url: Option<String>,
+    pub token: Option<String>,
+    pub sync_interval: Duration,
+}
+impl ClusterConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.role == ClusterRole::Standalone {
+            return Ok(());
+        }
+        if self.token.as_deref().is_none_or(str::is_empty) {
+            return Err(anyhow::anyhow!("Cluster mode requires a shared token"));
+        }
+        if self.role == ClusterRole::Follower && self.leader_url.is_none() {
+            return Err(anyhow::anyhow!("Follower mode requires a leader URL"));
+        }
+        if self.sync_interval.is_zero() {
+            return Err(anyhow::anyhow!("Cluster sync interval must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Configuration snapshot exchanged between leader and followers.
+ */
+pub struct ReplicatedConfig {
+    pub version: String,
+    pub config: String,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerStatus {
+    pub node_id: String,
+    pub last_seen: DateTime<Utc>,
+    pub config_version: Option<String>,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Replication state reported by `/api/cluster/status`.
+ */
+pub struct ClusterStatus {
+    pub node_id: Uuid,
+    pub role: ClusterRole,
+    pub leader_url: Option<String>,
+    pub config_version: String,
+    pub applied_version: Option<String>,
+    pub last_sync: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub peers: Vec<PeerStatus>,
+}
+#[derive(Default)]
+struct SyncState {
+    applied_version: Option<String>,
+    last_sync: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+    peers: HashMap<String, PeerStatus>,
+}
+/**
+ * Keeps instance configuration identical across cluster nodes.
+ *
+ * Followers poll the leader over HTTP with a shared bearer token and apply
+ * every new configuration version through the regular reload path, so only
+ * instances that actually changed are restarted.
+ */
+pub struct ClusterManager {
+    node_id: Uuid,
+    config: ClusterConfig,
+    instance_service: Arc<InstanceService>,
+    state: RwLock<SyncState>,
+}
+impl ClusterManager {
+    pub fn new(config: ClusterConfig, instance_service: Arc<InstanceService>) -> Self {
+        Self {
+            node_id: Uuid::new_v4(),
+            config,
+            instance_service,
+            state: RwLock::new(SyncState::default()),
+        }
+    }
+    pub fn role(&self) -> ClusterRole {
+        self.config.role
+    }
+    pub fn start(self: &Arc<Self>) {
+        if self.config.role != ClusterRole::Follower {
+            return;
+        }
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(manager.config.sync_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = manager.sync_once().await {
+                    warn!("Cluster sync with leader failed: {}", e);
+                    manager.state.write().await.last_error = Some(e.to_string());
+                }
+            }
+        });
+    }
+    pub async fn sync_once(&self) -> Result<bool> {
+        let leader_url = self
+            .config
+            .leader_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("No leader URL configured"))?;
+        let token = self.config.token.clone().unwrap_or_default();
+        let applied_version = self.state.read().await.applied_version.clone();
+        let mut headers = vec![
+            (header::AUTHORIZATION.as_str(), format!("Bearer {}", token)),
+            (NODE_HEADER, self.node_id.to_string()),
+        ];
+        if let Some(ref version) = applied_version {
+            headers.push((header::IF_NONE_MATCH.as_str(), version.clone()));
+        }
+        let url = format!("{}/api/cluster/config", leader_url.trim_end_matches('/'));
+        let response = crate::http_client::send(Method::GET, &url, &headers, None).await?;
+        let mut state = self.state.write().await;
+        if response.status == StatusCode::NOT_MODIFIED.as_u16() {
+            state.last_sync = Some(Utc::now());
+            state.last_error = None;
+            return Ok(false);
+        }
+        if !response.is_success() {
+            return Err(anyhow::anyhow!(
+                "Leader responded with status {}",
+                response.status
+            ));
+        }
+        let replicated: ReplicatedConfig = response.json()?;
+        if applied_version.as_deref() == Some(replicated.version.as_str()) {
+            state.last_sync = Some(Utc::now());
+            state.last_error = None;
+            return Ok(false);
+        }
+        drop(state);
+        let report = self
+            .instance_service
+            .apply_replicated_config(&replicated.config)
+            .await?;
+        info!(
+            "Applied cluster configuration version {} ({} added, {} removed, {} updated)",
+            replicated.version, report.added, report.removed, report.updated
+        );
+        let mut state = self.state.write().await;
+        state.applied_version = Some(replicated.version);
+        state.last_sync = Some(Utc::now());
+        state.last_error = None;
+        Ok(true)
+    }
+    pub async fn status(&self) -> ClusterStatus {
+        let state = self.state.read().await;
+        let mut peers: Vec<PeerStatus> = state.peers.values().cloned().collect();
+        peers.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        ClusterStatus {
+            node_id: self.node_id,
+            role: self.config.role,
+            leader_url: self.config.leader_url.clone(),
+            config_version: self.instance_service.config_version().await,
+            applied_version: state.applied_version.clone(),
+            last_sync: state.last_sync,
+            last_error: state.last_error.clone(),
+            peers,
+        }
+    }
+    fn is_authorized(&self, headers: &HeaderMap) -> bool {
+        let Some(expected) = self.config.token.as_deref() else {
+            return false;
+        };
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .unwrap_or_default();
+        constant_time_eq(provided.as_bytes(), expected.as_bytes())
+    }
+    async fn record_peer(&self, node_id: String, config_version: Option<String>) {
+        let mut state = self.state.write().await;
+        state.peers.insert(
+            node_id.clone(),
+            PeerStatus {
+                node_id,
+                last_seen: Utc::now(),
+                config_version,
+            },
+        );
+    }
+}
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+pub fn create_routes(cluster: Arc<ClusterManager>) -> Router {
+    Router::new()
+        .route("/api/cluster/status", get(cluster_status))
+        .route("/api/cluster/config", get(cluster_config))
+        .with_state(cluster)
+}
+async fn cluster_status(State(cluster): State<Arc<ClusterManager>>) -> Json<ClusterStatus> {
+    debug!("Getting cluster status");
+    Json(cluster.status().await)
+}
+async fn cluster_config(
+    State(cluster): State<Arc<ClusterManager>>,
+    headers: HeaderMap,
+) -> Result<Json<ReplicatedConfig>, StatusCode> {
+    if cluster.role() != ClusterRole::Leader {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !cluster.is_authorized(&headers) {
+        warn!("Rejected unauthorized cluster config request");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    let version = cluster.instance_service.config_version().await;
+    let known_version = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if let Some(node_id) = headers
+        .get(NODE_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        cluster
+            .record_peer(node_id.to_string(), known_version.clone())
+            .await;
+    }
+    if known_version.as_deref() == Some(version.as_str()) {
+        return Err(StatusCode::NOT_MODIFIED);
+    }
+    match cluster.instance_service.export_config().await {
+        Ok(config) => Ok(Json(ReplicatedConfig { version, config })),
+        Err(e) => {
+            error!("Failed to export configuration for cluster peer: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
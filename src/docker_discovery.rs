@@ -0,0 +1,248 @@
+use crate::config::{LogLevel, Protocol};
+use crate::instance::CreateInstanceRequest;
+use crate::instance_manager::InstanceService;
+use anyhow::Result;
+use axum::http::Method;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+const LABEL_PREFIX: &str = "voidproxy.";
+const INSTANCE_PREFIX: &str = "docker/";
+const CONTAINERS_PATH: &str =
+    "/containers/json?filters=%7B%22label%22%3A%5B%22voidproxy.listen_port%22%5D%7D";
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+/**
+ * Subset of a container description returned by the Docker Engine API.
+ */
+pub struct DockerContainer {
+    pub id: String,
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    #[serde(default)]
+    pub network_settings: Option<DockerNetworkSettings>,
+}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DockerNetworkSettings {
+    #[serde(default)]
+    pub networks: HashMap<String, DockerNetwork>,
+}
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DockerNetwork {
+    #[serde(rename = "IPAddress", default)]
+    pub ip_address: String,
+}
+impl DockerContainer {
+    pub fn name(&self) -> String {
+        self.names
+            .first()
+            .map(|name| name.trim_start_matches('/').to_string())
+            .unwrap_or_else(|| self.id.chars().take(12).collect())
+    }
+    pub fn instance_name(&self) -> String {
+        format!("{}{}", INSTANCE_PREFIX, self.name())
+    }
+    fn label(&self, key: &str) -> Option<&str> {
+        self.labels
+            .get(&format!("{}{}", LABEL_PREFIX, key))
+            .map(|value| value.trim())
+    }
+    fn container_ip(&self) -> Option<IpAddr> {
+        self.network_settings
+            .as_ref()?
+            .networks
+            .values()
+            .find_map(|network| network.ip_address.parse().ok())
+    }
+    /**
+     * Builds the instance request described by the container's `voidproxy.*` labels.
+     *
+     * `listen_port` and `target_port` are required; `listen_ip` defaults to
+     * all interfaces, `protocol` to TCP and `target_ip` to the container address.
+     */
+    pub fn to_instance_request(&self) -> Result<CreateInstanceRequest, String> {
+        let listen_port = self
+            .label("listen_port")
+            .ok_or("missing voidproxy.listen_port label")?
+            .parse()
+            .map_err(|e| format!("Invalid voidproxy.listen_port: {}", e))?;
+        let dst_port = self
+            .label("target_port")
+            .ok_or("missing voidproxy.target_port label")?
+            .parse()
+            .map_err(|e| format!("Invalid voidproxy.target_port: {}", e))?;
+        let listen_ip = match self.label("listen_ip") {
+            Some(ip) => ip
+                .parse()
+                .map_err(|e| format!("Invalid voidproxy.listen_ip: {}", e))?,
+            None => IpAddr::from([0, 0, 0, 0]),
+        };
+        let dst_ip = match self.label("target_ip") {
+            Some(ip) => ip
+                .parse()
+                .map_err(|e| format!("Invalid voidproxy.target_ip: {}", e))?,
+            None => self
+                .container_ip()
+                .ok_or("container has no IP address and no voidproxy.target_ip label")?,
+        };
+        let protocol = match self.label("protocol").unwrap_or("tcp") {
+            "tcp" => Protocol::Tcp,
+            "udp" => Protocol::Udp,
+            "both" => Protocol::Both,
+            other => return Err(format!("Invalid voidproxy.protocol: {}", other)),
+        };
+        Ok(CreateInstanceRequest {
+            name: self.instance_name(),
+            listen_ip,
+            listen_port,
+            dst_ip,
+            dst_port,
+            protocol,
+            auto_start: true,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        })
+    }
+}
+/**
+ * Creates and removes instances following the lifecycle of labelled containers.
+ *
+ * The Docker socket is polled for running containers carrying `voidproxy.*`
+ * labels. Managed instances are named `docker/<container>` so they are
+ * adopted again after a restart instead of being duplicated.
+ */
+pub struct DockerDiscovery {
+    socket_path: PathBuf,
+    poll_interval: Duration,
+    instance_service: Arc<InstanceService>,
+}
+impl DockerDiscovery {
+    pub fn new(
+        socket_path: PathBuf,
+        poll_interval: Duration,
+        instance_service: Arc<InstanceService>,
+    ) -> Self {
+        Self {
+            socket_path,
+            poll_interval,
+            instance_service,
+        }
+    }
+    pub fn start(self: Arc<Self>) {
+        tokio::spawn(async move {
+            info!("Docker discovery watching {:?}", self.socket_path);
+            let mut interval = tokio::time::interval(self.poll_interval);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.sync_once().await {
+                    warn!("Docker discovery failed: {}", e);
+                }
+            }
+        });
+    }
+    async fn list_containers(&self) -> Result<Vec<DockerContainer>> {
+        #[cfg(unix)]
+        {
+            let response = crate::http_client::send_unix(
+                &self.socket_path,
+                Method::GET,
+                CONTAINERS_PATH,
+                &[],
+                None,
+            )
+            .await?;
+            if !response.is_success() {
+                return Err(anyhow::anyhow!(
+                    "Docker API responded with status {}",
+                    response.status
+                ));
+            }
+            response.json()
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = Method::GET;
+            Err(anyhow::anyhow!("Docker discovery requires a unix socket"))
+        }
+    }
+    pub async fn sync_once(&self) -> Result<()> {
+        let containers = self.list_containers().await?;
+        let mut desired = HashMap::new();
+        for container in containers {
+            match container.to_instance_request() {
+                Ok(request) => {
+                    desired.insert(request.name.clone(), request);
+                }
+                Err(e) => warn!("Ignoring container {}: {}", container.name(), e),
+            }
+        }
+        let managed: Vec<_> = self
+            .instance_service
+            .get_instances()
+            .await
+            .into_iter()
+            .filter(|instance| instance.name.starts_with(INSTANCE_PREFIX))
+            .collect();
+        for instance in &managed {
+            let keep = desired
+                .get(&instance.name)
+                .is_some_and(|request| request.to_config() == instance.config);
+            if keep {
+                desired.remove(&instance.name);
+                continue;
+            }
+            info!("Docker discovery removing instance {}", instance.name);
+            if let Err(e) = self.instance_service.delete_instance(instance.id).await {
+                error!("Failed to remove instance {}: {}", instance.name, e);
+            } else if let Err(e) = self
+                .instance_service
+                .purge_deleted_instances(Some(instance.id))
+                .await
+            {
+                error!("Failed to purge instance {}: {}", instance.name, e);
+            }
+        }
+        for (name, request) in desired {
+            info!("Docker discovery creating instance {}", name);
+            if let Err(e) = self.instance_service.create_instance(request).await {
+                error!("Failed to create instance {}: {}", name, e);
+            }
+        }
+        debug!("Docker discovery sync complete");
+        Ok(())
+    }
+}
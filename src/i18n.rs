@@ -0,0 +1,99 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+/**
+ * Code, native name and JSON source of every bundled catalog. The first one
+ * is the default locale.
+ */
+const SOURCES: [(&str, &str, &str); 2] = [
+    ("en", "English", include_str!("../locales/en.json")),
+    ("fr", "Français", include_str!("../locales/fr.json")),
+];
+static CATALOGS: OnceLock<Vec<Catalog>> = OnceLock::new();
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Translations of the web UI for one locale, keyed by message ID. Messages
+ * may contain `{name}` placeholders filled in by the UI.
+ */
+pub struct Catalog {
+    pub locale: &'static str,
+    pub name: &'static str,
+    pub messages: BTreeMap<String, String>,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct LocaleInfo {
+    pub locale: &'static str,
+    pub name: &'static str,
+}
+/**
+ * All bundled catalogs, parsed on first use. Keys missing from a catalog
+ * are taken from the default one, so every catalog has the full set.
+ */
+pub fn catalogs() -> &'static [Catalog] {
+    CATALOGS.get_or_init(|| {
+        let parse = |(locale, name, source): (&'static str, &'static str, &str)| {
+            let messages: BTreeMap<String, String> = serde_json::from_str(source)
+                .unwrap_or_else(|e| {
+                    tracing::error!("Invalid {} locale catalog: {}", locale, e);
+                    BTreeMap::new()
+                });
+            Catalog {
+                locale,
+                name,
+                messages,
+            }
+        };
+        let mut catalogs: Vec<Catalog> = SOURCES.into_iter().map(parse).collect();
+        let defaults = catalogs[0].messages.clone();
+        for catalog in catalogs.iter_mut().skip(1) {
+            for (key, message) in &defaults {
+                catalog
+                    .messages
+                    .entry(key.clone())
+                    .or_insert_with(|| message.clone());
+            }
+        }
+        catalogs
+    })
+}
+pub fn available() -> Vec<LocaleInfo> {
+    catalogs()
+        .iter()
+        .map(|catalog| LocaleInfo {
+            locale: catalog.locale,
+            name: catalog.name,
+        })
+        .collect()
+}
+/**
+ * Catalog for a language tag such as `fr` or `fr-CA`, matched on the
+ * primary language subtag.
+ */
+pub fn catalog(tag: &str) -> Option<&'static Catalog> {
+    let language = tag.split(['-', '_']).next().unwrap_or_default();
+    catalogs()
+        .iter()
+        .find(|catalog| catalog.locale.eq_ignore_ascii_case(language))
+}
+/**
+ * Best catalog for an `Accept-Language` header, in order of preference,
+ * falling back to the default locale.
+ */
+pub fn negotiate(accept_language: &str) -> &'static Catalog {
+    let mut ranges: Vec<(f32, &str)> = accept_language
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            (quality > 0.0 && !tag.is_empty()).then_some((quality, tag))
+        })
+        .collect();
+    ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+    ranges
+        .into_iter()
+        .find_map(|(_, tag)| catalog(tag))
+        .unwrap_or(&catalogs()[0])
+}
@@ -1,11 +1,22 @@
-pub mod buffer_pool;
-pub mod config;
-pub mod instance;
-pub mod instance_manager;
-pub mod ip_cache;
-pub mod metrics;
-pub mod storage;
-pub mod tcp_proxy;
-pub mod udp_proxy;
+pub use voidproxy_core::{
+    backend, backpressure, backup, benchmark, blocklist, buffer_pool, builder, config, config_diff,
+    connection_registry, consul, events, fd_limit, footprint, geoip, global_filter, handoff,
+    happy_eyeballs, host_acl, hostname, http_client, icmp, instance, instance_manager, ip_cache,
+    kubernetes, listener, log_limit, metrics, middleware, mirror, naming, preflight, replay, srv,
+    storage, tcp_proxy, test_server, tls, traffic_report, tunnel, udp_batch, udp_proxy,
+    wasm_filter,
+};
+pub mod availability;
+pub mod backup_scheduler;
+pub mod cluster;
+pub mod docker_discovery;
+pub mod i18n;
+pub mod mqtt;
+pub mod prometheus;
+pub mod public_status;
+pub mod snmp;
+pub mod syslog;
+pub mod table_import;
 pub mod web_api;
 pub mod web_ui;
+pub mod webhook;
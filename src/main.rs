@@ -1,23 +1,19 @@
-mod buffer_pool;
-mod config;
-mod instance;
-mod instance_manager;
-mod ip_cache;
-mod metrics;
-mod storage;
-mod tcp_proxy;
-mod udp_proxy;
-mod web_api;
-mod web_ui;
+use void_proxy::{
+    availability, backup_scheduler, blocklist, cluster, docker_discovery, fd_limit, footprint,
+    geoip, handoff, instance_manager, metrics, mqtt, prometheus, public_status, snmp, storage,
+    syslog, test_server, traffic_report, web_api, web_ui, webhook,
+};
 use anyhow::Result;
-use clap::Parser;
+use clap::{Args as ClapArgs, Parser, Subcommand};
 use instance_manager::InstanceService;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::signal;
 use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use tracing_subscriber::filter::{FilterExt, LevelFilter, filter_fn};
+use tracing_subscriber::prelude::*;
 use web_api::create_routes as create_api_routes;
 use web_ui::create_routes;
 #[derive(Parser, Debug)]
@@ -27,6 +23,8 @@ use web_ui::create_routes;
     version
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     #[arg(long, default_value = "127.0.0.1", help = "Web UI listen IP")]
     web_listen_ip: String,
     #[arg(long, default_value = "8080", help = "Web UI listen port")]
@@ -39,31 +37,293 @@ struct Args {
         help = "Configuration file path"
     )]
     config_path: std::path::PathBuf,
+    #[arg(
+        long,
+        help = "Unix socket used to hand listeners over during binary upgrades"
+    )]
+    upgrade_socket: Option<std::path::PathBuf>,
+    #[arg(
+        long,
+        default_value = "30",
+        help = "Seconds to wait for connections to drain after an upgrade"
+    )]
+    drain_timeout_secs: u64,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "standalone",
+        help = "Cluster role of this node"
+    )]
+    cluster_role: cluster::ClusterRole,
+    #[arg(long, help = "Leader base URL followed by cluster followers")]
+    cluster_leader: Option<String>,
+    #[arg(long, help = "Shared token authenticating cluster peers")]
+    cluster_token: Option<String>,
+    #[arg(
+        long,
+        default_value = "5",
+        help = "Seconds between follower configuration syncs"
+    )]
+    cluster_sync_secs: u64,
+    #[arg(
+        long,
+        help = "Docker socket to watch for containers with voidproxy.* labels"
+    )]
+    docker_socket: Option<std::path::PathBuf>,
+    #[arg(
+        long,
+        default_value = "5",
+        help = "Seconds between Docker discovery polls"
+    )]
+    docker_poll_secs: u64,
+    #[arg(
+        long,
+        default_value = "webhooks.json",
+        help = "File storing registered webhooks"
+    )]
+    webhooks_path: std::path::PathBuf,
+    #[arg(long, help = "MQTT broker (host:port) to publish stats and events to")]
+    mqtt_broker: Option<String>,
+    #[arg(long, help = "MQTT client identifier (random when unset)")]
+    mqtt_client_id: Option<String>,
+    #[arg(long, help = "MQTT username")]
+    mqtt_username: Option<String>,
+    #[arg(long, help = "MQTT password")]
+    mqtt_password: Option<String>,
+    #[arg(long, default_value = "voidproxy", help = "MQTT topic prefix")]
+    mqtt_topic_prefix: String,
+    #[arg(
+        long,
+        default_value = "10",
+        help = "Seconds between MQTT stats publications"
+    )]
+    mqtt_stats_secs: u64,
+    #[arg(long, help = "Address for the SNMPv2c agent to listen on, e.g. 0.0.0.0:161")]
+    snmp_listen: Option<SocketAddr>,
+    #[arg(long, default_value = "public", help = "SNMP community string")]
+    snmp_community: String,
+    #[arg(long, help = "Serve the read-only /status page and /api/public/status on the web port")]
+    public_status: bool,
+    #[arg(
+        long,
+        help = "Separate address serving only the public status page, e.g. 0.0.0.0:8090"
+    )]
+    public_status_listen: Option<SocketAddr>,
+    #[arg(long, help = "Configuration profile whose destination overrides are applied")]
+    profile: Option<String>,
+    #[arg(long, help = "Bearer token granting the admin role for forced changes to locked instances")]
+    admin_token: Option<String>,
+    #[arg(
+        long,
+        default_value = "7",
+        help = "Days deleted instances stay in the trash before being purged"
+    )]
+    trash_retention_days: u64,
+    #[arg(
+        long,
+        default_value = "60",
+        help = "Seconds between saves of lifetime traffic counters (0 disables)"
+    )]
+    stats_persist_secs: u64,
+    #[arg(
+        long,
+        default_value = "availability.jsonl",
+        help = "File logging instance status transitions for availability reports"
+    )]
+    availability_path: std::path::PathBuf,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "0.1,0.5,1,5,10,30,60,300,900,3600",
+        help = "Upper bounds in seconds of the connection duration histograms"
+    )]
+    duration_buckets: Vec<f64>,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "Instance tags exported as tag_<name> labels on /metrics, e.g. env,team"
+    )]
+    metrics_tag_labels: Vec<String>,
+    #[arg(
+        long,
+        help = "Run on a single-threaded runtime with reduced pools and queues, for small devices"
+    )]
+    single_thread: bool,
+    #[arg(
+        long,
+        default_value_t = fd_limit::DEFAULT_TARGET,
+        help = "Open file limit to raise RLIMIT_NOFILE to at startup, up to the hard limit (0 keeps it)"
+    )]
+    nofile_limit: u64,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "URL of a plain-text IP blocklist denied on all instances (repeatable)"
+    )]
+    blocklist_feed: Vec<String>,
+    #[arg(
+        long,
+        default_value = "3600",
+        help = "Seconds between downloads of the blocklist feeds"
+    )]
+    blocklist_refresh_secs: u64,
+    #[arg(
+        long,
+        help = "CSV file mapping IP ranges to countries, for traffic reports per country"
+    )]
+    geoip_db: Option<std::path::PathBuf>,
+    #[arg(
+        long,
+        default_value = "3600",
+        help = "Seconds covered by each traffic report period"
+    )]
+    traffic_report_secs: u64,
+    #[arg(
+        long,
+        help = "Syslog collector to send logs to, e.g. udp://10.0.0.1:514, tcp://host:6514 or unix:///dev/log"
+    )]
+    syslog: Option<String>,
+    #[arg(long, default_value = "daemon", help = "Syslog facility, e.g. daemon or local0")]
+    syslog_facility: String,
+    #[arg(long, help = "Hostname reported in syslog messages (local hostname when unset)")]
+    syslog_hostname: Option<String>,
+    #[arg(long, help = "Also send an access log line per closed connection to syslog")]
+    syslog_access_log: bool,
+}
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(about = "Run echo/sink backends for testing instances end-to-end")]
+    Testserver(TestServerArgs),
+}
+#[derive(ClapArgs, Debug)]
+struct TestServerArgs {
+    #[arg(long, default_value = "127.0.0.1", help = "IP the test servers listen on")]
+    listen_ip: std::net::IpAddr,
+    #[arg(long, value_name = "PORT", help = "Port of a TCP server echoing everything back")]
+    tcp_echo: Vec<u16>,
+    #[arg(long, value_name = "PORT", help = "Port of a TCP server discarding everything")]
+    tcp_sink: Vec<u16>,
+    #[arg(long, value_name = "PORT", help = "Port of a UDP server echoing every datagram")]
+    udp_echo: Vec<u16>,
+    #[arg(long, value_name = "PORT", help = "Port of a UDP server discarding every datagram")]
+    udp_sink: Vec<u16>,
 }
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> Result<()> {
     let args = Args::parse();
-    tracing_subscriber::fmt()
-        .with_max_level(if args.verbose {
-            tracing::Level::DEBUG
-        } else {
-            tracing::Level::INFO
-        })
+    let runtime = if args.single_thread {
+        footprint::Footprint::Compact.install()?;
+        tokio::runtime::Builder::new_current_thread()
+    } else {
+        tokio::runtime::Builder::new_multi_thread()
+    }
+    .enable_all()
+    .build()?;
+    runtime.block_on(run(args))
+}
+async fn run(args: Args) -> Result<()> {
+    let max_level = if args.verbose {
+        LevelFilter::DEBUG
+    } else {
+        LevelFilter::INFO
+    };
+    let syslog_layer = match args.syslog {
+        Some(ref target) => {
+            let (layer, sink) = syslog::SyslogLayer::new(syslog::SyslogConfig {
+                transport: syslog::SyslogTransport::parse(target)?,
+                facility: syslog::facility_code(&args.syslog_facility)?,
+                hostname: args
+                    .syslog_hostname
+                    .clone()
+                    .unwrap_or_else(syslog::local_hostname),
+                access_log: args.syslog_access_log,
+            });
+            sink.start();
+            Some(layer.with_filter(max_level))
+        }
+        None => None,
+    };
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer().with_filter(
+                max_level.and(filter_fn(|metadata| {
+                    metadata.target() != metrics::ACCESS_LOG_TARGET
+                })),
+            ),
+        )
+        .with(syslog_layer)
         .init();
+    if let Some(Command::Testserver(test_args)) = args.command {
+        return run_test_servers(test_args).await;
+    }
     info!("Starting VoidProxy with persistent configuration");
     info!(
         "Web UI: http://{}:{}",
         args.web_listen_ip, args.web_listen_port
     );
     info!("Config: {:?}", args.config_path);
+    if args.single_thread {
+        info!("Running single-threaded with a compact footprint");
+    }
+    if args.nofile_limit > 0 {
+        match fd_limit::raise_limit(args.nofile_limit) {
+            Ok(limit) if limit < args.nofile_limit => warn!(
+                "Open file limit is {}, below the requested {}; raise the hard limit to proxy more connections",
+                limit, args.nofile_limit
+            ),
+            Ok(limit) => info!("Open file limit: {}", limit),
+            Err(e) => warn!("{:#}", e),
+        }
+    } else if let Some(limit) = fd_limit::current_limit() {
+        info!("Open file limit: {}", limit);
+    }
+    #[cfg(unix)]
+    if let Some(ref upgrade_socket) = args.upgrade_socket {
+        handoff::inherit_from(upgrade_socket)?;
+    }
+    let cluster_config = cluster::ClusterConfig {
+        role: args.cluster_role,
+        leader_url: args.cluster_leader.clone(),
+        token: args.cluster_token.clone(),
+        sync_interval: std::time::Duration::from_secs(args.cluster_sync_secs),
+    };
+    cluster_config.validate()?;
     let storage_manager = Arc::new(storage::StorageManager::new(args.config_path.clone()));
-    let instance_service = Arc::new(InstanceService::with_storage(storage_manager.clone()));
+    if let Some(ref profile) = args.profile {
+        let profiles = storage_manager.declared_profiles().await?;
+        if !profiles.contains(profile) {
+            anyhow::bail!(
+                "Unknown profile '{}' (declared profiles: {})",
+                profile,
+                profiles.join(", ")
+            );
+        }
+        info!("Using configuration profile: {}", profile);
+    }
+    let instance_service = Arc::new(
+        InstanceService::with_storage(storage_manager.clone())
+            .with_profile(args.profile.clone())
+            .with_duration_buckets(args.duration_buckets.clone())?,
+    );
+    let webhook_manager = Arc::new(webhook::WebhookManager::load(args.webhooks_path.clone()).await?);
+    webhook_manager.clone().start(instance_service.subscribe_events());
+    let availability_tracker = Arc::new(
+        availability::AvailabilityTracker::load(
+            args.availability_path.clone(),
+            instance_service.clone(),
+        )
+        .await?,
+    );
+    availability_tracker
+        .clone()
+        .start(instance_service.subscribe_events());
 
         let storage_manager_bg = storage_manager.clone();
     let instance_service_bg = instance_service.clone();
+    let stats_persist_secs = args.stats_persist_secs;
     tokio::spawn(async move {
         match storage_manager_bg.load().await {
             Ok(instances) => {
+                instance_service_bg.apply_global_filter().await;
                 let mut loaded_count = 0;
                 for instance in instances {
                     if let Err(e) = instance_service_bg.restore_instance(instance).await {
@@ -73,6 +333,32 @@ async fn main() -> Result<()> {
                     }
                 }
                 info!("Loaded {} instances from storage", loaded_count);
+                if stats_persist_secs > 0 {
+                    match instance_service_bg.restore_stats().await {
+                        Ok(restored) => info!("Restored traffic counters of {} instances", restored),
+                        Err(e) => error!("Failed to restore traffic counters: {}", e),
+                    }
+                    let stats_service = instance_service_bg.clone();
+                    tokio::spawn(async move {
+                        let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+                            stats_persist_secs,
+                        ));
+                        interval.tick().await;
+                        loop {
+                            interval.tick().await;
+                            if let Err(e) = stats_service.persist_stats().await {
+                                error!("Failed to save traffic counters: {}", e);
+                            }
+                        }
+                    });
+                }
+                let inherited = handoff::inherited_instance_ids();
+                let results = instance_service_bg.start_instances(&inherited).await;
+                for (id, result) in inherited.iter().zip(results) {
+                    if let Err(e) = result {
+                        error!("Failed to start inherited instance {}: {}", id, e);
+                    }
+                }
             }
             Err(e) => {
                 error!("Failed to load instances from storage: {}", e);
@@ -81,19 +367,180 @@ async fn main() -> Result<()> {
         }
     });
     instance_service.start_auto_instances().await?;
+    let trash_service = instance_service.clone();
+    let trash_retention = std::time::Duration::from_secs(args.trash_retention_days * 24 * 60 * 60);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+        loop {
+            interval.tick().await;
+            if let Err(e) = trash_service.purge_expired_instances(trash_retention).await {
+                error!("Failed to purge expired deleted instances: {}", e);
+            }
+        }
+    });
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup(instance_service.clone()));
+    if !args.blocklist_feed.is_empty() {
+        let blocklist = blocklist::global();
+        for url in &args.blocklist_feed {
+            blocklist.subscribe(url.clone());
+        }
+        info!("Subscribed to {} blocklist feeds", args.blocklist_feed.len());
+        blocklist.start(std::time::Duration::from_secs(args.blocklist_refresh_secs.max(1)));
+    }
+    let traffic = traffic_report::global();
+    if let Some(ref path) = args.geoip_db {
+        let db = geoip::GeoIpDb::load(path).await?;
+        info!("Loaded {} GeoIP ranges from {:?}", db.len(), path);
+        traffic.set_geoip(db);
+    }
+    traffic.start(std::time::Duration::from_secs(args.traffic_report_secs.max(1)));
+    Arc::new(backup_scheduler::BackupScheduler::new(instance_service.clone())).start();
+    let cluster_manager = Arc::new(cluster::ClusterManager::new(
+        cluster_config,
+        instance_service.clone(),
+    ));
+    cluster_manager.start();
+    if let Some(docker_socket) = args.docker_socket.clone() {
+        Arc::new(docker_discovery::DockerDiscovery::new(
+            docker_socket,
+            std::time::Duration::from_secs(args.docker_poll_secs.max(1)),
+            instance_service.clone(),
+        ))
+        .start();
+    }
+    if let Some(broker) = args.mqtt_broker.clone() {
+        let mqtt_config = mqtt::MqttConfig {
+            broker,
+            client_id: args.mqtt_client_id.clone().unwrap_or_else(|| {
+                format!("voidproxy-{}", &uuid::Uuid::new_v4().simple().to_string()[..8])
+            }),
+            username: args.mqtt_username.clone(),
+            password: args.mqtt_password.clone(),
+            topic_prefix: args.mqtt_topic_prefix.trim_end_matches('/').to_string(),
+            stats_interval: std::time::Duration::from_secs(args.mqtt_stats_secs.max(1)),
+        };
+        Arc::new(mqtt::MqttPublisher::new(mqtt_config, instance_service.clone())).start();
+    }
+    if let Some(listen_addr) = args.snmp_listen {
+        let snmp_config = snmp::SnmpConfig {
+            listen_addr,
+            community: args.snmp_community.clone(),
+        };
+        Arc::new(snmp::SnmpAgent::new(snmp_config, instance_service.clone()))
+            .start()
+            .await?;
+    }
     let cors = CorsLayer::permissive();
-    let app = axum::Router::new()
+    let mut api_routes = create_api_routes(instance_service.clone());
+    if let Some(admin_token) = args.admin_token.clone() {
+        api_routes = api_routes.layer(axum::Extension(web_api::AdminToken(admin_token)));
+    }
+    if let Some(status_addr) = args.public_status_listen {
+        let status_app = public_status::create_routes(availability_tracker.clone());
+        let status_listener = tokio::net::TcpListener::bind(status_addr).await?;
+        info!("Public status page listening on {}", status_addr);
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(status_listener, status_app).await {
+                error!("Public status server error: {}", e);
+            }
+        });
+    }
+    let mut app = axum::Router::new();
+    if args.public_status {
+        app = app.merge(public_status::create_routes(availability_tracker.clone()));
+    }
+    let app = app
         .merge(create_routes(args.web_listen_port))
-        .merge(create_api_routes(instance_service.clone()))
+        .merge(api_routes)
+        .merge(cluster::create_routes(cluster_manager))
+        .merge(webhook::create_routes(webhook_manager))
+        .merge(availability::create_routes(availability_tracker))
+        .merge(prometheus::create_routes(
+            instance_service.clone(),
+            args.metrics_tag_labels.clone(),
+        ))
         .layer(ServiceBuilder::new().layer(cors));
     let addr = SocketAddr::new(args.web_listen_ip.parse()?, args.web_listen_port);
-    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let listener = match handoff::take_tcp_listener(addr) {
+        Some(listener) => {
+            listener.set_nonblocking(true)?;
+            tokio::net::TcpListener::from_std(listener)?
+        }
+        None => tokio::net::TcpListener::bind(addr).await?,
+    };
+    #[cfg(unix)]
+    handoff::register_listener(
+        handoff::SocketKind::Tcp,
+        addr,
+        std::os::fd::AsRawFd::as_raw_fd(&listener),
+        None,
+    );
     info!("Web interface listening on {}", addr);
+    #[cfg(unix)]
+    if let Some(upgrade_socket) = args.upgrade_socket.clone() {
+        tokio::spawn(async move {
+            if let Err(e) = handoff::serve(upgrade_socket).await {
+                error!("Upgrade socket error: {}", e);
+            }
+        });
+    }
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal())
         .await?;
+    if args.stats_persist_secs > 0
+        && let Err(e) = instance_service.persist_stats().await
+    {
+        error!("Failed to save traffic counters: {}", e);
+    }
+    if handoff::drain_token().is_cancelled() {
+        instance_service
+            .wait_for_drain(std::time::Duration::from_secs(args.drain_timeout_secs))
+            .await;
+    }
     Ok(())
 }
+async fn run_test_servers(args: TestServerArgs) -> Result<()> {
+    use test_server::{TestServer, TestServerKind};
+    let ports = [
+        (TestServerKind::TcpEcho, &args.tcp_echo),
+        (TestServerKind::TcpSink, &args.tcp_sink),
+        (TestServerKind::UdpEcho, &args.udp_echo),
+        (TestServerKind::UdpSink, &args.udp_sink),
+    ];
+    let mut servers = tokio::task::JoinSet::new();
+    for (kind, ports) in ports {
+        for &port in ports {
+            let server = TestServer::bind(kind, SocketAddr::new(args.listen_ip, port)).await?;
+            servers.spawn(server.serve());
+        }
+    }
+    if servers.is_empty() {
+        anyhow::bail!(
+            "No test server requested, use --tcp-echo, --tcp-sink, --udp-echo or --udp-sink"
+        );
+    }
+    tokio::select! {
+        _ = shutdown_signal() => Ok(()),
+        Some(result) = servers.join_next() => result?,
+    }
+}
+#[cfg(unix)]
+async fn reload_on_sighup(instance_service: Arc<InstanceService>) {
+    let mut hangup = match signal::unix::signal(signal::unix::SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            error!("Failed to install SIGHUP handler: {}", e);
+            return;
+        }
+    };
+    while hangup.recv().await.is_some() {
+        info!("Received SIGHUP, reloading configuration...");
+        if let Err(e) = instance_service.reload_config().await {
+            error!("Failed to reload configuration: {}", e);
+        }
+    }
+}
 async fn shutdown_signal() {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -109,6 +556,7 @@ async fn shutdown_signal() {
     };
     #[cfg(not(unix))]
     let terminate = std::future::pending::<()>();
+    let drain_token = handoff::drain_token();
     tokio::select! {
         _ = ctrl_c => {
             info!("Received Ctrl+C, shutting down...");
@@ -116,5 +564,8 @@ async fn shutdown_signal() {
         _ = terminate => {
             info!("Received terminate signal, shutting down...");
         }
+        _ = drain_token.cancelled() => {
+            info!("Listeners handed over to new process, shutting down...");
+        }
     }
 }
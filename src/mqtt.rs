@@ -0,0 +1,168 @@
+use crate::events::Event;
+use crate::instance_manager::InstanceService;
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+const KEEP_ALIVE: Duration = Duration::from_secs(60);
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const PINGREQ: [u8; 2] = [0xc0, 0x00];
+#[derive(Debug, Clone)]
+/**
+ * MQTT publishing settings taken from the command line.
+ */
+pub struct MqttConfig {
+    pub broker: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub topic_prefix: String,
+    pub stats_interval: Duration,
+}
+fn encode_remaining_length(packet: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+}
+fn encode_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(value.as_bytes());
+}
+/**
+ * Encodes an MQTT 3.1.1 CONNECT packet with a clean session.
+ */
+pub fn encode_connect(config: &MqttConfig) -> Vec<u8> {
+    let mut flags = 0x02;
+    let mut payload = Vec::new();
+    encode_string(&mut payload, &config.client_id);
+    if let Some(username) = &config.username {
+        flags |= 0x80;
+        encode_string(&mut payload, username);
+        if let Some(password) = &config.password {
+            flags |= 0x40;
+            encode_string(&mut payload, password);
+        }
+    }
+    let mut body = Vec::new();
+    encode_string(&mut body, "MQTT");
+    body.push(0x04);
+    body.push(flags);
+    body.extend_from_slice(&(KEEP_ALIVE.as_secs() as u16).to_be_bytes());
+    body.extend_from_slice(&payload);
+    let mut packet = vec![0x10];
+    encode_remaining_length(&mut packet, body.len());
+    packet.extend_from_slice(&body);
+    packet
+}
+/**
+ * Encodes an MQTT 3.1.1 PUBLISH packet at QoS 0.
+ */
+pub fn encode_publish(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+    encode_string(&mut body, topic);
+    body.extend_from_slice(payload);
+    let mut packet = vec![if retain { 0x31 } else { 0x30 }];
+    encode_remaining_length(&mut packet, body.len());
+    packet.extend_from_slice(&body);
+    packet
+}
+/**
+ * Publishes lifecycle events and periodic instance stats to an MQTT broker.
+ *
+ * Events go to `<prefix>/events/<event>`, stats to the retained topic
+ * `<prefix>/instances/<id>/stats`. Messages are sent at QoS 0 and the
+ * connection is re-established after a broker outage; events emitted while
+ * disconnected are dropped.
+ */
+pub struct MqttPublisher {
+    config: MqttConfig,
+    instance_service: Arc<InstanceService>,
+}
+impl MqttPublisher {
+    pub fn new(config: MqttConfig, instance_service: Arc<InstanceService>) -> Self {
+        Self {
+            config,
+            instance_service,
+        }
+    }
+    pub fn start(self: Arc<Self>) {
+        tokio::spawn(async move {
+            info!("Publishing to MQTT broker {}", self.config.broker);
+            loop {
+                let events = self.instance_service.subscribe_events();
+                if let Err(e) = self.run_session(events).await {
+                    warn!("MQTT session with {} ended: {:#}", self.config.broker, e);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        });
+    }
+    async fn connect(&self) -> Result<TcpStream> {
+        let mut stream = TcpStream::connect(&self.config.broker)
+            .await
+            .with_context(|| format!("Failed to connect to {}", self.config.broker))?;
+        stream.write_all(&encode_connect(&self.config)).await?;
+        let mut connack = [0u8; 4];
+        tokio::time::timeout(Duration::from_secs(10), stream.read_exact(&mut connack))
+            .await
+            .context("Timed out waiting for CONNACK")??;
+        if connack[0] != 0x20 {
+            return Err(anyhow::anyhow!("Unexpected packet type {:#x}", connack[0]));
+        }
+        if connack[3] != 0 {
+            return Err(anyhow::anyhow!(
+                "Broker refused connection with code {}",
+                connack[3]
+            ));
+        }
+        debug!("Connected to MQTT broker {}", self.config.broker);
+        Ok(stream)
+    }
+    async fn run_session(&self, mut events: broadcast::Receiver<Event>) -> Result<()> {
+        let stream = self.connect().await?;
+        let (mut reader, mut writer) = stream.into_split();
+        let mut stats_interval = tokio::time::interval(self.config.stats_interval);
+        let mut ping_interval = tokio::time::interval(PING_INTERVAL);
+        let mut incoming = [0u8; 256];
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        let topic = format!("{}/events/{}", self.config.topic_prefix, event.kind.name());
+                        writer.write_all(&encode_publish(&topic, &serde_json::to_vec(&event)?, false)).await?;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("MQTT publisher fell behind, {} events dropped", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
+                },
+                _ = stats_interval.tick() => {
+                    for (id, stats) in self.instance_service.get_instance_stats().await {
+                        let topic = format!("{}/instances/{}/stats", self.config.topic_prefix, id);
+                        writer.write_all(&encode_publish(&topic, &serde_json::to_vec(&stats)?, true)).await?;
+                    }
+                }
+                _ = ping_interval.tick() => {
+                    writer.write_all(&PINGREQ).await?;
+                }
+                read = reader.read(&mut incoming) => {
+                    if read? == 0 {
+                        return Err(anyhow::anyhow!("Broker closed the connection"));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use crate::instance_manager::{InstanceService, InstanceStats};
+use crate::metrics::{HistogramSnapshot, TransportStats};
+use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use tracing::debug;
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+/**
+ * Label carrying the instance tag `tag`, such as `tag_env` for `env`.
+ * Characters Prometheus does not allow in label names become underscores.
+ */
+pub fn tag_label_name(tag: &str) -> String {
+    let sanitized: String = tag
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("tag_{}", sanitized)
+}
+fn labels(stats: &InstanceStats, tag_labels: &[String]) -> String {
+    let mut labels = format!(
+        "instance=\"{}\",id=\"{}\"",
+        escape_label(&stats.name),
+        stats.id
+    );
+    for tag in tag_labels {
+        if let Some(value) = stats.tags.get(tag) {
+            let _ = write!(
+                labels,
+                ",{}=\"{}\"",
+                tag_label_name(tag),
+                escape_label(value)
+            );
+        }
+    }
+    labels
+}
+fn write_family<T: std::fmt::Display>(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    stats: &[&InstanceStats],
+    tag_labels: &[String],
+    value: impl Fn(&InstanceStats) -> T,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for stats in stats {
+        let _ = writeln!(
+            out,
+            "{}{{{}}} {}",
+            name,
+            labels(stats, tag_labels),
+            value(stats)
+        );
+    }
+}
+/**
+ * Writes one counter series per instance and key of `counts`, labelled with
+ * the key as `label`.
+ */
+fn write_breakdown(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    label: &str,
+    stats: &[&InstanceStats],
+    tag_labels: &[String],
+    counts: impl Fn(&InstanceStats) -> &BTreeMap<String, u64>,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    for stats in stats {
+        let labels = labels(stats, tag_labels);
+        for (key, count) in counts(stats) {
+            let _ = writeln!(
+                out,
+                "{}{{{},{}=\"{}\"}} {}",
+                name,
+                labels,
+                label,
+                escape_label(key),
+                count
+            );
+        }
+    }
+}
+/**
+ * Writes a TCP and a UDP series per instance, labelled with `transport`.
+ */
+fn write_by_transport<T: std::fmt::Display>(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    stats: &[&InstanceStats],
+    tag_labels: &[String],
+    value: impl Fn(&TransportStats) -> T,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for stats in stats {
+        let labels = labels(stats, tag_labels);
+        for (transport, counters) in [("tcp", &stats.tcp), ("udp", &stats.udp)] {
+            let _ = writeln!(
+                out,
+                "{}{{{},transport=\"{}\"}} {}",
+                name,
+                labels,
+                transport,
+                value(counters)
+            );
+        }
+    }
+}
+fn write_histogram(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    stats: &[&InstanceStats],
+    tag_labels: &[String],
+    histogram: impl Fn(&InstanceStats) -> &HistogramSnapshot,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for stats in stats {
+        let labels = labels(stats, tag_labels);
+        let histogram = histogram(stats);
+        for bucket in &histogram.buckets {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{},le=\"{}\"}} {}",
+                name, labels, bucket.le, bucket.count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"+Inf\"}} {}",
+            name, labels, histogram.count
+        );
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count);
+    }
+}
+/**
+ * Renders instance statistics in the Prometheus text exposition format.
+ *
+ * Every series is labelled with the instance name and ID, and with the
+ * instance tags listed in `tag_labels` (see [`tag_label_name`]). Tags left
+ * out of the allow-list are not exported, keeping label cardinality under
+ * control.
+ */
+pub fn render(stats: &[InstanceStats], tag_labels: &[String]) -> String {
+    let mut stats: Vec<&InstanceStats> = stats.iter().collect();
+    stats.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+    let mut out = String::new();
+    write_family(
+        &mut out,
+        "voidproxy_instance_up",
+        "gauge",
+        "Whether the instance is running.",
+        &stats,
+        tag_labels,
+        |stats| u8::from(stats.is_running),
+    );
+    write_family(
+        &mut out,
+        "voidproxy_bytes_sent_total",
+        "counter",
+        "Bytes sent to clients.",
+        &stats,
+        tag_labels,
+        |stats| stats.bytes_sent,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_bytes_received_total",
+        "counter",
+        "Bytes received from clients.",
+        &stats,
+        tag_labels,
+        |stats| stats.bytes_received,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_connections_active",
+        "gauge",
+        "Open connections.",
+        &stats,
+        tag_labels,
+        |stats| stats.connections_active,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_connections_total",
+        "counter",
+        "Accepted connections.",
+        &stats,
+        tag_labels,
+        |stats| stats.connections_total,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_errors_total",
+        "counter",
+        "Failed upstream connections.",
+        &stats,
+        tag_labels,
+        |stats| stats.errors,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_connections_stalled",
+        "gauge",
+        "TCP connections paused until their peer drains its send queue.",
+        &stats,
+        tag_labels,
+        |stats| stats.connections_stalled,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_stalls_total",
+        "counter",
+        "Times a TCP connection was paused by backpressure.",
+        &stats,
+        tag_labels,
+        |stats| stats.stalls_total,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_handler_waits_total",
+        "counter",
+        "Connections or datagrams that waited for a free handler of the instance.",
+        &stats,
+        tag_labels,
+        |stats| stats.handler_waits,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_accept_errors_total",
+        "counter",
+        "Failed accepts of the TCP listener.",
+        &stats,
+        tag_labels,
+        |stats| stats.accept_errors,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_udp_unreachable_total",
+        "counter",
+        "ICMP port unreachable errors received from UDP destinations.",
+        &stats,
+        tag_labels,
+        |stats| stats.udp_unreachable,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_memory_bytes",
+        "gauge",
+        "Approximate memory held by buffers, UDP sessions and connections of the instance.",
+        &stats,
+        tag_labels,
+        |stats| stats.memory.total,
+    );
+    write_by_transport(
+        &mut out,
+        "voidproxy_transport_bytes_sent_total",
+        "counter",
+        "Bytes sent to clients per transport.",
+        &stats,
+        tag_labels,
+        |counters| counters.bytes_sent,
+    );
+    write_by_transport(
+        &mut out,
+        "voidproxy_transport_bytes_received_total",
+        "counter",
+        "Bytes received from clients per transport.",
+        &stats,
+        tag_labels,
+        |counters| counters.bytes_received,
+    );
+    write_by_transport(
+        &mut out,
+        "voidproxy_transport_connections_active",
+        "gauge",
+        "Open TCP connections and UDP sessions.",
+        &stats,
+        tag_labels,
+        |counters| counters.connections_active,
+    );
+    write_by_transport(
+        &mut out,
+        "voidproxy_transport_connections_total",
+        "counter",
+        "Accepted TCP connections and opened UDP sessions.",
+        &stats,
+        tag_labels,
+        |counters| counters.connections_total,
+    );
+    write_by_transport(
+        &mut out,
+        "voidproxy_transport_errors_total",
+        "counter",
+        "Failed TCP upstream connections and UDP sessions closed by an error.",
+        &stats,
+        tag_labels,
+        |counters| counters.errors,
+    );
+    let listening: Vec<&InstanceStats> = stats
+        .iter()
+        .copied()
+        .filter(|stats| stats.accept_queue.is_some())
+        .collect();
+    write_family(
+        &mut out,
+        "voidproxy_accept_queue_length",
+        "gauge",
+        "Connections waiting in the TCP accept queue.",
+        &listening,
+        tag_labels,
+        |stats| stats.accept_queue.map_or(0, |queue| queue.queued),
+    );
+    write_family(
+        &mut out,
+        "voidproxy_accept_queue_backlog",
+        "gauge",
+        "Effective backlog of the TCP listener.",
+        &listening,
+        tag_labels,
+        |stats| stats.accept_queue.map_or(0, |queue| queue.backlog),
+    );
+    let terminating: Vec<&InstanceStats> = stats
+        .iter()
+        .copied()
+        .filter(|stats| stats.certificate_expires_at.is_some())
+        .collect();
+    write_family(
+        &mut out,
+        "voidproxy_certificate_expiry_timestamp_seconds",
+        "gauge",
+        "Expiry of the served TLS certificate as a unix timestamp.",
+        &terminating,
+        tag_labels,
+        |stats| {
+            stats
+                .certificate_expires_at
+                .map_or(0, |expires_at| expires_at.timestamp())
+        },
+    );
+    let compressing: Vec<&InstanceStats> = stats
+        .iter()
+        .copied()
+        .filter(|stats| stats.tunnel_bytes_compressed > 0)
+        .collect();
+    write_family(
+        &mut out,
+        "voidproxy_tunnel_uncompressed_bytes_total",
+        "counter",
+        "Traffic of compressed tunnel links before compression.",
+        &compressing,
+        tag_labels,
+        |stats| stats.tunnel_bytes_uncompressed,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_tunnel_compressed_bytes_total",
+        "counter",
+        "Traffic of compressed tunnel links after compression.",
+        &compressing,
+        tag_labels,
+        |stats| stats.tunnel_bytes_compressed,
+    );
+    let mirroring: Vec<&InstanceStats> = stats
+        .iter()
+        .copied()
+        .filter(|stats| stats.mirrored_connections > 0)
+        .collect();
+    write_family(
+        &mut out,
+        "voidproxy_mirrored_connections_total",
+        "counter",
+        "Connections and sessions sampled for traffic mirroring.",
+        &mirroring,
+        tag_labels,
+        |stats| stats.mirrored_connections,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_mirrored_bytes_total",
+        "counter",
+        "Client bytes copied to the traffic mirror.",
+        &mirroring,
+        tag_labels,
+        |stats| stats.mirrored_bytes,
+    );
+    write_family(
+        &mut out,
+        "voidproxy_mirror_dropped_bytes_total",
+        "counter",
+        "Client bytes not mirrored because of the hourly cap or a slow mirror.",
+        &mirroring,
+        tag_labels,
+        |stats| stats.mirror_dropped_bytes,
+    );
+    write_histogram(
+        &mut out,
+        "voidproxy_tcp_connection_duration_seconds",
+        "Duration of completed TCP connections.",
+        &stats,
+        tag_labels,
+        |stats| &stats.tcp_connection_duration,
+    );
+    write_histogram(
+        &mut out,
+        "voidproxy_udp_session_duration_seconds",
+        "Duration of completed UDP sessions.",
+        &stats,
+        tag_labels,
+        |stats| &stats.udp_session_duration,
+    );
+    write_breakdown(
+        &mut out,
+        "voidproxy_tcp_disconnects_total",
+        "Closed TCP connections by close reason.",
+        "reason",
+        &stats,
+        tag_labels,
+        |stats| &stats.tcp_disconnects,
+    );
+    write_breakdown(
+        &mut out,
+        "voidproxy_udp_disconnects_total",
+        "Closed UDP sessions by close reason.",
+        "reason",
+        &stats,
+        tag_labels,
+        |stats| &stats.udp_disconnects,
+    );
+    out
+}
+/**
+ * `/metrics` endpoint. `tag_labels` is the allow-list of instance tags
+ * exported as labels.
+ */
+pub fn create_routes(instance_service: Arc<InstanceService>, tag_labels: Vec<String>) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state((instance_service, Arc::new(tag_labels)))
+}
+async fn metrics(
+    State((service, tag_labels)): State<(Arc<InstanceService>, Arc<Vec<String>>)>,
+) -> impl IntoResponse {
+    debug!("Rendering Prometheus metrics");
+    let stats: Vec<InstanceStats> = service.get_instance_stats().await.into_values().collect();
+    (
+        [(header::CONTENT_TYPE, CONTENT_TYPE)],
+        render(&stats, &tag_labels),
+    )
+}
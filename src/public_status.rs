@@ -0,0 +1,164 @@
+use crate::availability::AvailabilityTracker;
+use crate::instance::InstanceStatus;
+use axum::{
+    Router,
+    extract::State,
+    http::header,
+    response::{Html, IntoResponse, Json},
+    routing::get,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+/** Window over which the availability percentage is computed. */
+const AVAILABILITY_WINDOW_DAYS: i64 = 30;
+/** Seconds between automatic reloads of the status page. */
+const PAGE_REFRESH_SECS: u64 = 30;
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Health of an instance as shown to anyone: its name and whether it is
+ * running without errors, never its addresses, configuration or traffic.
+ */
+pub struct PublicInstanceStatus {
+    pub name: String,
+    pub up: bool,
+    /** Start of the current run while up. */
+    pub up_since: Option<DateTime<Utc>>,
+    pub uptime_secs: Option<i64>,
+    /** Availability over the last 30 days, unset when nothing was recorded. */
+    pub availability_percent: Option<f64>,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct PublicStatus {
+    pub updated_at: DateTime<Utc>,
+    pub instances: Vec<PublicInstanceStatus>,
+}
+/**
+ * Current health of all instances, ordered by name.
+ */
+pub async fn collect(tracker: &AvailabilityTracker) -> PublicStatus {
+    let now = Utc::now();
+    let from = now - chrono::Duration::days(AVAILABILITY_WINDOW_DAYS);
+    let mut instances = Vec::new();
+    for instance in tracker.instance_service().get_instances().await {
+        let up = instance.status == InstanceStatus::Running;
+        let up_since = instance.started_at.filter(|_| up);
+        let report = tracker.report(instance.id, from, now).await;
+        instances.push(PublicInstanceStatus {
+            name: instance.name,
+            up,
+            up_since,
+            uptime_secs: up_since.map(|since| (now - since).num_seconds().max(0)),
+            availability_percent: report.uptime_percent,
+        });
+    }
+    instances.sort_by(|a, b| a.name.cmp(&b.name));
+    PublicStatus {
+        updated_at: now,
+        instances,
+    }
+}
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+fn format_uptime(secs: i64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3_600, secs % 3_600 / 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+/**
+ * Renders the status as a self-contained HTML page reloading itself.
+ */
+pub fn render_page(status: &PublicStatus) -> String {
+    let rows: String = status
+        .instances
+        .iter()
+        .map(|instance| {
+            let (class, label) = if instance.up {
+                ("up", "Operational")
+            } else {
+                ("down", "Down")
+            };
+            let uptime = instance
+                .uptime_secs
+                .map_or_else(|| "-".to_string(), format_uptime);
+            let availability = instance
+                .availability_percent
+                .map_or_else(|| "-".to_string(), |percent| format!("{:.2}%", percent));
+            format!(
+                "<tr><td>{}</td><td class=\"{}\">{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&instance.name),
+                class,
+                label,
+                uptime,
+                availability
+            )
+        })
+        .collect();
+    let overall = if status.instances.iter().all(|instance| instance.up) {
+        "All services operational"
+    } else {
+        "Some services are down"
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1.0">
+<meta http-equiv="refresh" content="{refresh}">
+<title>Service Status</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 48rem; padding: 0 1rem; color: #1f2937; }}
+table {{ width: 100%; border-collapse: collapse; }}
+th, td {{ text-align: left; padding: 0.5rem; border-bottom: 1px solid #e5e7eb; }}
+.up {{ color: #15803d; }}
+.down {{ color: #b91c1c; }}
+small {{ color: #6b7280; }}
+</style>
+</head>
+<body>
+<h1>{overall}</h1>
+<table>
+<thead><tr><th>Service</th><th>Status</th><th>Uptime</th><th>Availability ({days} days)</th></tr></thead>
+<tbody>{rows}</tbody>
+</table>
+<p><small>Updated {updated}</small></p>
+</body>
+</html>
+"#,
+        refresh = PAGE_REFRESH_SECS,
+        overall = overall,
+        days = AVAILABILITY_WINDOW_DAYS,
+        rows = rows,
+        updated = status.updated_at.format("%Y-%m-%d %H:%M:%S UTC"),
+    )
+}
+/**
+ * Unauthenticated `/status` page and `/api/public/status`, safe to expose
+ * to the users of the forwarded services.
+ */
+pub fn create_routes(tracker: Arc<AvailabilityTracker>) -> Router {
+    Router::new()
+        .route("/status", get(status_page))
+        .route("/api/public/status", get(status_json))
+        .with_state(tracker)
+}
+async fn status_page(State(tracker): State<Arc<AvailabilityTracker>>) -> impl IntoResponse {
+    let status = collect(&tracker).await;
+    (
+        [(header::CACHE_CONTROL, "no-cache")],
+        Html(render_page(&status)),
+    )
+}
+async fn status_json(State(tracker): State<Arc<AvailabilityTracker>>) -> Json<PublicStatus> {
+    Json(collect(&tracker).await)
+}
@@ -0,0 +1,340 @@
+use crate::instance::InstanceStatus;
+use crate::instance_manager::InstanceService;
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+const SNMP_VERSION_2C: i64 = 1;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+const PDU_GET: u8 = 0xa0;
+const PDU_GET_NEXT: u8 = 0xa1;
+const PDU_RESPONSE: u8 = 0xa2;
+const PDU_GET_BULK: u8 = 0xa5;
+const MAX_BULK_REPETITIONS: usize = 64;
+/**
+ * Root of the VOIDPROXY-MIB object tree (see `mibs/VOIDPROXY-MIB.txt`).
+ */
+pub const VOIDPROXY_MIB_OID: [u32; 8] = [1, 3, 6, 1, 4, 1, 99999, 1];
+#[derive(Debug, Clone)]
+/**
+ * SNMP agent settings taken from the command line.
+ */
+pub struct SnmpConfig {
+    pub listen_addr: SocketAddr,
+    pub community: String,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SnmpValue {
+    Integer(i64),
+    OctetString(Vec<u8>),
+    Counter32(u32),
+    Gauge32(u32),
+    Counter64(u64),
+    NoSuchObject,
+    EndOfMibView,
+}
+type Oid = Vec<u32>;
+struct BerReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> BerReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+    fn read_tlv(&mut self) -> Option<(u8, &'a [u8])> {
+        let tag = *self.data.get(self.pos)?;
+        let mut length = *self.data.get(self.pos + 1)? as usize;
+        self.pos += 2;
+        if length & 0x80 != 0 {
+            let octets = length & 0x7f;
+            if octets == 0 || octets > 4 {
+                return None;
+            }
+            length = 0;
+            for _ in 0..octets {
+                length = (length << 8) | *self.data.get(self.pos)? as usize;
+                self.pos += 1;
+            }
+        }
+        let value = self.data.get(self.pos..self.pos.checked_add(length)?)?;
+        self.pos += length;
+        Some((tag, value))
+    }
+    fn expect(&mut self, expected: u8) -> Option<&'a [u8]> {
+        match self.read_tlv()? {
+            (tag, value) if tag == expected => Some(value),
+            _ => None,
+        }
+    }
+    fn read_integer(&mut self) -> Option<i64> {
+        let value = self.expect(TAG_INTEGER)?;
+        if value.is_empty() || value.len() > 8 {
+            return None;
+        }
+        let mut result = if value[0] & 0x80 != 0 { -1i64 } else { 0 };
+        for byte in value {
+            result = (result << 8) | *byte as i64;
+        }
+        Some(result)
+    }
+}
+fn decode_oid(value: &[u8]) -> Option<Oid> {
+    let (first, rest) = value.split_first()?;
+    let mut oid = vec![u32::from(*first / 40), u32::from(*first % 40)];
+    let mut arc = 0u32;
+    for byte in rest {
+        arc = arc.checked_mul(128)? | u32::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            oid.push(arc);
+            arc = 0;
+        }
+    }
+    Some(oid)
+}
+fn encode_length(out: &mut Vec<u8>, length: usize) {
+    if length < 0x80 {
+        out.push(length as u8);
+    } else {
+        let bytes = (length as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|byte| **byte == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+}
+fn encode_tlv(out: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    out.push(tag);
+    encode_length(out, value.len());
+    out.extend_from_slice(value);
+}
+fn integer_bytes(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    while start < 7 {
+        let redundant = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    bytes[start..].to_vec()
+}
+fn unsigned_bytes(value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().take_while(|byte| **byte == 0).count().min(7);
+    let mut result = Vec::with_capacity(9);
+    if bytes[start] & 0x80 != 0 {
+        result.push(0);
+    }
+    result.extend_from_slice(&bytes[start..]);
+    result
+}
+fn encode_oid(out: &mut Vec<u8>, oid: &[u32]) {
+    let mut value = Vec::new();
+    if oid.len() >= 2 {
+        value.push((oid[0] * 40 + oid[1]) as u8);
+    }
+    for arc in oid.iter().skip(2) {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        value.extend(chunk.iter().rev());
+    }
+    encode_tlv(out, TAG_OID, &value);
+}
+fn encode_value(out: &mut Vec<u8>, value: &SnmpValue) {
+    match value {
+        SnmpValue::Integer(v) => encode_tlv(out, TAG_INTEGER, &integer_bytes(*v)),
+        SnmpValue::OctetString(v) => encode_tlv(out, TAG_OCTET_STRING, v),
+        SnmpValue::Counter32(v) => encode_tlv(out, TAG_COUNTER32, &unsigned_bytes(u64::from(*v))),
+        SnmpValue::Gauge32(v) => encode_tlv(out, TAG_GAUGE32, &unsigned_bytes(u64::from(*v))),
+        SnmpValue::Counter64(v) => encode_tlv(out, TAG_COUNTER64, &unsigned_bytes(*v)),
+        SnmpValue::NoSuchObject => encode_tlv(out, TAG_NO_SUCH_OBJECT, &[]),
+        SnmpValue::EndOfMibView => encode_tlv(out, TAG_END_OF_MIB_VIEW, &[]),
+    }
+}
+fn status_code(status: InstanceStatus) -> i64 {
+    match status {
+        InstanceStatus::Stopped => 1,
+        InstanceStatus::Running => 2,
+        InstanceStatus::Error => 3,
+        InstanceStatus::Starting => 4,
+        InstanceStatus::Stopping => 5,
+    }
+}
+/**
+ * Minimal SNMPv2c agent exposing per-instance counters.
+ *
+ * Answers GET, GETNEXT and GETBULK for the VOIDPROXY-MIB tree only, so
+ * network management systems without Prometheus support can graph
+ * throughput and connection counts. Requests carrying another community
+ * string are ignored.
+ */
+pub struct SnmpAgent {
+    config: SnmpConfig,
+    instance_service: Arc<InstanceService>,
+}
+impl SnmpAgent {
+    pub fn new(config: SnmpConfig, instance_service: Arc<InstanceService>) -> Self {
+        Self {
+            config,
+            instance_service,
+        }
+    }
+    pub async fn start(self: Arc<Self>) -> Result<()> {
+        let socket = UdpSocket::bind(self.config.listen_addr)
+            .await
+            .with_context(|| format!("Failed to bind SNMP agent on {}", self.config.listen_addr))?;
+        info!("SNMP agent listening on {}", self.config.listen_addr);
+        tokio::spawn(async move {
+            let mut buffer = vec![0u8; 65535];
+            loop {
+                let (len, peer_addr) = match socket.recv_from(&mut buffer).await {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!("SNMP agent receive failed: {}", e);
+                        continue;
+                    }
+                };
+                if let Some(response) = self.handle_request(&buffer[..len]).await
+                    && let Err(e) = socket.send_to(&response, peer_addr).await
+                {
+                    warn!("Failed to send SNMP response to {}: {}", peer_addr, e);
+                }
+            }
+        });
+        Ok(())
+    }
+    async fn mib_snapshot(&self) -> Vec<(Oid, SnmpValue)> {
+        let mut instances = self.instance_service.get_instances().await;
+        instances.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+        let mut entries = vec![(
+            [&VOIDPROXY_MIB_OID[..], &[1, 0]].concat(),
+            SnmpValue::Gauge32(instances.len() as u32),
+        )];
+        for (position, instance) in instances.iter().enumerate() {
+            let index = position as u32 + 1;
+            let metrics = &instance.metrics;
+            let columns = [
+                SnmpValue::Integer(i64::from(index)),
+                SnmpValue::OctetString(instance.name.as_bytes().to_vec()),
+                SnmpValue::Integer(status_code(instance.status)),
+                SnmpValue::Counter64(metrics.bytes_sent.load(Ordering::Relaxed)),
+                SnmpValue::Counter64(metrics.bytes_received.load(Ordering::Relaxed)),
+                SnmpValue::Gauge32(metrics.connections_active.load(Ordering::Relaxed)),
+                SnmpValue::Counter32(metrics.connections_total.load(Ordering::Relaxed)),
+                SnmpValue::Counter32(metrics.errors.load(Ordering::Relaxed)),
+            ];
+            for (column, value) in columns.into_iter().enumerate() {
+                entries.push((
+                    [&VOIDPROXY_MIB_OID[..], &[2, 1, column as u32 + 1, index]].concat(),
+                    value,
+                ));
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+    /**
+     * Processes one SNMP datagram and returns the encoded response, if any.
+     */
+    pub async fn handle_request(&self, packet: &[u8]) -> Option<Vec<u8>> {
+        let mut message = BerReader::new(BerReader::new(packet).expect(TAG_SEQUENCE)?);
+        if message.read_integer()? != SNMP_VERSION_2C {
+            debug!("Ignoring SNMP request with unsupported version");
+            return None;
+        }
+        let community = message.expect(TAG_OCTET_STRING)?;
+        if community != self.config.community.as_bytes() {
+            debug!("Ignoring SNMP request with wrong community");
+            return None;
+        }
+        let (pdu_type, pdu) = message.read_tlv()?;
+        let mut pdu = BerReader::new(pdu);
+        let request_id = pdu.read_integer()?;
+        let non_repeaters = pdu.read_integer()?.max(0) as usize;
+        let max_repetitions = pdu.read_integer()?.clamp(0, MAX_BULK_REPETITIONS as i64) as usize;
+        let mut varbinds = BerReader::new(pdu.expect(TAG_SEQUENCE)?);
+        let mut requested = Vec::new();
+        while !varbinds.is_empty() {
+            let mut varbind = BerReader::new(varbinds.expect(TAG_SEQUENCE)?);
+            requested.push(decode_oid(varbind.expect(TAG_OID)?)?);
+        }
+        let mib = self.mib_snapshot().await;
+        let get = |oid: &Oid| match mib.iter().find(|(candidate, _)| candidate == oid) {
+            Some((oid, value)) => (oid.clone(), value.clone()),
+            None => (oid.clone(), SnmpValue::NoSuchObject),
+        };
+        let next = |oid: &Oid| match mib.iter().find(|(candidate, _)| candidate > oid) {
+            Some((oid, value)) => (oid.clone(), value.clone()),
+            None => (oid.clone(), SnmpValue::EndOfMibView),
+        };
+        let results: Vec<(Oid, SnmpValue)> = match pdu_type {
+            PDU_GET => requested.iter().map(get).collect(),
+            PDU_GET_NEXT => requested.iter().map(next).collect(),
+            PDU_GET_BULK => {
+                let non_repeaters = non_repeaters.min(requested.len());
+                let mut results: Vec<_> = requested[..non_repeaters].iter().map(next).collect();
+                let mut cursors = requested[non_repeaters..].to_vec();
+                for _ in 0..max_repetitions {
+                    if cursors.is_empty() {
+                        break;
+                    }
+                    let row: Vec<_> = cursors.iter().map(next).collect();
+                    let finished = row
+                        .iter()
+                        .all(|(_, value)| *value == SnmpValue::EndOfMibView);
+                    cursors = row.iter().map(|(oid, _)| oid.clone()).collect();
+                    results.extend(row);
+                    if finished {
+                        break;
+                    }
+                }
+                results
+            }
+            _ => {
+                debug!("Ignoring unsupported SNMP PDU type {:#x}", pdu_type);
+                return None;
+            }
+        };
+        Some(self.encode_response(request_id, &results))
+    }
+    fn encode_response(&self, request_id: i64, results: &[(Oid, SnmpValue)]) -> Vec<u8> {
+        let mut varbinds = Vec::new();
+        for (oid, value) in results {
+            let mut varbind = Vec::new();
+            encode_oid(&mut varbind, oid);
+            encode_value(&mut varbind, value);
+            encode_tlv(&mut varbinds, TAG_SEQUENCE, &varbind);
+        }
+        let mut pdu = Vec::new();
+        encode_tlv(&mut pdu, TAG_INTEGER, &integer_bytes(request_id));
+        encode_tlv(&mut pdu, TAG_INTEGER, &[0]);
+        encode_tlv(&mut pdu, TAG_INTEGER, &[0]);
+        encode_tlv(&mut pdu, TAG_SEQUENCE, &varbinds);
+        let mut message = Vec::new();
+        encode_tlv(&mut message, TAG_INTEGER, &integer_bytes(SNMP_VERSION_2C));
+        encode_tlv(&mut message, TAG_OCTET_STRING, self.config.community.as_bytes());
+        encode_tlv(&mut message, PDU_RESPONSE, &pdu);
+        let mut packet = Vec::new();
+        encode_tlv(&mut packet, TAG_SEQUENCE, &message);
+        packet
+    }
+}
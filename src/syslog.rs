@@ -0,0 +1,329 @@
+use crate::metrics::ACCESS_LOG_TARGET;
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber, warn};
+use tracing_subscriber::layer::{Context as LayerContext, Layer};
+const QUEUE_CAPACITY: usize = 4096;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const APP_NAME: &str = "voidproxy";
+const FACILITIES: [(&str, u8); 20] = [
+    ("kern", 0),
+    ("user", 1),
+    ("mail", 2),
+    ("daemon", 3),
+    ("auth", 4),
+    ("syslog", 5),
+    ("lpr", 6),
+    ("news", 7),
+    ("uucp", 8),
+    ("cron", 9),
+    ("authpriv", 10),
+    ("ftp", 11),
+    ("local0", 16),
+    ("local1", 17),
+    ("local2", 18),
+    ("local3", 19),
+    ("local4", 20),
+    ("local5", 21),
+    ("local6", 22),
+    ("local7", 23),
+];
+#[derive(Debug, Clone, PartialEq, Eq)]
+/**
+ * Where syslog messages are sent, parsed from `udp://host:port`,
+ * `tcp://host:port` or `unix:///path`. The port defaults to 514.
+ */
+pub enum SyslogTransport {
+    Udp(String),
+    Tcp(String),
+    Unix(PathBuf),
+}
+impl SyslogTransport {
+    pub fn parse(target: &str) -> Result<Self> {
+        let (scheme, rest) = target
+            .split_once("://")
+            .with_context(|| format!("Syslog target {:?} lacks a scheme", target))?;
+        let with_port = |rest: &str| {
+            if rest.is_empty() {
+                anyhow::bail!("Syslog target {:?} lacks a host", target);
+            }
+            let has_port = match rest.rsplit_once(':') {
+                Some((host, port)) => {
+                    port.parse::<u16>().is_ok() && (!host.contains(':') || host.ends_with(']'))
+                }
+                None => false,
+            };
+            Ok(if has_port {
+                rest.to_string()
+            } else {
+                format!("{}:514", rest)
+            })
+        };
+        match scheme {
+            "udp" => Ok(Self::Udp(with_port(rest)?)),
+            "tcp" => Ok(Self::Tcp(with_port(rest)?)),
+            "unix" if !rest.is_empty() => Ok(Self::Unix(PathBuf::from(rest))),
+            "unix" => anyhow::bail!("Syslog target {:?} lacks a socket path", target),
+            other => anyhow::bail!(
+                "Unsupported syslog transport {:?}, expected udp, tcp or unix",
+                other
+            ),
+        }
+    }
+}
+/**
+ * Numeric code of a syslog facility name such as `daemon` or `local0`.
+ */
+pub fn facility_code(name: &str) -> Result<u8> {
+    FACILITIES
+        .iter()
+        .find(|(facility, _)| facility.eq_ignore_ascii_case(name))
+        .map(|(_, code)| *code)
+        .with_context(|| format!("Unknown syslog facility {:?}", name))
+}
+/**
+ * Syslog severity of a log level: error, warning, informational or debug.
+ */
+pub fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+#[derive(Debug, Clone)]
+/**
+ * Syslog output settings taken from the command line.
+ */
+pub struct SyslogConfig {
+    pub transport: SyslogTransport,
+    pub facility: u8,
+    pub hostname: String,
+    pub access_log: bool,
+}
+/**
+ * Name of this host for the HOSTNAME header field, `-` when unknown.
+ */
+pub fn local_hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "-".to_string())
+}
+/**
+ * Formats an RFC 5424 message without structured data. Access log lines get
+ * the MSGID `access`, daemon logs none.
+ */
+pub fn format_message(
+    config: &SyslogConfig,
+    severity: u8,
+    timestamp: DateTime<Utc>,
+    msgid: Option<&str>,
+    message: &str,
+) -> String {
+    let header_field = |value: &str, max: usize| -> String {
+        let value: String = value
+            .chars()
+            .filter(|c| c.is_ascii_graphic())
+            .take(max)
+            .collect();
+        if value.is_empty() { "-".to_string() } else { value }
+    };
+    format!(
+        "<{}>1 {} {} {} {} {} - {}",
+        u16::from(config.facility) * 8 + u16::from(severity),
+        timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+        header_field(&config.hostname, 255),
+        APP_NAME,
+        std::process::id(),
+        msgid.map_or_else(|| "-".to_string(), |id| header_field(id, 32)),
+        message.replace(['\r', '\n'], " ")
+    )
+}
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
+/**
+ * Tracing layer queueing every event it sees as a syslog message for its
+ * [`SyslogSink`]. Messages are dropped while the queue is full.
+ */
+pub struct SyslogLayer {
+    config: SyslogConfig,
+    sender: mpsc::Sender<String>,
+}
+impl SyslogLayer {
+    pub fn new(config: SyslogConfig) -> (Self, SyslogSink) {
+        let (sender, receiver) = mpsc::channel(QUEUE_CAPACITY);
+        let sink = SyslogSink {
+            transport: config.transport.clone(),
+            receiver,
+        };
+        (Self { config, sender }, sink)
+    }
+    /**
+     * Whether events of `target` are forwarded: daemon logs always, the
+     * access log only when enabled.
+     */
+    pub fn forwards(&self, target: &str) -> bool {
+        self.config.access_log || target != ACCESS_LOG_TARGET
+    }
+}
+impl<S: Subscriber> Layer<S> for SyslogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        let metadata = event.metadata();
+        if !self.forwards(metadata.target()) {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let (msgid, text) = if metadata.target() == ACCESS_LOG_TARGET {
+            (Some("access"), visitor.message)
+        } else {
+            (
+                None,
+                format!("{}: {}{}", metadata.target(), visitor.message, visitor.fields),
+            )
+        };
+        let message = format_message(
+            &self.config,
+            severity(metadata.level()),
+            Utc::now(),
+            msgid,
+            &text,
+        );
+        let _ = self.sender.try_send(message);
+    }
+}
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixDatagram),
+}
+impl Connection {
+    async fn open(transport: &SyslogTransport) -> Result<Self> {
+        match transport {
+            SyslogTransport::Udp(addr) => {
+                let target = tokio::net::lookup_host(addr.as_str())
+                    .await?
+                    .next()
+                    .with_context(|| format!("No address found for {}", addr))?;
+                let bind = if target.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+                let socket = UdpSocket::bind(bind).await?;
+                socket.connect(target).await?;
+                Ok(Self::Udp(socket))
+            }
+            SyslogTransport::Tcp(addr) => Ok(Self::Tcp(TcpStream::connect(addr.as_str()).await?)),
+            #[cfg(unix)]
+            SyslogTransport::Unix(path) => {
+                let socket = tokio::net::UnixDatagram::unbound()?;
+                socket.connect(path)?;
+                Ok(Self::Unix(socket))
+            }
+            #[cfg(not(unix))]
+            SyslogTransport::Unix(_) => {
+                anyhow::bail!("Unix syslog sockets are not supported on this platform")
+            }
+        }
+    }
+    /**
+     * Sends one message; over TCP it is framed by octet counting (RFC 6587).
+     */
+    async fn send(&mut self, message: &str) -> Result<()> {
+        match self {
+            Self::Udp(socket) => {
+                socket.send(message.as_bytes()).await?;
+            }
+            Self::Tcp(stream) => {
+                let frame = format!("{} {}", message.len(), message);
+                stream.write_all(frame.as_bytes()).await?;
+            }
+            #[cfg(unix)]
+            Self::Unix(socket) => {
+                socket.send(message.as_bytes()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+/**
+ * Delivers the messages queued by a [`SyslogLayer`] to the collector.
+ *
+ * The connection is opened on the first message and re-established after
+ * a failure, at most every few seconds; messages arriving in between are
+ * dropped and counted.
+ */
+pub struct SyslogSink {
+    transport: SyslogTransport,
+    receiver: mpsc::Receiver<String>,
+}
+impl SyslogSink {
+    pub fn start(self) {
+        tokio::spawn(self.run());
+    }
+    pub async fn run(mut self) {
+        let mut connection: Option<Connection> = None;
+        let mut retry_at: Option<Instant> = None;
+        let mut dropped = 0u64;
+        while let Some(message) = self.receiver.recv().await {
+            if connection.is_none() {
+                if retry_at.is_some_and(|at| Instant::now() < at) {
+                    dropped += 1;
+                    continue;
+                }
+                match Connection::open(&self.transport).await {
+                    Ok(opened) => {
+                        connection = Some(opened);
+                        retry_at = None;
+                    }
+                    Err(e) => {
+                        warn!("Failed to connect to syslog {:?}: {:#}", self.transport, e);
+                        retry_at = Some(Instant::now() + RECONNECT_DELAY);
+                        dropped += 1;
+                        continue;
+                    }
+                }
+            }
+            if let Some(open) = connection.as_mut() {
+                if dropped > 0 {
+                    warn!("{} syslog messages dropped while disconnected", dropped);
+                    dropped = 0;
+                }
+                if let Err(e) = open.send(&message).await {
+                    warn!("Failed to send to syslog {:?}: {:#}", self.transport, e);
+                    connection = None;
+                    retry_at = Some(Instant::now() + RECONNECT_DELAY);
+                    dropped += 1;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::config::{LogLevel, Protocol};
+use crate::hostname;
+use crate::instance::CreateInstanceRequest;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 300;
+const COLUMNS: &[&str] = &[
+    "name",
+    "listen",
+    "listen_ip",
+    "listen_port",
+    "destination",
+    "dst_ip",
+    "dst_port",
+    "protocol",
+    "auto_start",
+    "allow_list",
+    "deny_list",
+    "connect_timeout_secs",
+    "idle_timeout_secs",
+    "log_level",
+    "bind_device",
+    "bind_nonlocal",
+    "backends",
+];
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Outcome of one data row of an imported table.
+ *
+ * `row` is the 1-based line number in the submitted table (the header is
+ * line 1). `id` is set for created instances, `error` for rejected rows.
+ */
+pub struct RowReport {
+    pub row: usize,
+    pub name: Option<String>,
+    pub status: RowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    Created,
+    Valid,
+    Error,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Per-row validation report of a table import. `unknown_columns` lists the
+ * header columns that were ignored, typically misspelled ones.
+ */
+pub struct ImportReport {
+    pub created: usize,
+    pub failed: usize,
+    pub rows: Vec<RowReport>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unknown_columns: Vec<String>,
+}
+/**
+ * Parsed data row of an imported table: the request it describes or why it
+ * was rejected.
+ */
+pub struct TableRow {
+    pub row: usize,
+    pub name: Option<String>,
+    pub request: Result<CreateInstanceRequest, String>,
+}
+fn split_record(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+fn parse_endpoint(value: &str, default_ip: Option<IpAddr>) -> Result<(IpAddr, u16), String> {
+    if let Ok(addr) = value.parse::<SocketAddr>() {
+        return Ok((addr.ip(), addr.port()));
+    }
+    match (default_ip, value.parse::<u16>()) {
+        (Some(ip), Ok(port)) => Ok((ip, port)),
+        _ => Err(format!("Invalid address {:?}, expected ip:port", value)),
+    }
+}
+/**
+ * Splits an allow or deny list into its IPs and hostnames. Entries with a
+ * letter and no `:` are hostnames, anything else must be an IP.
+ */
+fn parse_ip_list(value: &str) -> Result<(Option<Vec<IpAddr>>, Vec<String>), String> {
+    let mut ips = Vec::new();
+    let mut hosts = Vec::new();
+    for entry in value.split([';', ' ']).filter(|entry| !entry.is_empty()) {
+        if entry.chars().any(|c| c.is_ascii_alphabetic()) && hostname::is_valid_hostname(entry) {
+            hosts.push(entry.to_string());
+        } else {
+            ips.push(
+                entry
+                    .parse()
+                    .map_err(|e| format!("Invalid IP {}: {}", entry, e))?,
+            );
+        }
+    }
+    Ok((if ips.is_empty() { None } else { Some(ips) }, hosts))
+}
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "" | "0" | "false" | "no" | "n" => Ok(false),
+        "1" | "true" | "yes" | "y" => Ok(true),
+        _ => Err(format!("Invalid boolean {:?}", value)),
+    }
+}
+fn parse_row(fields: &HashMap<&str, &str>) -> Result<CreateInstanceRequest, String> {
+    let field = |key: &str| fields.get(key).copied().unwrap_or_default();
+    let name = field("name");
+    if name.is_empty() {
+        return Err("Missing name".to_string());
+    }
+    let (listen_ip, listen_port) = if !field("listen").is_empty() {
+        parse_endpoint(field("listen"), Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)))?
+    } else {
+        let ip = match field("listen_ip") {
+            "" => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            ip => ip.parse().map_err(|e| format!("Invalid listen IP: {}", e))?,
+        };
+        let port = field("listen_port")
+            .parse()
+            .map_err(|_| "Missing or invalid listen port".to_string())?;
+        (ip, port)
+    };
+    let (dst_ip, dst_port) = if !field("destination").is_empty() {
+        parse_endpoint(field("destination"), None)?
+    } else {
+        let ip = field("dst_ip")
+            .parse()
+            .map_err(|_| "Missing or invalid destination IP".to_string())?;
+        let port = field("dst_port")
+            .parse()
+            .map_err(|_| "Missing or invalid destination port".to_string())?;
+        (ip, port)
+    };
+    let protocol = match field("protocol").to_lowercase().as_str() {
+        "" | "tcp" => Protocol::Tcp,
+        "udp" => Protocol::Udp,
+        "both" | "tcp/udp" | "tcp+udp" => Protocol::Both,
+        other => return Err(format!("Invalid protocol {:?}", other)),
+    };
+    let log_level = match field("log_level") {
+        "" => LogLevel::Info,
+        level => level.parse()?,
+    };
+    let seconds = |key: &str, default: u64| match field(key) {
+        "" => Ok(default),
+        value => value
+            .parse()
+            .map_err(|_| format!("Invalid {} {:?}", key, value)),
+    };
+    let (allow_list, allow_hosts) = parse_ip_list(field("allow_list"))?;
+    let (deny_list, deny_hosts) = parse_ip_list(field("deny_list"))?;
+    let request = CreateInstanceRequest {
+        name: name.to_string(),
+        listen_ip,
+        listen_port,
+        dst_ip,
+        dst_port,
+        protocol,
+        auto_start: parse_bool(field("auto_start"))?,
+        allow_list,
+        deny_list,
+        allow_hosts,
+        deny_hosts,
+        connect_timeout_secs: seconds("connect_timeout_secs", DEFAULT_CONNECT_TIMEOUT_SECS)?,
+        idle_timeout_secs: seconds("idle_timeout_secs", DEFAULT_IDLE_TIMEOUT_SECS)?,
+        log_level,
+        bind_device: match field("bind_device") {
+            "" => None,
+            device => Some(device.to_string()),
+        },
+        bind_nonlocal: parse_bool(field("bind_nonlocal"))?,
+        backends: field("backends")
+            .split([';', ' '])
+            .filter(|backend| !backend.is_empty())
+            .map(str::to_string)
+            .collect(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
+    };
+    request.to_config().validate().map_err(|e| e.to_string())?;
+    Ok(request)
+}
+/**
+ * Parses a CSV (or tab-separated) table of instances.
+ *
+ * The first line is a header naming the columns, in any order. `name`,
+ * `listen` (`ip:port` or a bare port) and `destination` (`ip:port`) are
+ * required; the split `listen_ip`/`listen_port`/`dst_ip`/`dst_port` columns
+ * are accepted as well. Optional columns are `protocol`, `auto_start`,
+ * `allow_list`, `deny_list` (IPs or hostnames separated by `;`), `connect_timeout_secs`,
+ * `idle_timeout_secs`, `log_level`, `bind_device`, `bind_nonlocal` and
+ * `backends`. Blank lines and lines starting with `#` are skipped.
+ */
+pub fn parse_table(table: &str) -> Result<Vec<TableRow>, String> {
+    let mut lines = records(table);
+    let Some((_, header)) = lines.next() else {
+        return Err("Table is empty".to_string());
+    };
+    let delimiter = if header.contains('\t') { '\t' } else { ',' };
+    let columns = header_columns(header, delimiter);
+    if !columns.iter().any(|column| column == "name") {
+        return Err("Header must contain a name column".to_string());
+    }
+    Ok(lines
+        .map(|(row, line)| {
+            let values = split_record(line, delimiter);
+            let fields: HashMap<&str, &str> = columns
+                .iter()
+                .map(String::as_str)
+                .zip(values.iter().map(String::as_str))
+                .collect();
+            let name = fields
+                .get("name")
+                .filter(|name| !name.is_empty())
+                .map(|name| name.to_string());
+            let request = if values.len() > columns.len() {
+                Err(format!(
+                    "Row has {} fields, header has {}",
+                    values.len(),
+                    columns.len()
+                ))
+            } else {
+                parse_row(&fields)
+            };
+            TableRow { row, name, request }
+        })
+        .collect())
+}
+fn records(table: &str) -> impl Iterator<Item = (usize, &str)> {
+    table
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+}
+fn header_columns(header: &str, delimiter: char) -> Vec<String> {
+    split_record(header, delimiter)
+        .into_iter()
+        .map(|column| column.to_lowercase().replace(' ', "_"))
+        .collect()
+}
+/**
+ * Header columns of a table that `parse_table` does not read.
+ */
+pub fn unknown_columns(table: &str) -> Vec<String> {
+    let Some((_, header)) = records(table).next() else {
+        return Vec::new();
+    };
+    let delimiter = if header.contains('\t') { '\t' } else { ',' };
+    header_columns(header, delimiter)
+        .into_iter()
+        .filter(|column| !COLUMNS.contains(&column.as_str()))
+        .collect()
+}
@@ -1,23 +1,30 @@
-use crate::instance::{CreateInstanceRequestStrings, UpdateInstanceRequest};
-use crate::instance_manager::InstanceService;
+use crate::benchmark::{BenchmarkOptions, BenchmarkReport};
+use crate::instance::{
+    CreateInstanceRequestStrings, InvalidTransition, StatusTransition, UpdateInstanceRequest,
+};
+use crate::instance_manager::{InstanceService, UpsertResult};
+use crate::metrics::{BackendStats, ClosedConnection, SlowLogEntry};
+use crate::naming::NameError;
+use crate::replay::{RecordOptions, Recording, ReplayOptions, ReplayReport};
+use crate::table_import::{ImportReport, RowReport, RowStatus, parse_table, unknown_columns};
 use axum::{
-    Router,
+    Extension, Router,
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::Json,
-    routing::{get, post},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 #[derive(Serialize)]
-struct ErrorResponse {
+pub(crate) struct ErrorResponse {
     error: String,
     message: String,
 }
 impl ErrorResponse {
-    fn new(error: String, message: String) -> Self {
+    pub(crate) fn new(error: String, message: String) -> Self {
         Self { error, message }
     }
 }
@@ -30,14 +37,51 @@ pub fn create_routes(instance_service: Arc<InstanceService>) -> Router {
                 .put(update_instance)
                 .delete(delete_instance),
         )
+        .route(
+            "/api/instances/by-name/:name",
+            get(get_instance_by_name).put(upsert_instance),
+        )
+        .route("/api/instances/import-table", post(import_table))
         .route("/api/instances/:id/start", post(start_instance))
         .route("/api/instances/:id/stop", post(stop_instance))
+        .route("/api/instances/:id/restore", post(restore_instance))
+        .route("/api/trash", get(get_trash).delete(purge_trash))
+        .route("/api/trash/:id", delete(purge_trashed_instance))
         .route("/api/instances/:id/stats", get(get_instance_stats))
+        .route("/api/instances/:id/benchmark", post(benchmark_instance))
+        .route("/api/instances/:id/record", post(record_connection))
+        .route(
+            "/api/instances/:id/connections/history",
+            get(get_connection_history),
+        )
+        .route("/api/instances/:id/backends/stats", get(get_backend_stats))
+        .route("/api/instances/:id/status/history", get(get_status_history))
+        .route(
+            "/api/instances/:id/slowlog",
+            get(get_slow_log).delete(reset_slow_log),
+        )
+        .route("/api/debug/recordings", get(get_recordings))
+        .route("/api/debug/recordings/:id", get(get_recording))
+        .route("/api/debug/replay", post(replay_recording))
         .route("/api/stats", get(get_all_stats))
         .route("/api/config/export", get(export_config))
         .route("/api/config/import", post(import_config))
         .route("/api/config/backup", post(create_backup))
+        .route("/api/config/diff", get(diff_config))
+        .route("/api/config/defaults", get(get_instance_defaults))
         .route("/api/performance", get(get_performance_metrics))
+        .route("/api/blocklists", get(get_blocklists))
+        .route("/api/reports/traffic", get(get_traffic_reports))
+        .route("/api/locales", get(get_locales))
+        .route("/api/locales/:locale", get(get_locale_catalog))
+        .route(
+            "/api/settings/global-filter",
+            get(get_global_filter).put(set_global_filter),
+        )
+        .route(
+            "/api/settings/backup",
+            get(get_backup_schedule).put(set_backup_schedule),
+        )
         .route(
             "/api/instances/:id/session-metrics",
             get(get_instance_session_metrics),
@@ -45,6 +89,62 @@ pub fn create_routes(instance_service: Arc<InstanceService>) -> Router {
         .route("/api/health", get(health_check))
         .with_state(instance_service)
 }
+#[derive(Clone)]
+/**
+ * Bearer token granting the admin role, which is required to force changes
+ * to locked instances. Installed as a request extension by the daemon.
+ */
+pub struct AdminToken(pub String);
+#[derive(Deserialize, Debug, Default)]
+pub struct ForceQuery {
+    #[serde(default)]
+    pub force: bool,
+}
+fn is_admin(admin_token: Option<&AdminToken>, headers: &HeaderMap) -> bool {
+    let Some(AdminToken(expected)) = admin_token else {
+        return false;
+    };
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    !expected.is_empty()
+        && crate::cluster::constant_time_eq(provided.as_bytes(), expected.as_bytes())
+}
+async fn ensure_unlocked(
+    service: &InstanceService,
+    id: Uuid,
+    force: bool,
+    admin_token: Option<Extension<AdminToken>>,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    let Some(instance) = service.get_instance(id).await else {
+        return Ok(());
+    };
+    if !instance.locked {
+        return Ok(());
+    }
+    if !force {
+        let error_response = ErrorResponse::new(
+            "INSTANCE_LOCKED".to_string(),
+            format!(
+                "Instance {} is locked; retry with force=true as an administrator",
+                instance.name
+            ),
+        );
+        return Err((StatusCode::LOCKED, Json(error_response)));
+    }
+    if !is_admin(admin_token.as_ref().map(|Extension(token)| token), headers) {
+        let error_response = ErrorResponse::new(
+            "ADMIN_REQUIRED".to_string(),
+            "Forcing changes to a locked instance requires the admin token".to_string(),
+        );
+        return Err((StatusCode::FORBIDDEN, Json(error_response)));
+    }
+    info!("Admin forced change to locked instance {}", instance.name);
+    Ok(())
+}
 #[derive(Deserialize, Debug)]
 pub struct InstanceQuery {
     pub status: Option<String>,
@@ -77,12 +177,37 @@ async fn get_instance(
         None => Err(StatusCode::NOT_FOUND),
     }
 }
+async fn get_instance_by_name(
+    State(service): State<Arc<InstanceService>>,
+    Path(name): Path<String>,
+) -> Result<Json<crate::instance::ProxyInstance>, (StatusCode, Json<ErrorResponse>)> {
+    debug!("Getting instance by name: {}", name);
+    service.get_instance_by_name(&name).await.map(Json).ok_or_else(|| {
+        let error_response = ErrorResponse::new(
+            "NOT_FOUND".to_string(),
+            format!("Instance named {} not found", name),
+        );
+        (StatusCode::NOT_FOUND, Json(error_response))
+    })
+}
+/**
+ * Response for a name refused by the naming policy: `409` when another
+ * instance has it, `400` when it is empty or does not match the pattern.
+ */
+fn name_error_response(e: &anyhow::Error) -> Option<(StatusCode, Json<ErrorResponse>)> {
+    let (status, code) = match e.downcast_ref::<NameError>()? {
+        NameError::Duplicate(_) => (StatusCode::CONFLICT, "NAME_CONFLICT"),
+        NameError::Empty | NameError::Pattern { .. } => (StatusCode::BAD_REQUEST, "INVALID_NAME"),
+    };
+    let error_response = ErrorResponse::new(code.to_string(), e.to_string());
+    Some((status, Json(error_response)))
+}
 async fn create_instance(
     State(service): State<Arc<InstanceService>>,
     Json(request): Json<CreateInstanceRequestStrings>,
 ) -> Result<Json<crate::instance::ProxyInstance>, (StatusCode, Json<ErrorResponse>)> {
     debug!("Creating instance: {}", request.name);
-    match request.to_typed() {
+    match request.to_typed_with(&service.instance_defaults().await) {
         Ok(typed_request) => match service.create_instance(typed_request).await {
             Ok(instance) => {
                 info!("Created instance: {}", instance.name);
@@ -90,9 +215,11 @@ async fn create_instance(
             }
             Err(e) => {
                 error!("Failed to create instance: {}", e);
-                let error_response =
-                    ErrorResponse::new("CREATION_ERROR".to_string(), e.to_string());
-                Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
+                Err(name_error_response(&e).unwrap_or_else(|| {
+                    let error_response =
+                        ErrorResponse::new("CREATION_ERROR".to_string(), e.to_string());
+                    (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+                }))
             }
         },
         Err(e) => {
@@ -105,9 +232,13 @@ async fn create_instance(
 async fn update_instance(
     State(service): State<Arc<InstanceService>>,
     Path(id): Path<Uuid>,
+    Query(query): Query<ForceQuery>,
+    admin_token: Option<Extension<AdminToken>>,
+    headers: HeaderMap,
     Json(request): Json<UpdateInstanceRequest>,
 ) -> Result<Json<crate::instance::ProxyInstance>, (StatusCode, Json<ErrorResponse>)> {
     debug!("Updating instance: {}", id);
+    ensure_unlocked(&service, id, query.force, admin_token, &headers).await?;
     match service.update_instance(id, request).await {
         Ok(Some(instance)) => {
             info!("Updated instance: {}", instance.name);
@@ -122,25 +253,233 @@ async fn update_instance(
         }
         Err(e) => {
             error!("Failed to update instance {}: {}", id, e);
-            let error_response = ErrorResponse::new("VALIDATION_ERROR".to_string(), e.to_string());
-            Err((StatusCode::BAD_REQUEST, Json(error_response)))
+            Err(name_error_response(&e).unwrap_or_else(|| {
+                let error_response =
+                    ErrorResponse::new("VALIDATION_ERROR".to_string(), e.to_string());
+                (StatusCode::BAD_REQUEST, Json(error_response))
+            }))
         }
     }
 }
+async fn upsert_instance(
+    State(service): State<Arc<InstanceService>>,
+    Path(name): Path<String>,
+    Query(query): Query<ForceQuery>,
+    admin_token: Option<Extension<AdminToken>>,
+    headers: HeaderMap,
+    Json(mut body): Json<serde_json::Value>,
+) -> Result<(StatusCode, Json<UpsertResult>), (StatusCode, Json<ErrorResponse>)> {
+    debug!("Upserting instance: {}", name);
+    let validation_error = |message: String| {
+        error!("Invalid request data: {}", message);
+        let error_response = ErrorResponse::new("VALIDATION_ERROR".to_string(), message);
+        (StatusCode::BAD_REQUEST, Json(error_response))
+    };
+    let Some(fields) = body.as_object_mut() else {
+        return Err(validation_error("Request body must be a JSON object".to_string()));
+    };
+    match fields.get("name").and_then(|value| value.as_str()) {
+        Some(body_name) if body_name != name => {
+            return Err(validation_error(format!(
+                "Body name {} does not match path name {}",
+                body_name, name
+            )));
+        }
+        _ => {
+            fields.insert("name".to_string(), serde_json::Value::String(name.clone()));
+        }
+    }
+    let request: CreateInstanceRequestStrings =
+        serde_json::from_value(body).map_err(|e| validation_error(e.to_string()))?;
+    let typed_request = request
+        .to_typed_with(&service.instance_defaults().await)
+        .map_err(validation_error)?;
+    if let Some(existing) = service.get_instance_by_name(&name).await {
+        ensure_unlocked(&service, existing.id, query.force, admin_token, &headers).await?;
+    }
+    match service.upsert_instance(typed_request).await {
+        Ok(result) => {
+            if result.changed {
+                info!("Upserted instance: {}", result.instance.name);
+            }
+            let status = if result.created {
+                StatusCode::CREATED
+            } else {
+                StatusCode::OK
+            };
+            Ok((status, Json(result)))
+        }
+        Err(e) => {
+            error!("Failed to upsert instance {}: {}", name, e);
+            Err(name_error_response(&e).unwrap_or_else(|| {
+                let error_response =
+                    ErrorResponse::new("VALIDATION_ERROR".to_string(), e.to_string());
+                (StatusCode::BAD_REQUEST, Json(error_response))
+            }))
+        }
+    }
+}
+#[derive(Deserialize, Debug, Default)]
+pub struct ImportTableQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+async fn import_table(
+    State(service): State<Arc<InstanceService>>,
+    Query(query): Query<ImportTableQuery>,
+    body: String,
+) -> Result<Json<ImportReport>, (StatusCode, Json<ErrorResponse>)> {
+    debug!("Importing instance table (dry run: {})", query.dry_run);
+    let rows = parse_table(&body).map_err(|e| {
+        let error_response = ErrorResponse::new("VALIDATION_ERROR".to_string(), e);
+        (StatusCode::BAD_REQUEST, Json(error_response))
+    })?;
+    let mut names: std::collections::HashSet<String> = service
+        .get_instances()
+        .await
+        .into_iter()
+        .map(|instance| instance.name)
+        .collect();
+    let mut report = ImportReport {
+        created: 0,
+        failed: 0,
+        rows: Vec::with_capacity(rows.len()),
+        unknown_columns: unknown_columns(&body),
+    };
+    for row in rows {
+        let result = match row.request {
+            Ok(request) if !names.insert(request.name.clone()) => {
+                Err(format!("An instance named {} already exists", request.name))
+            }
+            Ok(_) if query.dry_run => Ok(None),
+            Ok(request) => service
+                .create_instance(request)
+                .await
+                .map(|instance| Some(instance.id))
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+        let (status, id, error) = match result {
+            Ok(Some(id)) => {
+                report.created += 1;
+                (RowStatus::Created, Some(id), None)
+            }
+            Ok(None) => (RowStatus::Valid, None, None),
+            Err(e) => {
+                report.failed += 1;
+                (RowStatus::Error, None, Some(e))
+            }
+        };
+        report.rows.push(RowReport {
+            row: row.row,
+            name: row.name,
+            status,
+            id,
+            error,
+        });
+    }
+    info!(
+        "Table import: {} created, {} failed",
+        report.created, report.failed
+    );
+    Ok(Json(report))
+}
 async fn delete_instance(
     State(service): State<Arc<InstanceService>>,
     Path(id): Path<Uuid>,
-) -> Result<StatusCode, StatusCode> {
+    Query(query): Query<ForceQuery>,
+    admin_token: Option<Extension<AdminToken>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
     debug!("Deleting instance: {}", id);
+    ensure_unlocked(&service, id, query.force, admin_token, &headers).await?;
     match service.delete_instance(id).await {
         Ok(true) => {
             info!("Deleted instance: {}", id);
             Ok(StatusCode::NO_CONTENT)
         }
-        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Ok(false) => {
+            let error_response = ErrorResponse::new(
+                "NOT_FOUND".to_string(),
+                format!("Instance with ID {} not found", id),
+            );
+            Err((StatusCode::NOT_FOUND, Json(error_response)))
+        }
         Err(e) => {
             error!("Failed to delete instance {}: {}", id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            let error_response = ErrorResponse::new("DELETE_ERROR".to_string(), e.to_string());
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
+        }
+    }
+}
+async fn get_trash(
+    State(service): State<Arc<InstanceService>>,
+) -> Result<Json<Vec<crate::instance::ProxyInstance>>, (StatusCode, Json<ErrorResponse>)> {
+    debug!("Getting deleted instances");
+    service.get_deleted_instances().await.map(Json).map_err(|e| {
+        error!("Failed to read the trash: {}", e);
+        let error_response = ErrorResponse::new("TRASH_ERROR".to_string(), e.to_string());
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(error_response))
+    })
+}
+async fn restore_instance(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<crate::instance::ProxyInstance>, (StatusCode, Json<ErrorResponse>)> {
+    debug!("Restoring deleted instance: {}", id);
+    match service.restore_deleted_instance(id).await {
+        Ok(Some(instance)) => {
+            info!("Restored instance: {}", instance.name);
+            Ok(Json(instance))
+        }
+        Ok(None) => {
+            let error_response = ErrorResponse::new(
+                "NOT_FOUND".to_string(),
+                format!("Deleted instance with ID {} not found", id),
+            );
+            Err((StatusCode::NOT_FOUND, Json(error_response)))
+        }
+        Err(e) => {
+            error!("Failed to restore instance {}: {}", id, e);
+            let error_response = ErrorResponse::new("RESTORE_ERROR".to_string(), e.to_string());
+            Err((StatusCode::CONFLICT, Json(error_response)))
+        }
+    }
+}
+async fn purge_trashed_instance(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+    debug!("Purging deleted instance: {}", id);
+    match service.purge_deleted_instances(Some(id)).await {
+        Ok(0) => {
+            let error_response = ErrorResponse::new(
+                "NOT_FOUND".to_string(),
+                format!("Deleted instance with ID {} not found", id),
+            );
+            Err((StatusCode::NOT_FOUND, Json(error_response)))
+        }
+        Ok(_) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => {
+            error!("Failed to purge instance {}: {}", id, e);
+            let error_response = ErrorResponse::new("PURGE_ERROR".to_string(), e.to_string());
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
+        }
+    }
+}
+async fn purge_trash(
+    State(service): State<Arc<InstanceService>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+    debug!("Emptying the trash");
+    match service.purge_deleted_instances(None).await {
+        Ok(purged) => {
+            info!("Purged {} deleted instances", purged);
+            Ok(Json(serde_json::json!({ "purged": purged })))
+        }
+        Err(e) => {
+            error!("Failed to empty the trash: {}", e);
+            let error_response = ErrorResponse::new("PURGE_ERROR".to_string(), e.to_string());
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
         }
     }
 }
@@ -159,6 +498,10 @@ async fn start_instance(
             }
         }
         Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) if e.is::<InvalidTransition>() => {
+            warn!("Refused to start instance {}: {}", id, e);
+            Err(StatusCode::CONFLICT)
+        }
         Err(e) => {
             error!("Failed to start instance {}: {}", id, e);
             Err(StatusCode::INTERNAL_SERVER_ERROR)
@@ -168,21 +511,38 @@ async fn start_instance(
 async fn stop_instance(
     State(service): State<Arc<InstanceService>>,
     Path(id): Path<Uuid>,
-) -> Result<Json<crate::instance::ProxyInstance>, StatusCode> {
+    Query(query): Query<ForceQuery>,
+    admin_token: Option<Extension<AdminToken>>,
+    headers: HeaderMap,
+) -> Result<Json<crate::instance::ProxyInstance>, (StatusCode, Json<ErrorResponse>)> {
     debug!("Stopping instance: {}", id);
+    ensure_unlocked(&service, id, query.force, admin_token, &headers).await?;
+    let not_found = || {
+        let error_response = ErrorResponse::new(
+            "NOT_FOUND".to_string(),
+            format!("Instance with ID {} not found", id),
+        );
+        (StatusCode::NOT_FOUND, Json(error_response))
+    };
     match service.stop_instance(id).await {
         Ok(true) => {
             if let Some(instance) = service.get_instance(id).await {
                 info!("Stopped instance: {}", instance.name);
                 Ok(Json(instance))
             } else {
-                Err(StatusCode::NOT_FOUND)
+                Err(not_found())
             }
         }
-        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Ok(false) => Err(not_found()),
+        Err(e) if e.is::<InvalidTransition>() => {
+            let error_response =
+                ErrorResponse::new("INVALID_TRANSITION".to_string(), e.to_string());
+            Err((StatusCode::CONFLICT, Json(error_response)))
+        }
         Err(e) => {
             error!("Failed to stop instance {}: {}", id, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            let error_response = ErrorResponse::new("STOP_ERROR".to_string(), e.to_string());
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)))
         }
     }
 }
@@ -197,6 +557,154 @@ async fn get_instance_stats(
         None => Err(StatusCode::NOT_FOUND),
     }
 }
+async fn benchmark_instance(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<BenchmarkOptions>,
+) -> Result<Json<BenchmarkReport>, (StatusCode, Json<ErrorResponse>)> {
+    info!("Benchmarking instance {}", id);
+    match service.benchmark_instance(id, options).await {
+        Ok(Some(report)) => Ok(Json(report)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "NOT_FOUND".to_string(),
+                format!("Instance {} not found", id),
+            )),
+        )),
+        Err(e) => {
+            error!("Benchmark of instance {} failed: {:#}", id, e);
+            Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "BENCHMARK_FAILED".to_string(),
+                    format!("{:#}", e),
+                )),
+            ))
+        }
+    }
+}
+async fn record_connection(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+    Query(options): Query<RecordOptions>,
+) -> Result<Json<Recording>, (StatusCode, Json<ErrorResponse>)> {
+    match service.record_connection(id, options).await {
+        Ok(Some(recording)) => Ok(Json(recording)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "NOT_FOUND".to_string(),
+                format!("Instance {} not found", id),
+            )),
+        )),
+        Err(e) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ErrorResponse::new(
+                "INVALID_RECORDING".to_string(),
+                e.to_string(),
+            )),
+        )),
+    }
+}
+async fn get_connection_history(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<ClosedConnection>>, StatusCode> {
+    debug!("Getting connection history of instance: {}", id);
+    service
+        .connection_history(id)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+async fn get_backend_stats(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<BackendStats>>, StatusCode> {
+    debug!("Getting backend statistics of instance: {}", id);
+    service
+        .backend_stats(id)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+async fn get_status_history(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<StatusTransition>>, StatusCode> {
+    debug!("Getting status history of instance: {}", id);
+    service
+        .status_history(id)
+        .await
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+async fn get_slow_log(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<SlowLogEntry>>, StatusCode> {
+    debug!("Getting slow log of instance: {}", id);
+    service.slow_log(id).await.map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+async fn reset_slow_log(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, StatusCode> {
+    match service.reset_slow_log(id).await {
+        Some(removed) => {
+            info!("Cleared {} slow-log entries of instance {}", removed, id);
+            Ok(Json(serde_json::json!({ "removed": removed })))
+        }
+        None => Err(StatusCode::NOT_FOUND),
+    }
+}
+async fn get_recordings(State(service): State<Arc<InstanceService>>) -> Json<Vec<Recording>> {
+    Json(service.recordings())
+}
+async fn get_recording(
+    State(service): State<Arc<InstanceService>>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Recording>, StatusCode> {
+    service.recording(id).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+#[derive(Deserialize)]
+pub struct ReplayRequest {
+    pub recording_id: Uuid,
+    #[serde(default)]
+    pub target: Option<std::net::SocketAddr>,
+    #[serde(flatten)]
+    pub options: ReplayOptions,
+}
+async fn replay_recording(
+    State(service): State<Arc<InstanceService>>,
+    Json(request): Json<ReplayRequest>,
+) -> Result<Json<ReplayReport>, (StatusCode, Json<ErrorResponse>)> {
+    info!("Replaying recording {}", request.recording_id);
+    match service
+        .replay_recording(request.recording_id, request.target, request.options)
+        .await
+    {
+        Ok(Some(report)) => Ok(Json(report)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            Json(ErrorResponse::new(
+                "NOT_FOUND".to_string(),
+                format!("Recording {} not found", request.recording_id),
+            )),
+        )),
+        Err(e) => {
+            error!("Replay of recording {} failed: {:#}", request.recording_id, e);
+            Err((
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse::new(
+                    "REPLAY_FAILED".to_string(),
+                    format!("{:#}", e),
+                )),
+            ))
+        }
+    }
+}
 async fn get_all_stats(
     State(service): State<Arc<InstanceService>>,
 ) -> Json<std::collections::HashMap<Uuid, crate::instance_manager::InstanceStats>> {
@@ -207,6 +715,15 @@ async fn get_all_stats(
 #[derive(Deserialize)]
 pub struct ImportConfigRequest {
     pub config: String,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub strict: bool,
+}
+async fn get_instance_defaults(
+    State(service): State<Arc<InstanceService>>,
+) -> Json<crate::config::InstanceDefaults> {
+    Json(service.instance_defaults().await)
 }
 async fn export_config(
     State(service): State<Arc<InstanceService>>,
@@ -224,12 +741,40 @@ async fn export_config(
 async fn import_config(
     State(service): State<Arc<InstanceService>>,
     Json(request): Json<ImportConfigRequest>,
-) -> Result<StatusCode, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Response, (StatusCode, Json<ErrorResponse>)> {
+    if request.dry_run {
+        debug!("Validating configuration import (dry run)");
+        return match service.plan_import(&request.config).await {
+            Ok(mut plan) => {
+                plan.valid &= !request.strict || plan.unknown_fields.is_empty();
+                Ok(Json(plan).into_response())
+            }
+            Err(e) => {
+                let error_response = ErrorResponse::new("IMPORT_ERROR".to_string(), e.to_string());
+                Err((StatusCode::BAD_REQUEST, Json(error_response)))
+            }
+        };
+    }
     debug!("Importing configuration");
+    if request.strict {
+        let unknown = crate::storage::StorageManager::unknown_fields(&request.config)
+            .map_err(|e| {
+                let error_response =
+                    ErrorResponse::new("IMPORT_ERROR".to_string(), e.to_string());
+                (StatusCode::BAD_REQUEST, Json(error_response))
+            })?;
+        if !unknown.is_empty() {
+            let error_response = ErrorResponse::new(
+                "UNKNOWN_FIELDS".to_string(),
+                format!("Unknown settings: {}", unknown.join(", ")),
+            );
+            return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+        }
+    }
     match service.import_config(&request.config).await {
         Ok(_) => {
             info!("Configuration imported successfully");
-            Ok(StatusCode::OK)
+            Ok(StatusCode::OK.into_response())
         }
         Err(e) => {
             error!("Failed to import configuration: {}", e);
@@ -246,6 +791,7 @@ async fn create_backup(
         Ok(backup_path) => {
             info!("Backup created: {:?}", backup_path);
             Ok(Json(BackupResponse {
+                backup_id: crate::storage::StorageManager::backup_id(&backup_path),
                 backup_path: backup_path.to_string_lossy().to_string(),
             }))
         }
@@ -263,6 +809,34 @@ struct ExportConfigResponse {
 #[derive(Serialize)]
 struct BackupResponse {
     pub backup_path: String,
+    pub backup_id: Option<String>,
+}
+#[derive(Deserialize, Debug)]
+pub struct DiffQuery {
+    pub against: Option<String>,
+}
+async fn diff_config(
+    State(service): State<Arc<InstanceService>>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<crate::config_diff::ConfigDiff>, (StatusCode, Json<ErrorResponse>)> {
+    let against = query.against.as_deref().unwrap_or("file");
+    debug!("Diffing running configuration against {}", against);
+    let backup_id = (against != "file").then_some(against);
+    match service.diff_config(backup_id).await {
+        Ok(Some(diff)) => Ok(Json(diff)),
+        Ok(None) => {
+            let error_response = ErrorResponse::new(
+                "NOT_FOUND".to_string(),
+                format!("No configuration found for {}", against),
+            );
+            Err((StatusCode::NOT_FOUND, Json(error_response)))
+        }
+        Err(e) => {
+            error!("Failed to diff configuration: {}", e);
+            let error_response = ErrorResponse::new("DIFF_ERROR".to_string(), e.to_string());
+            Err((StatusCode::BAD_REQUEST, Json(error_response)))
+        }
+    }
 }
 async fn get_performance_metrics(
     State(service): State<Arc<InstanceService>>,
@@ -271,6 +845,93 @@ async fn get_performance_metrics(
     let metrics = service.get_performance_metrics().await;
     Json(metrics)
 }
+async fn get_blocklists() -> Json<crate::blocklist::BlocklistStatus> {
+    Json(crate::blocklist::global().status())
+}
+#[derive(Debug, Deserialize)]
+pub struct TrafficReportQuery {
+    #[serde(default)]
+    pub group_by: crate::traffic_report::GroupBy,
+    pub limit: Option<usize>,
+}
+#[derive(Serialize)]
+pub struct TrafficReportResponse {
+    pub group_by: crate::traffic_report::GroupBy,
+    pub period_secs: u64,
+    pub reports: Vec<crate::traffic_report::TrafficReport>,
+}
+async fn get_traffic_reports(
+    Query(query): Query<TrafficReportQuery>,
+) -> Result<Json<TrafficReportResponse>, (StatusCode, Json<ErrorResponse>)> {
+    let traffic = crate::traffic_report::global();
+    if query.group_by == crate::traffic_report::GroupBy::Country && traffic.geoip().is_none() {
+        let error_response = ErrorResponse::new(
+            "GEOIP_UNAVAILABLE".to_string(),
+            "No GeoIP database loaded, start with --geoip-db".to_string(),
+        );
+        return Err((StatusCode::BAD_REQUEST, Json(error_response)));
+    }
+    Ok(Json(TrafficReportResponse {
+        group_by: query.group_by,
+        period_secs: traffic.report_interval().as_secs(),
+        reports: traffic.reports(query.group_by, query.limit.unwrap_or(100)),
+    }))
+}
+#[derive(Serialize)]
+pub struct LocalesResponse {
+    /** Locale matching the request's `Accept-Language`, for first visits. */
+    pub preferred: &'static str,
+    pub locales: Vec<crate::i18n::LocaleInfo>,
+}
+async fn get_locales(headers: HeaderMap) -> Json<LocalesResponse> {
+    let accept_language = headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    Json(LocalesResponse {
+        preferred: crate::i18n::negotiate(accept_language).locale,
+        locales: crate::i18n::available(),
+    })
+}
+async fn get_locale_catalog(
+    Path(locale): Path<String>,
+) -> Result<Json<&'static crate::i18n::Catalog>, (StatusCode, Json<ErrorResponse>)> {
+    crate::i18n::catalog(&locale).map(Json).ok_or_else(|| {
+        let error_response = ErrorResponse::new(
+            "LOCALE_NOT_FOUND".to_string(),
+            format!("No translation catalog for locale {}", locale),
+        );
+        (StatusCode::NOT_FOUND, Json(error_response))
+    })
+}
+async fn get_global_filter() -> Json<crate::global_filter::GlobalFilterStatus> {
+    Json(crate::global_filter::global().status())
+}
+async fn set_global_filter(
+    State(service): State<Arc<InstanceService>>,
+    Json(request): Json<crate::global_filter::GlobalFilterConfig>,
+) -> Json<crate::global_filter::GlobalFilterStatus> {
+    service.set_global_filter(request).await;
+    Json(crate::global_filter::global().status())
+}
+async fn get_backup_schedule(
+    State(service): State<Arc<InstanceService>>,
+) -> Json<crate::backup::BackupSchedule> {
+    Json(service.backup_schedule().await)
+}
+async fn set_backup_schedule(
+    State(service): State<Arc<InstanceService>>,
+    Json(request): Json<crate::backup::BackupSchedule>,
+) -> Result<Json<crate::backup::BackupSchedule>, (StatusCode, Json<ErrorResponse>)> {
+    match service.set_backup_schedule(request).await {
+        Ok(()) => Ok(Json(service.backup_schedule().await)),
+        Err(e) => {
+            warn!("Failed to update backup schedule: {}", e);
+            let error_response = ErrorResponse::new("INVALID_SETTINGS".to_string(), e.to_string());
+            Err((StatusCode::BAD_REQUEST, Json(error_response)))
+        }
+    }
+}
 async fn get_instance_session_metrics(
     State(service): State<Arc<InstanceService>>,
     Path(id): Path<Uuid>,
@@ -1,36 +1,198 @@
 use axum::{
     Router,
     extract::Path,
-    http::{StatusCode, header},
-    response::{Html, Response},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::Response,
     routing::get,
 };
 use include_dir::{Dir, include_dir};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, OnceLock};
 static STATIC_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/static");
+static STATIC_ASSETS: OnceLock<HashMap<&'static str, Asset>> = OnceLock::new();
+/** Embedded assets may be cached for a while, then revalidated by ETag. */
+const STATIC_CACHE_CONTROL: &str = "public, max-age=600";
+/** The index page embeds the API port and is revalidated on every load. */
+const INDEX_CACHE_CONTROL: &str = "no-cache";
+/** Bodies smaller than this are not worth compressing. */
+const MIN_COMPRESS_SIZE: usize = 512;
 pub fn create_routes(api_port: u16) -> Router {
+    let index = STATIC_DIR
+        .get_file("html/index.html")
+        .and_then(|f| f.contents_utf8())
+        .map(|html| {
+            let html = html.replace("{{API_PORT}}", &api_port.to_string());
+            Arc::new(Asset::new("text/html; charset=utf-8", html.into_bytes()))
+        });
     Router::new()
-        .route("/", get(move || root(api_port)))
+        .route(
+            "/",
+            get(move |headers: HeaderMap| root(index.clone(), headers)),
+        )
         .route("/static/*path", get(static_files))
 }
-async fn root(api_port: u16) -> Result<Html<String>, StatusCode> {
-    let html = STATIC_DIR
-        .get_file("html/index.html")
-        .and_then(|f| f.contents_utf8())
-        .ok_or_else(|| {
-            tracing::error!("index.html not found in embedded static files");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    let html_with_port = html.replace("{{API_PORT}}", &api_port.to_string());
-    Ok(Html(html_with_port))
-}
-async fn static_files(Path(path): Path<String>) -> Result<Response, StatusCode> {
-    if path.contains("..") {
-        tracing::warn!("Blocked path traversal attempt: {}", path);
-        return Err(StatusCode::FORBIDDEN);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/**
+ * Content coding of an asset response.
+ */
+pub enum Encoding {
+    Identity,
+    Gzip,
+    Brotli,
+}
+impl Encoding {
+    /**
+     * Best coding allowed by an `Accept-Encoding` header, preferring brotli
+     * over gzip. Codings with `q=0` are refused.
+     */
+    pub fn negotiate(accept_encoding: &str) -> Self {
+        let mut gzip = false;
+        let mut brotli = false;
+        for item in accept_encoding.split(',') {
+            let mut parts = item.split(';');
+            let coding = parts.next().unwrap_or_default().trim();
+            let refused = parts.any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.trim().parse::<f32>().ok())
+                    .is_some_and(|q| q <= 0.0)
+            });
+            if refused {
+                continue;
+            }
+            if coding.eq_ignore_ascii_case("br") {
+                brotli = true;
+            } else if coding.eq_ignore_ascii_case("gzip") || coding == "*" {
+                gzip = true;
+            }
+        }
+        if brotli {
+            Self::Brotli
+        } else if gzip {
+            Self::Gzip
+        } else {
+            Self::Identity
+        }
     }
-    let sanitized_path = path.trim_start_matches('/');
-    let file = STATIC_DIR.get_file(sanitized_path).ok_or(StatusCode::NOT_FOUND)?;
-    let content_type = match path.as_str() {
+    fn header_value(self) -> Option<&'static str> {
+        match self {
+            Self::Identity => None,
+            Self::Gzip => Some("gzip"),
+            Self::Brotli => Some("br"),
+        }
+    }
+}
+/**
+ * Embedded file with its ETag and compressed variants, computed once. A
+ * variant is kept only when it is smaller than the original.
+ */
+struct Asset {
+    content_type: &'static str,
+    etag: String,
+    identity: Vec<u8>,
+    gzip: Option<Vec<u8>>,
+    brotli: Option<Vec<u8>>,
+}
+impl Asset {
+    fn new(content_type: &'static str, content: Vec<u8>) -> Self {
+        let digest = Sha256::digest(&content);
+        let etag = digest[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        let compressible = content.len() >= MIN_COMPRESS_SIZE && is_compressible(content_type);
+        let smaller =
+            |compressed: Vec<u8>| (compressed.len() < content.len()).then_some(compressed);
+        let gzip = compressible.then(|| gzip(&content)).and_then(smaller);
+        let brotli = compressible.then(|| brotli(&content)).and_then(smaller);
+        Self {
+            content_type,
+            etag,
+            identity: content,
+            gzip,
+            brotli,
+        }
+    }
+    /**
+     * Body to send for the negotiated coding, falling back to gzip and then
+     * to the uncompressed file when a variant is missing.
+     */
+    fn variant(&self, wanted: Encoding) -> (Encoding, &[u8]) {
+        match (wanted, &self.brotli, &self.gzip) {
+            (Encoding::Brotli, Some(body), _) => (Encoding::Brotli, body),
+            (Encoding::Brotli | Encoding::Gzip, _, Some(body)) => (Encoding::Gzip, body),
+            _ => (Encoding::Identity, &self.identity),
+        }
+    }
+    fn respond(&self, headers: &HeaderMap, cache_control: &'static str) -> Response {
+        let wanted = headers
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .map_or(Encoding::Identity, Encoding::negotiate);
+        let (encoding, body) = self.variant(wanted);
+        let etag = match encoding.header_value() {
+            Some(coding) => format!("\"{}-{}\"", self.etag, coding),
+            None => format!("\"{}\"", self.etag),
+        };
+        let builder = Response::builder()
+            .header(header::CACHE_CONTROL, cache_control)
+            .header(header::ETAG, &etag)
+            .header(header::VARY, "Accept-Encoding");
+        let not_modified = headers
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|tags| etag_matches(tags, &etag));
+        let response = if not_modified {
+            builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Default::default())
+        } else {
+            let builder = builder.header(header::CONTENT_TYPE, self.content_type);
+            match encoding.header_value() {
+                Some(coding) => {
+                    builder.header(header::CONTENT_ENCODING, HeaderValue::from_static(coding))
+                }
+                None => builder,
+            }
+            .body(body.to_vec().into())
+        };
+        response.unwrap_or_else(|_| {
+            let mut response = Response::default();
+            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+            response
+        })
+    }
+}
+/**
+ * Whether an `If-None-Match` header lists `etag`, compared weakly.
+ */
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+fn is_compressible(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || content_type.starts_with("application/javascript")
+        || content_type.starts_with("application/json")
+        || content_type.starts_with("image/svg+xml")
+}
+fn gzip(content: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    let _ = encoder.write_all(content);
+    encoder.finish().unwrap_or_default()
+}
+fn brotli(content: &[u8]) -> Vec<u8> {
+    let mut compressed = Vec::new();
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+        let _ = encoder.write_all(content);
+    }
+    compressed
+}
+fn content_type(path: &str) -> &'static str {
+    match path {
         p if p.ends_with(".css") => "text/css",
         p if p.ends_with(".js") => "application/javascript",
         p if p.ends_with(".html") => "text/html",
@@ -39,10 +201,42 @@ async fn static_files(Path(path): Path<String>) -> Result<Response, StatusCode>
         p if p.ends_with(".svg") => "image/svg+xml",
         p if p.ends_with(".json") => "application/json",
         _ => "application/octet-stream",
-    };
-    let content = file.contents().to_vec();
-    Response::builder()
-        .header(header::CONTENT_TYPE, content_type)
-        .body(content.into())
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+fn static_assets() -> &'static HashMap<&'static str, Asset> {
+    STATIC_ASSETS.get_or_init(|| {
+        let mut assets = HashMap::new();
+        let mut dirs = vec![&STATIC_DIR];
+        while let Some(dir) = dirs.pop() {
+            for file in dir.files() {
+                if let Some(path) = file.path().to_str() {
+                    let asset = Asset::new(content_type(path), file.contents().to_vec());
+                    assets.insert(path, asset);
+                }
+            }
+            dirs.extend(dir.dirs());
+        }
+        assets
+    })
+}
+async fn root(index: Option<Arc<Asset>>, headers: HeaderMap) -> Result<Response, StatusCode> {
+    let index = index.ok_or_else(|| {
+        tracing::error!("index.html not found in embedded static files");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(index.respond(&headers, INDEX_CACHE_CONTROL))
+}
+async fn static_files(
+    Path(path): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    if path.contains("..") {
+        tracing::warn!("Blocked path traversal attempt: {}", path);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let sanitized_path = path.trim_start_matches('/');
+    let asset = static_assets()
+        .get(sanitized_path)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(asset.respond(&headers, STATIC_CACHE_CONTROL))
 }
@@ -0,0 +1,278 @@
+use crate::events::{EVENT_NAMES, Event};
+use crate::web_api::ErrorResponse;
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::{Method, StatusCode},
+    response::Json,
+    routing::{delete, get},
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{RwLock, broadcast};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const SIGNATURE_HEADER: &str = "x-voidproxy-signature";
+const EVENT_HEADER: &str = "x-voidproxy-event";
+const DELIVERY_HEADER: &str = "x-voidproxy-delivery";
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Outbound HTTP callback notified of lifecycle events.
+ *
+ * An empty `events` list subscribes to every event. When a secret is set the
+ * payload is signed with HMAC-SHA256 and the hex digest is sent as
+ * `X-VoidProxy-Signature: sha256=<digest>`.
+ */
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub events: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+impl Webhook {
+    fn matches(&self, event: &Event) -> bool {
+        self.events.is_empty() || self.events.iter().any(|name| name == event.kind.name())
+    }
+    fn redacted(&self) -> Self {
+        Self {
+            secret: self.secret.as_ref().map(|_| "********".to_string()),
+            ..self.clone()
+        }
+    }
+}
+impl CreateWebhookRequest {
+    pub fn validate(&self) -> Result<()> {
+        if !self.url.starts_with("http://") && !self.url.starts_with("https://") {
+            return Err(anyhow::anyhow!("Webhook URL must use http or https"));
+        }
+        if let Some(name) = self
+            .events
+            .iter()
+            .find(|name| !EVENT_NAMES.contains(&name.as_str()))
+        {
+            return Err(anyhow::anyhow!(
+                "Unknown event {}, expected one of {}",
+                name,
+                EVENT_NAMES.join(", ")
+            ));
+        }
+        Ok(())
+    }
+}
+/**
+ * Computes the `X-VoidProxy-Signature` value for a payload.
+ */
+pub fn sign_payload(secret: &str, payload: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", digest)
+}
+/**
+ * Registry of webhooks and dispatcher of lifecycle events to them.
+ *
+ * Registrations are persisted as JSON so they survive restarts. Failed
+ * deliveries are retried with exponential backoff.
+ */
+pub struct WebhookManager {
+    path: PathBuf,
+    webhooks: RwLock<HashMap<Uuid, Webhook>>,
+}
+impl WebhookManager {
+    pub async fn load(path: PathBuf) -> Result<Self> {
+        let webhooks = match tokio::fs::read_to_string(&path).await {
+            Ok(content) => serde_json::from_str::<Vec<Webhook>>(&content)
+                .with_context(|| format!("Invalid webhook file {:?}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+        };
+        info!("Loaded {} webhooks from {:?}", webhooks.len(), path);
+        Ok(Self {
+            path,
+            webhooks: RwLock::new(
+                webhooks
+                    .into_iter()
+                    .map(|webhook| (webhook.id, webhook))
+                    .collect(),
+            ),
+        })
+    }
+    async fn save(&self, webhooks: &HashMap<Uuid, Webhook>) -> Result<()> {
+        let mut list: Vec<&Webhook> = webhooks.values().collect();
+        list.sort_by_key(|webhook| webhook.created_at);
+        let content = serde_json::to_string_pretty(&list)?;
+        tokio::fs::write(&self.path, content)
+            .await
+            .with_context(|| format!("Failed to write {:?}", self.path))
+    }
+    pub async fn register(&self, request: CreateWebhookRequest) -> Result<Webhook> {
+        request.validate()?;
+        let webhook = Webhook {
+            id: Uuid::new_v4(),
+            url: request.url,
+            secret: request.secret.filter(|secret| !secret.is_empty()),
+            events: request.events,
+            created_at: Utc::now(),
+        };
+        let mut webhooks = self.webhooks.write().await;
+        webhooks.insert(webhook.id, webhook.clone());
+        self.save(&webhooks).await?;
+        info!("Registered webhook {} for {}", webhook.id, webhook.url);
+        Ok(webhook)
+    }
+    pub async fn list(&self) -> Vec<Webhook> {
+        let webhooks = self.webhooks.read().await;
+        let mut list: Vec<Webhook> = webhooks.values().map(Webhook::redacted).collect();
+        list.sort_by_key(|webhook| webhook.created_at);
+        list
+    }
+    pub async fn remove(&self, id: Uuid) -> Result<bool> {
+        let mut webhooks = self.webhooks.write().await;
+        if webhooks.remove(&id).is_none() {
+            return Ok(false);
+        }
+        self.save(&webhooks).await?;
+        info!("Removed webhook {}", id);
+        Ok(true)
+    }
+    /**
+     * Forwards every event received on `events` to the matching webhooks.
+     */
+    pub fn start(self: Arc<Self>, mut events: broadcast::Receiver<Event>) {
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => self.dispatch(&event).await,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Webhook dispatcher fell behind, {} events dropped", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+    async fn dispatch(&self, event: &Event) {
+        let payload = match serde_json::to_vec(event) {
+            Ok(payload) => payload,
+            Err(e) => {
+                error!("Failed to serialize event {}: {}", event.id, e);
+                return;
+            }
+        };
+        let webhooks = self.webhooks.read().await;
+        for webhook in webhooks.values().filter(|webhook| webhook.matches(event)) {
+            tokio::spawn(deliver(webhook.clone(), event.clone(), payload.clone()));
+        }
+    }
+}
+async fn deliver(webhook: Webhook, event: Event, payload: Vec<u8>) {
+    let mut headers = vec![
+        ("content-type", "application/json".to_string()),
+        (EVENT_HEADER, event.kind.name().to_string()),
+        (DELIVERY_HEADER, event.id.to_string()),
+    ];
+    if let Some(secret) = &webhook.secret {
+        headers.push((SIGNATURE_HEADER, sign_payload(secret, &payload)));
+    }
+    let mut delay = INITIAL_RETRY_DELAY;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let error = match crate::http_client::send(
+            Method::POST,
+            &webhook.url,
+            &headers,
+            Some(payload.clone()),
+        )
+        .await
+        {
+            Ok(response) if response.is_success() => {
+                debug!(
+                    "Delivered {} to webhook {}",
+                    event.kind.name(),
+                    webhook.url
+                );
+                return;
+            }
+            Ok(response) => format!("status {}", response.status),
+            Err(e) => e.to_string(),
+        };
+        if attempt == MAX_ATTEMPTS {
+            error!(
+                "Giving up delivering {} to webhook {} after {} attempts: {}",
+                event.kind.name(),
+                webhook.url,
+                attempt,
+                error
+            );
+            return;
+        }
+        warn!(
+            "Webhook {} failed ({}), retrying in {}s",
+            webhook.url,
+            error,
+            delay.as_secs()
+        );
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+}
+pub fn create_routes(webhooks: Arc<WebhookManager>) -> Router {
+    Router::new()
+        .route("/api/webhooks", get(list_webhooks).post(create_webhook))
+        .route("/api/webhooks/:id", delete(delete_webhook))
+        .with_state(webhooks)
+}
+async fn list_webhooks(State(webhooks): State<Arc<WebhookManager>>) -> Json<Vec<Webhook>> {
+    debug!("Listing webhooks");
+    Json(webhooks.list().await)
+}
+async fn create_webhook(
+    State(webhooks): State<Arc<WebhookManager>>,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<Webhook>), (StatusCode, Json<ErrorResponse>)> {
+    match webhooks.register(request).await {
+        Ok(webhook) => Ok((StatusCode::CREATED, Json(webhook.redacted()))),
+        Err(e) => {
+            error!("Failed to register webhook: {}", e);
+            let error_response = ErrorResponse::new("VALIDATION_ERROR".to_string(), e.to_string());
+            Err((StatusCode::BAD_REQUEST, Json(error_response)))
+        }
+    }
+}
+async fn delete_webhook(
+    State(webhooks): State<Arc<WebhookManager>>,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, StatusCode> {
+    match webhooks.remove(id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to remove webhook {}: {}", id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
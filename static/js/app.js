@@ -11,6 +11,7 @@ class VoidProxyManager {
 
     init() {
         console.log('VoidProxy Manager initialized');
+        this.renderLanguageSelect();
         this.setupEventListeners();
         this.loadInstances();
         this.injectIcons();
@@ -73,8 +74,31 @@ class VoidProxyManager {
         });
     }
 
+    renderLanguageSelect() {
+        const select = document.getElementById('languageSelect');
+        if (!select) return;
+
+        select.innerHTML = I18n.locales.map(({ locale, name }) =>
+            `<option value="${locale}"${locale === I18n.locale ? ' selected' : ''}>${Utils.escapeHtml(name)}</option>`
+        ).join('');
+        select.style.display = I18n.locales.length > 1 ? '' : 'none';
+    }
+
+    async changeLanguage(locale) {
+        try {
+            await I18n.setLocale(locale);
+            this.renderInstances();
+        } catch (error) {
+            console.error('Error changing language:', error);
+        }
+    }
+
+    statusLabel(status) {
+        return I18n.t(`status.${status}`);
+    }
+
     // Modal Management
-    showModal(title = 'Create New Proxy Instance', instance = null) {
+    showModal(title = I18n.t('modal.create_title'), instance = null) {
         this.editingId = instance ? instance.id : null;
 
         // Update modal title and button text
@@ -82,7 +106,7 @@ class VoidProxyManager {
         const saveButtonText = document.getElementById('saveButtonText');
 
         if (modalTitle) modalTitle.textContent = title;
-        if (saveButtonText) saveButtonText.textContent = instance ? 'Update Instance' : 'Create Instance';
+        if (saveButtonText) saveButtonText.textContent = I18n.t(instance ? 'instance.update' : 'instance.create');
 
         // Fill form if editing
         if (instance) {
@@ -138,17 +162,20 @@ class VoidProxyManager {
         const ipFilterType = document.getElementById('ipFilterType');
         const ipList = document.getElementById('ipList');
 
-        // Check if allow_list or deny_list exists in config
-        if (instance.config.ip_filter && instance.config.ip_filter.allow_list && instance.config.ip_filter.allow_list.length > 0) {
+        // Check if allow or deny entries (IPs and hostnames) exist in config
+        const ipFilter = instance.config.ip_filter || {};
+        const allowEntries = (ipFilter.allow_list || []).concat(ipFilter.allow_hosts || []);
+        const denyEntries = (ipFilter.deny_list || []).concat(ipFilter.deny_hosts || []);
+        if (allowEntries.length > 0) {
             if (ipFilterType) ipFilterType.value = 'allow';
             if (ipList) {
-                ipList.value = instance.config.ip_filter.allow_list.join('\n');
+                ipList.value = allowEntries.join('\n');
                 ipList.style.display = 'block';
             }
-        } else if (instance.config.ip_filter && instance.config.ip_filter.deny_list && instance.config.ip_filter.deny_list.length > 0) {
+        } else if (denyEntries.length > 0) {
             if (ipFilterType) ipFilterType.value = 'deny';
             if (ipList) {
-                ipList.value = instance.config.ip_filter.deny_list.join('\n');
+                ipList.value = denyEntries.join('\n');
                 ipList.style.display = 'block';
             }
         } else {
@@ -185,6 +212,8 @@ class VoidProxyManager {
                     instance.bytes_received = existing.bytes_received || 0;
                     instance.bytes_sent_per_sec = existing.bytes_sent_per_sec || 0;
                     instance.bytes_received_per_sec = existing.bytes_received_per_sec || 0;
+                    instance.tcp = existing.tcp;
+                    instance.udp = existing.udp;
                       instance.session_metrics = existing.session_metrics;
                 }
             });
@@ -194,7 +223,7 @@ class VoidProxyManager {
             this.updateStats();
         } catch (error) {
             console.error('Error loading instances:', error);
-            ToastSystem.show('Failed to load instances', 'error');
+            ToastSystem.show(I18n.t('toast.load_failed'), 'error');
         }
     }
 
@@ -210,11 +239,11 @@ class VoidProxyManager {
                             <div class="empty-icon">
                                 ${IconSystem.create('network', 'icon-xl')}
                             </div>
-                            <div class="empty-title">No Proxy Instances</div>
-                            <div class="empty-description">Create your first proxy instance to get started</div>
+                            <div class="empty-title">${I18n.t('empty.title')}</div>
+                            <div class="empty-description">${I18n.t('empty.description')}</div>
                             <button class="btn btn-primary" onclick="proxyManager.showModal()">
                                 <span class="btn-icon">${IconSystem.create('plus')}</span>
-                                Create Instance
+                                ${I18n.t('instance.create')}
                             </button>
                         </div>
                     </td>
@@ -227,6 +256,7 @@ class VoidProxyManager {
             <tr>
                 <td>
                     <strong>${Utils.escapeHtml(instance.name)}</strong>
+                    ${instance.locked ? `<span class="status-badge inactive" title="${Utils.escapeHtml(I18n.t('instance.locked_title'))}">${I18n.t('instance.locked')}</span>` : ''}
                 </td>
                 <td>${Utils.escapeHtml(instance.config.proxy.listen_ip)}:${instance.config.proxy.listen_port}</td>
                 <td>${Utils.escapeHtml(instance.config.proxy.dst_ip)}:${instance.config.proxy.dst_port}</td>
@@ -237,9 +267,9 @@ class VoidProxyManager {
                     </span>
                 </td>
                 <td>
-                    <span class="status-badge ${instance.status === 'running' ? 'active' : 'inactive'}">
+                    <span class="status-badge ${instance.status === 'running' ? 'active' : 'inactive'}"${instance.last_error ? ` title="${Utils.escapeHtml(I18n.t('instance.last_error', { category: instance.last_error.category, time: new Date(instance.last_error.timestamp).toLocaleString(I18n.locale), message: instance.last_error.message })).replace(/"/g, '&quot;')}"` : ''}>
                         <span class="status-dot"></span>
-                        ${instance.status.charAt(0).toUpperCase() + instance.status.slice(1)}
+                        ${this.statusLabel(instance.status)}
                     </span>
                 </td>
                 <td>
@@ -250,23 +280,24 @@ class VoidProxyManager {
                                 `${Utils.formatBytes((instance.bytes_sent_per_sec || 0) + (instance.bytes_received_per_sec || 0))}/s` :
                                 '-'}
                         </div>
+                        <div class="traffic-split" data-instance="${instance.id}">${this.transportSplit(instance)}</div>
                     </div>
                       <div class="session-metrics"></div>
                 </td>
                 <td>
                     <div class="table-actions">
                         ${instance.status === 'running' ?
-                            `<button class="btn btn-sm" onclick="proxyManager.toggleInstance('${instance.id}')" title="Stop">
+                            `<button class="btn btn-sm" onclick="proxyManager.toggleInstance('${instance.id}')" title="${I18n.t('action.stop')}">
                                 ${IconSystem.create('stop')}
                             </button>` :
-                            `<button class="btn btn-sm" onclick="proxyManager.toggleInstance('${instance.id}')" title="Start">
+                            `<button class="btn btn-sm" onclick="proxyManager.toggleInstance('${instance.id}')" title="${I18n.t('action.start')}">
                                 ${IconSystem.create('play')}
                             </button>`
                         }
-                        <button class="btn btn-sm" onclick="proxyManager.editInstance('${instance.id}')" title="Edit">
+                        <button class="btn btn-sm" onclick="proxyManager.editInstance('${instance.id}')" title="${I18n.t('action.edit')}">
                             ${IconSystem.create('edit')}
                         </button>
-                        <button class="btn btn-sm btn-danger" onclick="proxyManager.deleteInstance('${instance.id}')" title="Delete">
+                        <button class="btn btn-sm btn-danger" onclick="proxyManager.deleteInstance('${instance.id}')" title="${I18n.t('action.delete')}">
                             ${IconSystem.create('trash')}
                         </button>
                     </div>
@@ -275,6 +306,12 @@ class VoidProxyManager {
         `).join('');
     }
 
+    transportSplit(instance) {
+        if (instance.config.proxy.protocol !== 'both' || !instance.tcp || !instance.udp) return '';
+        const total = (counters) => Utils.formatBytes((counters.bytes_sent || 0) + (counters.bytes_received || 0));
+        return `TCP ${total(instance.tcp)} · UDP ${total(instance.udp)}`;
+    }
+
     updateStats() {
         const active = Array.from(this.instances.values()).filter(i => i.status === 'running').length;
         const inactive = this.instances.size - active;
@@ -346,8 +383,8 @@ class VoidProxyManager {
             if (sessionCell) {
                 sessionCell.innerHTML = `
                     <div class="session-info">
-                        <span class="session-count">${metrics.active_sessions} sessions</span>
-                        <span class="session-timeout">Timeout: ${metrics.session_timeout_seconds}s</span>
+                        <span class="session-count">${I18n.t('instance.sessions', { count: metrics.active_sessions })}</span>
+                        <span class="session-timeout">${I18n.t('instance.session_timeout', { secs: metrics.session_timeout_seconds })}</span>
                     </div>
                 `;
             }
@@ -362,6 +399,8 @@ class VoidProxyManager {
                 // Update metrics
                 instance.bytes_sent = statData.bytes_sent;
                 instance.bytes_received = statData.bytes_received;
+                instance.tcp = statData.tcp;
+                instance.udp = statData.udp;
                 instance.status = statData.status;
 
                 // Update status badge in DOM if changed
@@ -370,7 +409,7 @@ class VoidProxyManager {
                     statusCell.className = `status-badge ${statData.status === 'running' ? 'active' : 'inactive'}`;
                     const statusText = statusCell.querySelector('.status-dot').nextSibling;
                     if (statusText) {
-                        statusText.textContent = statData.status.charAt(0).toUpperCase() + statData.status.slice(1);
+                        statusText.textContent = this.statusLabel(statData.status);
                     }
                 }
 
@@ -379,10 +418,14 @@ class VoidProxyManager {
                 if (trafficCell) {
                     const totalElement = trafficCell.querySelector('.traffic-total');
                     const rateElement = trafficCell.querySelector('.traffic-rate');
+                    const splitElement = trafficCell.querySelector('.traffic-split');
 
                     if (totalElement) {
                         totalElement.textContent = Utils.formatBytes((statData.bytes_sent || 0) + (statData.bytes_received || 0));
                     }
+                    if (splitElement) {
+                        splitElement.textContent = this.transportSplit(instance);
+                    }
                     if (rateElement) {
                         const totalRate = (statData.bytes_sent_per_sec || 0) + (statData.bytes_received_per_sec || 0);
                         rateElement.textContent = totalRate > 0 ? `${Utils.formatBytes(totalRate)}/s` : '-';
@@ -421,13 +464,17 @@ class VoidProxyManager {
             const action = instance.status === 'running' ? 'stop' : 'start';
             const response = await fetch(`${window.API_BASE_URL}/api/instances/${id}/${action}`, { method: 'POST' });
 
+            if (response.status === 423) {
+                ToastSystem.show(I18n.t('toast.locked'), 'error');
+                return;
+            }
             if (!response.ok) throw new Error(`Failed to ${action} instance`);
 
             await this.loadInstances();
-            ToastSystem.show(`Instance ${action}ed successfully`, 'success');
+            ToastSystem.show(I18n.t(action === 'start' ? 'toast.started' : 'toast.stopped'), 'success');
         } catch (error) {
             console.error(`Error ${action}ing instance:`, error);
-            ToastSystem.show(`Failed to ${action} instance`, 'error');
+            ToastSystem.show(I18n.t(action === 'start' ? 'toast.start_failed' : 'toast.stop_failed'), 'error');
         }
     }
 
@@ -435,7 +482,7 @@ class VoidProxyManager {
         const instance = this.instances.get(id);
         if (!instance) return;
 
-        this.showModal('Edit Proxy Instance', instance);
+        this.showModal(I18n.t('modal.edit_title'), instance);
     }
 
     deleteInstance(id) {
@@ -443,21 +490,25 @@ class VoidProxyManager {
         if (!instance) return;
 
         ModalSystem.confirm(
-            'Delete Instance',
-            `Are you sure you want to delete "${Utils.escapeHtml(instance.name)}"?`,
+            I18n.t('confirm.delete_title'),
+            I18n.t('confirm.delete_message', { name: instance.name }),
             async () => {
                 try {
                     const response = await fetch(`${window.API_BASE_URL}/api/instances/${id}`, { method: 'DELETE' });
 
+                    if (response.status === 423) {
+                        ToastSystem.show(I18n.t('toast.locked'), 'error');
+                        return;
+                    }
                     if (!response.ok) throw new Error('Failed to delete instance');
 
                     this.instances.delete(id);
                     this.renderInstances();
                     this.updateStats();
-                    ToastSystem.show('Instance deleted successfully', 'success');
+                    ToastSystem.show(I18n.t('toast.deleted'), 'success');
                 } catch (error) {
                     console.error('Error deleting instance:', error);
-                    ToastSystem.show('Failed to delete instance', 'error');
+                    ToastSystem.show(I18n.t('toast.delete_failed'), 'error');
                 }
             }
         );
@@ -476,14 +527,18 @@ class VoidProxyManager {
                 body: JSON.stringify(formData)
             });
 
+            if (response.status === 423) {
+                ToastSystem.show(I18n.t('toast.locked'), 'error');
+                return;
+            }
             if (!response.ok) throw new Error('Failed to save instance');
 
             await this.loadInstances();
             this.hideModal();
-            ToastSystem.show(`Instance ${this.editingId ? 'updated' : 'created'} successfully`, 'success');
+            ToastSystem.show(I18n.t(this.editingId ? 'toast.updated' : 'toast.created'), 'success');
         } catch (error) {
             console.error('Error saving instance:', error);
-            ToastSystem.show('Failed to save instance', 'error');
+            ToastSystem.show(I18n.t('toast.save_failed'), 'error');
         }
     }
 
@@ -504,11 +559,17 @@ class VoidProxyManager {
             log_level: document.getElementById('logLevel').value
         };
 
-        // Add IP filtering based on type
+        // Add IP filtering based on type; entries with a letter and no ':' are hostnames
+        const entries = ipList.split('\n').map(entry => entry.trim()).filter(entry => entry);
+        const isHost = entry => /[a-z]/i.test(entry) && !entry.includes(':');
+        const ips = entries.filter(entry => !isHost(entry));
+        const hosts = entries.filter(isHost);
         if (ipFilterType === 'allow') {
-            data.allow_list = ipList.split('\n').map(ip => ip.trim()).filter(ip => ip);
+            if (ips.length > 0) data.allow_list = ips;
+            data.allow_hosts = hosts;
         } else if (ipFilterType === 'deny') {
-            data.deny_list = ipList.split('\n').map(ip => ip.trim()).filter(ip => ip);
+            if (ips.length > 0) data.deny_list = ips;
+            data.deny_hosts = hosts;
         }
 
         return data;
@@ -529,7 +590,12 @@ function saveInstance() {
     proxyManager.saveInstance();
 }
 
-// Initialize when DOM is ready
-document.addEventListener('DOMContentLoaded', () => {
+function changeLanguage(locale) {
+    proxyManager.changeLanguage(locale);
+}
+
+// Initialize when DOM is ready, once translations are loaded
+document.addEventListener('DOMContentLoaded', async () => {
+    await I18n.init();
     window.proxyManager = new VoidProxyManager();
 });
\ No newline at end of file
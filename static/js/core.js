@@ -178,10 +178,10 @@ class ModalSystem {
         `, {
             footer: `
                 <button class="btn btn-secondary" onclick="ModalSystem.handleConfirmCancel()">
-                    Cancel
+                    ${I18n.t('action.cancel')}
                 </button>
                 <button class="btn btn-danger" onclick="ModalSystem.handleConfirmOk()">
-                    Delete
+                    ${I18n.t('action.delete')}
                 </button>
             `
         });
@@ -213,7 +213,73 @@ class ModalSystem {
     }
 }
 
+// Internationalization - translation catalogs served by the API
+class I18n {
+    static STORAGE_KEY = 'voidproxy.locale';
+    static locale = 'en';
+    static locales = [];
+    static messages = {};
+
+    static async init() {
+        try {
+            const response = await fetch(`${window.API_BASE_URL}/api/locales`);
+            if (!response.ok) throw new Error('Failed to load locales');
+
+            const { preferred, locales } = await response.json();
+            this.locales = locales;
+
+            // The choice made in this browser wins over Accept-Language
+            const stored = localStorage.getItem(this.STORAGE_KEY);
+            const locale = locales.some(l => l.locale === stored) ? stored : preferred;
+            await this.load(locale);
+        } catch (error) {
+            console.error('Error loading translations:', error);
+        }
+    }
+
+    static async load(locale) {
+        const response = await fetch(`${window.API_BASE_URL}/api/locales/${encodeURIComponent(locale)}`);
+        if (!response.ok) throw new Error(`Failed to load locale ${locale}`);
+
+        const catalog = await response.json();
+        this.locale = catalog.locale;
+        this.messages = catalog.messages;
+        document.documentElement.lang = catalog.locale;
+        this.apply();
+    }
+
+    static async setLocale(locale) {
+        localStorage.setItem(this.STORAGE_KEY, locale);
+        await this.load(locale);
+    }
+
+    // Message for a key with {name} placeholders filled from params
+    static t(key, params = {}) {
+        const message = this.messages[key] ?? key;
+        return message.replace(/\{(\w+)\}/g, (match, name) => name in params ? params[name] : match);
+    }
+
+    // Translate static markup; elements keep their text when a key is missing
+    static apply(root = document) {
+        const attributes = { 'data-i18n-placeholder': 'placeholder', 'data-i18n-title': 'title' };
+        root.querySelectorAll('[data-i18n]').forEach(element => {
+            if (this.messages[element.dataset.i18n]) {
+                element.textContent = this.t(element.dataset.i18n);
+            }
+        });
+        Object.entries(attributes).forEach(([attribute, target]) => {
+            root.querySelectorAll(`[${attribute}]`).forEach(element => {
+                const key = element.getAttribute(attribute);
+                if (this.messages[key]) {
+                    element.setAttribute(target, this.t(key));
+                }
+            });
+        });
+    }
+}
+
 // Make available globally
+window.I18n = I18n;
 window.IconSystem = IconSystem;
 window.Utils = Utils;
 window.ToastSystem = ToastSystem;
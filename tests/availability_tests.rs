@@ -0,0 +1,98 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use tempfile::TempDir;
+use uuid::Uuid;
+use void_proxy::availability::{
+    AvailabilityRecord, AvailabilityTracker, compute_report, parse_range,
+};
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::storage::StorageManager;
+
+fn at(minutes: i64) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+        .unwrap()
+        .with_timezone(&Utc)
+        + Duration::minutes(minutes)
+}
+
+fn record(id: Uuid, minutes: i64, up: bool, reason: Option<&str>) -> AvailabilityRecord {
+    AvailabilityRecord {
+        timestamp: at(minutes),
+        instance_id: id,
+        up,
+        reason: reason.map(str::to_string),
+    }
+}
+
+#[test]
+fn test_parse_range() {
+    assert_eq!(parse_range("30d").unwrap(), Duration::days(30));
+    assert_eq!(parse_range("90m").unwrap(), Duration::minutes(90));
+    assert!(parse_range("30").is_err());
+    assert!(parse_range("0h").is_err());
+    assert!(parse_range("5é").is_err());
+}
+
+#[test]
+fn test_compute_report_counts_downtime_incidents() {
+    let id = Uuid::new_v4();
+    let other = Uuid::new_v4();
+    let records = vec![
+        record(id, 0, true, None),
+        record(other, 10, false, Some("stopped")),
+        record(id, 30, false, Some("failed: TCP proxy: boom")),
+        record(id, 35, false, Some("stopped")),
+        record(id, 40, true, None),
+        record(id, 90, false, Some("stopped")),
+    ];
+
+    let report = compute_report(id, &records, at(20), at(100));
+    assert_eq!(report.tracked_secs, 80 * 60);
+    assert_eq!(report.uptime_secs, 60 * 60);
+    assert_eq!(report.downtime_secs, 20 * 60);
+    assert_eq!(report.uptime_percent, Some(75.0));
+    assert_eq!(report.incidents.len(), 2);
+    assert_eq!(report.incidents[0].start, at(30));
+    assert_eq!(report.incidents[0].end, Some(at(40)));
+    assert_eq!(
+        report.incidents[0].reason.as_deref(),
+        Some("failed: TCP proxy: boom")
+    );
+    assert_eq!(report.incidents[1].end, None);
+
+    let untracked = compute_report(Uuid::new_v4(), &records, at(0), at(100));
+    assert_eq!(untracked.uptime_percent, None);
+}
+
+#[tokio::test]
+async fn test_tracker_closes_periods_left_open_by_daemon() {
+    let temp_dir = TempDir::new().unwrap();
+    let log_path = temp_dir.path().join("availability.jsonl");
+    let id = Uuid::new_v4();
+    let line = serde_json::to_string(&record(id, 0, true, None)).unwrap();
+    std::fs::write(&log_path, format!("{}\n", line)).unwrap();
+    std::fs::write(
+        temp_dir.path().join("availability.heartbeat"),
+        at(45).to_rfc3339(),
+    )
+    .unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = Arc::new(InstanceService::with_storage(storage));
+
+    let tracker = AvailabilityTracker::load(log_path.clone(), service)
+        .await
+        .unwrap();
+    tracker.record(id, true, None).await.unwrap();
+
+    let report = tracker.report(id, at(0), at(60)).await;
+    assert_eq!(report.uptime_secs, 45 * 60);
+    assert_eq!(report.incidents[0].start, at(45));
+    assert_eq!(
+        report.incidents[0].reason.as_deref(),
+        Some("daemon stopped")
+    );
+    assert_eq!(
+        std::fs::read_to_string(&log_path).unwrap().lines().count(),
+        3
+    );
+}
@@ -0,0 +1,180 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use void_proxy::backend::{BackendEntry, BackendSet, parse_backend_entry};
+use void_proxy::config::{LogLevel, Protocol, ProxyConfig};
+use void_proxy::happy_eyeballs;
+
+fn proxy_config(backends: &[&str]) -> ProxyConfig {
+    ProxyConfig {
+        listen_ip: "127.0.0.1".parse().unwrap(),
+        listen_port: 18600,
+        dst_ip: "127.0.0.1".parse().unwrap(),
+        dst_port: 80,
+        protocol: Protocol::Tcp,
+        connect_timeout_secs: 30,
+        idle_timeout_secs: 300,
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: backends.iter().map(|backend| backend.to_string()).collect(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn test_static_backends_round_robin() {
+    assert!(BackendSet::from_config(&proxy_config(&[])).is_none());
+
+    let backends = BackendSet::from_config(&proxy_config(&["10.0.0.1:80", "10.0.0.2:80"])).unwrap();
+    let first: SocketAddr = "10.0.0.1:80".parse().unwrap();
+    let second: SocketAddr = "10.0.0.2:80".parse().unwrap();
+    assert_eq!(backends.pick(), Some(first));
+    assert_eq!(backends.pick(), Some(second));
+    assert_eq!(backends.pick(), Some(first));
+
+    let peer: SocketAddr = "192.168.1.50:4000".parse().unwrap();
+    assert_eq!(backends.pick_sticky(&peer), backends.pick_sticky(&peer));
+}
+
+#[tokio::test]
+async fn test_parse_discovery_backend_entries() {
+    match parse_backend_entry("k8s://default/web:http").unwrap() {
+        BackendEntry::Provider(provider) => assert_eq!(provider.describe(), "k8s://default/web"),
+        BackendEntry::Static(_) => panic!("expected a discovery provider"),
+    }
+    match parse_backend_entry("srv://_http._tcp.example.com").unwrap() {
+        BackendEntry::Provider(provider) => {
+            assert_eq!(provider.describe(), "srv://_http._tcp.example.com")
+        }
+        BackendEntry::Static(_) => panic!("expected a discovery provider"),
+    }
+    assert!(parse_backend_entry("k8s://web").is_err());
+    assert!(parse_backend_entry("srv://").is_err());
+    assert!(parse_backend_entry("unknown://web").is_err());
+    assert!(parse_backend_entry("not-an-address").is_err());
+    assert!(parse_backend_entry("bad host:80").is_err());
+    match parse_backend_entry("db.example.com:5432").unwrap() {
+        BackendEntry::Provider(provider) => assert_eq!(provider.describe(), "db.example.com:5432"),
+        BackendEntry::Static(_) => panic!("expected a hostname provider"),
+    }
+}
+
+#[tokio::test]
+async fn test_hostname_backend_resolves_to_tagged_addresses() {
+    let backends = BackendSet::from_config(&proxy_config(&["localhost:8080"])).unwrap();
+    backends.refresh().await;
+
+    let resolved = backends.backends();
+    let local: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+    assert!(resolved.iter().any(|backend| backend.addr == local));
+    assert!(
+        resolved
+            .iter()
+            .all(|backend| backend.host.as_deref() == Some("localhost"))
+    );
+    let candidates = backends.connect_candidates(local);
+    assert_eq!(candidates[0], local);
+    assert_eq!(candidates.len(), resolved.len());
+}
+
+#[test]
+fn test_happy_eyeballs_interleaves_address_families() {
+    let addrs: Vec<SocketAddr> = [
+        "[2001:db8::1]:80",
+        "[2001:db8::2]:80",
+        "192.0.2.1:80",
+        "192.0.2.2:80",
+    ]
+    .iter()
+    .map(|addr| addr.parse().unwrap())
+    .collect();
+    let ordered = happy_eyeballs::interleave(&addrs);
+    assert_eq!(ordered, vec![addrs[0], addrs[2], addrs[1], addrs[3]]);
+}
+
+#[tokio::test]
+async fn test_happy_eyeballs_falls_back_to_reachable_address() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let reachable = listener.local_addr().unwrap();
+    let closed = {
+        let socket = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        socket.local_addr().unwrap()
+    };
+    let blackhole: SocketAddr = "[2001:db8::1]:80".parse().unwrap();
+
+    let started = Instant::now();
+    let stream = happy_eyeballs::connect(
+        &[blackhole, closed, reachable],
+        Duration::from_millis(100),
+        tokio::net::TcpStream::connect,
+    )
+    .await
+    .unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), reachable);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    assert!(
+        happy_eyeballs::connect(
+            &[closed],
+            Duration::from_millis(100),
+            tokio::net::TcpStream::connect
+        )
+        .await
+        .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_consul_provider_reads_health_catalog() {
+    let catalog = serde_json::json!([
+        {
+            "Node": {"Address": "10.0.0.5"},
+            "Service": {"Address": "", "Port": 8080, "Weights": {"Passing": 3}},
+            "Checks": [{"Status": "passing"}]
+        },
+        {
+            "Node": {"Address": "10.0.0.6"},
+            "Service": {"Address": "10.1.0.6", "Port": 8080},
+            "Checks": [{"Status": "passing"}, {"Status": "critical"}]
+        }
+    ]);
+    let app = axum::Router::new().route(
+        "/v1/health/service/web",
+        axum::routing::get(move || async move { axum::Json(catalog) }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let agent_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    unsafe { std::env::set_var("CONSUL_HTTP_ADDR", agent_addr.to_string()) };
+    let BackendEntry::Provider(provider) = parse_backend_entry("consul://web").unwrap() else {
+        panic!("expected a discovery provider");
+    };
+    let backends = provider.resolve().await.unwrap().backends;
+
+    assert_eq!(backends.len(), 2);
+    assert_eq!(backends[0].addr, "10.0.0.5:8080".parse().unwrap());
+    assert_eq!(backends[0].weight, 3);
+    assert!(backends[0].healthy);
+    assert_eq!(backends[1].addr, "10.1.0.6:8080".parse().unwrap());
+    assert!(!backends[1].healthy);
+}
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+use tempfile::TempDir;
+use void_proxy::backup::{BackupFrequency, BackupSchedule};
+use void_proxy::backup_scheduler::BackupScheduler;
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::storage::StorageManager;
+
+#[tokio::test]
+async fn test_scheduled_backup_is_pruned() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let old_backup = storage.backup_path_for("20200101_000000").unwrap();
+    tokio::fs::write(&old_backup, "").await.unwrap();
+    let service = Arc::new(InstanceService::with_storage(storage.clone()));
+    let schedule = BackupSchedule {
+        frequency: BackupFrequency::Daily,
+        keep: 1,
+        ..Default::default()
+    };
+    service.set_backup_schedule(schedule.clone()).await.unwrap();
+
+    let backup_path = BackupScheduler::new(service).run(&schedule).await.unwrap();
+    assert!(!old_backup.exists());
+    assert_eq!(storage.list_backups().await.unwrap(), vec![backup_path]);
+}
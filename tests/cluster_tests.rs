@@ -0,0 +1,105 @@
+use void_proxy::cluster::{ClusterConfig, ClusterManager, ClusterRole, create_routes};
+use void_proxy::config::{LogLevel, Protocol};
+use void_proxy::instance::CreateInstanceRequest;
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::storage::StorageManager;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn cluster_config(role: ClusterRole, leader_url: Option<String>) -> ClusterConfig {
+    ClusterConfig {
+        role,
+        leader_url,
+        token: Some("secret".to_string()),
+        sync_interval: Duration::from_secs(5),
+    }
+}
+
+#[tokio::test]
+async fn test_cluster_config_validation() {
+    assert!(cluster_config(ClusterRole::Leader, None).validate().is_ok());
+    assert!(cluster_config(ClusterRole::Follower, None).validate().is_err());
+
+    let mut no_token = cluster_config(ClusterRole::Leader, None);
+    no_token.token = None;
+    assert!(no_token.validate().is_err());
+
+    no_token.role = ClusterRole::Standalone;
+    assert!(no_token.validate().is_ok());
+}
+
+#[tokio::test]
+async fn test_follower_replicates_leader_instances() {
+    let temp_dir = TempDir::new().unwrap();
+    let leader_service = Arc::new(InstanceService::with_storage(Arc::new(StorageManager::new(
+        temp_dir.path().join("leader.toml"),
+    ))));
+    let follower_service = Arc::new(InstanceService::with_storage(Arc::new(
+        StorageManager::new(temp_dir.path().join("follower.toml")),
+    )));
+
+    leader_service
+        .create_instance(CreateInstanceRequest {
+            name: "Replicated Instance".to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            listen_port: 8080,
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100)),
+            dst_port: 80,
+            protocol: Protocol::Tcp,
+            auto_start: false,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        })
+        .await
+        .unwrap();
+
+    let leader = Arc::new(ClusterManager::new(
+        cluster_config(ClusterRole::Leader, None),
+        leader_service,
+    ));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let leader_addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, create_routes(leader)).await.unwrap();
+    });
+
+    let follower = ClusterManager::new(
+        cluster_config(ClusterRole::Follower, Some(format!("http://{}", leader_addr))),
+        follower_service.clone(),
+    );
+    assert!(follower.sync_once().await.unwrap());
+    assert!(!follower.sync_once().await.unwrap());
+
+    let instances = follower_service.get_instances().await;
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances[0].name, "Replicated Instance");
+    assert!(follower.status().await.applied_version.is_some());
+}
@@ -1,4 +1,8 @@
-use void_proxy::config::{Config, ProxyConfig, Protocol};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use void_proxy::builder::ProxyBuilder;
+use void_proxy::config::{Config, LogLevel, ProxyConfig, Protocol};
+use void_proxy::host_acl::ResolvedHosts;
 
 #[tokio::test]
 async fn test_config_creation() {
@@ -11,9 +15,32 @@ async fn test_config_creation() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     assert_eq!(config.proxy.listen_port, 8080);
@@ -21,7 +48,7 @@ async fn test_config_creation() {
     assert_eq!(config.proxy.protocol, Protocol::Tcp);
     assert_eq!(config.proxy.connect_timeout_secs, 30);
     assert_eq!(config.proxy.idle_timeout_secs, 300);
-    assert_eq!(config.proxy.log_level, "info");
+    assert_eq!(config.proxy.log_level, LogLevel::Info);
 }
 
 #[tokio::test]
@@ -35,9 +62,32 @@ async fn test_config_validation() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     // Test validation - this should not panic
@@ -56,21 +106,46 @@ async fn test_config_with_timeouts() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 10,
             idle_timeout_secs: 60,
-            log_level: "debug".to_string(),
+            log_level: LogLevel::Debug,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     assert_eq!(config.proxy.connect_timeout_secs, 10);
     assert_eq!(config.proxy.idle_timeout_secs, 60);
-    assert_eq!(config.proxy.log_level, "debug");
+    assert_eq!(config.proxy.log_level, LogLevel::Debug);
 }
 
 #[tokio::test]
 async fn test_config_log_levels() {
     let log_levels = vec!["error", "warn", "info", "debug", "trace"];
 
-    for level in log_levels {
+    for name in log_levels {
+        let level: LogLevel = name.parse().unwrap();
+        assert_eq!(level.to_string(), name);
         let config = Config {
             proxy: ProxyConfig {
                 listen_ip: "127.0.0.1".parse().unwrap(),
@@ -80,15 +155,38 @@ async fn test_config_log_levels() {
                 protocol: Protocol::Tcp,
                 connect_timeout_secs: 30,
                 idle_timeout_secs: 300,
-                log_level: level.to_string(),
+                log_level: level,
+                bind_device: None,
+                bind_nonlocal: false,
+                backends: Vec::new(),
+                wasm_filters: Vec::new(),
+                listen_backlog: None,
+                bind_src_ip: None,
+                bind_src_device: None,
+                max_inflight_bytes: None,
+                tls: None,
+                tunnel: None,
+                slow_log: None,
+                max_concurrent_handlers: None,
+                buffer_pool_size: None,
+                max_concurrent_buffers: None,
+                ip_cache_size: None,
+                ip_cache_ttl_secs: None,
+                propagate_unreachable: false,
+                log_limit: None,
+                mirror: None,
+                max_connection_lifetime_secs: None,
+                verify_destination_on_start: Default::default(),
             },
             ip_filter: None,
+            profiles: BTreeMap::new(),
+            tags: BTreeMap::new(),
         };
 
         assert_eq!(config.proxy.log_level, level);
         // Test that validation passes for valid log levels
         let result = config.validate();
-        assert!(result.is_ok(), "Log level {} should be valid", level);
+        assert!(result.is_ok(), "Log level {} should be valid", name);
     }
 }
 
@@ -103,11 +201,59 @@ async fn test_config_timeout_bounds() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 1,  // Minimum value
             idle_timeout_secs: 3600, // Maximum value
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     assert_eq!(config.proxy.connect_timeout_secs, 1);
     assert_eq!(config.proxy.idle_timeout_secs, 3600);
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_filter_hostnames_use_resolved_addresses() {
+    let mut config = ProxyBuilder::new(
+        "127.0.0.1:8080".parse().unwrap(),
+        "127.0.0.1:8081".parse().unwrap(),
+    )
+    .allow_hosts(vec!["home.example.org".to_string()])
+    .into_request()
+    .to_config();
+    assert!(config.validate().is_ok());
+    let home: IpAddr = "203.0.113.7".parse().unwrap();
+    let mut resolved = ResolvedHosts::default();
+    assert!(!config.is_ip_allowed_with(&home, &resolved));
+    resolved.allow.insert(home);
+    assert!(config.is_ip_allowed_with(&home, &resolved));
+    assert!(!config.is_ip_allowed_with(&"203.0.113.8".parse().unwrap(), &resolved));
+
+    let filter = config.ip_filter.as_mut().unwrap();
+    filter.deny_list = Some(vec!["10.0.0.1".parse().unwrap()]);
+    assert!(config.validate().is_err());
+    let filter = config.ip_filter.as_mut().unwrap();
+    filter.deny_list = None;
+    filter.allow_hosts = vec!["not a host".to_string()];
+    assert!(config.validate().is_err());
+}
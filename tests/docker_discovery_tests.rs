@@ -0,0 +1,78 @@
+use void_proxy::config::Protocol;
+use void_proxy::docker_discovery::{DockerContainer, DockerDiscovery};
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::storage::StorageManager;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const CONTAINERS_JSON: &str = r#"[{
+    "Id": "4f2a9c1b7e3d",
+    "Names": ["/web"],
+    "Labels": {
+        "voidproxy.listen_port": "18580",
+        "voidproxy.listen_ip": "127.0.0.1",
+        "voidproxy.target_port": "80",
+        "voidproxy.protocol": "udp"
+    },
+    "NetworkSettings": {"Networks": {"bridge": {"IPAddress": "172.17.0.2"}}}
+}]"#;
+
+#[tokio::test]
+async fn test_container_labels_to_instance_request() {
+    let containers: Vec<DockerContainer> = serde_json::from_str(CONTAINERS_JSON).unwrap();
+    let request = containers[0].to_instance_request().unwrap();
+
+    assert_eq!(request.name, "docker/web");
+    assert_eq!(request.listen_ip, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+    assert_eq!(request.listen_port, 18580);
+    assert_eq!(request.dst_ip, IpAddr::V4(Ipv4Addr::new(172, 17, 0, 2)));
+    assert_eq!(request.dst_port, 80);
+    assert_eq!(request.protocol, Protocol::Udp);
+    assert!(request.auto_start);
+}
+
+#[tokio::test]
+async fn test_container_without_target_port_is_rejected() {
+    let json = r#"{"Id": "abc", "Names": ["/db"], "Labels": {"voidproxy.listen_port": "5432"}}"#;
+    let container: DockerContainer = serde_json::from_str(json).unwrap();
+
+    assert!(container.to_instance_request().is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_docker_discovery_sync_creates_and_removes_instances() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let temp_dir = TempDir::new().unwrap();
+    let socket_path = temp_dir.path().join("docker.sock");
+    let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+    tokio::spawn(async move {
+        for body in [CONTAINERS_JSON, "[]"] {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+
+    let service = Arc::new(InstanceService::with_storage(Arc::new(StorageManager::new(
+        temp_dir.path().join("test_config.toml"),
+    ))));
+    let discovery = DockerDiscovery::new(socket_path, Duration::from_secs(5), service.clone());
+
+    discovery.sync_once().await.unwrap();
+    let instances = service.get_instances().await;
+    assert_eq!(instances.len(), 1);
+    assert_eq!(instances[0].name, "docker/web");
+
+    discovery.sync_once().await.unwrap();
+    assert!(service.get_instances().await.is_empty());
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use void_proxy::i18n::{available, catalog, catalogs, negotiate};
+
+fn raw_keys(locale: &str) -> Vec<String> {
+    let path = format!("{}/locales/{}.json", env!("CARGO_MANIFEST_DIR"), locale);
+    let messages: BTreeMap<String, String> =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    messages.into_keys().collect()
+}
+
+#[test]
+fn test_catalogs_translate_every_key() {
+    let english = raw_keys("en");
+    assert!(!english.is_empty());
+    for info in available() {
+        assert_eq!(raw_keys(info.locale), english, "{} catalog keys differ", info.locale);
+    }
+    assert_eq!(catalogs()[0].locale, "en");
+    assert_eq!(catalog("fr").unwrap().messages["action.delete"], "Supprimer");
+}
+
+#[test]
+fn test_ui_keys_exist_in_catalog() {
+    let english = raw_keys("en");
+    let static_dir = format!("{}/static", env!("CARGO_MANIFEST_DIR"));
+    let sources = ["html/index.html", "js/app.js", "js/core.js"]
+        .map(|file| std::fs::read_to_string(format!("{}/{}", static_dir, file)).unwrap());
+    let mut used = Vec::new();
+    for source in &sources {
+        for marker in ["data-i18n=\"", "data-i18n-placeholder=\"", "data-i18n-title=\"", "I18n.t('"] {
+            for part in source.split(marker).skip(1) {
+                let key: String = part
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '_')
+                    .collect();
+                if !key.is_empty() && !key.ends_with('.') {
+                    used.push(key);
+                }
+            }
+        }
+    }
+    assert!(used.len() > 50);
+    for key in used {
+        assert!(english.contains(&key), "Missing translation key {}", key);
+    }
+}
+
+#[test]
+fn test_locale_negotiation() {
+    assert_eq!(catalog("fr-CA").unwrap().locale, "fr");
+    assert_eq!(catalog("EN_us").unwrap().locale, "en");
+    assert!(catalog("de").is_none());
+    assert_eq!(negotiate("de-DE, fr;q=0.8, en;q=0.5").locale, "fr");
+    assert_eq!(negotiate("en;q=0.2, fr-FR").locale, "fr");
+    assert_eq!(negotiate("fr;q=0, de").locale, "en");
+    assert_eq!(negotiate("").locale, "en");
+}
+
+#[tokio::test]
+async fn test_locale_endpoints() {
+    use axum::http::Method;
+    use std::sync::Arc;
+    use void_proxy::http_client::send;
+    use void_proxy::instance_manager::InstanceService;
+    use void_proxy::storage::StorageManager;
+    use void_proxy::web_api::create_routes;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let storage_manager = Arc::new(StorageManager::new(temp_dir.path().join("config.toml")));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let router = create_routes(instance_service);
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("accept-language", "fr-FR,fr;q=0.9,en;q=0.8".to_string())];
+    let locales = send(Method::GET, &format!("{}/api/locales", base), &headers, None)
+        .await
+        .unwrap();
+    let locales: serde_json::Value = locales.json().unwrap();
+    assert_eq!(locales["preferred"], "fr");
+    assert_eq!(locales["locales"][1]["name"], "Français");
+
+    let french = send(Method::GET, &format!("{}/api/locales/fr", base), &[], None)
+        .await
+        .unwrap();
+    let french: serde_json::Value = french.json().unwrap();
+    assert_eq!(french["locale"], "fr");
+    assert_eq!(french["messages"]["status.running"], "En cours");
+
+    let missing = send(Method::GET, &format!("{}/api/locales/de", base), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(missing.status, 404);
+}
@@ -1,7 +1,7 @@
 use void_proxy::instance_manager::InstanceService;
 use void_proxy::storage::StorageManager;
 use void_proxy::instance::{CreateInstanceRequest, InstanceStatus};
-use void_proxy::config::Protocol;
+use void_proxy::config::{LogLevel, Protocol};
 use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
 use tempfile::TempDir;
@@ -35,9 +35,32 @@ async fn test_instance_service_create_instance() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let instance = service.create_instance(request).await.unwrap();
@@ -66,9 +89,32 @@ async fn test_instance_service_get_instance() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let instance = service.create_instance(request).await.unwrap();
@@ -108,9 +154,32 @@ async fn test_instance_service_delete_instance() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let instance = service.create_instance(request).await.unwrap();
@@ -152,9 +221,32 @@ async fn test_instance_service_update_instance() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let instance = service.create_instance(request).await.unwrap();
@@ -169,9 +261,35 @@ async fn test_instance_service_update_instance() {
         auto_start: None,
         allow_list: None,
         deny_list: None,
+        allow_hosts: None,
+        deny_hosts: None,
         connect_timeout_secs: None,
         idle_timeout_secs: None,
         log_level: None,
+        bind_device: None,
+        bind_nonlocal: None,
+        backends: None,
+        wasm_filters: None,
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: None,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
+        profiles: None,
+        tags: None,
+        locked: None,
     };
 
     let updated_instance = service.update_instance(instance.id, update_request).await.unwrap();
@@ -197,9 +315,32 @@ async fn test_instance_service_get_instance_stats() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let instance = service.create_instance(request).await.unwrap();
@@ -238,9 +379,32 @@ async fn test_instance_service_create_auto_start_instance() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 1,
         idle_timeout_secs: 1,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let _instance = service.create_instance(request).await.unwrap();
@@ -256,7 +420,6 @@ async fn test_instance_service_clone() {
     let config_path = temp_dir.path().join("test_config.toml");
     let storage_manager = Arc::new(StorageManager::new(config_path));
     let service = InstanceService::with_storage(storage_manager);
-    assert!(true);
 
     let request = CreateInstanceRequest {
         name: "Test Instance".to_string(),
@@ -268,14 +431,36 @@ async fn test_instance_service_clone() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let instance = service.create_instance(request).await.unwrap();
     let _retrieved_instance = service.get_instance(instance.id).await;
-    assert!(true);
 }
 
 #[tokio::test]
@@ -295,9 +480,32 @@ async fn test_instance_service_multiple_instances() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let request2 = CreateInstanceRequest {
@@ -310,9 +518,32 @@ async fn test_instance_service_multiple_instances() {
         auto_start: false,
         allow_list: None,
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let _instance1 = service.create_instance(request1).await.unwrap();
@@ -324,4 +555,132 @@ async fn test_instance_service_multiple_instances() {
     let names: Vec<String> = instances.iter().map(|i| i.name.clone()).collect();
     assert!(names.contains(&"Instance 1".to_string()));
     assert!(names.contains(&"Instance 2".to_string()));
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_instance_service_reload_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    let storage_manager = Arc::new(StorageManager::new(config_path.clone()));
+    let service = InstanceService::with_storage(storage_manager);
+
+    let request = CreateInstanceRequest {
+        name: "Kept Instance".to_string(),
+        listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+        listen_port: 8080,
+        dst_ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100)),
+        dst_port: 80,
+        protocol: Protocol::Tcp,
+        auto_start: false,
+        allow_list: None,
+        deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
+        connect_timeout_secs: 30,
+        idle_timeout_secs: 300,
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
+    };
+    let kept = service.create_instance(request.clone()).await.unwrap();
+    let removed = service
+        .create_instance(CreateInstanceRequest {
+            name: "Removed Instance".to_string(),
+            listen_port: 8081,
+            ..request.clone()
+        })
+        .await
+        .unwrap();
+
+    let external_storage = StorageManager::new(config_path);
+    external_storage.load().await.unwrap();
+    external_storage.remove_instance(removed.id).await.unwrap();
+    let mut renamed = kept.clone();
+    renamed.name = "Renamed Instance".to_string();
+    external_storage.update_instance(&renamed).await.unwrap();
+    let added = void_proxy::instance::ProxyInstance::new(
+        "Added Instance".to_string(),
+        CreateInstanceRequest {
+            listen_port: 8082,
+            ..request
+        }
+        .to_config(),
+        false,
+    );
+    external_storage.add_instance(&added).await.unwrap();
+
+    let report = service.reload_config().await.unwrap();
+    assert_eq!(report.added, 1);
+    assert_eq!(report.removed, 1);
+    assert_eq!(report.updated, 1);
+    assert_eq!(report.restarted, 0);
+
+    assert!(service.get_instance(removed.id).await.is_none());
+    assert!(service.get_instance(added.id).await.is_some());
+    assert_eq!(
+        service.get_instance(kept.id).await.unwrap().name,
+        "Renamed Instance"
+    );
+}
+
+#[tokio::test]
+async fn test_instance_service_status_history_and_events() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage_manager = Arc::new(StorageManager::new(temp_dir.path().join("test_config.toml")));
+    let service = InstanceService::with_storage(storage_manager);
+    let mut events = service.subscribe_events();
+    let mut request = void_proxy::builder::ProxyBuilder::new(
+        "127.0.0.1:18845".parse().unwrap(),
+        "127.0.0.1:18846".parse().unwrap(),
+    )
+    .into_request();
+    request.auto_start = false;
+    let instance = service.create_instance(request).await.unwrap();
+    assert!(service.status_history(instance.id).await.unwrap().is_empty());
+
+    service.start_instance(instance.id).await.unwrap();
+    service.stop_instance(instance.id).await.unwrap();
+    let history: Vec<_> = service
+        .status_history(instance.id)
+        .await
+        .unwrap()
+        .iter()
+        .map(|transition| transition.to)
+        .collect();
+    assert_eq!(
+        history,
+        vec![
+            InstanceStatus::Starting,
+            InstanceStatus::Running,
+            InstanceStatus::Stopping,
+            InstanceStatus::Stopped,
+        ]
+    );
+    let mut changes = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let void_proxy::events::EventKind::InstanceStatusChanged { from, to, .. } = event.kind {
+            changes.push((from, to));
+        }
+    }
+    assert_eq!(changes.len(), 4);
+    assert_eq!(changes[0], (InstanceStatus::Stopped, InstanceStatus::Starting));
+    assert!(service.status_history(Uuid::new_v4()).await.is_none());
+}
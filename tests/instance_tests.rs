@@ -1,5 +1,6 @@
+use std::collections::BTreeMap;
 use void_proxy::instance::{ProxyInstance, InstanceStatus, CreateInstanceRequest, CreateInstanceRequestStrings, UpdateInstanceRequest};
-use void_proxy::config::{Config, ProxyConfig, Protocol};
+use void_proxy::config::{Config, LogLevel, ProxyConfig, Protocol};
 use std::net::{IpAddr, Ipv4Addr};
 
 #[tokio::test]
@@ -13,9 +14,32 @@ async fn test_proxy_instance_creation() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let instance = ProxyInstance::new("Test Instance".to_string(), config, false);
@@ -37,9 +61,32 @@ async fn test_proxy_instance_auto_start() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let instance = ProxyInstance::new("Auto Start Instance".to_string(), config, true);
@@ -59,14 +106,37 @@ async fn test_proxy_instance_start() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let mut instance = ProxyInstance::new("Test Instance".to_string(), config, false);
 
-    instance.start();
+    instance.start().unwrap();
 
     assert_eq!(instance.status, InstanceStatus::Starting);
     assert!(instance.started_at.is_some());
@@ -83,15 +153,38 @@ async fn test_proxy_instance_set_running() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let mut instance = ProxyInstance::new("Test Instance".to_string(), config, false);
 
-    instance.start();
-    instance.set_running();
+    instance.start().unwrap();
+    instance.set_running().unwrap();
 
     assert_eq!(instance.status, InstanceStatus::Running);
 }
@@ -107,16 +200,39 @@ async fn test_proxy_instance_stop() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let mut instance = ProxyInstance::new("Test Instance".to_string(), config, false);
 
-    instance.start();
-    instance.set_running();
-    instance.stop();
+    instance.start().unwrap();
+    instance.set_running().unwrap();
+    instance.stop().unwrap();
 
     assert_eq!(instance.status, InstanceStatus::Stopping);
     assert!(instance.started_at.is_none());
@@ -133,16 +249,40 @@ async fn test_proxy_instance_set_stopped() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let mut instance = ProxyInstance::new("Test Instance".to_string(), config, false);
 
-    instance.start();
-    instance.set_running();
-    instance.set_stopped();
+    instance.start().unwrap();
+    instance.set_running().unwrap();
+    instance.stop().unwrap();
+    instance.set_stopped().unwrap();
 
     assert_eq!(instance.status, InstanceStatus::Stopped);
 }
@@ -158,9 +298,32 @@ async fn test_proxy_instance_unique_id() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let instance1 = ProxyInstance::new("Instance 1".to_string(), config.clone(), false);
@@ -180,9 +343,32 @@ async fn test_proxy_instance_clone() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let instance = ProxyInstance::new("Test Instance".to_string(), config, false);
@@ -201,13 +387,36 @@ async fn test_create_instance_request_strings_valid() {
         listen_port: 8080,
         dst_ip: "192.168.1.100".to_string(),
         dst_port: 80,
-        protocol: Protocol::Tcp,
-        auto_start: false,
+        protocol: Some(Protocol::Tcp),
+        auto_start: Some(false),
         allow_list: Some(vec!["192.168.1.10".to_string()]),
         deny_list: None,
-        connect_timeout_secs: 30,
-        idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
+        connect_timeout_secs: Some(30),
+        idle_timeout_secs: Some(300),
+        log_level: Some(LogLevel::Info),
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let result = request.to_typed();
@@ -227,13 +436,36 @@ async fn test_create_instance_request_strings_invalid_ip() {
         listen_port: 8080,
         dst_ip: "192.168.1.100".to_string(),
         dst_port: 80,
-        protocol: Protocol::Tcp,
-        auto_start: false,
+        protocol: Some(Protocol::Tcp),
+        auto_start: Some(false),
         allow_list: None,
         deny_list: None,
-        connect_timeout_secs: 30,
-        idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
+        connect_timeout_secs: Some(30),
+        idle_timeout_secs: Some(300),
+        log_level: Some(LogLevel::Info),
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let result = request.to_typed();
@@ -252,9 +484,32 @@ async fn test_create_instance_request_to_config() {
         auto_start: false,
         allow_list: Some(vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))]),
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
         connect_timeout_secs: 30,
         idle_timeout_secs: 300,
-        log_level: "info".to_string(),
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal: false,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
     };
 
     let config = request.to_config();
@@ -278,14 +533,59 @@ async fn test_update_instance_request_partial() {
         auto_start: None,
         allow_list: None,
         deny_list: None,
+        allow_hosts: None,
+        deny_hosts: None,
         connect_timeout_secs: None,
         idle_timeout_secs: None,
         log_level: None,
+        bind_device: None,
+        bind_nonlocal: None,
+        backends: None,
+        wasm_filters: None,
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: None,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
+        profiles: None,
+        tags: None,
+        locked: None,
     };
 
     assert_eq!(request.name, Some("Updated Name".to_string()));
     assert!(request.listen_ip.is_none());
     assert!(request.listen_port.is_none());
+
+    let config = void_proxy::builder::ProxyBuilder::new(
+        "127.0.0.1:8080".parse().unwrap(),
+        "127.0.0.1:80".parse().unwrap(),
+    )
+    .into_request()
+    .to_config();
+    let mut instance = ProxyInstance::new("Tuned".to_string(), config, false);
+    let mut tuning = request.clone();
+    tuning.buffer_pool_size = Some(64);
+    tuning.ip_cache_size = Some(128);
+    tuning.apply_to(&mut instance);
+    assert_eq!(instance.config.proxy.buffer_pool_size, Some(64));
+    assert_eq!(instance.config.proxy.ip_cache_size, Some(128));
+    tuning.buffer_pool_size = None;
+    tuning.ip_cache_size = Some(0);
+    tuning.apply_to(&mut instance);
+    assert_eq!(instance.config.proxy.buffer_pool_size, Some(64));
+    assert_eq!(instance.config.proxy.ip_cache_size, None);
 }
 
 #[tokio::test]
@@ -307,15 +607,37 @@ async fn test_proxy_instance_metrics() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let instance = ProxyInstance::new("Test Instance".to_string(), config, false);
 
     let _metrics = instance.metrics.clone();
-    assert!(true);
 }
 
 #[tokio::test]
@@ -329,9 +651,32 @@ async fn test_proxy_instance_serialization() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     let instance = ProxyInstance::new("Test Instance".to_string(), config, false);
@@ -369,4 +714,39 @@ async fn test_proxy_instance_deserialization() {
     assert_eq!(instance.name, "Test Instance");
     assert_eq!(instance.status, InstanceStatus::Stopped);
     assert!(!instance.auto_start);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_proxy_instance_rejects_invalid_transitions() {
+    let config = void_proxy::builder::ProxyBuilder::new(
+        "127.0.0.1:8080".parse().unwrap(),
+        "192.168.1.100:80".parse().unwrap(),
+    )
+    .into_request()
+    .to_config();
+    let mut instance = ProxyInstance::new("Test Instance".to_string(), config, false);
+
+    let error = instance.set_running().unwrap_err();
+    assert_eq!((error.from, error.to), (InstanceStatus::Stopped, InstanceStatus::Running));
+    instance.start().unwrap();
+    instance.set_running().unwrap();
+    instance.stop().unwrap();
+    assert!(instance.start().is_err());
+    assert_eq!(instance.status, InstanceStatus::Stopping);
+    instance.set_stopped().unwrap();
+
+    let history: Vec<_> = instance
+        .status_history
+        .iter()
+        .map(|transition| (transition.from, transition.to))
+        .collect();
+    assert_eq!(
+        history,
+        vec![
+            (InstanceStatus::Stopped, InstanceStatus::Starting),
+            (InstanceStatus::Starting, InstanceStatus::Running),
+            (InstanceStatus::Running, InstanceStatus::Stopping),
+            (InstanceStatus::Stopping, InstanceStatus::Stopped),
+        ]
+    );
+}
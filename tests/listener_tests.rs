@@ -0,0 +1,110 @@
+use std::net::SocketAddr;
+use void_proxy::config::{LogLevel, Protocol, ProxyConfig};
+use void_proxy::listener::{
+    bind_tcp_listener, bind_udp_socket, bind_upstream_udp_socket, connect_upstream,
+    find_accept_queue, read_tcp_tables,
+};
+
+fn proxy_config(bind_nonlocal: bool) -> ProxyConfig {
+    ProxyConfig {
+        listen_ip: "192.0.2.10".parse().unwrap(),
+        listen_port: 18443,
+        dst_ip: "127.0.0.1".parse().unwrap(),
+        dst_port: 80,
+        protocol: Protocol::Both,
+        connect_timeout_secs: 30,
+        idle_timeout_secs: 300,
+        log_level: LogLevel::Info,
+        bind_device: None,
+        bind_nonlocal,
+        backends: Vec::new(),
+        wasm_filters: Vec::new(),
+        listen_backlog: None,
+        bind_src_ip: None,
+        bind_src_device: None,
+        max_inflight_bytes: None,
+        tls: None,
+        tunnel: None,
+        slow_log: None,
+        max_concurrent_handlers: None,
+        buffer_pool_size: None,
+        max_concurrent_buffers: None,
+        ip_cache_size: None,
+        ip_cache_ttl_secs: None,
+        propagate_unreachable: false,
+        log_limit: None,
+        mirror: None,
+        max_connection_lifetime_secs: None,
+        verify_destination_on_start: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn test_bind_unassigned_address_fails_without_nonlocal() {
+    let addr: SocketAddr = "192.0.2.10:18443".parse().unwrap();
+    assert!(bind_tcp_listener(addr, &proxy_config(false)).is_err());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_bind_nonlocal_allows_floating_ip() {
+    let addr: SocketAddr = "192.0.2.10:18443".parse().unwrap();
+    let config = proxy_config(true);
+    let listener = bind_tcp_listener(addr, &config).unwrap();
+    assert_eq!(listener.local_addr().unwrap(), addr);
+    let socket = bind_udp_socket(addr, &config).unwrap();
+    assert_eq!(socket.local_addr().unwrap(), addr);
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_listen_backlog_is_applied_and_reported() {
+    let addr: SocketAddr = "127.0.0.1:18444".parse().unwrap();
+    let mut config = proxy_config(false);
+    config.listen_ip = addr.ip();
+    config.listen_port = addr.port();
+    config.listen_backlog = Some(7);
+    let _listener = bind_tcp_listener(addr, &config).unwrap();
+    let _client = tokio::net::TcpStream::connect(addr).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+    let queue = find_accept_queue(&read_tcp_tables().unwrap(), &config).unwrap();
+    assert_eq!(queue.backlog, 7);
+    assert_eq!(queue.queued, 1);
+}
+
+#[test]
+fn test_find_accept_queue_parses_proc_table() {
+    let table = "  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode\n   0: 0100007F:1F90 00000000:0000 0A 00000400:00000003 00:00000000 00000000     0        0 1 1 0000000000000000 100 0 0 10 0\n";
+    let mut config = proxy_config(false);
+    config.listen_ip = "127.0.0.1".parse().unwrap();
+    config.listen_port = 8080;
+    let queue = find_accept_queue(table, &config).unwrap();
+    assert_eq!(queue.backlog, 1024);
+    assert_eq!(queue.queued, 3);
+    config.listen_port = 8081;
+    assert!(find_accept_queue(table, &config).is_none());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn test_upstream_connections_leave_from_source_ip() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream = listener.local_addr().unwrap();
+    let mut config = proxy_config(false);
+    config.bind_src_ip = Some("127.0.0.2".parse().unwrap());
+
+    let _stream = connect_upstream(upstream, &config).await.unwrap();
+    let (_, peer) = listener.accept().await.unwrap();
+    assert_eq!(peer.ip(), config.bind_src_ip.unwrap());
+
+    let socket = bind_upstream_udp_socket(upstream, &config).unwrap();
+    assert_eq!(
+        socket.local_addr().unwrap().ip(),
+        config.bind_src_ip.unwrap()
+    );
+
+    let ipv6_upstream: SocketAddr = "[::1]:80".parse().unwrap();
+    assert!(connect_upstream(ipv6_upstream, &config).await.is_err());
+    assert!(bind_upstream_udp_socket(ipv6_upstream, &config).is_err());
+}
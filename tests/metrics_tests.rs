@@ -1,4 +1,6 @@
-use void_proxy::metrics::InstanceMetrics;
+use std::sync::Arc;
+use void_proxy::metrics::{InstanceMetrics, MetricsManager};
+use void_proxy::middleware::Transport;
 
 #[tokio::test]
 async fn test_instance_metrics() {
@@ -26,4 +28,55 @@ async fn test_metrics_overflow_protection() {
     // Test overflow - should saturate
     metrics.add_bytes_sent(1000);
     assert_eq!(metrics.bytes_sent.load(std::sync::atomic::Ordering::Relaxed), u64::MAX);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_transport_counters_add_up_to_the_totals() {
+    let metrics = InstanceMetrics::new();
+
+    metrics.record_sent(Transport::Tcp, 100);
+    metrics.record_sent(Transport::Udp, 20);
+    metrics.record_received(Transport::Udp, 5);
+    metrics.connection_opened(Transport::Tcp);
+    metrics.connection_opened(Transport::Udp);
+    metrics.record_error(Transport::Udp);
+    metrics.connection_closed(Transport::Udp);
+
+    let stats = metrics.get_stats(None).await;
+    assert_eq!((stats.tcp.bytes_sent, stats.udp.bytes_sent), (100, 20));
+    assert_eq!(stats.bytes_sent, 120);
+    assert_eq!(stats.bytes_received, stats.udp.bytes_received);
+    assert_eq!((stats.connections_active, stats.connections_total), (1, 1));
+    assert_eq!((stats.udp.connections_active, stats.udp.connections_total), (0, 1));
+    assert_eq!((stats.errors, stats.udp.errors), (0, 1));
+}
+
+#[tokio::test]
+async fn test_metrics_manager_aggregates_live_instance_metrics() {
+    let manager = MetricsManager::new();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let tcp = Arc::new(InstanceMetrics::new());
+    let udp = Arc::new(InstanceMetrics::new());
+    manager.register_instance(uuid::Uuid::new_v4(), tcp.clone()).await;
+    let udp_id = uuid::Uuid::new_v4();
+    manager.register_instance(udp_id, udp.clone()).await;
+    tcp.connection_opened(Transport::Tcp);
+    udp.connection_opened(Transport::Udp);
+    tcp.record_sent(Transport::Tcp, 500);
+    manager.refresh().await;
+
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    tcp.record_sent(Transport::Tcp, 1000);
+    udp.record_received(Transport::Udp, 1000);
+    manager.refresh().await;
+    let system = manager.get_system_metrics().await;
+    assert_eq!(system.active_connections, 2);
+    assert!(system.bytes_sent_per_sec > 1000.0 && system.bytes_sent_per_sec <= 5000.0);
+    assert!(system.bytes_received_per_sec > 1000.0);
+
+    manager.unregister_instance(&udp_id).await;
+    manager.refresh().await;
+    let system = manager.get_system_metrics().await;
+    assert_eq!(system.active_connections, 1);
+    assert_eq!(system.bytes_received_per_sec, 0.0);
+}
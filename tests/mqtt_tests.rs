@@ -0,0 +1,129 @@
+use void_proxy::config::{LogLevel, Protocol};
+use void_proxy::instance::CreateInstanceRequest;
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::mqtt::{MqttConfig, MqttPublisher, encode_connect, encode_publish};
+use void_proxy::storage::StorageManager;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+fn mqtt_config(broker: String) -> MqttConfig {
+    MqttConfig {
+        broker,
+        client_id: "voidproxy-test".to_string(),
+        username: Some("user".to_string()),
+        password: Some("pass".to_string()),
+        topic_prefix: "voidproxy".to_string(),
+        stats_interval: Duration::from_secs(60),
+    }
+}
+
+async fn read_packet(stream: &mut tokio::net::TcpStream) -> (u8, Vec<u8>) {
+    let packet_type = stream.read_u8().await.unwrap();
+    let mut length = 0usize;
+    let mut multiplier = 1usize;
+    loop {
+        let byte = stream.read_u8().await.unwrap();
+        length += (byte & 0x7f) as usize * multiplier;
+        multiplier *= 128;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).await.unwrap();
+    (packet_type, body)
+}
+
+#[tokio::test]
+async fn test_encode_mqtt_packets() {
+    let connect = encode_connect(&mqtt_config("127.0.0.1:1883".to_string()));
+    assert_eq!(connect[0], 0x10);
+    assert_eq!(connect[1] as usize, connect.len() - 2);
+    assert_eq!(&connect[4..8], b"MQTT");
+    assert_eq!(connect[9], 0xc2);
+
+    let payload = vec![b'x'; 200];
+    let publish = encode_publish("a/b", &payload, true);
+    assert_eq!(publish[0], 0x31);
+    assert_eq!(&publish[1..3], &[0xcd, 0x01]);
+    assert_eq!(&publish[3..8], &[0x00, 0x03, b'a', b'/', b'b']);
+    assert_eq!(publish.len(), 3 + 5 + 200);
+}
+
+#[tokio::test]
+async fn test_mqtt_publisher_sends_instance_stats() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let broker = listener.local_addr().unwrap().to_string();
+
+    let temp_dir = TempDir::new().unwrap();
+    let service = Arc::new(InstanceService::with_storage(Arc::new(StorageManager::new(
+        temp_dir.path().join("test_config.toml"),
+    ))));
+    let instance = service
+        .create_instance(CreateInstanceRequest {
+            name: "MQTT Instance".to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            listen_port: 18720,
+            dst_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            dst_port: 18721,
+            protocol: Protocol::Tcp,
+            auto_start: false,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        })
+        .await
+        .unwrap();
+    Arc::new(MqttPublisher::new(mqtt_config(broker), service)).start();
+
+    let (mut stream, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let (packet_type, _) = read_packet(&mut stream).await;
+    assert_eq!(packet_type, 0x10);
+    stream.write_all(&[0x20, 0x02, 0x00, 0x00]).await.unwrap();
+
+    let expected_topic = format!("voidproxy/instances/{}/stats", instance.id);
+    loop {
+        let (packet_type, body) = tokio::time::timeout(Duration::from_secs(5), read_packet(&mut stream))
+            .await
+            .unwrap();
+        if packet_type & 0xf0 != 0x30 {
+            continue;
+        }
+        let topic_len = u16::from_be_bytes([body[0], body[1]]) as usize;
+        assert_eq!(std::str::from_utf8(&body[2..2 + topic_len]).unwrap(), expected_topic);
+        let stats: serde_json::Value = serde_json::from_slice(&body[2 + topic_len..]).unwrap();
+        assert_eq!(stats["name"], "MQTT Instance");
+        break;
+    }
+}
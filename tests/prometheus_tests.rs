@@ -0,0 +1,172 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use void_proxy::config::{LogLevel, Protocol};
+use void_proxy::instance::CreateInstanceRequest;
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::metrics::Histogram;
+use void_proxy::prometheus::render;
+use void_proxy::storage::StorageManager;
+
+#[test]
+fn test_histogram_buckets_are_cumulative() {
+    let mut histogram = Histogram::new(&[1.0, 10.0]);
+    for value in [0.5, 1.0, 5.0, 60.0] {
+        histogram.observe(value);
+    }
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.buckets[0].count, 2);
+    assert_eq!(snapshot.buckets[1].count, 3);
+    assert_eq!(snapshot.count, 4);
+    assert_eq!(snapshot.sum, 66.5);
+}
+
+#[tokio::test]
+async fn test_connection_durations_are_exported() {
+    let upstream = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_port = upstream.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = upstream.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 64];
+                while let Ok(n) = stream.read(&mut buffer).await {
+                    if n == 0 || stream.write_all(&buffer[..n]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage)
+        .with_duration_buckets(vec![0.05, 30.0])
+        .unwrap();
+    assert!(
+        InstanceService::with_storage(Arc::new(StorageManager::new(
+            temp_dir.path().join("other.toml")
+        )))
+        .with_duration_buckets(vec![10.0, 1.0])
+        .is_err()
+    );
+    service
+        .create_instance(CreateInstanceRequest {
+            name: "echo \"quoted\"".to_string(),
+            listen_ip: "127.0.0.1".parse().unwrap(),
+            listen_port: 18731,
+            dst_ip: "127.0.0.1".parse().unwrap(),
+            dst_port: upstream_port,
+            protocol: Protocol::Tcp,
+            auto_start: true,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 5,
+            idle_timeout_secs: 60,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        })
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = tokio::net::TcpStream::connect("127.0.0.1:18731")
+        .await
+        .unwrap();
+    client.write_all(b"ping").await.unwrap();
+    let mut reply = [0u8; 4];
+    client.read_exact(&mut reply).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let stats: Vec<_> = service.get_instance_stats().await.into_values().collect();
+    let histogram = &stats[0].tcp_connection_duration;
+    assert_eq!(histogram.count, 1);
+    assert_eq!(histogram.buckets[0].count, 0);
+    assert_eq!(histogram.buckets[1].count, 1);
+    assert_eq!(stats[0].tcp_disconnects.get("client_eof"), Some(&1));
+
+    let text = render(&stats, &[]);
+    let labels = format!("instance=\"echo \\\"quoted\\\"\",id=\"{}\"", stats[0].id);
+    assert!(text.contains("# TYPE voidproxy_tcp_connection_duration_seconds histogram"));
+    assert!(text.contains(&format!(
+        "voidproxy_tcp_connection_duration_seconds_bucket{{{},le=\"30\"}} 1",
+        labels
+    )));
+    assert!(text.contains(&format!(
+        "voidproxy_tcp_connection_duration_seconds_count{{{}}} 1",
+        labels
+    )));
+    assert!(text.contains(&format!(
+        "voidproxy_tcp_disconnects_total{{{},reason=\"client_eof\"}} 1",
+        labels
+    )));
+    assert!(text.contains(&format!("voidproxy_instance_up{{{}}} 1", labels)));
+}
+
+#[tokio::test]
+async fn test_allowed_tags_are_exported_as_labels() {
+    let temp_dir = TempDir::new().unwrap();
+    let service = InstanceService::with_storage(Arc::new(StorageManager::new(
+        temp_dir.path().join("t.toml"),
+    )));
+    let mut request = void_proxy::builder::ProxyBuilder::new(
+        "127.0.0.1:18732".parse().unwrap(),
+        "127.0.0.1:18733".parse().unwrap(),
+    )
+    .name("tagged")
+    .into_request();
+    request.auto_start = false;
+    let instance = service.create_instance(request).await.unwrap();
+    let update: void_proxy::instance::UpdateInstanceRequest = serde_json::from_value(
+        serde_json::json!({"tags": {"env": "prod", "cost-center": "a\"b", "owner": "ops"}}),
+    )
+    .unwrap();
+    service.update_instance(instance.id, update).await.unwrap();
+
+    let stats: Vec<_> = service.get_instance_stats().await.into_values().collect();
+    assert_eq!(stats[0].tags.get("env").map(String::as_str), Some("prod"));
+    let text = render(
+        &stats,
+        &[
+            "env".to_string(),
+            "cost-center".to_string(),
+            "team".to_string(),
+        ],
+    );
+    let labels = format!(
+        "instance=\"tagged\",id=\"{}\",tag_env=\"prod\",tag_cost_center=\"a\\\"b\"",
+        instance.id
+    );
+    assert!(text.contains(&format!("voidproxy_instance_up{{{}}} 0", labels)));
+    assert!(text.contains(&format!(
+        "voidproxy_transport_bytes_sent_total{{{},transport=\"tcp\"}} 0",
+        labels
+    )));
+    assert!(!text.contains("tag_owner"));
+    assert!(!text.contains("tag_team"));
+    assert!(!render(&stats, &[]).contains("tag_"));
+}
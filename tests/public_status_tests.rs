@@ -0,0 +1,69 @@
+use axum::body::{Body, to_bytes};
+use axum::http::Request;
+use std::sync::Arc;
+use tempfile::TempDir;
+use tower::ServiceExt;
+use void_proxy::availability::AvailabilityTracker;
+use void_proxy::builder::ProxyBuilder;
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::public_status::{collect, create_routes};
+use void_proxy::storage::StorageManager;
+
+#[tokio::test]
+async fn test_public_status_hides_addresses() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = Arc::new(InstanceService::with_storage(storage));
+    let tracker = Arc::new(
+        AvailabilityTracker::load(temp_dir.path().join("availability.jsonl"), service.clone())
+            .await
+            .unwrap(),
+    );
+    tracker.clone().start(service.subscribe_events());
+
+    let request = |name: &str, port: u16| {
+        let mut request = ProxyBuilder::new(
+            format!("127.0.0.1:{}", port).parse().unwrap(),
+            "192.0.2.10:80".parse().unwrap(),
+        )
+        .name(name)
+        .into_request();
+        request.auto_start = false;
+        request
+    };
+    let web = service.create_instance(request("web", 18760)).await.unwrap();
+    service.create_instance(request("<db>", 18761)).await.unwrap();
+    service.start_instance(web.id).await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let status = collect(&tracker).await;
+    assert_eq!(status.instances.len(), 2);
+    assert_eq!(status.instances[0].name, "<db>");
+    assert!(!status.instances[0].up);
+    assert_eq!(status.instances[0].uptime_secs, None);
+    assert!(status.instances[1].up);
+    assert!(status.instances[1].up_since.is_some());
+
+    let router = create_routes(tracker.clone());
+    let json = router
+        .clone()
+        .oneshot(Request::get("/api/public/status").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let json = to_bytes(json.into_body(), usize::MAX).await.unwrap();
+    let json = String::from_utf8(json.to_vec()).unwrap();
+    assert!(json.contains("\"name\":\"web\""));
+    assert!(!json.contains("127.0.0.1") && !json.contains("192.0.2.10"));
+
+    let page = router
+        .oneshot(Request::get("/status").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let page = to_bytes(page.into_body(), usize::MAX).await.unwrap();
+    let page = String::from_utf8(page.to_vec()).unwrap();
+    assert!(page.contains("Some services are down"));
+    assert!(page.contains("&lt;db&gt;"));
+    assert!(!page.contains("192.0.2.10"));
+
+    service.stop_instance(web.id).await.unwrap();
+}
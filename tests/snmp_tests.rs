@@ -0,0 +1,138 @@
+use void_proxy::config::{LogLevel, Protocol};
+use void_proxy::instance::CreateInstanceRequest;
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::snmp::{SnmpAgent, SnmpConfig, VOIDPROXY_MIB_OID};
+use void_proxy::storage::StorageManager;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag, value.len() as u8];
+    out.extend_from_slice(value);
+    out
+}
+
+fn oid(arcs: &[u32]) -> Vec<u8> {
+    let mut value = vec![(arcs[0] * 40 + arcs[1]) as u8];
+    for arc in &arcs[2..] {
+        let mut chunk = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        value.extend(chunk.iter().rev());
+    }
+    tlv(0x06, &value)
+}
+
+fn request(pdu_type: u8, community: &str, arcs: &[u32]) -> Vec<u8> {
+    let varbind = tlv(0x30, &[oid(arcs), vec![0x05, 0x00]].concat());
+    let pdu = [
+        tlv(0x02, &[0x2a]),
+        tlv(0x02, &[0x00]),
+        tlv(0x02, &[0x00]),
+        tlv(0x30, &varbind),
+    ]
+    .concat();
+    let message = [
+        tlv(0x02, &[0x01]),
+        tlv(0x04, community.as_bytes()),
+        tlv(pdu_type, &pdu),
+    ]
+    .concat();
+    tlv(0x30, &message)
+}
+
+async fn agent_with_instance() -> SnmpAgent {
+    let temp_dir = TempDir::new().unwrap();
+    let service = Arc::new(InstanceService::with_storage(Arc::new(StorageManager::new(
+        temp_dir.path().join("test_config.toml"),
+    ))));
+    service
+        .create_instance(CreateInstanceRequest {
+            name: "snmp-edge".to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            listen_port: 18730,
+            dst_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            dst_port: 18731,
+            protocol: Protocol::Tcp,
+            auto_start: false,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        })
+        .await
+        .unwrap();
+    SnmpAgent::new(
+        SnmpConfig {
+            listen_addr: "127.0.0.1:0".parse().unwrap(),
+            community: "public".to_string(),
+        },
+        service,
+    )
+}
+
+#[tokio::test]
+async fn test_snmp_get_instance_name() {
+    let agent = agent_with_instance().await;
+    let name_oid = [&VOIDPROXY_MIB_OID[..], &[2, 1, 2, 1]].concat();
+
+    let response = agent
+        .handle_request(&request(0xa0, "public", &name_oid))
+        .await
+        .unwrap();
+    let expected = [oid(&name_oid), tlv(0x04, b"snmp-edge")].concat();
+    assert!(response.ends_with(&tlv(0x30, &tlv(0x30, &expected))));
+
+    assert!(agent
+        .handle_request(&request(0xa0, "private", &name_oid))
+        .await
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_snmp_get_next_walks_the_mib() {
+    let agent = agent_with_instance().await;
+
+    let response = agent
+        .handle_request(&request(0xa1, "public", &VOIDPROXY_MIB_OID))
+        .await
+        .unwrap();
+    let count_oid = [&VOIDPROXY_MIB_OID[..], &[1, 0]].concat();
+    let expected = [oid(&count_oid), tlv(0x42, &[0x01])].concat();
+    assert!(response.ends_with(&tlv(0x30, &tlv(0x30, &expected))));
+
+    let past_end = [&VOIDPROXY_MIB_OID[..], &[3]].concat();
+    let response = agent
+        .handle_request(&request(0xa1, "public", &past_end))
+        .await
+        .unwrap();
+    assert!(response.ends_with(&[0x82, 0x00]));
+}
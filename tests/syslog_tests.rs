@@ -0,0 +1,114 @@
+use chrono::{TimeZone, Utc};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::{TcpListener, UdpSocket};
+use tracing_subscriber::prelude::*;
+use void_proxy::metrics::ACCESS_LOG_TARGET;
+use void_proxy::syslog::{
+    SyslogConfig, SyslogLayer, SyslogTransport, facility_code, format_message,
+};
+
+fn syslog_config(transport: SyslogTransport, access_log: bool) -> SyslogConfig {
+    SyslogConfig {
+        transport,
+        facility: facility_code("local0").unwrap(),
+        hostname: "proxy-1".to_string(),
+        access_log,
+    }
+}
+
+#[test]
+fn test_parse_targets() {
+    assert_eq!(
+        SyslogTransport::parse("udp://10.0.0.1").unwrap(),
+        SyslogTransport::Udp("10.0.0.1:514".to_string())
+    );
+    assert_eq!(
+        SyslogTransport::parse("tcp://logs.example.com:6514").unwrap(),
+        SyslogTransport::Tcp("logs.example.com:6514".to_string())
+    );
+    assert_eq!(
+        SyslogTransport::parse("udp://[::1]").unwrap(),
+        SyslogTransport::Udp("[::1]:514".to_string())
+    );
+    assert_eq!(
+        SyslogTransport::parse("unix:///dev/log").unwrap(),
+        SyslogTransport::Unix(PathBuf::from("/dev/log"))
+    );
+    assert!(SyslogTransport::parse("10.0.0.1:514").is_err());
+    assert!(SyslogTransport::parse("http://10.0.0.1").is_err());
+    assert!(SyslogTransport::parse("unix://").is_err());
+    assert_eq!(facility_code("daemon").unwrap(), 3);
+    assert!(facility_code("local8").is_err());
+}
+
+#[test]
+fn test_format_rfc5424_message() {
+    let config = syslog_config(SyslogTransport::Udp("127.0.0.1:514".to_string()), false);
+    let timestamp = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+    let message = format_message(&config, 4, timestamp, Some("access"), "line\nbreak");
+    assert_eq!(
+        message,
+        format!(
+            "<132>1 2024-05-01T12:30:00.000000Z proxy-1 voidproxy {} access - line break",
+            std::process::id()
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_udp_sink_forwards_daemon_logs() {
+    let collector = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let transport = SyslogTransport::Udp(collector.local_addr().unwrap().to_string());
+    let (layer, sink) = SyslogLayer::new(syslog_config(transport, false));
+    sink.start();
+    let subscriber = tracing_subscriber::registry().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(target: ACCESS_LOG_TARGET, "instance=skipped");
+        tracing::warn!(instance = 7, "Backend unreachable");
+    });
+    let mut buffer = [0u8; 1024];
+    let len = tokio::time::timeout(Duration::from_secs(5), collector.recv(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+    let message = String::from_utf8_lossy(&buffer[..len]).to_string();
+    assert!(message.starts_with("<132>1 "), "{}", message);
+    assert!(
+        message.ends_with(" - - syslog_tests: Backend unreachable instance=7"),
+        "{}",
+        message
+    );
+}
+
+#[tokio::test]
+async fn test_tcp_sink_frames_access_log() {
+    let collector = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let transport = SyslogTransport::Tcp(collector.local_addr().unwrap().to_string());
+    let (layer, sink) = SyslogLayer::new(syslog_config(transport, true));
+    sink.start();
+    let subscriber = tracing_subscriber::registry().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(target: ACCESS_LOG_TARGET, "instance=1 client=127.0.0.1:5000");
+    });
+    let (mut stream, _) = tokio::time::timeout(Duration::from_secs(5), collector.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut received = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !received.ends_with(b"client=127.0.0.1:5000") {
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(read > 0);
+        received.extend_from_slice(&buffer[..read]);
+    }
+    let received = String::from_utf8(received).unwrap();
+    let (length, message) = received.split_once(' ').unwrap();
+    assert_eq!(length.parse::<usize>().unwrap(), message.len());
+    assert!(message.starts_with("<134>1 "), "{}", message);
+    assert!(message.contains(" access - instance=1 "), "{}", message);
+}
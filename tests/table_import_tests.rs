@@ -0,0 +1,96 @@
+use axum::http::Method;
+use std::sync::Arc;
+use tempfile::TempDir;
+use void_proxy::config::Protocol;
+use void_proxy::http_client::send;
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::storage::StorageManager;
+use void_proxy::table_import::{parse_table, unknown_columns};
+use void_proxy::web_api::create_routes;
+
+const TABLE: &str = "\
+name,listen,destination,protocol,allow_list
+web,8080,10.0.0.5:80,tcp,
+\"dns, internal\",127.0.0.1:5353,10.0.0.53:53,udp,10.0.0.1;10.0.0.2;home.example.org
+
+broken,8081,not-an-address,tcp,
+";
+
+#[tokio::test]
+async fn test_parse_table_rows() {
+    let rows = parse_table(TABLE).unwrap();
+    assert_eq!(rows.len(), 3);
+
+    let web = rows[0].request.as_ref().unwrap();
+    assert_eq!(rows[0].row, 2);
+    assert_eq!(web.listen_ip.to_string(), "0.0.0.0");
+    assert_eq!(web.listen_port, 8080);
+    assert_eq!(web.dst_port, 80);
+
+    let dns = rows[1].request.as_ref().unwrap();
+    assert_eq!(dns.name, "dns, internal");
+    assert_eq!(dns.protocol, Protocol::Udp);
+    assert_eq!(dns.allow_list.as_ref().unwrap().len(), 2);
+    assert_eq!(dns.allow_hosts, vec!["home.example.org".to_string()]);
+
+    assert_eq!(rows[2].row, 5);
+    assert!(rows[2].request.as_ref().unwrap_err().contains("not-an-address"));
+
+    let tsv = parse_table("name\tlisten_port\tdst_ip\tdst_port\nssh\t2222\t10.0.0.9\t22\n").unwrap();
+    assert_eq!(tsv[0].request.as_ref().unwrap().dst_port, 22);
+    assert!(parse_table("listen,destination\n").is_err());
+    assert!(unknown_columns(TABLE).is_empty());
+    assert_eq!(
+        unknown_columns("# instances\nName,Listen,Idle Timout Secs\n"),
+        vec!["idle_timout_secs"]
+    );
+}
+
+#[tokio::test]
+async fn test_import_table_endpoint_reports_each_row() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage_manager = Arc::new(StorageManager::new(temp_dir.path().join("test_config.toml")));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!(
+        "http://{}/api/instances/import-table",
+        listener.local_addr().unwrap()
+    );
+    let router = create_routes(instance_service.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("content-type", "text/csv".to_string())];
+    let dry_run = send(
+        Method::POST,
+        &format!("{}?dry_run=true", url),
+        &headers,
+        Some(TABLE.as_bytes().to_vec()),
+    )
+    .await
+    .unwrap();
+    let dry_run: serde_json::Value = dry_run.json().unwrap();
+    assert_eq!(dry_run["created"], 0);
+    assert_eq!(dry_run["rows"][0]["status"], "valid");
+    assert!(instance_service.get_instances().await.is_empty());
+
+    let report = send(Method::POST, &url, &headers, Some(TABLE.as_bytes().to_vec()))
+        .await
+        .unwrap();
+    assert_eq!(report.status, 200);
+    let report: serde_json::Value = report.json().unwrap();
+    assert_eq!(report["created"], 2);
+    assert_eq!(report["failed"], 1);
+    assert_eq!(report["rows"][2]["status"], "error");
+    assert_eq!(report["rows"][2]["row"], 5);
+    assert_eq!(instance_service.get_instances().await.len(), 2);
+
+    let again: serde_json::Value = send(Method::POST, &url, &headers, Some(TABLE.as_bytes().to_vec()))
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(again["created"], 0);
+    assert!(again["rows"][0]["error"].as_str().unwrap().contains("already exists"));
+}
@@ -1,5 +1,6 @@
+use std::collections::BTreeMap;
 use void_proxy::tcp_proxy::TcpProxy;
-use void_proxy::config::{Config, ProxyConfig, Protocol};
+use void_proxy::config::{Config, LogLevel, ProxyConfig, Protocol};
 use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
@@ -16,17 +17,38 @@ async fn test_tcp_proxy_creation() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     });
 
     let instance_id = Uuid::new_v4();
     let instances = Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new()));
 
     let _proxy = TcpProxy::new(config, instance_id, instances);
-
-    assert!(true);
 }
 
 #[tokio::test]
@@ -40,9 +62,32 @@ async fn test_tcp_proxy_cancellation() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 1,
             idle_timeout_secs: 1,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     });
 
     let instance_id = Uuid::new_v4();
@@ -75,14 +120,39 @@ async fn test_tcp_proxy_with_ip_filter() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 1,
             idle_timeout_secs: 1,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     };
 
     config.ip_filter = Some(void_proxy::config::IpFilterConfig {
         allow_list: Some(vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100))]),
         deny_list: None,
+        allow_hosts: Vec::new(),
+        deny_hosts: Vec::new(),
     });
 
     let config = Arc::new(config);
@@ -116,16 +186,37 @@ async fn test_tcp_proxy_buffer_pool() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 1,
             idle_timeout_secs: 1,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     });
 
     let instance_id = Uuid::new_v4();
     let instances = Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new()));
     let _proxy = TcpProxy::new(config, instance_id, instances);
-
-    assert!(true);
 }
 
 #[tokio::test]
@@ -139,16 +230,37 @@ async fn test_tcp_proxy_ip_cache() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 1,
             idle_timeout_secs: 1,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     });
 
     let instance_id = Uuid::new_v4();
     let instances = Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new()));
     let _proxy = TcpProxy::new(config, instance_id, instances);
-
-    assert!(true);
 }
 
 #[tokio::test]
@@ -162,9 +274,32 @@ async fn test_tcp_proxy_clone() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 30,
             idle_timeout_secs: 300,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     });
 
     let instance_id = Uuid::new_v4();
@@ -172,8 +307,6 @@ async fn test_tcp_proxy_clone() {
     let proxy = TcpProxy::new(config, instance_id, instances);
 
     let _proxy_clone = proxy.clone();
-
-    assert!(true);
 }
 
 #[tokio::test]
@@ -187,14 +320,35 @@ async fn test_tcp_proxy_timeout_values() {
             protocol: Protocol::Tcp,
             connect_timeout_secs: 15,
             idle_timeout_secs: 60,
-            log_level: "info".to_string(),
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
         },
         ip_filter: None,
+        profiles: BTreeMap::new(),
+        tags: BTreeMap::new(),
     });
 
     let instance_id = Uuid::new_v4();
     let instances = Arc::new(tokio::sync::RwLock::new(std::collections::HashMap::new()));
     let _proxy = TcpProxy::new(config, instance_id, instances);
-
-    assert!(true);
 }
\ No newline at end of file
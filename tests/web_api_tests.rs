@@ -12,8 +12,6 @@ async fn test_web_api_routes_creation() {
     let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
 
     let _router = create_routes(instance_service);
-
-    assert!(true);
 }
 
 
@@ -37,6 +35,8 @@ async fn test_instance_query_empty() {
 async fn test_import_config_request_creation() {
     let request = ImportConfigRequest {
         config: "test_config_content".to_string(),
+        dry_run: false,
+        strict: false,
     };
 
     assert_eq!(request.config, "test_config_content");
@@ -71,3 +71,599 @@ async fn test_web_api_instance_query_empty_deserialization() {
 }
 
 
+
+#[tokio::test]
+async fn test_web_api_upsert_instance_by_name() {
+    use axum::http::Method;
+    use void_proxy::http_client::send;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    let storage_manager = Arc::new(StorageManager::new(config_path));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/api/instances/by-name/web", listener.local_addr().unwrap());
+    let router = create_routes(instance_service.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("content-type", "application/json".to_string())];
+    let body = |dst_port: u16| {
+        serde_json::json!({
+            "listen_ip": "127.0.0.1",
+            "listen_port": 18700,
+            "dst_ip": "127.0.0.1",
+            "dst_port": dst_port,
+            "protocol": "tcp",
+            "auto_start": false,
+            "connect_timeout_secs": 30,
+            "idle_timeout_secs": 300,
+            "log_level": "info",
+        })
+        .to_string()
+        .into_bytes()
+    };
+
+    let created = send(Method::PUT, &url, &headers, Some(body(80))).await.unwrap();
+    assert_eq!(created.status, 201);
+    let created: serde_json::Value = created.json().unwrap();
+    assert_eq!(created["changed"], true);
+
+    let unchanged = send(Method::PUT, &url, &headers, Some(body(80))).await.unwrap();
+    assert_eq!(unchanged.status, 200);
+    let unchanged: serde_json::Value = unchanged.json().unwrap();
+    assert_eq!(unchanged["changed"], false);
+    assert_eq!(unchanged["instance"]["id"], created["instance"]["id"]);
+
+    let updated = send(Method::PUT, &url, &headers, Some(body(8080))).await.unwrap();
+    let updated: serde_json::Value = updated.json().unwrap();
+    assert_eq!(updated["changed"], true);
+    assert_eq!(updated["created"], false);
+    assert_eq!(updated["instance"]["config"]["proxy"]["dst_port"], 8080);
+    assert_eq!(instance_service.get_instances().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_web_api_instance_names_are_unique() {
+    use axum::http::Method;
+    use void_proxy::http_client::send;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    let storage_manager = Arc::new(StorageManager::new(config_path));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/api/instances", listener.local_addr().unwrap());
+    let router = create_routes(instance_service.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("content-type", "application/json".to_string())];
+    let body = |listen_port: u16| {
+        serde_json::json!({
+            "name": "edge",
+            "listen_ip": "127.0.0.1",
+            "listen_port": listen_port,
+            "dst_ip": "127.0.0.1",
+            "dst_port": 80,
+            "auto_start": false,
+        })
+        .to_string()
+        .into_bytes()
+    };
+    let created = send(Method::POST, &base, &headers, Some(body(18702))).await.unwrap();
+    assert_eq!(created.status, 200);
+    let created: serde_json::Value = created.json().unwrap();
+    let duplicate = send(Method::POST, &base, &headers, Some(body(18703))).await.unwrap();
+    assert_eq!(duplicate.status, 409);
+    let duplicate: serde_json::Value = duplicate.json().unwrap();
+    assert_eq!(duplicate["error"], "NAME_CONFLICT");
+
+    let found = send(Method::GET, &format!("{}/by-name/edge", base), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(found.status, 200);
+    let found: serde_json::Value = found.json().unwrap();
+    assert_eq!(found["id"], created["id"]);
+    let missing = send(Method::GET, &format!("{}/by-name/core", base), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(missing.status, 404);
+    assert_eq!(instance_service.get_instances().await.len(), 1);
+}
+
+#[tokio::test]
+async fn test_web_api_locked_instance_requires_forced_admin() {
+    use axum::http::Method;
+    use void_proxy::http_client::send;
+    use void_proxy::web_api::AdminToken;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    let storage_manager = Arc::new(StorageManager::new(config_path));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/api/instances", listener.local_addr().unwrap());
+    let router = create_routes(instance_service.clone())
+        .layer(axum::Extension(AdminToken("s3cret".to_string())));
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("content-type", "application/json".to_string())];
+    let body = serde_json::json!({
+        "name": "critical",
+        "listen_ip": "127.0.0.1",
+        "listen_port": 18701,
+        "dst_ip": "127.0.0.1",
+        "dst_port": 80,
+        "protocol": "tcp",
+        "auto_start": false,
+        "connect_timeout_secs": 30,
+        "idle_timeout_secs": 300,
+        "log_level": "info",
+    });
+    let created = send(Method::POST, &base, &headers, Some(body.to_string().into_bytes()))
+        .await
+        .unwrap();
+    let created: serde_json::Value = created.json().unwrap();
+    let url = format!("{}/{}", base, created["id"].as_str().unwrap());
+    let lock = serde_json::json!({ "locked": true }).to_string().into_bytes();
+    let locked = send(Method::PUT, &url, &headers, Some(lock)).await.unwrap();
+    assert_eq!(locked.status, 200);
+
+    let refused = send(Method::DELETE, &url, &[], None).await.unwrap();
+    assert_eq!(refused.status, 423);
+    let stop = send(Method::POST, &format!("{}/stop", url), &[], None).await.unwrap();
+    assert_eq!(stop.status, 423);
+    let forbidden = send(Method::DELETE, &format!("{}?force=true", url), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(forbidden.status, 403);
+    assert_eq!(instance_service.get_instances().await.len(), 1);
+
+    let admin = [("authorization", "Bearer s3cret".to_string())];
+    let deleted = send(Method::DELETE, &format!("{}?force=true", url), &admin, None)
+        .await
+        .unwrap();
+    assert_eq!(deleted.status, 204);
+    assert!(instance_service.get_instances().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_web_api_deleted_instances_can_be_restored_and_purged() {
+    use axum::http::Method;
+    use void_proxy::http_client::send;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    let storage_manager = Arc::new(StorageManager::new(config_path.clone()));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/api", listener.local_addr().unwrap());
+    let router = create_routes(instance_service.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("content-type", "application/json".to_string())];
+    let body = serde_json::json!({
+        "name": "fat-fingered",
+        "listen_ip": "127.0.0.1",
+        "listen_port": 18702,
+        "dst_ip": "127.0.0.1",
+        "dst_port": 80,
+        "protocol": "tcp",
+        "auto_start": false,
+        "connect_timeout_secs": 30,
+        "idle_timeout_secs": 300,
+        "log_level": "info",
+    });
+    let created = send(
+        Method::POST,
+        &format!("{}/instances", base),
+        &headers,
+        Some(body.to_string().into_bytes()),
+    )
+    .await
+    .unwrap();
+    let created: serde_json::Value = created.json().unwrap();
+    let id = created["id"].as_str().unwrap().to_string();
+
+    let deleted = send(Method::DELETE, &format!("{}/instances/{}", base, id), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(deleted.status, 204);
+    assert!(instance_service.get_instances().await.is_empty());
+    let trash: serde_json::Value = send(Method::GET, &format!("{}/trash", base), &[], None)
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(trash[0]["id"], id.as_str());
+    assert!(trash[0]["deleted_at"].is_string());
+
+    let restored = send(Method::POST, &format!("{}/instances/{}/restore", base, id), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(restored.status, 200);
+    assert_eq!(instance_service.get_instances().await.len(), 1);
+    assert!(instance_service.get_deleted_instances().await.unwrap().is_empty());
+
+    send(Method::DELETE, &format!("{}/instances/{}", base, id), &[], None)
+        .await
+        .unwrap();
+    let purged = send(Method::DELETE, &format!("{}/trash/{}", base, id), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(purged.status, 204);
+    let missing = send(Method::POST, &format!("{}/instances/{}/restore", base, id), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(missing.status, 404);
+    assert!(!std::fs::read_to_string(&config_path).unwrap().contains("fat-fingered"));
+}
+
+#[tokio::test]
+async fn test_web_api_config_diff_against_backup() {
+    use axum::http::Method;
+    use void_proxy::http_client::send;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    let storage_manager = Arc::new(StorageManager::new(config_path));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/api", listener.local_addr().unwrap());
+    let router = create_routes(instance_service.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("content-type", "application/json".to_string())];
+    let body = |name: &str, listen_port: u16| {
+        serde_json::json!({
+            "listen_ip": "127.0.0.1",
+            "listen_port": listen_port,
+            "dst_ip": "127.0.0.1",
+            "dst_port": 80,
+            "protocol": "tcp",
+            "auto_start": false,
+            "connect_timeout_secs": 30,
+            "idle_timeout_secs": 300,
+            "log_level": "info",
+            "name": name,
+        })
+        .to_string()
+        .into_bytes()
+    };
+    let created: serde_json::Value = send(
+        Method::POST,
+        &format!("{}/instances", base),
+        &headers,
+        Some(body("kept", 18703)),
+    )
+    .await
+    .unwrap()
+    .json()
+    .unwrap();
+    let backup: serde_json::Value = send(Method::POST, &format!("{}/config/backup", base), &[], None)
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    let backup_id = backup["backup_id"].as_str().unwrap().to_string();
+
+    let update = serde_json::json!({ "dst_port": 8080 }).to_string().into_bytes();
+    send(
+        Method::PUT,
+        &format!("{}/instances/{}", base, created["id"].as_str().unwrap()),
+        &headers,
+        Some(update),
+    )
+    .await
+    .unwrap();
+    send(
+        Method::POST,
+        &format!("{}/instances", base),
+        &headers,
+        Some(body("added-later", 18704)),
+    )
+    .await
+    .unwrap();
+
+    let diff: serde_json::Value = send(
+        Method::GET,
+        &format!("{}/config/diff?against={}", base, backup_id),
+        &[],
+        None,
+    )
+    .await
+    .unwrap()
+    .json()
+    .unwrap();
+    assert_eq!(diff["removed"][0]["name"], "added-later");
+    assert_eq!(diff["added"].as_array().unwrap().len(), 0);
+    let change = &diff["changed"][0];
+    assert_eq!(change["name"], "kept");
+    assert_eq!(change["fields"][0]["field"], "config.proxy.dst_port");
+    assert_eq!(change["fields"][0]["running"], 8080);
+    assert_eq!(change["fields"][0]["candidate"], 80);
+
+    let against_file: serde_json::Value = send(Method::GET, &format!("{}/config/diff", base), &[], None)
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(against_file["unchanged"], 2);
+    assert!(against_file["changed"].as_array().unwrap().is_empty());
+
+    let missing = send(
+        Method::GET,
+        &format!("{}/config/diff?against=19990101_000000", base),
+        &[],
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(missing.status, 404);
+    let invalid = send(Method::GET, &format!("{}/config/diff?against=..%2Fetc", base), &[], None)
+        .await
+        .unwrap();
+    assert_eq!(invalid.status, 400);
+}
+
+#[tokio::test]
+async fn test_web_api_import_dry_run_reports_conflicts() {
+    use axum::http::Method;
+    use void_proxy::http_client::send;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    let storage_manager = Arc::new(StorageManager::new(config_path.clone()));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/api", listener.local_addr().unwrap());
+    let router = create_routes(instance_service.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("content-type", "application/json".to_string())];
+    let body = |name: &str, listen_port: u16| {
+        serde_json::json!({
+            "listen_ip": "127.0.0.1",
+            "listen_port": listen_port,
+            "dst_ip": "127.0.0.1",
+            "dst_port": 80,
+            "protocol": "tcp",
+            "auto_start": false,
+            "connect_timeout_secs": 30,
+            "idle_timeout_secs": 300,
+            "log_level": "info",
+            "name": name,
+        })
+        .to_string()
+        .into_bytes()
+    };
+    send(Method::POST, &format!("{}/instances", base), &headers, Some(body("first", 18711)))
+        .await
+        .unwrap();
+    let created: serde_json::Value = send(
+        Method::POST,
+        &format!("{}/instances", base),
+        &headers,
+        Some(body("second", 18712)),
+    )
+    .await
+    .unwrap()
+    .json()
+    .unwrap();
+    let exported = instance_service.export_config().await.unwrap();
+    let second_id = created["id"].as_str().unwrap();
+    send(Method::DELETE, &format!("{}/instances/{}", base, second_id), &[], None)
+        .await
+        .unwrap();
+    let stored = std::fs::read_to_string(&config_path).unwrap();
+
+    let candidate = exported.replace("listen_port = 18712", "listen_port = 18711");
+    let request = serde_json::json!({ "config": candidate, "dry_run": true })
+        .to_string()
+        .into_bytes();
+    let response = send(
+        Method::POST,
+        &format!("{}/config/import", base),
+        &headers,
+        Some(request),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status, 200);
+    let plan: serde_json::Value = response.json().unwrap();
+    assert_eq!(plan["valid"], false);
+    let entries = plan["instances"].as_array().unwrap();
+    let first = entries.iter().find(|e| e["name"] == "first").unwrap();
+    let second = entries.iter().find(|e| e["name"] == "second").unwrap();
+    assert_eq!(first["action"], "unchanged");
+    assert_eq!(second["action"], "create");
+    assert!(second["errors"][0].as_str().unwrap().contains("conflicts with first"));
+    assert_eq!(plan["removed"].as_array().unwrap().len(), 0);
+
+    let invalid = serde_json::json!({ "config": "not = [toml", "dry_run": true })
+        .to_string()
+        .into_bytes();
+    let response = send(
+        Method::POST,
+        &format!("{}/config/import", base),
+        &headers,
+        Some(invalid),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status, 400);
+    assert_eq!(instance_service.get_instances().await.len(), 1);
+    assert_eq!(std::fs::read_to_string(&config_path).unwrap(), stored);
+
+    let misspelled = exported.replace("idle_timeout_secs = 300", "idle_timeout_secs = 300\nidle_timout_secs = 60");
+    for (dry_run, status) in [(true, 200), (false, 400)] {
+        let request = serde_json::json!({ "config": misspelled, "dry_run": dry_run, "strict": true })
+            .to_string()
+            .into_bytes();
+        let response = send(
+            Method::POST,
+            &format!("{}/config/import", base),
+            &headers,
+            Some(request),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status, status);
+        let body: serde_json::Value = response.json().unwrap();
+        if dry_run {
+            assert_eq!(body["valid"], false);
+            assert_eq!(body["unknown_fields"].as_array().unwrap().len(), 2);
+        } else {
+            assert_eq!(body["error"], "UNKNOWN_FIELDS");
+        }
+    }
+    assert_eq!(std::fs::read_to_string(&config_path).unwrap(), stored);
+}
+
+#[tokio::test]
+async fn test_web_api_instance_reports_last_error() {
+    use axum::http::Method;
+    use void_proxy::http_client::send;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    let storage_manager = Arc::new(StorageManager::new(config_path));
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/api", listener.local_addr().unwrap());
+    let router = create_routes(instance_service.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+    let closed_port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+
+    let headers = [("content-type", "application/json".to_string())];
+    let body = serde_json::json!({
+        "listen_ip": "127.0.0.1",
+        "listen_port": 18721,
+        "dst_ip": "127.0.0.1",
+        "dst_port": closed_port,
+        "protocol": "tcp",
+        "auto_start": true,
+        "connect_timeout_secs": 5,
+        "idle_timeout_secs": 300,
+        "log_level": "info",
+        "name": "unreachable",
+    })
+    .to_string()
+    .into_bytes();
+    let created: serde_json::Value =
+        send(Method::POST, &format!("{}/instances", base), &headers, Some(body))
+            .await
+            .unwrap()
+            .json()
+            .unwrap();
+    let url = format!("{}/instances/{}", base, created["id"].as_str().unwrap());
+    let instance: serde_json::Value = send(Method::GET, &url, &[], None)
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert!(instance["last_error"].is_null());
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let _client = tokio::net::TcpStream::connect("127.0.0.1:18721").await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let instance: serde_json::Value = send(Method::GET, &url, &[], None)
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(instance["last_error"]["category"], "upstream");
+    let message = instance["last_error"]["message"].as_str().unwrap();
+    assert!(message.contains(&format!("127.0.0.1:{}", closed_port)));
+    assert!(instance["last_error"]["timestamp"].is_string());
+}
+
+#[tokio::test]
+async fn test_web_api_create_fills_omitted_fields_from_defaults() {
+    use axum::http::Method;
+    use void_proxy::http_client::send;
+
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("test_config.toml");
+    std::fs::write(
+        &config_path,
+        r#"
+instances = []
+version = "1.0"
+created_at = "2026-01-01T00:00:00Z"
+updated_at = "2026-01-01T00:00:00Z"
+
+[defaults]
+protocol = "udp"
+auto_start = false
+idle_timeout_secs = 60
+log_level = "debug"
+"#,
+    )
+    .unwrap();
+    let storage_manager = Arc::new(StorageManager::new(config_path));
+    storage_manager.load().await.unwrap();
+    let instance_service = Arc::new(InstanceService::with_storage(storage_manager));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}/api", listener.local_addr().unwrap());
+    let router = create_routes(instance_service.clone());
+    tokio::spawn(async move {
+        axum::serve(listener, router).await.unwrap();
+    });
+
+    let headers = [("content-type", "application/json".to_string())];
+    let body = serde_json::json!({
+        "name": "minimal",
+        "listen_ip": "127.0.0.1",
+        "listen_port": 18816,
+        "dst_ip": "127.0.0.1",
+        "dst_port": 53,
+    })
+    .to_string()
+    .into_bytes();
+    let created = send(Method::POST, &format!("{}/instances", base), &headers, Some(body))
+        .await
+        .unwrap();
+    assert_eq!(created.status, 200);
+    let created: serde_json::Value = created.json().unwrap();
+    assert_eq!(created["auto_start"], false);
+    let proxy = &created["config"]["proxy"];
+    assert_eq!(proxy["protocol"], "udp");
+    assert_eq!(proxy["connect_timeout_secs"], 30);
+    assert_eq!(proxy["idle_timeout_secs"], 60);
+    assert_eq!(proxy["log_level"], "debug");
+
+    let defaults_url = format!("{}/config/defaults", base);
+    let defaults: serde_json::Value = send(Method::GET, &defaults_url, &[], None)
+        .await
+        .unwrap()
+        .json()
+        .unwrap();
+    assert_eq!(defaults["protocol"], "udp");
+    assert_eq!(defaults["connect_timeout_secs"], 30);
+    assert!(
+        instance_service
+            .export_config()
+            .await
+            .unwrap()
+            .contains("[defaults]")
+    );
+}
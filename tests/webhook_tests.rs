@@ -0,0 +1,137 @@
+use void_proxy::config::{LogLevel, Protocol};
+use void_proxy::instance::CreateInstanceRequest;
+use void_proxy::instance_manager::InstanceService;
+use void_proxy::storage::StorageManager;
+use void_proxy::webhook::{CreateWebhookRequest, WebhookManager, sign_payload};
+use axum::http::HeaderMap;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn webhook_request(url: &str, events: &[&str]) -> CreateWebhookRequest {
+    CreateWebhookRequest {
+        url: url.to_string(),
+        secret: Some("s3cret".to_string()),
+        events: events.iter().map(|event| event.to_string()).collect(),
+    }
+}
+
+#[tokio::test]
+async fn test_webhooks_are_persisted_and_redacted() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("webhooks.json");
+    let manager = WebhookManager::load(path.clone()).await.unwrap();
+
+    assert!(manager
+        .register(webhook_request("ftp://example.com", &[]))
+        .await
+        .is_err());
+    assert!(manager
+        .register(webhook_request("https://example.com", &["instance_exploded"]))
+        .await
+        .is_err());
+    let webhook = manager
+        .register(webhook_request("https://example.com/hook", &["instance_started"]))
+        .await
+        .unwrap();
+
+    let reloaded = WebhookManager::load(path).await.unwrap();
+    let listed = reloaded.list().await;
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, webhook.id);
+    assert_eq!(listed[0].secret.as_deref(), Some("********"));
+
+    assert!(reloaded.remove(webhook.id).await.unwrap());
+    assert!(!reloaded.remove(webhook.id).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_webhook_receives_signed_lifecycle_event() {
+    let (sender, mut received) = tokio::sync::mpsc::unbounded_channel();
+    let app = axum::Router::new().route(
+        "/hook",
+        axum::routing::post(move |headers: HeaderMap, body: axum::body::Bytes| {
+            let sender = sender.clone();
+            async move {
+                sender.send((headers, body)).unwrap();
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let hook_url = format!("http://{}/hook", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, app).await.unwrap();
+    });
+
+    let temp_dir = TempDir::new().unwrap();
+    let service = Arc::new(InstanceService::with_storage(Arc::new(StorageManager::new(
+        temp_dir.path().join("test_config.toml"),
+    ))));
+    let manager = Arc::new(
+        WebhookManager::load(temp_dir.path().join("webhooks.json"))
+            .await
+            .unwrap(),
+    );
+    manager
+        .register(webhook_request(&hook_url, &["instance_started"]))
+        .await
+        .unwrap();
+    manager.clone().start(service.subscribe_events());
+
+    let instance = service
+        .create_instance(CreateInstanceRequest {
+            name: "Webhook Instance".to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            listen_port: 18710,
+            dst_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            dst_port: 18711,
+            protocol: Protocol::Tcp,
+            auto_start: true,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        })
+        .await
+        .unwrap();
+
+    let (headers, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let payload: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(payload["event"], "instance_started");
+    assert_eq!(payload["instance_id"], instance.id.to_string());
+    assert_eq!(headers["x-voidproxy-event"], "instance_started");
+    assert_eq!(
+        headers["x-voidproxy-signature"].to_str().unwrap(),
+        sign_payload("s3cret", &body)
+    );
+
+    service.stop_instance(instance.id).await.unwrap();
+}
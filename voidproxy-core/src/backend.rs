@@ -0,0 +1,274 @@
+use crate::config::ProxyConfig;
+use crate::metrics::InstanceMetrics;
+use anyhow::Result;
+use serde::Serialize;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+/**
+ * A single upstream endpoint an instance can forward to.
+ *
+ * Priority and weight follow SRV semantics: the lowest priority group with a
+ * healthy member is used, and weight biases selection inside that group.
+ * `host` is set on addresses resolved from a `host:port` entry.
+ */
+pub struct Backend {
+    pub addr: SocketAddr,
+    pub weight: u32,
+    pub priority: u16,
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+}
+impl Backend {
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            weight: 1,
+            priority: 0,
+            healthy: true,
+            host: None,
+        }
+    }
+}
+/**
+ * Result of resolving a backend provider.
+ *
+ * `ttl` tells when the provider wants to be asked again; providers without
+ * an expiry are refreshed on the default interval.
+ */
+pub struct Resolution {
+    pub backends: Vec<Backend>,
+    pub ttl: Option<Duration>,
+}
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<Resolution>> + Send + 'a>>;
+/**
+ * Source of dynamically discovered backends (service registries, DNS, ...).
+ *
+ * Providers are built from a `scheme://...` entry of an instance's `backends`
+ * list and are resolved periodically while the instance runs.
+ */
+pub trait BackendProvider: Send + Sync {
+    fn resolve(&self) -> ResolveFuture<'_>;
+    fn describe(&self) -> String;
+}
+/**
+ * Parsed entry of an instance's `backends` list.
+ */
+pub enum BackendEntry {
+    Static(SocketAddr),
+    Provider(Arc<dyn BackendProvider>),
+}
+/**
+ * Parses a `backends` entry: a literal `ip:port`, a `host:port` resolved
+ * through DNS, or a discovery URI.
+ */
+pub fn parse_backend_entry(entry: &str) -> Result<BackendEntry> {
+    let entry = entry.trim();
+    if let Some((scheme, target)) = entry.split_once("://") {
+        let provider: Arc<dyn BackendProvider> = match scheme {
+            "k8s" => Arc::new(crate::kubernetes::KubernetesProvider::from_target(target)?),
+            "consul" => Arc::new(crate::consul::ConsulProvider::from_target(target)?),
+            "srv" => Arc::new(crate::srv::SrvProvider::from_target(target)?),
+            _ => return Err(anyhow::anyhow!("Unsupported backend scheme: {}", scheme)),
+        };
+        return Ok(BackendEntry::Provider(provider));
+    }
+    if let Ok(addr) = entry.parse() {
+        return Ok(BackendEntry::Static(addr));
+    }
+    let provider = crate::hostname::HostnameProvider::from_entry(entry)?;
+    Ok(BackendEntry::Provider(Arc::new(provider)))
+}
+/**
+ * The live set of backends of a running instance.
+ *
+ * Combines static addresses with the last successful resolution of every
+ * provider. Selection is lock-free apart from a short read lock on the list.
+ */
+pub struct BackendSet {
+    static_backends: Vec<Backend>,
+    providers: Vec<Arc<dyn BackendProvider>>,
+    discovered: std::sync::RwLock<Vec<Vec<Backend>>>,
+    counter: AtomicUsize,
+}
+impl BackendSet {
+    /**
+     * Builds the backend set of an instance, or `None` when it only uses its
+     * fixed destination address.
+     */
+    pub fn from_config(proxy: &ProxyConfig) -> Option<Arc<Self>> {
+        if proxy.backends.is_empty() {
+            return None;
+        }
+        let mut static_backends = Vec::new();
+        let mut providers = Vec::new();
+        for entry in &proxy.backends {
+            match parse_backend_entry(entry) {
+                Ok(BackendEntry::Static(addr)) => static_backends.push(Backend::new(addr)),
+                Ok(BackendEntry::Provider(provider)) => providers.push(provider),
+                Err(e) => warn!("Ignoring backend {}: {}", entry, e),
+            }
+        }
+        let discovered = vec![Vec::new(); providers.len()];
+        Some(Arc::new(Self {
+            static_backends,
+            providers,
+            discovered: std::sync::RwLock::new(discovered),
+            counter: AtomicUsize::new(0),
+        }))
+    }
+    pub fn backends(&self) -> Vec<Backend> {
+        let discovered = self.discovered.read().unwrap_or_else(|e| e.into_inner());
+        self.static_backends
+            .iter()
+            .chain(discovered.iter().flatten())
+            .cloned()
+            .collect()
+    }
+    fn candidates(&self) -> Vec<Backend> {
+        let healthy: Vec<Backend> = self
+            .backends()
+            .into_iter()
+            .filter(|backend| backend.healthy)
+            .collect();
+        let Some(priority) = healthy.iter().map(|backend| backend.priority).min() else {
+            return Vec::new();
+        };
+        healthy
+            .into_iter()
+            .filter(|backend| backend.priority == priority)
+            .collect()
+    }
+    fn select(candidates: &[Backend], position: usize) -> Option<SocketAddr> {
+        let total: usize = candidates
+            .iter()
+            .map(|backend| backend.weight.max(1) as usize)
+            .sum();
+        if total == 0 {
+            return None;
+        }
+        let mut position = position % total;
+        for backend in candidates {
+            let weight = backend.weight.max(1) as usize;
+            if position < weight {
+                return Some(backend.addr);
+            }
+            position -= weight;
+        }
+        None
+    }
+    /**
+     * Picks the next backend in weighted round-robin order.
+     */
+    pub fn pick(&self) -> Option<SocketAddr> {
+        let position = self.counter.fetch_add(1, Ordering::Relaxed);
+        Self::select(&self.candidates(), position)
+    }
+    /**
+     * Picks a backend deterministically for a client, so datagrams of one
+     * UDP peer keep reaching the same upstream.
+     */
+    pub fn pick_sticky(&self, key: &SocketAddr) -> Option<SocketAddr> {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        Self::select(&self.candidates(), hasher.finish() as usize)
+    }
+    /**
+     * Addresses to try for a connection to `addr`: `addr` itself, followed by
+     * the other healthy addresses its hostname resolved to.
+     */
+    pub fn connect_candidates(&self, addr: SocketAddr) -> Vec<SocketAddr> {
+        let backends = self.backends();
+        let host = backends
+            .iter()
+            .find(|backend| backend.addr == addr)
+            .and_then(|backend| backend.host.as_ref());
+        let mut candidates = vec![addr];
+        if let Some(host) = host {
+            candidates.extend(
+                backends
+                    .iter()
+                    .filter(|backend| {
+                        backend.healthy
+                            && backend.addr != addr
+                            && backend.host.as_ref() == Some(host)
+                    })
+                    .map(|backend| backend.addr),
+            );
+        }
+        candidates
+    }
+    /**
+     * Resolves every provider once and returns the delay before the next refresh.
+     */
+    pub async fn refresh(&self) -> Duration {
+        let mut next_refresh = DEFAULT_REFRESH_INTERVAL;
+        for (index, provider) in self.providers.iter().enumerate() {
+            match provider.resolve().await {
+                Ok(resolution) => {
+                    debug!(
+                        "Resolved {} backends from {}",
+                        resolution.backends.len(),
+                        provider.describe()
+                    );
+                    if let Some(ttl) = resolution.ttl {
+                        next_refresh = next_refresh.min(ttl.max(MIN_REFRESH_INTERVAL));
+                    }
+                    let mut discovered = self.discovered.write().unwrap_or_else(|e| e.into_inner());
+                    discovered[index] = resolution.backends;
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to resolve backends from {}: {}",
+                        provider.describe(),
+                        e
+                    );
+                }
+            }
+        }
+        next_refresh
+    }
+    /**
+     * Resolves the providers once, then keeps discovered backends up to date
+     * in the background until the instance stops. The backends are reported
+     * to `metrics` after every resolution.
+     */
+    pub async fn start_refresh(
+        self: &Arc<Self>,
+        cancel_token: Arc<CancellationToken>,
+        metrics: Option<Arc<InstanceMetrics>>,
+    ) {
+        let report = move |backend_set: &Self| {
+            if let Some(ref metrics) = metrics {
+                metrics.report_backends(&backend_set.backends());
+            }
+        };
+        if self.providers.is_empty() {
+            report(self);
+            return;
+        }
+        let mut delay = self.refresh().await;
+        report(self);
+        let backend_set = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => break,
+                    _ = tokio::time::sleep(delay) => {}
+                }
+                delay = backend_set.refresh().await;
+                report(&backend_set);
+            }
+        });
+    }
+}
@@ -0,0 +1,105 @@
+use crate::metrics::InstanceMetrics;
+use socket2::Socket;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/**
+ * Bytes written to a TCP socket that the peer has not acknowledged yet
+ * (`SIOCOUTQ`), or `None` where the platform does not report it.
+ */
+#[cfg(target_os = "linux")]
+pub fn unsent_bytes(socket: &Socket) -> Option<u32> {
+    use std::os::fd::AsRawFd;
+    let mut queued: nix::libc::c_int = 0;
+    let result = unsafe { nix::libc::ioctl(socket.as_raw_fd(), nix::libc::TIOCOUTQ, &mut queued) };
+    (result == 0).then_some(queued.max(0) as u32)
+}
+#[cfg(not(target_os = "linux"))]
+pub fn unsent_bytes(_socket: &Socket) -> Option<u32> {
+    None
+}
+/**
+ * Waits until the unacknowledged bytes queued on `socket` drop to
+ * `watermark`, so a fast sender cannot pile data up in the kernel send
+ * queue of a slow receiver.
+ *
+ * While waiting the connection counts as stalled in `metrics`. Returns
+ * `false` when the queue did not drain within `limit`.
+ */
+pub async fn wait_for_drain(
+    socket: &Socket,
+    watermark: u32,
+    limit: Duration,
+    metrics: Option<&InstanceMetrics>,
+) -> bool {
+    if unsent_bytes(socket).is_none_or(|queued| queued <= watermark) {
+        return true;
+    }
+    let _stalled = metrics.map(Stalled::new);
+    let started = Instant::now();
+    let mut interval = MIN_POLL_INTERVAL;
+    loop {
+        tokio::time::sleep(interval).await;
+        if unsent_bytes(socket).is_none_or(|queued| queued <= watermark) {
+            return true;
+        }
+        if started.elapsed() >= limit {
+            return false;
+        }
+        interval = (interval * 2).min(MAX_POLL_INTERVAL);
+    }
+}
+/**
+ * Marks a connection as stalled until dropped, which also happens when the
+ * wait is cancelled.
+ */
+struct Stalled<'a>(&'a InstanceMetrics);
+impl<'a> Stalled<'a> {
+    fn new(metrics: &'a InstanceMetrics) -> Self {
+        metrics.connections_stalled.fetch_add(1, Ordering::Relaxed);
+        metrics.stalls_total.fetch_add(1, Ordering::Relaxed);
+        Self(metrics)
+    }
+}
+impl Drop for Stalled<'_> {
+    fn drop(&mut self) {
+        self.0.connections_stalled.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+/**
+ * Budget of connection handlers an instance may run at once. A burst on
+ * one instance then queues up behind its own listener instead of starving
+ * the tasks of the others.
+ */
+pub struct HandlerLimit {
+    semaphore: Arc<Semaphore>,
+    metrics: Option<Arc<InstanceMetrics>>,
+}
+impl HandlerLimit {
+    pub fn new(limit: u32, metrics: Option<Arc<InstanceMetrics>>) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit as usize)),
+            metrics,
+        }
+    }
+    /**
+     * Waits for a free handler, counting the wait in `handler_waits` when
+     * the budget is exhausted. The handler is released with the permit.
+     */
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        if let Ok(permit) = self.semaphore.clone().try_acquire_owned() {
+            return permit;
+        }
+        if let Some(ref metrics) = self.metrics {
+            metrics.handler_waits.fetch_add(1, Ordering::Relaxed);
+        }
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Handler semaphore is never closed")
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/**
+ * How often scheduled backups of the configuration are taken.
+ */
+pub enum BackupFrequency {
+    #[default]
+    Off,
+    Daily,
+    Weekly,
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+/**
+ * Scheduled backups, configured in the `[backup]` section of the
+ * configuration file.
+ *
+ * A backup is taken every day, or every week on `weekday`, at `at`
+ * (`HH:MM`, UTC). Only the `keep` most recent backups are kept next to the
+ * configuration file; 0 keeps them all.
+ */
+pub struct BackupSchedule {
+    pub frequency: BackupFrequency,
+    pub at: String,
+    pub weekday: Weekday,
+    pub keep: usize,
+}
+impl Default for BackupSchedule {
+    fn default() -> Self {
+        Self {
+            frequency: BackupFrequency::Off,
+            at: "03:00".to_string(),
+            weekday: Weekday::Sun,
+            keep: 7,
+        }
+    }
+}
+impl BackupSchedule {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+    fn time_of_day(&self) -> Result<NaiveTime> {
+        NaiveTime::parse_from_str(&self.at, "%H:%M")
+            .map_err(|_| anyhow::anyhow!("Invalid backup time {}, expected HH:MM", self.at))
+    }
+    pub fn validate(&self) -> Result<()> {
+        self.time_of_day()?;
+        Ok(())
+    }
+    /**
+     * First scheduled run strictly after `after`, or `None` when scheduled
+     * backups are off.
+     */
+    pub fn next_run(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if self.frequency == BackupFrequency::Off {
+            return None;
+        }
+        let time = self.time_of_day().ok()?;
+        let mut next = after.date_naive().and_time(time).and_utc();
+        if next <= after {
+            next += chrono::Duration::days(1);
+        }
+        if self.frequency == BackupFrequency::Weekly {
+            while next.weekday() != self.weekday {
+                next += chrono::Duration::days(1);
+            }
+        }
+        Some(next)
+    }
+}
@@ -0,0 +1,355 @@
+use crate::config::{Config, Protocol};
+use crate::instance::{InstanceManager, ProxyInstance};
+use crate::middleware::MiddlewareChain;
+use crate::tcp_proxy::TcpProxy;
+use crate::udp_proxy::UdpProxy;
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+pub const MAX_DURATION_SECS: u64 = 30;
+pub const MAX_PAYLOAD_SIZE: usize = 65_507;
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(10);
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/**
+ * Settings of a loopback benchmark.
+ *
+ * The generator sends `payload_size` byte messages for `duration_secs`
+ * seconds; each message counts as one packet.
+ */
+pub struct BenchmarkOptions {
+    #[serde(default = "default_duration_secs")]
+    pub duration_secs: u64,
+    #[serde(default = "default_payload_size")]
+    pub payload_size: usize,
+}
+fn default_duration_secs() -> u64 {
+    3
+}
+fn default_payload_size() -> usize {
+    1400
+}
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        Self {
+            duration_secs: default_duration_secs(),
+            payload_size: default_payload_size(),
+        }
+    }
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/**
+ * Throughput measured for each protocol of the benchmarked instance.
+ */
+pub struct BenchmarkReport {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp: Option<Throughput>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub udp: Option<Throughput>,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Traffic that reached the sink while the generator was sending.
+ * Megabytes are 1,000,000 bytes.
+ */
+pub struct Throughput {
+    pub duration_secs: f64,
+    pub bytes: u64,
+    pub packets: u64,
+    pub megabytes_per_sec: f64,
+    pub packets_per_sec: f64,
+}
+impl Throughput {
+    fn new(bytes: u64, packets: u64, elapsed: Duration) -> Self {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        Self {
+            duration_secs: secs,
+            bytes,
+            packets,
+            megabytes_per_sec: bytes as f64 / secs / 1_000_000.0,
+            packets_per_sec: packets as f64 / secs,
+        }
+    }
+}
+#[derive(Default)]
+struct Counters {
+    bytes: AtomicU64,
+    packets: AtomicU64,
+}
+impl Counters {
+    fn snapshot(&self) -> (u64, u64) {
+        (
+            self.bytes.load(Ordering::Relaxed),
+            self.packets.load(Ordering::Relaxed),
+        )
+    }
+}
+/**
+ * Measures the throughput of an instance's data path on loopback.
+ *
+ * A temporary copy of the instance listens on a free loopback port and
+ * forwards to a sink that discards everything, so neither the real listener
+ * nor the real destination is touched and the instance's own metrics stay
+ * unchanged. Middleware, timeouts and backpressure settings are kept; IP
+ * filters, backends, source binding, TLS and tunnels are left out because
+ * they need peers outside the host.
+ */
+pub async fn run(
+    config: &Config,
+    middleware: MiddlewareChain,
+    options: BenchmarkOptions,
+) -> Result<BenchmarkReport> {
+    if !(1..=MAX_DURATION_SECS).contains(&options.duration_secs) {
+        bail!(
+            "Benchmark duration must be between 1 and {} seconds",
+            MAX_DURATION_SECS
+        );
+    }
+    if !(1..=MAX_PAYLOAD_SIZE).contains(&options.payload_size) {
+        bail!(
+            "Benchmark payload size must be between 1 and {} bytes",
+            MAX_PAYLOAD_SIZE
+        );
+    }
+    let mut report = BenchmarkReport::default();
+    if matches!(config.proxy.protocol, Protocol::Tcp | Protocol::Both) {
+        report.tcp = Some(
+            run_tcp(config, middleware.clone(), options)
+                .await
+                .context("TCP benchmark failed")?,
+        );
+    }
+    if matches!(config.proxy.protocol, Protocol::Udp | Protocol::Both) {
+        report.udp = Some(
+            run_udp(config, middleware, options)
+                .await
+                .context("UDP benchmark failed")?,
+        );
+    }
+    Ok(report)
+}
+async fn run_tcp(
+    config: &Config,
+    middleware: MiddlewareChain,
+    options: BenchmarkOptions,
+) -> Result<Throughput> {
+    let sink = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let sink_addr = sink.local_addr()?;
+    let counters = Arc::new(Counters::default());
+    let sink_task = tokio::spawn({
+        let counters = counters.clone();
+        async move {
+            while let Ok((mut stream, _)) = sink.accept().await {
+                let counters = counters.clone();
+                tokio::spawn(async move {
+                    let mut buffer = vec![0u8; 64 * 1024];
+                    while let Ok(read @ 1..) = stream.read(&mut buffer).await {
+                        counters.bytes.fetch_add(read as u64, Ordering::Relaxed);
+                    }
+                });
+            }
+        }
+    });
+    let listen_port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+        .local_addr()?
+        .port();
+    let proxy = LoopbackProxy::spawn(
+        loopback_config(config, Protocol::Tcp, listen_port, sink_addr),
+        middleware,
+    );
+    let result = async {
+        let listen_addr = SocketAddr::from((Ipv4Addr::LOCALHOST, listen_port));
+        let started = Instant::now();
+        let mut stream = loop {
+            match TcpStream::connect(listen_addr).await {
+                Ok(stream) => break stream,
+                Err(e) if started.elapsed() >= READY_TIMEOUT => {
+                    return Err(
+                        anyhow::Error::from(e).context("Benchmark listener did not come up")
+                    );
+                }
+                Err(_) => proxy.wait(READY_POLL_INTERVAL).await?,
+            }
+        };
+        stream.set_nodelay(true)?;
+        let payload = vec![0x5a; options.payload_size];
+        stream.write_all(&payload).await?;
+        while counters.snapshot().0 == 0 {
+            if started.elapsed() >= READY_TIMEOUT {
+                bail!("No traffic reached the benchmark sink");
+            }
+            proxy.wait(READY_POLL_INTERVAL).await?;
+        }
+        let (baseline, _) = counters.snapshot();
+        let started = Instant::now();
+        let duration = Duration::from_secs(options.duration_secs);
+        let deadline = tokio::time::Instant::from_std(started) + duration;
+        while started.elapsed() < duration {
+            match tokio::time::timeout_at(deadline, stream.write_all(&payload)).await {
+                Ok(written) => written?,
+                Err(_) => break,
+            }
+        }
+        let elapsed = started.elapsed();
+        let bytes = counters.snapshot().0 - baseline;
+        Ok(Throughput::new(
+            bytes,
+            bytes / options.payload_size as u64,
+            elapsed,
+        ))
+    }
+    .await;
+    sink_task.abort();
+    proxy.shutdown().await?;
+    result
+}
+async fn run_udp(
+    config: &Config,
+    middleware: MiddlewareChain,
+    options: BenchmarkOptions,
+) -> Result<Throughput> {
+    let sink = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let sink_addr = sink.local_addr()?;
+    let counters = Arc::new(Counters::default());
+    let sink_task = tokio::spawn({
+        let counters = counters.clone();
+        async move {
+            let mut buffer = vec![0u8; MAX_PAYLOAD_SIZE];
+            while let Ok((read, _)) = sink.recv_from(&mut buffer).await {
+                counters.bytes.fetch_add(read as u64, Ordering::Relaxed);
+                counters.packets.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    });
+    let listen_port = std::net::UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?
+        .local_addr()?
+        .port();
+    let proxy = LoopbackProxy::spawn(
+        loopback_config(config, Protocol::Udp, listen_port, sink_addr),
+        middleware,
+    );
+    let result = async {
+        let listen_addr = SocketAddr::from((Ipv4Addr::LOCALHOST, listen_port));
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+        socket.connect(listen_addr).await?;
+        let payload = vec![0x5a; options.payload_size];
+        let started = Instant::now();
+        while counters.snapshot().1 == 0 {
+            if started.elapsed() >= READY_TIMEOUT {
+                bail!("No traffic reached the benchmark sink");
+            }
+            let _ = socket.send(&payload).await;
+            proxy.wait(READY_POLL_INTERVAL).await?;
+        }
+        let (baseline_bytes, baseline_packets) = counters.snapshot();
+        let started = Instant::now();
+        let duration = Duration::from_secs(options.duration_secs);
+        while started.elapsed() < duration {
+            if let Err(e) = socket.send(&payload).await
+                && e.kind() != std::io::ErrorKind::ConnectionRefused
+            {
+                return Err(e.into());
+            }
+            // Loopback sends hardly ever block, so let the proxy and the sink run.
+            tokio::task::yield_now().await;
+        }
+        let elapsed = started.elapsed();
+        let (bytes, packets) = counters.snapshot();
+        Ok(Throughput::new(
+            bytes - baseline_bytes,
+            packets - baseline_packets,
+            elapsed,
+        ))
+    }
+    .await;
+    sink_task.abort();
+    proxy.shutdown().await?;
+    result
+}
+fn loopback_config(
+    config: &Config,
+    protocol: Protocol,
+    listen_port: u16,
+    sink: SocketAddr,
+) -> Config {
+    let mut config = config.clone();
+    config.ip_filter = None;
+    config.profiles.clear();
+    let proxy = &mut config.proxy;
+    proxy.listen_ip = Ipv4Addr::LOCALHOST.into();
+    proxy.listen_port = listen_port;
+    proxy.dst_ip = sink.ip();
+    proxy.dst_port = sink.port();
+    proxy.protocol = protocol;
+    proxy.bind_device = None;
+    proxy.bind_nonlocal = false;
+    proxy.backends.clear();
+    proxy.bind_src_ip = None;
+    proxy.bind_src_device = None;
+    proxy.tls = None;
+    proxy.tunnel = None;
+    config
+}
+/**
+ * Temporary proxy of a benchmark, registered in its own instance map.
+ */
+struct LoopbackProxy {
+    cancel_token: Arc<CancellationToken>,
+    handle: JoinHandle<Result<()>>,
+}
+impl LoopbackProxy {
+    fn spawn(config: Config, middleware: MiddlewareChain) -> Self {
+        let protocol = config.proxy.protocol;
+        let mut instance = ProxyInstance::new("benchmark".to_string(), config, false);
+        let _ = instance.start();
+        let _ = instance.set_running();
+        let id = instance.id;
+        let state = instance.state();
+        let config = Arc::new(instance.config.clone());
+        let instances: InstanceManager = Arc::new(RwLock::new(HashMap::from([(id, instance)])));
+        let cancel_token = Arc::new(CancellationToken::new());
+        let token = cancel_token.clone();
+        let handle = match protocol {
+            Protocol::Udp => {
+                let proxy = UdpProxy::new(config, id, instances)
+                    .with_middleware(middleware)
+                    .with_state(state);
+                tokio::spawn(async move { proxy.run_with_token(token).await })
+            }
+            _ => {
+                let proxy = TcpProxy::new(config, id, instances)
+                    .with_middleware(middleware)
+                    .with_state(state);
+                tokio::spawn(async move { proxy.run_with_token(token).await })
+            }
+        };
+        Self {
+            cancel_token,
+            handle,
+        }
+    }
+    /**
+     * Sleeps for `interval`, failing early when the proxy has already
+     * stopped (e.g. because its port was taken in the meantime).
+     */
+    async fn wait(&self, interval: Duration) -> Result<()> {
+        if self.handle.is_finished() {
+            bail!("Benchmark proxy stopped unexpectedly");
+        }
+        tokio::time::sleep(interval).await;
+        Ok(())
+    }
+    async fn shutdown(self) -> Result<()> {
+        self.cancel_token.cancel();
+        self.handle.await?
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hyper::Method;
+use ipnet::IpNet;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+use tracing::{debug, info, warn};
+static BLOCKLIST: OnceLock<Blocklist> = OnceLock::new();
+/**
+ * Blocklist shared by all instances of the process.
+ */
+pub fn global() -> &'static Blocklist {
+    BLOCKLIST.get_or_init(Blocklist::default)
+}
+#[derive(Debug, Clone, Default)]
+/**
+ * Set of networks matched by prefix length, so a lookup costs one hash
+ * probe per distinct prefix length instead of a scan of every entry.
+ */
+pub struct NetworkSet {
+    by_prefix: BTreeMap<u8, HashSet<IpNet>>,
+    len: usize,
+}
+impl NetworkSet {
+    pub fn insert(&mut self, network: IpNet) {
+        let added = self
+            .by_prefix
+            .entry(network.prefix_len())
+            .or_default()
+            .insert(network.trunc());
+        if added {
+            self.len += 1;
+        }
+    }
+    pub fn extend(&mut self, other: &NetworkSet) {
+        for network in other.by_prefix.values().flatten() {
+            self.insert(*network);
+        }
+    }
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.by_prefix.iter().any(|(prefix_len, networks)| {
+            IpNet::new(ip, *prefix_len).is_ok_and(|network| networks.contains(&network.trunc()))
+        })
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+/**
+ * Parses a plain-text blocklist feed.
+ *
+ * Every line holds an IP or a CIDR network as its first field; comments
+ * starting with `#` or `;` and lines that do not parse are skipped, which
+ * covers the common abuse feed formats (e.g. Spamhaus DROP, FireHOL).
+ */
+pub fn parse_feed(body: &str) -> NetworkSet {
+    let mut networks = NetworkSet::default();
+    for line in body.lines() {
+        let entry = line
+            .split(['#', ';'])
+            .next()
+            .and_then(|entry| entry.split_whitespace().next());
+        let Some(entry) = entry else {
+            continue;
+        };
+        if let Ok(network) = entry.parse::<IpNet>() {
+            networks.insert(network);
+        } else if let Ok(ip) = entry.parse::<IpAddr>() {
+            networks.insert(IpNet::from(ip.to_canonical()));
+        }
+    }
+    networks
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * State of a subscribed feed: the `entries` of its latest successful
+ * download and when that happened, and the error of the latest attempt if
+ * it failed. A feed that fails keeps blocking its previous entries.
+ */
+pub struct FeedStatus {
+    pub url: String,
+    pub entries: usize,
+    pub last_updated: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Blocklist status reported by the API.
+ */
+pub struct BlocklistStatus {
+    pub refresh_interval_secs: u64,
+    pub entries: usize,
+    pub blocked: u64,
+    pub feeds: Vec<FeedStatus>,
+}
+struct Feed {
+    status: FeedStatus,
+    networks: NetworkSet,
+}
+/**
+ * Global deny layer fed by remote IP blocklists.
+ *
+ * Subscribed feeds are downloaded at startup and then on a schedule; their
+ * entries are merged into one set that every instance checks before its own
+ * IP filter. `blocked` counts the connections and datagrams turned away.
+ */
+#[derive(Default)]
+pub struct Blocklist {
+    feeds: RwLock<Vec<Feed>>,
+    networks: RwLock<Arc<NetworkSet>>,
+    refresh_interval: RwLock<Duration>,
+    blocked: AtomicU64,
+}
+impl Blocklist {
+    pub fn subscribe(&self, url: impl Into<String>) {
+        let url = url.into();
+        let mut feeds = self.feeds.write().unwrap_or_else(|e| e.into_inner());
+        if feeds.iter().all(|feed| feed.status.url != url) {
+            feeds.push(Feed {
+                status: FeedStatus {
+                    url,
+                    entries: 0,
+                    last_updated: None,
+                    last_error: None,
+                },
+                networks: NetworkSet::default(),
+            });
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.feeds
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .is_empty()
+    }
+    /**
+     * Whether `ip` is on a feed; counts it as blocked if so.
+     */
+    pub fn check(&self, ip: &IpAddr) -> bool {
+        let networks = self
+            .networks
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let blocked = networks.contains(ip);
+        if blocked {
+            self.blocked.fetch_add(1, Ordering::Relaxed);
+        }
+        blocked
+    }
+    /**
+     * Downloads every feed once and rebuilds the merged set.
+     */
+    pub async fn refresh(&self) {
+        let urls: Vec<String> = self
+            .feeds
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|feed| feed.status.url.clone())
+            .collect();
+        for url in urls {
+            let result = fetch_feed(&url).await;
+            let mut feeds = self.feeds.write().unwrap_or_else(|e| e.into_inner());
+            let Some(feed) = feeds.iter_mut().find(|feed| feed.status.url == url) else {
+                continue;
+            };
+            match result {
+                Ok(networks) => {
+                    debug!("Blocklist feed {} lists {} entries", url, networks.len());
+                    feed.status.entries = networks.len();
+                    feed.status.last_updated = Some(Utc::now());
+                    feed.status.last_error = None;
+                    feed.networks = networks;
+                }
+                Err(e) => {
+                    warn!("Failed to refresh blocklist feed {}: {:#}", url, e);
+                    feed.status.last_error = Some(format!("{:#}", e));
+                }
+            }
+        }
+        let mut merged = NetworkSet::default();
+        for feed in self.feeds.read().unwrap_or_else(|e| e.into_inner()).iter() {
+            merged.extend(&feed.networks);
+        }
+        info!("Blocklist holds {} entries", merged.len());
+        *self.networks.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(merged);
+    }
+    /**
+     * Downloads the feeds now and then every `interval` in the background.
+     */
+    pub fn start(&'static self, interval: Duration) {
+        *self
+            .refresh_interval
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = interval;
+        if self.is_empty() {
+            return;
+        }
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            loop {
+                ticks.tick().await;
+                self.refresh().await;
+            }
+        });
+    }
+    pub fn status(&self) -> BlocklistStatus {
+        BlocklistStatus {
+            refresh_interval_secs: self
+                .refresh_interval
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_secs(),
+            entries: self
+                .networks
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .len(),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            feeds: self
+                .feeds
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .iter()
+                .map(|feed| feed.status.clone())
+                .collect(),
+        }
+    }
+}
+async fn fetch_feed(url: &str) -> Result<NetworkSet> {
+    let response = crate::http_client::send(Method::GET, url, &[], None).await?;
+    if !response.is_success() {
+        anyhow::bail!("HTTP status {}", response.status);
+    }
+    let body = std::str::from_utf8(&response.body).context("Feed is not valid UTF-8")?;
+    Ok(parse_feed(body))
+}
@@ -1,7 +1,9 @@
+use crate::middleware::ConnectionContext;
 use bytes::BytesMut;
 use std::convert::AsMut;
 use std::collections::VecDeque;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::{Mutex, Semaphore};
 #[derive(Clone)]
@@ -14,7 +16,9 @@ use tokio::sync::{Mutex, Semaphore};
  * - Large buffers (> 8192 bytes, up to 65535 bytes)
  *
  * The pool includes concurrency limiting to prevent excessive memory usage
- * and automatic buffer recycling when buffers are dropped.
+ * and automatic buffer recycling when buffers are dropped. `held_bytes`
+ * tracks the capacity of every buffer the pool allocated and still owns,
+ * whether idle or lent out.
  */
 pub struct BufferPool {
     small_buffers: Arc<Mutex<VecDeque<BytesMut>>>,
@@ -22,6 +26,37 @@ pub struct BufferPool {
     large_buffers: Arc<Mutex<VecDeque<BytesMut>>>,
     max_pool_size: usize,
     concurrency_limiter: Arc<Semaphore>,
+    accounting: Arc<Accounting>,
+}
+/**
+ * Bytes held by a pool, mirrored into an optional shared gauge that gets
+ * them back once the last clone of the pool is gone.
+ */
+#[derive(Default)]
+struct Accounting {
+    held: AtomicU64,
+    gauge: Option<Arc<AtomicU64>>,
+}
+impl Accounting {
+    fn add(&self, bytes: u64) {
+        self.held.fetch_add(bytes, Ordering::Relaxed);
+        if let Some(ref gauge) = self.gauge {
+            gauge.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+    fn sub(&self, bytes: u64) {
+        self.held.fetch_sub(bytes, Ordering::Relaxed);
+        if let Some(ref gauge) = self.gauge {
+            gauge.fetch_sub(bytes, Ordering::Relaxed);
+        }
+    }
+}
+impl Drop for Accounting {
+    fn drop(&mut self) {
+        if let Some(ref gauge) = self.gauge {
+            gauge.fetch_sub(*self.held.get_mut(), Ordering::Relaxed);
+        }
+    }
 }
 impl BufferPool {
     pub fn new(max_pool_size: usize, max_concurrent: usize) -> Self {
@@ -31,6 +66,32 @@ impl BufferPool {
             large_buffers: Arc::new(Mutex::new(VecDeque::new())),
             max_pool_size,
             concurrency_limiter: Arc::new(Semaphore::new(max_concurrent)),
+            accounting: Arc::new(Accounting::default()),
+        }
+    }
+    /**
+     * Also adds the held bytes to `gauge`, e.g. the buffer gauge of an
+     * instance. Meant for a fresh pool.
+     */
+    pub fn with_gauge(mut self, gauge: Arc<AtomicU64>) -> Self {
+        let held = self.held_bytes();
+        gauge.fetch_add(held, Ordering::Relaxed);
+        self.accounting = Arc::new(Accounting {
+            held: AtomicU64::new(held),
+            gauge: Some(gauge),
+        });
+        self
+    }
+    pub fn held_bytes(&self) -> u64 {
+        self.accounting.held.load(Ordering::Relaxed)
+    }
+    fn tier_size(size: usize) -> usize {
+        if size <= 1024 {
+            1024
+        } else if size <= 8192 {
+            8192
+        } else {
+            65535
         }
     }
     pub async fn acquire(&self, size: usize) -> PooledBuffer {
@@ -54,9 +115,10 @@ impl BufferPool {
         default_size: usize,
     ) -> BytesMut {
         let mut pool_guard = pool.lock().await;
-        pool_guard
-            .pop_front()
-            .unwrap_or_else(|| BytesMut::with_capacity(default_size))
+        pool_guard.pop_front().unwrap_or_else(|| {
+            self.accounting.add(default_size as u64);
+            BytesMut::with_capacity(default_size)
+        })
     }
     async fn return_buffer(&self, mut buffer: BytesMut, size_hint: usize) {
         buffer.clear();
@@ -70,8 +132,13 @@ impl BufferPool {
         let mut pool_guard = pool.lock().await;
         if pool_guard.len() < self.max_pool_size {
             pool_guard.push_back(buffer);
+        } else {
+            self.release(size_hint);
         }
       }
+    fn release(&self, size_hint: usize) {
+        self.accounting.sub(Self::tier_size(size_hint) as u64);
+    }
 }
 /**
  * A pooled buffer that automatically returns itself to the buffer pool when dropped.
@@ -122,6 +189,8 @@ impl Drop for PooledBuffer {
             handle.spawn(async move {
                 pool.return_buffer(buffer, size_hint).await;
             });
+        } else {
+            pool.release(size_hint);
         }
     }
 }
@@ -143,11 +212,14 @@ impl std::ops::DerefMut for PooledBuffer {
  * A UDP session tracks the client socket and local address used for forwarding
  * UDP packets. Since UDP is stateless, sessions are used to maintain context
  * for response packets and manage session lifecycle with timeout handling.
+ * `bytes_received` counts the bytes forwarded from the client.
  */
 pub struct UdpSession {
     pub client_socket: Arc<tokio::net::UdpSocket>,
     #[allow(dead_code)]
     pub local_addr: std::net::SocketAddr,
+    pub context: Arc<ConnectionContext>,
+    pub bytes_received: Arc<std::sync::atomic::AtomicU64>,
     pub last_activity: Instant,
 }
 impl UdpSession {
@@ -157,6 +229,7 @@ impl UdpSession {
      * Arguments:
      *   client_socket - The UDP socket used for communication with the client
      *   local_addr - The local address bound to this session
+     *   context - The middleware context shared by all datagrams of the session
      *
      * Returns:
      *   A new UdpSession instance with the current timestamp as last_activity
@@ -164,10 +237,13 @@ impl UdpSession {
     pub fn new(
         client_socket: Arc<tokio::net::UdpSocket>,
         local_addr: std::net::SocketAddr,
+        context: Arc<ConnectionContext>,
     ) -> Self {
         Self {
             client_socket,
             local_addr,
+            context,
+            bytes_received: Arc::new(std::sync::atomic::AtomicU64::new(0)),
             last_activity: Instant::now(),
         }
     }
@@ -247,22 +323,26 @@ pub fn new(session_timeout: Duration, cleanup_interval: Duration) -> Self {
             }
         });
     }
-    pub async fn get_or_create_session(
+    /**
+     * Returns the session of `peer_addr`, creating it with the socket and
+     * context from `create` when there is none. The flag is `true` when the
+     * session was created by this call.
+     */
+    pub async fn get_or_create_session<F>(
         &self,
         peer_addr: std::net::SocketAddr,
-    ) -> Option<UdpSession> {
+        create: F,
+    ) -> Option<(UdpSession, bool)>
+    where
+        F: FnOnce() -> std::io::Result<(tokio::net::UdpSocket, ConnectionContext)>,
+    {
         let mut sessions = self.sessions.write().await;
         if let Some(session) = sessions.get_mut(&peer_addr) {
             session.update_activity();
-            return None; 
+            return Some((session.clone(), false));
         }
-        let bind_addr = if peer_addr.is_ipv4() {
-            "0.0.0.0:0"
-        } else {
-            "[::]:0"
-        };
-        match tokio::net::UdpSocket::bind(bind_addr).await {
-            Ok(client_socket) => {
+        match create() {
+            Ok((client_socket, context)) => {
                 let local_addr = match client_socket.local_addr() {
                     Ok(addr) => addr,
                     Err(e) => {
@@ -270,9 +350,10 @@ pub fn new(session_timeout: Duration, cleanup_interval: Duration) -> Self {
                         return None;
                     }
                 };
-                let session = UdpSession::new(Arc::new(client_socket), local_addr);
+                let session =
+                    UdpSession::new(Arc::new(client_socket), local_addr, Arc::new(context));
                 sessions.insert(peer_addr, session.clone());
-                Some(session)
+                Some((session, true))
             }
             Err(e) => {
                 tracing::error!("Failed to bind UDP socket for {}: {}", peer_addr, e);
@@ -280,6 +361,21 @@ pub fn new(session_timeout: Duration, cleanup_interval: Duration) -> Self {
             }
         }
     }
+    /**
+     * Whether `client_socket` still serves the session of `peer_addr`, i.e.
+     * the session has neither expired nor been replaced.
+     */
+    pub async fn is_active(
+        &self,
+        peer_addr: &std::net::SocketAddr,
+        client_socket: &Arc<tokio::net::UdpSocket>,
+    ) -> bool {
+        self.sessions
+            .read()
+            .await
+            .get(peer_addr)
+            .is_some_and(|session| Arc::ptr_eq(&session.client_socket, client_socket))
+    }
     pub async fn remove_session(&self, peer_addr: &std::net::SocketAddr) {
         let mut sessions = self.sessions.write().await;
         sessions.remove(peer_addr);
@@ -302,4 +398,16 @@ pub fn new(session_timeout: Duration, cleanup_interval: Duration) -> Self {
     pub async fn active_session_count(&self) -> usize {
         self.sessions.read().await.len()
     }
+    /**
+     * Time since the least recently active session last saw a datagram.
+     */
+    pub async fn longest_idle(&self) -> Duration {
+        self.sessions
+            .read()
+            .await
+            .values()
+            .map(|session| session.last_activity.elapsed())
+            .max()
+            .unwrap_or_default()
+    }
 }
@@ -0,0 +1,250 @@
+use crate::config::{
+    DestinationCheck, LogLevel, LogLimitConfig, MirrorConfig, Protocol, SlowLogConfig, TlsConfig,
+    TunnelConfig,
+};
+use crate::instance::{CreateInstanceRequest, InstanceManager, ProxyInstance};
+use crate::metrics::InstanceMetrics;
+use crate::middleware::{ConnectionMiddleware, MiddlewareChain};
+use crate::tcp_proxy::TcpProxy;
+use crate::udp_proxy::UdpProxy;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+/**
+ * Builder for a forwarding instance run directly by an embedding program.
+ *
+ * `spawn` starts the TCP and/or UDP proxy on the current tokio runtime
+ * without storage, web API or any other daemon machinery. The same settings
+ * can be turned into a `CreateInstanceRequest` for an `InstanceService`.
+ */
+pub struct ProxyBuilder {
+    request: CreateInstanceRequest,
+    middleware: Vec<Arc<dyn ConnectionMiddleware>>,
+}
+impl ProxyBuilder {
+    pub fn new(listen: SocketAddr, destination: SocketAddr) -> Self {
+        Self {
+            request: CreateInstanceRequest {
+                name: format!("{} -> {}", listen, destination),
+                listen_ip: listen.ip(),
+                listen_port: listen.port(),
+                dst_ip: destination.ip(),
+                dst_port: destination.port(),
+                protocol: Protocol::Tcp,
+                auto_start: true,
+                allow_list: None,
+                deny_list: None,
+                allow_hosts: Vec::new(),
+                deny_hosts: Vec::new(),
+                connect_timeout_secs: 30,
+                idle_timeout_secs: 300,
+                log_level: LogLevel::Info,
+                bind_device: None,
+                bind_nonlocal: false,
+                backends: Vec::new(),
+                wasm_filters: Vec::new(),
+                listen_backlog: None,
+                bind_src_ip: None,
+                bind_src_device: None,
+                max_inflight_bytes: None,
+                tls: None,
+                tunnel: None,
+                slow_log: None,
+                max_concurrent_handlers: None,
+                buffer_pool_size: None,
+                max_concurrent_buffers: None,
+                ip_cache_size: None,
+                ip_cache_ttl_secs: None,
+                propagate_unreachable: false,
+                log_limit: None,
+                mirror: None,
+                max_connection_lifetime_secs: None,
+                verify_destination_on_start: DestinationCheck::Off,
+            },
+            middleware: Vec::new(),
+        }
+    }
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.request.name = name.into();
+        self
+    }
+    pub fn protocol(mut self, protocol: Protocol) -> Self {
+        self.request.protocol = protocol;
+        self
+    }
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.request.connect_timeout_secs = timeout.as_secs();
+        self
+    }
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.request.idle_timeout_secs = timeout.as_secs();
+        self
+    }
+    pub fn allow_list(mut self, ips: Vec<IpAddr>) -> Self {
+        self.request.allow_list = Some(ips);
+        self
+    }
+    pub fn deny_list(mut self, ips: Vec<IpAddr>) -> Self {
+        self.request.deny_list = Some(ips);
+        self
+    }
+    pub fn allow_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.request.allow_hosts = hosts;
+        self
+    }
+    pub fn deny_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.request.deny_hosts = hosts;
+        self
+    }
+    pub fn backends(mut self, backends: Vec<String>) -> Self {
+        self.request.backends = backends;
+        self
+    }
+    pub fn bind_device(mut self, device: impl Into<String>) -> Self {
+        self.request.bind_device = Some(device.into());
+        self
+    }
+    pub fn bind_nonlocal(mut self, bind_nonlocal: bool) -> Self {
+        self.request.bind_nonlocal = bind_nonlocal;
+        self
+    }
+    pub fn wasm_filter(mut self, path: impl Into<String>) -> Self {
+        self.request.wasm_filters.push(path.into());
+        self
+    }
+    pub fn tls(mut self, tls: TlsConfig) -> Self {
+        self.request.tls = Some(tls);
+        self
+    }
+    pub fn tunnel(mut self, tunnel: TunnelConfig) -> Self {
+        self.request.tunnel = Some(tunnel);
+        self
+    }
+    pub fn slow_log(mut self, slow_log: SlowLogConfig) -> Self {
+        self.request.slow_log = Some(slow_log);
+        self
+    }
+    pub fn max_concurrent_handlers(mut self, limit: u32) -> Self {
+        self.request.max_concurrent_handlers = Some(limit);
+        self
+    }
+    pub fn buffer_pool(mut self, pool_size: u32, max_concurrent: u32) -> Self {
+        self.request.buffer_pool_size = Some(pool_size);
+        self.request.max_concurrent_buffers = Some(max_concurrent);
+        self
+    }
+    pub fn ip_cache(mut self, size: u32, ttl: Duration) -> Self {
+        self.request.ip_cache_size = Some(size);
+        self.request.ip_cache_ttl_secs = Some(ttl.as_secs());
+        self
+    }
+    pub fn propagate_unreachable(mut self, propagate_unreachable: bool) -> Self {
+        self.request.propagate_unreachable = propagate_unreachable;
+        self
+    }
+    pub fn log_limit(mut self, log_limit: LogLimitConfig) -> Self {
+        self.request.log_limit = Some(log_limit);
+        self
+    }
+    pub fn mirror(mut self, mirror: MirrorConfig) -> Self {
+        self.request.mirror = Some(mirror);
+        self
+    }
+    pub fn max_connection_lifetime(mut self, lifetime: Duration) -> Self {
+        self.request.max_connection_lifetime_secs = Some(lifetime.as_secs());
+        self
+    }
+    pub fn verify_destination_on_start(mut self, check: DestinationCheck) -> Self {
+        self.request.verify_destination_on_start = check;
+        self
+    }
+    pub fn middleware(mut self, middleware: Arc<dyn ConnectionMiddleware>) -> Self {
+        self.middleware.push(middleware);
+        self
+    }
+    pub fn into_request(self) -> CreateInstanceRequest {
+        self.request
+    }
+    /**
+     * Validates the configuration and starts forwarding.
+     *
+     * Listener errors (e.g. the port being in use) are reported by
+     * `RunningProxy::shutdown` once the proxy tasks have ended.
+     */
+    pub fn spawn(self) -> Result<RunningProxy> {
+        let config = self.request.to_config();
+        config.validate()?;
+        let mut instance = ProxyInstance::new(self.request.name, config, true);
+        instance.start()?;
+        instance.set_running()?;
+        let id = instance.id;
+        let metrics = instance.metrics.clone();
+        let state = instance.state();
+        let config = Arc::new(instance.config.clone());
+        let instances: InstanceManager = Arc::new(RwLock::new(HashMap::from([(id, instance)])));
+        let cancel_token = Arc::new(CancellationToken::new());
+        let mut middleware = self.middleware;
+        middleware.extend(crate::wasm_filter::load_wasm_filters(&config.proxy.wasm_filters)?);
+        middleware.extend(crate::mirror::mirror_middleware(&config, &metrics));
+        let middleware = MiddlewareChain::new(middleware);
+        let mut handles = Vec::new();
+        if matches!(config.proxy.protocol, Protocol::Tcp | Protocol::Both) {
+            let proxy = TcpProxy::new(config.clone(), id, instances.clone())
+                .with_middleware(middleware.clone())
+                .with_state(state.clone());
+            let token = cancel_token.clone();
+            handles.push(tokio::spawn(async move { proxy.run_with_token(token).await }));
+        }
+        if matches!(config.proxy.protocol, Protocol::Udp | Protocol::Both) {
+            let proxy = UdpProxy::new(config.clone(), id, instances.clone())
+                .with_middleware(middleware.clone())
+                .with_state(state.clone());
+            let token = cancel_token.clone();
+            handles.push(tokio::spawn(async move { proxy.run_with_token(token).await }));
+        }
+        Ok(RunningProxy {
+            id,
+            metrics,
+            cancel_token,
+            handles,
+        })
+    }
+}
+/**
+ * Handle to a proxy started with `ProxyBuilder::spawn`.
+ */
+pub struct RunningProxy {
+    id: Uuid,
+    metrics: Arc<InstanceMetrics>,
+    cancel_token: Arc<CancellationToken>,
+    handles: Vec<JoinHandle<Result<()>>>,
+}
+impl RunningProxy {
+    pub fn id(&self) -> Uuid {
+        self.id
+    }
+    pub fn metrics(&self) -> &InstanceMetrics {
+        &self.metrics
+    }
+    /**
+     * Stops accepting traffic, waits for the proxy tasks and returns the
+     * first error they reported.
+     */
+    pub async fn shutdown(self) -> Result<()> {
+        self.cancel_token.cancel();
+        let mut result = Ok(());
+        for handle in self.handles {
+            let outcome = handle.await.map_err(anyhow::Error::from).and_then(|r| r);
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result
+    }
+}
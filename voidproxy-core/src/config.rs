@@ -0,0 +1,737 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/**
+ * Main configuration structure for proxy instances.
+ *
+ * Contains all the necessary configuration for a proxy instance including
+ * the proxy settings and optional IP filtering configuration. `profiles`
+ * holds per-environment destination overrides keyed by profile name.
+ * `tags` are free-form key/value pairs describing the instance, such as
+ * `env = "prod"`.
+ */
+pub struct Config {
+    pub proxy: ProxyConfig,
+    pub ip_filter: Option<IpFilterConfig>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileOverride>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, String>,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/**
+ * Core proxy configuration settings.
+ *
+ * Defines the listening and destination addresses and ports for the proxy,
+ * as well as the protocol to use and automatic startup behavior. The optional
+ * bind settings let an instance listen on a specific interface or on a
+ * floating IP that is not assigned to the host yet. When `backends` is set,
+ * connections are balanced across those upstreams (static `ip:port` entries
+ * or discovery URIs) instead of going to `dst_ip`/`dst_port`.
+ * `wasm_filters` lists WebAssembly filter modules applied, in order, to
+ * every connection of the instance. `listen_backlog` sizes the TCP accept
+ * queue (1024 when unset; the kernel caps it at `net.core.somaxconn`).
+ * `bind_src_ip` and `bind_src_device` select the local address and
+ * interface that connections to the destination leave from.
+ * `max_inflight_bytes` caps the unacknowledged bytes a TCP connection may
+ * queue towards either peer before reading from the other side pauses.
+ * `tls` terminates TLS on accepted TCP connections. `tunnel` makes the
+ * instance one end of a multiplexed tunnel between two nodes. `slow_log`
+ * keeps the most recent TCP connections that were slow to reach or hear
+ * back from the destination. `max_concurrent_handlers` caps the TCP
+ * connections or UDP datagrams the instance handles at once; beyond it the
+ * instance stops accepting until a handler finishes. `buffer_pool_size`
+ * (idle buffers kept per size tier) and `max_concurrent_buffers` (buffer
+ * requests served at once) size the TCP buffer pool; `ip_cache_size` and
+ * `ip_cache_ttl_secs` size the cache of IP filter decisions, which
+ * otherwise holds its entries for `idle_timeout_secs`. Unset sizes follow
+ * the process footprint. With `propagate_unreachable`, a UDP destination
+ * answering with ICMP port unreachable is reported back to the client the
+ * same way. `log_limit` rate limits repeated warnings such as rejected
+ * clients; unset, the default limit applies. `mirror` copies the traffic
+ * clients send to a second destination. `max_connection_lifetime_secs`
+ * closes TCP connections and UDP sessions that have been open that long,
+ * busy or not. `verify_destination_on_start` probes the destination before
+ * the instance starts.
+ */
+pub struct ProxyConfig {
+    pub listen_ip: IpAddr,
+    pub listen_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+    pub protocol: Protocol,
+    pub connect_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub log_level: LogLevel,
+    pub bind_device: Option<String>,
+    #[serde(default)]
+    pub bind_nonlocal: bool,
+    #[serde(default)]
+    pub backends: Vec<String>,
+    #[serde(default)]
+    pub wasm_filters: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub listen_backlog: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_src_ip: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_src_device: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_inflight_bytes: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tunnel: Option<TunnelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_log: Option<SlowLogConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_handlers: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub buffer_pool_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_buffers: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_cache_size: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_cache_ttl_secs: Option<u64>,
+    #[serde(default)]
+    pub propagate_unreachable: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_limit: Option<LogLimitConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<MirrorConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_connection_lifetime_secs: Option<u64>,
+    #[serde(default, skip_serializing_if = "DestinationCheck::is_off")]
+    pub verify_destination_on_start: DestinationCheck,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/**
+ * TLS termination settings of an instance.
+ *
+ * Paths point to PEM files. With `client_ca_path` set, clients must present
+ * a certificate issued by that CA (mutual TLS); `allowed_client_names`
+ * further restricts them to certificates whose common name or a DNS subject
+ * alternative name is listed. `sni_certificates` are served instead of the
+ * default certificate to clients requesting one of their server names.
+ */
+pub struct TlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_ca_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_client_names: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sni_certificates: Vec<SniCertificate>,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/**
+ * Certificate served to clients requesting one of `server_names` through
+ * SNI. A name may start with `*.` to match any direct subdomain.
+ */
+pub struct SniCertificate {
+    pub server_names: Vec<String>,
+    pub cert_path: String,
+    pub key_path: String,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/**
+ * Tunnel settings of an instance.
+ *
+ * An `edge` instance relays the connections it accepts over a single
+ * multiplexed link to the `exit` instance at `dst_ip`/`dst_port`, which
+ * forwards each of them to its own destination. Both ends share `token`.
+ * The edge encrypts the link when `tls` is set, verifying the exit's
+ * certificate against `ca_path` (public roots otherwise) and `server_name`
+ * (`dst_ip` otherwise); the exit terminates it through its `tls` settings.
+ *
+ * For a service behind NAT the link runs the other way: the `agent`
+ * instance dials out to the `gateway` at `gateway_addr` and forwards the
+ * streams the gateway opens to its own destination, while the gateway
+ * accepts agent links on `agent_addr` and relays the clients of its
+ * listener over the most recent one.
+ *
+ * `compression` on the dialing side (edge or agent) asks for the link to be
+ * compressed with LZ4 or zstd; the accepting side agrees unless its own
+ * `compression` is `none`.
+ */
+pub struct TunnelConfig {
+    pub mode: TunnelMode,
+    pub token: String,
+    #[serde(default)]
+    pub tls: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gateway_addr: Option<SocketAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_addr: Option<SocketAddr>,
+    #[serde(default)]
+    pub compression: TunnelCompression,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/**
+ * End of a tunnel an instance acts as.
+ */
+pub enum TunnelMode {
+    Edge,
+    Exit,
+    Agent,
+    Gateway,
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/**
+ * Compression applied to the traffic of a tunnel link.
+ */
+pub enum TunnelCompression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/**
+ * Whether starting an instance first checks that its destination accepts
+ * TCP connections, and what happens when it does not: `fail` refuses to
+ * start, `warn` logs a warning and `start` goes ahead. With `warn` and
+ * `start` the failure is kept as the instance's last error.
+ */
+pub enum DestinationCheck {
+    #[default]
+    Off,
+    Start,
+    Warn,
+    Fail,
+}
+impl DestinationCheck {
+    pub fn is_off(&self) -> bool {
+        *self == Self::Off
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/**
+ * Slow-log thresholds of an instance.
+ *
+ * A TCP connection is logged when connecting to the destination takes longer
+ * than `connect_ms`, or when the destination sends its first byte more
+ * than `first_byte_ms` after the connection to it was established. Only the
+ * latest `capacity` entries are kept.
+ */
+pub struct SlowLogConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_byte_ms: Option<u64>,
+    #[serde(default = "default_slow_log_capacity")]
+    pub capacity: usize,
+}
+fn default_slow_log_capacity() -> usize {
+    128
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/**
+ * Rate limit of an instance's hot-path log messages.
+ *
+ * Each message class, such as rejected clients or relay errors, is logged at
+ * most `per_sec` times per second (0 disables the limit). Beyond it, every
+ * `sample_every`-th message is still logged when set; the others are
+ * suppressed and counted in the next message logged for the class.
+ */
+pub struct LogLimitConfig {
+    #[serde(default = "default_log_limit_per_sec")]
+    pub per_sec: u32,
+    #[serde(default)]
+    pub sample_every: u32,
+}
+fn default_log_limit_per_sec() -> u32 {
+    10
+}
+impl Default for LogLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_sec: default_log_limit_per_sec(),
+            sample_every: 0,
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/**
+ * Traffic mirroring settings of an instance.
+ *
+ * What clients send is copied to `addr`, over TCP for TCP connections and
+ * as datagrams for UDP sessions; whatever the mirror answers is discarded.
+ * Only `sample_percent` of the connections and sessions are mirrored, and
+ * at most `max_bytes_per_hour` bytes per clock hour when set. Mirroring
+ * never holds back the proxied traffic: chunks the mirror cannot take in
+ * time are dropped and counted.
+ */
+pub struct MirrorConfig {
+    pub addr: SocketAddr,
+    #[serde(default = "default_mirror_sample_percent")]
+    pub sample_percent: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes_per_hour: Option<u64>,
+}
+fn default_mirror_sample_percent() -> u8 {
+    100
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/**
+ * Supported proxy protocols.
+ *
+ * Defines which network protocols the proxy should handle.
+ */
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Both,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+/**
+ * Supported logging levels.
+ *
+ * Defines the verbosity level for logging output. Levels are written in
+ * lowercase and parsed ignoring case; anything but the names in `ALL` is
+ * rejected when deserializing.
+ */
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+impl LogLevel {
+    pub const ALL: [LogLevel; 5] = [
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|level| level.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let allowed: Vec<&str> = Self::ALL.iter().map(|level| level.as_str()).collect();
+                format!(
+                    "Invalid log level {:?}, expected one of {}",
+                    s,
+                    allowed.join(", ")
+                )
+            })
+    }
+}
+impl<'de> Deserialize<'de> for LogLevel {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+/**
+ * Values used for the fields an API request to create an instance leaves
+ * out, configured in the `[defaults]` section of the configuration file.
+ */
+pub struct InstanceDefaults {
+    pub protocol: Protocol,
+    pub auto_start: bool,
+    pub connect_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub log_level: LogLevel,
+}
+impl Default for InstanceDefaults {
+    fn default() -> Self {
+        Self {
+            protocol: Protocol::Tcp,
+            auto_start: true,
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+        }
+    }
+}
+impl InstanceDefaults {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/**
+ * IP filtering configuration for access control.
+ *
+ * Allows defining allow lists and deny lists to control which clients
+ * can connect to the proxy. Only one of allow_list or deny_list can be used.
+ * `allow_hosts` and `deny_hosts` add hostnames to the allow or deny list,
+ * matching the addresses they currently resolve to.
+ */
+pub struct IpFilterConfig {
+    pub allow_list: Option<Vec<IpAddr>>,
+    pub deny_list: Option<Vec<IpAddr>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow_hosts: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny_hosts: Vec<String>,
+}
+impl IpFilterConfig {
+    pub fn is_allow_list(&self) -> bool {
+        self.allow_list.is_some() || !self.allow_hosts.is_empty()
+    }
+    pub fn is_deny_list(&self) -> bool {
+        self.deny_list.is_some() || !self.deny_hosts.is_empty()
+    }
+}
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/**
+ * Destination settings an instance uses while a profile is active.
+ *
+ * Unset fields keep the instance's base value.
+ */
+pub struct ProfileOverride {
+    pub dst_ip: Option<IpAddr>,
+    pub dst_port: Option<u16>,
+    pub backends: Option<Vec<String>>,
+}
+impl Config {
+    pub fn for_profile(&self, profile: Option<&str>) -> Config {
+        let mut config = self.clone();
+        if let Some(profile_override) = profile.and_then(|name| self.profiles.get(name)) {
+            if let Some(dst_ip) = profile_override.dst_ip {
+                config.proxy.dst_ip = dst_ip;
+            }
+            if let Some(dst_port) = profile_override.dst_port {
+                config.proxy.dst_port = dst_port;
+            }
+            if let Some(ref backends) = profile_override.backends {
+                config.proxy.backends = backends.clone();
+            }
+        }
+        config
+    }
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.proxy.listen_port == 0 {
+            return Err(anyhow::anyhow!("Listen port cannot be 0"));
+        }
+        if self.proxy.dst_port == 0 {
+            return Err(anyhow::anyhow!("Destination port cannot be 0"));
+        }
+        if self.proxy.connect_timeout_secs == 0 {
+            return Err(anyhow::anyhow!("Connect timeout must be greater than 0"));
+        }
+        if self.proxy.idle_timeout_secs == 0 {
+            return Err(anyhow::anyhow!("Idle timeout must be greater than 0"));
+        }
+        if self.proxy.connect_timeout_secs > 300 {
+            return Err(anyhow::anyhow!("Connect timeout cannot exceed 300 seconds"));
+        }
+        if self.proxy.idle_timeout_secs > 3600 {
+            return Err(anyhow::anyhow!("Idle timeout cannot exceed 3600 seconds"));
+        }
+        if self.proxy.listen_port == self.proxy.dst_port
+            && self.proxy.listen_ip == self.proxy.dst_ip
+        {
+            return Err(anyhow::anyhow!(
+                "Listen and destination cannot be the same address and port"
+            ));
+        }
+        if let Some(ref device) = self.proxy.bind_device
+            && (device.is_empty() || device.len() > 15)
+        {
+            return Err(anyhow::anyhow!(
+                "Bind device name must be between 1 and 15 characters"
+            ));
+        }
+        if let Some(backlog) = self.proxy.listen_backlog
+            && !(1..=65535).contains(&backlog)
+        {
+            return Err(anyhow::anyhow!(
+                "Listen backlog must be between 1 and 65535"
+            ));
+        }
+        if let Some(ref device) = self.proxy.bind_src_device
+            && (device.is_empty() || device.len() > 15)
+        {
+            return Err(anyhow::anyhow!(
+                "Source device name must be between 1 and 15 characters"
+            ));
+        }
+        if let Some(src_ip) = self.proxy.bind_src_ip
+            && self.proxy.backends.is_empty()
+            && src_ip.is_ipv4() != self.proxy.dst_ip.is_ipv4()
+        {
+            return Err(anyhow::anyhow!(
+                "Source address {} cannot reach destination {}",
+                src_ip,
+                self.proxy.dst_ip
+            ));
+        }
+        if self.proxy.max_inflight_bytes == Some(0) {
+            return Err(anyhow::anyhow!("Max in-flight bytes cannot be 0"));
+        }
+        if self.proxy.max_connection_lifetime_secs == Some(0) {
+            return Err(anyhow::anyhow!("Max connection lifetime cannot be 0"));
+        }
+        if let Some(ref tls) = self.proxy.tls {
+            if self.proxy.protocol == Protocol::Udp {
+                return Err(anyhow::anyhow!("TLS termination requires TCP"));
+            }
+            if !tls.allowed_client_names.is_empty() && tls.client_ca_path.is_none() {
+                return Err(anyhow::anyhow!("Allowed client names require a client CA"));
+            }
+            let mut server_names = std::collections::HashSet::new();
+            for sni in &tls.sni_certificates {
+                if sni.server_names.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "SNI certificate {} needs at least one server name",
+                        sni.cert_path
+                    ));
+                }
+                for server_name in &sni.server_names {
+                    let name = server_name.strip_prefix("*.").unwrap_or(server_name);
+                    let valid = !name.is_empty()
+                        && name.len() <= 253
+                        && name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+                    if !valid {
+                        return Err(anyhow::anyhow!("Invalid SNI server name: {}", server_name));
+                    }
+                    if !server_names.insert(server_name.to_ascii_lowercase()) {
+                        return Err(anyhow::anyhow!(
+                            "Duplicate SNI server name: {}",
+                            server_name
+                        ));
+                    }
+                }
+            }
+            let sni_paths = tls
+                .sni_certificates
+                .iter()
+                .flat_map(|sni| [&sni.cert_path, &sni.key_path]);
+            for path in [
+                Some(&tls.cert_path),
+                Some(&tls.key_path),
+                tls.client_ca_path.as_ref(),
+            ]
+            .into_iter()
+            .flatten()
+            .chain(sni_paths)
+            {
+                if !std::path::Path::new(path).is_file() {
+                    return Err(anyhow::anyhow!("TLS file not found: {}", path));
+                }
+            }
+        }
+        if let Some(ref tunnel) = self.proxy.tunnel {
+            if self.proxy.protocol != Protocol::Tcp {
+                return Err(anyhow::anyhow!("Tunnel instances must use TCP"));
+            }
+            if tunnel.token.is_empty() || tunnel.token.len() > 1024 {
+                return Err(anyhow::anyhow!(
+                    "Tunnel token must be between 1 and 1024 bytes"
+                ));
+            }
+            if tunnel.gateway_addr.is_some() != (tunnel.mode == TunnelMode::Agent) {
+                return Err(anyhow::anyhow!(
+                    "Tunnel gateway_addr is required for agents and only allowed for them"
+                ));
+            }
+            if tunnel.agent_addr.is_some() != (tunnel.mode == TunnelMode::Gateway) {
+                return Err(anyhow::anyhow!(
+                    "Tunnel agent_addr is required for gateways and only allowed for them"
+                ));
+            }
+            match tunnel.mode {
+                TunnelMode::Edge | TunnelMode::Gateway if !self.proxy.backends.is_empty() => {
+                    return Err(anyhow::anyhow!(
+                        "Tunnel edges and gateways relay over the link and cannot use backends"
+                    ));
+                }
+                TunnelMode::Edge | TunnelMode::Agent => {
+                    if let Some(ref ca_path) = tunnel.ca_path
+                        && !std::path::Path::new(ca_path).is_file()
+                    {
+                        return Err(anyhow::anyhow!("Tunnel CA not found: {}", ca_path));
+                    }
+                }
+                TunnelMode::Exit | TunnelMode::Gateway => {
+                    if tunnel.tls || tunnel.ca_path.is_some() || tunnel.server_name.is_some() {
+                        return Err(anyhow::anyhow!(
+                            "Tunnel exits and gateways encrypt through their tls settings, not tunnel.tls"
+                        ));
+                    }
+                }
+            }
+            if let Some(agent_addr) = tunnel.agent_addr
+                && agent_addr == SocketAddr::new(self.proxy.listen_ip, self.proxy.listen_port)
+            {
+                return Err(anyhow::anyhow!(
+                    "Tunnel agent_addr must differ from the listen address"
+                ));
+            }
+        }
+        if self.proxy.max_concurrent_handlers == Some(0) {
+            return Err(anyhow::anyhow!("Max concurrent handlers cannot be 0"));
+        }
+        if self.proxy.max_concurrent_buffers == Some(0) {
+            return Err(anyhow::anyhow!("Max concurrent buffers cannot be 0"));
+        }
+        if self.proxy.ip_cache_size == Some(0) {
+            return Err(anyhow::anyhow!("IP cache size cannot be 0"));
+        }
+        if let Some(slow_log) = self.proxy.slow_log {
+            if self.proxy.protocol == Protocol::Udp {
+                return Err(anyhow::anyhow!("The slow log requires TCP"));
+            }
+            if slow_log.connect_ms.is_none() && slow_log.first_byte_ms.is_none() {
+                return Err(anyhow::anyhow!(
+                    "The slow log needs a connect or first-byte threshold"
+                ));
+            }
+            if !(1..=10_000).contains(&slow_log.capacity) {
+                return Err(anyhow::anyhow!(
+                    "Slow log capacity must be between 1 and 10000"
+                ));
+            }
+        }
+        if let Some(log_limit) = self.proxy.log_limit
+            && log_limit.per_sec == 0
+            && log_limit.sample_every > 0
+        {
+            return Err(anyhow::anyhow!("Log sampling requires a per-second log limit"));
+        }
+        if let Some(mirror) = self.proxy.mirror {
+            if !(1..=100).contains(&mirror.sample_percent) {
+                return Err(anyhow::anyhow!(
+                    "Mirror sample percentage must be between 1 and 100"
+                ));
+            }
+            if mirror.addr.port() == 0 {
+                return Err(anyhow::anyhow!("Mirror port cannot be 0"));
+            }
+            if mirror.addr == SocketAddr::new(self.proxy.listen_ip, self.proxy.listen_port) {
+                return Err(anyhow::anyhow!("Mirror address cannot be the listen address"));
+            }
+        }
+        for backend in &self.proxy.backends {
+            crate::backend::parse_backend_entry(backend)?;
+        }
+        for (name, profile_override) in &self.profiles {
+            if name.is_empty() {
+                return Err(anyhow::anyhow!("Profile name cannot be empty"));
+            }
+            if profile_override.dst_port == Some(0) {
+                return Err(anyhow::anyhow!(
+                    "Destination port of profile {} cannot be 0",
+                    name
+                ));
+            }
+            for backend in profile_override.backends.iter().flatten() {
+                crate::backend::parse_backend_entry(backend)?;
+            }
+        }
+        if self.tags.keys().any(|key| key.trim().is_empty()) {
+            return Err(anyhow::anyhow!("Tag names cannot be empty"));
+        }
+        for filter in &self.proxy.wasm_filters {
+            if !std::path::Path::new(filter).is_file() {
+                return Err(anyhow::anyhow!("WASM filter not found: {}", filter));
+            }
+        }
+        if self.proxy.listen_ip.is_loopback() && !self.proxy.dst_ip.is_loopback() {
+            tracing::warn!(
+                "Instance listens on loopback but forwards to non-loopback - this may create a security risk"
+            );
+        }
+        if let Some(ref ip_filter) = self.ip_filter {
+            if let Some(ref allow_list) = ip_filter.allow_list {
+                if allow_list.is_empty() {
+                    return Err(anyhow::anyhow!("Allow list cannot be empty"));
+                }
+                let mut unique_ips = std::collections::HashSet::new();
+                for ip in allow_list {
+                    if !unique_ips.insert(ip) {
+                        return Err(anyhow::anyhow!(
+                            "Duplicate IP address in allow list: {}",
+                            ip
+                        ));
+                    }
+                }
+            }
+            if let Some(ref deny_list) = ip_filter.deny_list {
+                if deny_list.is_empty() {
+                    return Err(anyhow::anyhow!("Deny list cannot be empty"));
+                }
+                let mut unique_ips = std::collections::HashSet::new();
+                for ip in deny_list {
+                    if !unique_ips.insert(ip) {
+                        return Err(anyhow::anyhow!("Duplicate IP address in deny list: {}", ip));
+                    }
+                }
+            }
+            for host in ip_filter.allow_hosts.iter().chain(&ip_filter.deny_hosts) {
+                if !crate::hostname::is_valid_hostname(host) {
+                    return Err(anyhow::anyhow!("Invalid hostname in IP filter: {}", host));
+                }
+            }
+            if ip_filter.is_allow_list() && ip_filter.is_deny_list() {
+                return Err(anyhow::anyhow!(
+                    "Cannot specify both allow_list and deny_list"
+                ));
+            }
+        }
+        Ok(())
+    }
+    pub fn is_ip_allowed(&self, ip: &IpAddr) -> bool {
+        self.is_ip_allowed_with(ip, &crate::host_acl::ResolvedHosts::default())
+    }
+    /**
+     * Checks `ip` against the IP filter, matching the filter's hostnames by
+     * the addresses in `resolved`.
+     */
+    pub fn is_ip_allowed_with(&self, ip: &IpAddr, resolved: &crate::host_acl::ResolvedHosts) -> bool {
+        match &self.ip_filter {
+            Some(filter) => {
+                if filter.is_allow_list() {
+                    filter.allow_list.iter().flatten().any(|allowed| allowed == ip)
+                        || resolved.allow.contains(ip)
+                } else if filter.is_deny_list() {
+                    !filter.deny_list.iter().flatten().any(|denied| denied == ip)
+                        && !resolved.deny.contains(ip)
+                } else {
+                    true
+                }
+            }
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use crate::instance::ProxyInstance;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Instance present on only one side of a diff.
+ */
+pub struct InstanceSummary {
+    pub id: Uuid,
+    pub name: String,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Setting that differs between the running instance and the candidate.
+ *
+ * `field` is a dotted path such as `config.proxy.dst_port`; a missing value
+ * is reported as `null`.
+ */
+pub struct FieldChange {
+    pub field: String,
+    pub running: Value,
+    pub candidate: Value,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct InstanceChange {
+    pub id: Uuid,
+    pub name: String,
+    pub fields: Vec<FieldChange>,
+}
+#[derive(Debug, Clone, Default, Serialize)]
+/**
+ * Structured difference between the running instances and a candidate
+ * configuration (a file or backup), as applying the candidate would change
+ * them. Instances are matched by ID.
+ */
+pub struct ConfigDiff {
+    pub added: Vec<InstanceSummary>,
+    pub removed: Vec<InstanceSummary>,
+    pub changed: Vec<InstanceChange>,
+    pub unchanged: usize,
+}
+impl ConfigDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+fn settings(instance: &ProxyInstance) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+    fields.insert("name".to_string(), Value::from(instance.name.clone()));
+    fields.insert("auto_start".to_string(), Value::from(instance.auto_start));
+    fields.insert("locked".to_string(), Value::from(instance.locked));
+    flatten(
+        "config",
+        serde_json::to_value(&instance.config).unwrap_or(Value::Null),
+        &mut fields,
+    );
+    fields
+}
+fn flatten(prefix: &str, value: Value, fields: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                flatten(&format!("{}.{}", prefix, key), value, fields);
+            }
+        }
+        Value::Null => {}
+        value => {
+            fields.insert(prefix.to_string(), value);
+        }
+    }
+}
+pub fn diff_instances(running: &[ProxyInstance], candidate: &[ProxyInstance]) -> ConfigDiff {
+    let mut diff = ConfigDiff::default();
+    for instance in running {
+        if !candidate.iter().any(|c| c.id == instance.id) {
+            diff.removed.push(InstanceSummary {
+                id: instance.id,
+                name: instance.name.clone(),
+            });
+        }
+    }
+    for instance in candidate {
+        let Some(current) = running.iter().find(|r| r.id == instance.id) else {
+            diff.added.push(InstanceSummary {
+                id: instance.id,
+                name: instance.name.clone(),
+            });
+            continue;
+        };
+        let before = settings(current);
+        let after = settings(instance);
+        let mut keys: Vec<&String> = before.keys().chain(after.keys()).collect();
+        keys.sort();
+        keys.dedup();
+        let fields: Vec<FieldChange> = keys
+            .into_iter()
+            .filter(|key| before.get(*key) != after.get(*key))
+            .map(|key| FieldChange {
+                field: key.clone(),
+                running: before.get(key).cloned().unwrap_or(Value::Null),
+                candidate: after.get(key).cloned().unwrap_or(Value::Null),
+            })
+            .collect();
+        if fields.is_empty() {
+            diff.unchanged += 1;
+        } else {
+            diff.changed.push(InstanceChange {
+                id: instance.id,
+                name: instance.name.clone(),
+                fields,
+            });
+        }
+    }
+    diff
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportAction {
+    Create,
+    Replace,
+    Unchanged,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * What importing a configuration would do to one of its instances.
+ *
+ * `errors` make the instance unusable (invalid settings, listen address
+ * clashing with another imported instance); `warnings` flag problems that
+ * may resolve themselves, such as a port currently held by another process.
+ */
+pub struct ImportPlanEntry {
+    pub id: Uuid,
+    pub name: String,
+    pub action: ImportAction,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Validation report of a dry-run import. `removed` lists the running
+ * instances the import would drop; `unknown_fields` the keys of the
+ * candidate that no setting reads and the import would ignore.
+ */
+pub struct ImportPlan {
+    pub valid: bool,
+    pub instances: Vec<ImportPlanEntry>,
+    pub removed: Vec<InstanceSummary>,
+    pub diff: ConfigDiff,
+    pub unknown_fields: Vec<String>,
+}
+fn listen_conflict(a: &ProxyInstance, b: &ProxyInstance) -> bool {
+    let (a, b) = (&a.config.proxy, &b.config.proxy);
+    let same_ip =
+        a.listen_ip == b.listen_ip || a.listen_ip.is_unspecified() || b.listen_ip.is_unspecified();
+    let shared_protocol = a.protocol == b.protocol
+        || a.protocol == crate::config::Protocol::Both
+        || b.protocol == crate::config::Protocol::Both;
+    a.listen_port == b.listen_port && same_ip && shared_protocol
+}
+fn port_in_use(instance: &ProxyInstance) -> Option<String> {
+    let proxy = &instance.config.proxy;
+    let addr = std::net::SocketAddr::new(proxy.listen_ip, proxy.listen_port);
+    if matches!(
+        proxy.protocol,
+        crate::config::Protocol::Tcp | crate::config::Protocol::Both
+    ) && let Err(e) = std::net::TcpListener::bind(addr)
+    {
+        return Some(format!("TCP {} is not available: {}", addr, e));
+    }
+    if matches!(
+        proxy.protocol,
+        crate::config::Protocol::Udp | crate::config::Protocol::Both
+    ) && let Err(e) = std::net::UdpSocket::bind(addr)
+    {
+        return Some(format!("UDP {} is not available: {}", addr, e));
+    }
+    None
+}
+/**
+ * Validates a candidate configuration against the running instances
+ * without changing anything.
+ *
+ * Listen ports are probed only when no running instance currently holds a
+ * conflicting address, since the import stops those first.
+ */
+pub fn plan_import(running: &[ProxyInstance], candidate: &[ProxyInstance]) -> ImportPlan {
+    let diff = diff_instances(running, candidate);
+    let instances: Vec<ImportPlanEntry> = candidate
+        .iter()
+        .enumerate()
+        .map(|(index, instance)| {
+            let mut errors = Vec::new();
+            let mut warnings = Vec::new();
+            if let Err(e) = instance.config.validate() {
+                errors.push(e.to_string());
+            }
+            for (other_index, other) in candidate.iter().enumerate() {
+                if other_index == index {
+                    continue;
+                }
+                if other.id == instance.id {
+                    errors.push(format!("Duplicate instance ID shared with {}", other.name));
+                } else if listen_conflict(instance, other) {
+                    errors.push(format!(
+                        "Listen address {}:{} conflicts with {}",
+                        instance.config.proxy.listen_ip,
+                        instance.config.proxy.listen_port,
+                        other.name
+                    ));
+                }
+            }
+            let held_by_running = running
+                .iter()
+                .any(|current| current.status.is_up() && listen_conflict(current, instance));
+            if !held_by_running && let Some(warning) = port_in_use(instance) {
+                warnings.push(warning);
+            }
+            let action = if diff.added.iter().any(|added| added.id == instance.id) {
+                ImportAction::Create
+            } else if diff.changed.iter().any(|changed| changed.id == instance.id) {
+                ImportAction::Replace
+            } else {
+                ImportAction::Unchanged
+            };
+            ImportPlanEntry {
+                id: instance.id,
+                name: instance.name.clone(),
+                action,
+                errors,
+                warnings,
+            }
+        })
+        .collect();
+    ImportPlan {
+        valid: instances.iter().all(|entry| entry.errors.is_empty()),
+        instances,
+        removed: diff.removed.clone(),
+        diff,
+        unknown_fields: Vec::new(),
+    }
+}
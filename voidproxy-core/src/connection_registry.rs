@@ -0,0 +1,107 @@
+use crate::middleware::ConnectionId;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+/**
+ * Open TCP connections of an instance with their last activity, the TCP
+ * counterpart of `UdpSessionManager`.
+ *
+ * A connection is idle while neither of its directions carries data, so a
+ * download does not time out because the client stays silent.
+ */
+pub struct ConnectionRegistry {
+    connections: Mutex<HashMap<ConnectionId, Arc<TrackedConnection>>>,
+    idle_timeout: Duration,
+}
+/**
+ * Activity of one registered connection.
+ */
+pub struct TrackedConnection {
+    pub peer_addr: SocketAddr,
+    opened_at: Instant,
+    last_activity_ms: AtomicU64,
+}
+impl TrackedConnection {
+    /**
+     * Marks the connection as active now.
+     */
+    pub fn touch(&self) {
+        self.last_activity_ms.fetch_max(
+            self.opened_at.elapsed().as_millis() as u64,
+            Ordering::Relaxed,
+        );
+    }
+    pub fn idle(&self) -> Duration {
+        let last_activity = Duration::from_millis(self.last_activity_ms.load(Ordering::Relaxed));
+        self.opened_at.elapsed().saturating_sub(last_activity)
+    }
+}
+/**
+ * Registration of a connection, removed from its registry when dropped.
+ */
+pub struct ConnectionGuard {
+    registry: Arc<ConnectionRegistry>,
+    id: ConnectionId,
+    connection: Arc<TrackedConnection>,
+}
+impl ConnectionGuard {
+    pub fn connection(&self) -> &Arc<TrackedConnection> {
+        &self.connection
+    }
+}
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.registry
+            .connections
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.id);
+    }
+}
+impl ConnectionRegistry {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            connections: Mutex::new(HashMap::new()),
+            idle_timeout,
+        }
+    }
+    pub fn register(self: &Arc<Self>, id: ConnectionId, peer_addr: SocketAddr) -> ConnectionGuard {
+        let connection = Arc::new(TrackedConnection {
+            peer_addr,
+            opened_at: Instant::now(),
+            last_activity_ms: AtomicU64::new(0),
+        });
+        self.connections
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, connection.clone());
+        ConnectionGuard {
+            registry: self.clone(),
+            id,
+            connection,
+        }
+    }
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+    pub fn active_count(&self) -> usize {
+        self.connections
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+    /**
+     * Idle time of the connection that has been idle the longest.
+     */
+    pub fn longest_idle(&self) -> Duration {
+        self.connections
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .map(|connection| connection.idle())
+            .max()
+            .unwrap_or_default()
+    }
+}
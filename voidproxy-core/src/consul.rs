@@ -0,0 +1,127 @@
+use crate::backend::{Backend, BackendProvider, Resolution, ResolveFuture};
+use anyhow::{Context, Result};
+use hyper::Method;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+const DEFAULT_AGENT_ADDR: &str = "http://127.0.0.1:8500";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+/**
+ * Backend provider resolving a service from the Consul health catalog.
+ *
+ * Configured as `consul://<service>`. The agent is taken from
+ * `CONSUL_HTTP_ADDR` (default `http://127.0.0.1:8500`) and `CONSUL_HTTP_TOKEN`
+ * is sent when set. Instances with a failing check are marked unhealthy.
+ */
+pub struct ConsulProvider {
+    service: String,
+    agent_addr: String,
+    token: Option<String>,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ServiceEntry {
+    node: ConsulNode,
+    service: ConsulService,
+    #[serde(default)]
+    checks: Vec<ConsulCheck>,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulNode {
+    #[serde(default)]
+    address: String,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulService {
+    #[serde(default)]
+    address: String,
+    port: u16,
+    #[serde(default)]
+    weights: Option<ConsulWeights>,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulWeights {
+    passing: u32,
+}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ConsulCheck {
+    status: String,
+}
+impl ConsulProvider {
+    pub fn from_target(target: &str) -> Result<Self> {
+        let service = target.trim_matches('/');
+        if service.is_empty() || service.contains('/') {
+            return Err(anyhow::anyhow!("Consul backend must be consul://<service>"));
+        }
+        let agent_addr = std::env::var("CONSUL_HTTP_ADDR")
+            .ok()
+            .filter(|addr| !addr.is_empty())
+            .unwrap_or_else(|| DEFAULT_AGENT_ADDR.to_string());
+        let agent_addr = if agent_addr.contains("://") {
+            agent_addr
+        } else {
+            format!("http://{}", agent_addr)
+        };
+        Ok(Self {
+            service: service.to_string(),
+            agent_addr: agent_addr.trim_end_matches('/').to_string(),
+            token: std::env::var("CONSUL_HTTP_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+        })
+    }
+    async fn fetch(&self) -> Result<Resolution> {
+        let url = format!("{}/v1/health/service/{}", self.agent_addr, self.service);
+        let headers: Vec<(&str, String)> = self
+            .token
+            .iter()
+            .map(|token| ("x-consul-token", token.clone()))
+            .collect();
+        let response = crate::http_client::send(Method::GET, &url, &headers, None).await?;
+        if !response.is_success() {
+            return Err(anyhow::anyhow!(
+                "Consul responded with status {}",
+                response.status
+            ));
+        }
+        let entries: Vec<ServiceEntry> = response
+            .json()
+            .context("Invalid Consul health response")?;
+        let backends = entries
+            .iter()
+            .filter_map(|entry| {
+                let address = if entry.service.address.is_empty() {
+                    &entry.node.address
+                } else {
+                    &entry.service.address
+                };
+                let ip: IpAddr = address.parse().ok()?;
+                Some(Backend {
+                    weight: entry
+                        .service
+                        .weights
+                        .as_ref()
+                        .map_or(1, |weights| weights.passing),
+                    healthy: entry.checks.iter().all(|check| check.status == "passing"),
+                    ..Backend::new(SocketAddr::new(ip, entry.service.port))
+                })
+            })
+            .collect();
+        Ok(Resolution {
+            backends,
+            ttl: Some(REFRESH_INTERVAL),
+        })
+    }
+}
+impl BackendProvider for ConsulProvider {
+    fn resolve(&self) -> ResolveFuture<'_> {
+        Box::pin(self.fetch())
+    }
+    fn describe(&self) -> String {
+        format!("consul://{}", self.service)
+    }
+}
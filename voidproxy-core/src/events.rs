@@ -0,0 +1,56 @@
+use crate::instance::InstanceStatus;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+pub const EVENT_NAMES: [&str; 5] = [
+    "instance_started",
+    "instance_stopped",
+    "instance_failed",
+    "instance_status_changed",
+    "config_imported",
+];
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+/**
+ * Lifecycle change of an instance or of the configuration as a whole.
+ */
+pub enum EventKind {
+    InstanceStarted { instance_id: Uuid, name: String },
+    InstanceStopped { instance_id: Uuid, name: String },
+    InstanceFailed { instance_id: Uuid, error: String },
+    InstanceStatusChanged { instance_id: Uuid, from: InstanceStatus, to: InstanceStatus },
+    ConfigImported { instances: usize },
+}
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InstanceStarted { .. } => "instance_started",
+            Self::InstanceStopped { .. } => "instance_stopped",
+            Self::InstanceFailed { .. } => "instance_failed",
+            Self::InstanceStatusChanged { .. } => "instance_status_changed",
+            Self::ConfigImported { .. } => "config_imported",
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Event published on the instance service's event channel.
+ *
+ * Serialized flat, e.g. `{"id": ..., "timestamp": ..., "event":
+ * "instance_started", "instance_id": ..., "name": ...}`.
+ */
+pub struct Event {
+    pub id: Uuid,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+impl Event {
+    pub fn new(kind: EventKind) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            kind,
+        }
+    }
+}
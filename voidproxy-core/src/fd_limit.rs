@@ -0,0 +1,65 @@
+use anyhow::Result;
+/**
+ * Default soft limit of open files requested at startup. Every proxied TCP
+ * connection holds two descriptors, so the usual default of 1024 caps an
+ * instance at about 500 connections.
+ */
+pub const DEFAULT_TARGET: u64 = 65536;
+/**
+ * Share of the open file limit in use above which the system metrics
+ * collection logs a warning.
+ */
+pub const WARN_RATIO: f64 = 0.8;
+/**
+ * Raises the soft limit of open files (`RLIMIT_NOFILE`) to `target`, capped
+ * by the hard limit, and returns the limit in effect. A limit already above
+ * `target` is kept.
+ */
+#[cfg(unix)]
+pub fn raise_limit(target: u64) -> Result<u64> {
+    let mut limit = unsafe { std::mem::zeroed::<nix::libc::rlimit>() };
+    if unsafe { nix::libc::getrlimit(nix::libc::RLIMIT_NOFILE, &mut limit) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let wanted = (target as nix::libc::rlim_t).min(limit.rlim_max);
+    if wanted > limit.rlim_cur {
+        let raised = nix::libc::rlimit {
+            rlim_cur: wanted,
+            rlim_max: limit.rlim_max,
+        };
+        if unsafe { nix::libc::setrlimit(nix::libc::RLIMIT_NOFILE, &raised) } != 0 {
+            return Err(anyhow::anyhow!(
+                "Failed to raise the open file limit from {} to {}: {}",
+                limit.rlim_cur,
+                wanted,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    current_limit().ok_or_else(|| anyhow::anyhow!("Failed to read the open file limit"))
+}
+#[cfg(not(unix))]
+pub fn raise_limit(_target: u64) -> Result<u64> {
+    anyhow::bail!("Open file limits are not supported on this platform")
+}
+/**
+ * Soft limit of open files of the process, where the platform has one.
+ */
+#[cfg(unix)]
+pub fn current_limit() -> Option<u64> {
+    let mut limit = unsafe { std::mem::zeroed::<nix::libc::rlimit>() };
+    let result = unsafe { nix::libc::getrlimit(nix::libc::RLIMIT_NOFILE, &mut limit) };
+    (result == 0).then_some(limit.rlim_cur as u64)
+}
+#[cfg(not(unix))]
+pub fn current_limit() -> Option<u64> {
+    None
+}
+/**
+ * Descriptors the process has open, counted from `/proc/self/fd` where
+ * available.
+ */
+pub fn open_files() -> Option<u64> {
+    let entries = std::fs::read_dir("/proc/self/fd").ok()?;
+    Some((entries.count() as u64).saturating_sub(1))
+}
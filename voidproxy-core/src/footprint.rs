@@ -0,0 +1,61 @@
+use anyhow::{Result, bail};
+use std::sync::OnceLock;
+/**
+ * Memory footprint proxies size their pools, caches and queues for.
+ *
+ * `Compact` trades throughput under heavy load for a much smaller resident
+ * set and is meant for single-threaded deployments on router-class devices.
+ * The footprint is process-wide and chosen once at startup, before any
+ * instance runs.
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Footprint {
+    #[default]
+    Standard,
+    Compact,
+}
+static FOOTPRINT: OnceLock<Footprint> = OnceLock::new();
+impl Footprint {
+    pub fn current() -> Self {
+        FOOTPRINT.get().copied().unwrap_or_default()
+    }
+    /**
+     * Sets the process-wide footprint; fails once a footprint has been set.
+     */
+    pub fn install(self) -> Result<()> {
+        if FOOTPRINT.set(self).is_err() {
+            bail!("footprint already set to {:?}", Self::current());
+        }
+        Ok(())
+    }
+    pub fn buffer_pool_size(self) -> usize {
+        match self {
+            Self::Standard => 1000,
+            Self::Compact => 32,
+        }
+    }
+    pub fn ip_cache_entries(self) -> usize {
+        match self {
+            Self::Standard => 10_000,
+            Self::Compact => 256,
+        }
+    }
+    pub fn udp_batch_size(self) -> usize {
+        match self {
+            Self::Standard => crate::udp_batch::BATCH_SIZE,
+            Self::Compact => 4,
+        }
+    }
+    pub fn udp_send_queue(self) -> usize {
+        match self {
+            Self::Standard => 4096,
+            Self::Compact => 256,
+        }
+    }
+    pub fn event_queue(self) -> usize {
+        match self {
+            Self::Standard => 256,
+            Self::Compact => 32,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{Context, Result};
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::Path;
+#[derive(Debug, Clone, PartialEq)]
+struct CountryRange {
+    start: u128,
+    end: u128,
+    country: String,
+}
+/**
+ * IP to country database loaded from a CSV range file.
+ *
+ * Each line holds the first and last address of a range and its ISO
+ * country code, e.g. `1.0.0.0,1.0.0.255,AU` as in the DB-IP lite country
+ * file. Addresses may also be given as integers, as in the IP2Location LITE
+ * files; extra columns and quotes are ignored, as are ranges without a
+ * country (`-`).
+ */
+#[derive(Debug, Clone, Default)]
+pub struct GeoIpDb {
+    ranges: Vec<CountryRange>,
+}
+impl GeoIpDb {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read GeoIP database {:?}", path))?;
+        Self::parse(&content).with_context(|| format!("Invalid GeoIP database {:?}", path))
+    }
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut ranges = Vec::new();
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line
+                .split(',')
+                .map(|f| f.trim().trim_matches('"'))
+                .collect();
+            let [start, end, country, ..] = fields[..] else {
+                anyhow::bail!("Line {}: expected start, end and country", index + 1);
+            };
+            let (Some(start), Some(end)) = (parse_address(start), parse_address(end)) else {
+                if index == 0 {
+                    continue;
+                }
+                anyhow::bail!("Line {}: invalid address range", index + 1);
+            };
+            if country.is_empty() || country == "-" {
+                continue;
+            }
+            ranges.push(CountryRange {
+                start,
+                end,
+                country: country.to_ascii_uppercase(),
+            });
+        }
+        ranges.sort_by_key(|range| range.start);
+        Ok(Self { ranges })
+    }
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+    /**
+     * ISO country code of the range holding `ip`, if any.
+     */
+    pub fn country(&self, ip: &IpAddr) -> Option<&str> {
+        let ip = address_key(ip);
+        let index = self.ranges.partition_point(|range| range.start <= ip);
+        let range = self.ranges.get(index.checked_sub(1)?)?;
+        (ip <= range.end).then_some(range.country.as_str())
+    }
+}
+fn address_key(ip: &IpAddr) -> u128 {
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => u128::from(ip.to_ipv6_mapped()),
+        IpAddr::V6(ip) => u128::from(ip),
+    }
+}
+fn parse_address(field: &str) -> Option<u128> {
+    if let Ok(ip) = field.parse::<IpAddr>() {
+        return Some(address_key(&ip));
+    }
+    let value = field.parse::<u128>().ok()?;
+    Some(match u32::try_from(value) {
+        Ok(v4) => u128::from(std::net::Ipv4Addr::from(v4).to_ipv6_mapped()),
+        Err(_) => u128::from(Ipv6Addr::from(value)),
+    })
+}
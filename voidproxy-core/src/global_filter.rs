@@ -0,0 +1,120 @@
+use crate::blocklist::NetworkSet;
+use ipnet::IpNet;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+static GLOBAL_FILTER: OnceLock<GlobalFilter> = OnceLock::new();
+/**
+ * Global IP filter shared by all instances of the process.
+ */
+pub fn global() -> &'static GlobalFilter {
+    GLOBAL_FILTER.get_or_init(GlobalFilter::default)
+}
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+/**
+ * Daemon-wide IP filter, applied before the filter of each instance.
+ *
+ * Entries are IPs or CIDR networks. A client matching `deny` is rejected;
+ * when `allow` is not empty, a client must also match it.
+ */
+pub struct GlobalFilterConfig {
+    #[serde(default, deserialize_with = "deserialize_networks")]
+    pub allow: Vec<IpNet>,
+    #[serde(default, deserialize_with = "deserialize_networks")]
+    pub deny: Vec<IpNet>,
+}
+impl GlobalFilterConfig {
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+}
+/**
+ * Parses an IP, taken as a network of that single address, or a CIDR
+ * network.
+ */
+pub fn parse_network(entry: &str) -> Result<IpNet, String> {
+    let entry = entry.trim();
+    entry
+        .parse::<IpNet>()
+        .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("Invalid IP or CIDR network: {}", entry))
+}
+fn deserialize_networks<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<IpNet>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|entry| parse_network(entry).map_err(serde::de::Error::custom))
+        .collect()
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Global filter status reported by the API.
+ */
+pub struct GlobalFilterStatus {
+    #[serde(flatten)]
+    pub config: GlobalFilterConfig,
+    pub rejected: u64,
+}
+#[derive(Debug, Default)]
+struct CompiledFilter {
+    config: GlobalFilterConfig,
+    allow: NetworkSet,
+    deny: NetworkSet,
+}
+/**
+ * Global IP filter checked on every connection and datagram. `rejected`
+ * counts the clients it turned away.
+ */
+#[derive(Default)]
+pub struct GlobalFilter {
+    filter: RwLock<Arc<CompiledFilter>>,
+    rejected: AtomicU64,
+}
+impl GlobalFilter {
+    pub fn set(&self, config: GlobalFilterConfig) {
+        let mut allow = NetworkSet::default();
+        let mut deny = NetworkSet::default();
+        for network in &config.allow {
+            allow.insert(*network);
+        }
+        for network in &config.deny {
+            deny.insert(*network);
+        }
+        *self.filter.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(CompiledFilter {
+            config,
+            allow,
+            deny,
+        });
+    }
+    pub fn config(&self) -> GlobalFilterConfig {
+        self.filter
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .config
+            .clone()
+    }
+    /**
+     * Whether `ip` passes the filter; counts it as rejected if not.
+     */
+    pub fn is_allowed(&self, ip: &IpAddr) -> bool {
+        let filter = self
+            .filter
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let allowed =
+            !filter.deny.contains(ip) && (filter.allow.is_empty() || filter.allow.contains(ip));
+        if !allowed {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        allowed
+    }
+    pub fn status(&self) -> GlobalFilterStatus {
+        GlobalFilterStatus {
+            config: self.config(),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
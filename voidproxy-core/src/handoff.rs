@@ -0,0 +1,245 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Mutex, OnceLock};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use uuid::Uuid;
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/**
+ * Kind of listening socket that can be handed over to a new process.
+ */
+pub enum SocketKind {
+    Tcp,
+    Udp,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SocketEntry {
+    kind: SocketKind,
+    addr: SocketAddr,
+    instance_id: Option<Uuid>,
+}
+/**
+ * Process-wide registry of listening sockets used for zero-downtime upgrades.
+ *
+ * The old process tracks every bound listener so it can pass duplicates to its
+ * successor over a unix socket (SCM_RIGHTS). The new process keeps the received
+ * sockets here until the matching listener claims them instead of binding.
+ */
+#[derive(Default)]
+struct HandoffState {
+    #[cfg(unix)]
+    active: HashMap<(SocketKind, SocketAddr), (RawFd, Option<Uuid>)>,
+    #[cfg(unix)]
+    inherited: HashMap<(SocketKind, SocketAddr), OwnedFd>,
+    inherited_instances: Vec<Uuid>,
+}
+static STATE: OnceLock<Mutex<HandoffState>> = OnceLock::new();
+static DRAIN: OnceLock<CancellationToken> = OnceLock::new();
+fn state() -> std::sync::MutexGuard<'static, HandoffState> {
+    STATE
+        .get_or_init(|| Mutex::new(HandoffState::default()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+/**
+ * Token cancelled once the listening sockets have been handed to a new process.
+ *
+ * Listeners stop accepting when it fires while established connections keep
+ * running until they finish on their own.
+ */
+pub fn drain_token() -> CancellationToken {
+    DRAIN.get_or_init(CancellationToken::new).clone()
+}
+#[cfg(unix)]
+pub fn register_listener(
+    kind: SocketKind,
+    addr: SocketAddr,
+    fd: RawFd,
+    instance_id: Option<Uuid>,
+) {
+    state().active.insert((kind, addr), (fd, instance_id));
+}
+pub fn unregister_listener(kind: SocketKind, addr: SocketAddr) {
+    #[cfg(unix)]
+    state().active.remove(&(kind, addr));
+    #[cfg(not(unix))]
+    let _ = (kind, addr);
+}
+pub fn take_tcp_listener(addr: SocketAddr) -> Option<std::net::TcpListener> {
+    #[cfg(unix)]
+    {
+        state()
+            .inherited
+            .remove(&(SocketKind::Tcp, addr))
+            .map(std::net::TcpListener::from)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = addr;
+        None
+    }
+}
+pub fn take_udp_socket(addr: SocketAddr) -> Option<std::net::UdpSocket> {
+    #[cfg(unix)]
+    {
+        state()
+            .inherited
+            .remove(&(SocketKind::Udp, addr))
+            .map(std::net::UdpSocket::from)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = addr;
+        None
+    }
+}
+/**
+ * Instances that were running in the previous process and whose listeners
+ * were inherited, so they can be started again right after restore.
+ */
+pub fn inherited_instance_ids() -> Vec<Uuid> {
+    state().inherited_instances.clone()
+}
+/**
+ * Requests the listening sockets of a running VoidProxy process.
+ *
+ * Returns the number of sockets received, or zero when no previous process is
+ * listening on the upgrade socket.
+ */
+#[cfg(unix)]
+pub fn inherit_from(path: &std::path::Path) -> Result<usize> {
+    use nix::sys::socket::{ControlMessageOwned, MsgFlags, recvmsg};
+    use std::io::IoSliceMut;
+    use std::os::fd::AsRawFd;
+    let stream = match std::os::unix::net::UnixStream::connect(path) {
+        Ok(stream) => stream,
+        Err(e)
+            if matches!(
+                e.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+            ) =>
+        {
+            return Ok(0);
+        }
+        Err(e) => return Err(e).context("Failed to connect to upgrade socket"),
+    };
+    let mut buffer = vec![0u8; 64 * 1024];
+    let mut cmsg_buffer = nix::cmsg_space!([RawFd; 253]);
+    let mut iov = [IoSliceMut::new(&mut buffer)];
+    let msg = recvmsg::<()>(
+        stream.as_raw_fd(),
+        &mut iov,
+        Some(&mut cmsg_buffer),
+        MsgFlags::MSG_CMSG_CLOEXEC,
+    )
+    .context("Failed to receive sockets from previous process")?;
+    let mut fds = Vec::new();
+    for cmsg in msg.cmsgs().context("Truncated socket handoff message")? {
+        if let ControlMessageOwned::ScmRights(received) = cmsg {
+            fds.extend(
+                received
+                    .into_iter()
+                    .map(|fd| unsafe { OwnedFd::from_raw_fd(fd) }),
+            );
+        }
+    }
+    let len = msg.bytes;
+    let entries: Vec<SocketEntry> = serde_json::from_slice(&buffer[..len])
+        .context("Failed to parse socket handoff message")?;
+    if entries.len() != fds.len() {
+        return Err(anyhow::anyhow!(
+            "Socket handoff mismatch: {} entries for {} descriptors",
+            entries.len(),
+            fds.len()
+        ));
+    }
+    let count = entries.len();
+    let mut state = state();
+    for (entry, fd) in entries.into_iter().zip(fds) {
+        if let Some(instance_id) = entry.instance_id
+            && !state.inherited_instances.contains(&instance_id)
+        {
+            state.inherited_instances.push(instance_id);
+        }
+        state.inherited.insert((entry.kind, entry.addr), fd);
+    }
+    info!("Inherited {} listening sockets from previous process", count);
+    Ok(count)
+}
+/**
+ * Waits for a successor process on the upgrade socket and hands it every
+ * registered listener, then triggers the drain token.
+ */
+#[cfg(unix)]
+pub async fn serve(path: std::path::PathBuf) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    if path.exists() {
+        std::fs::remove_file(&path).context("Failed to remove stale upgrade socket")?;
+    }
+    let listener =
+        tokio::net::UnixListener::bind(&path).context("Failed to bind upgrade socket")?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .context("Failed to restrict upgrade socket permissions")?;
+    info!("Upgrade socket listening on {:?}", path);
+    let (stream, _) = listener
+        .accept()
+        .await
+        .context("Failed to accept upgrade connection")?;
+    let stream = stream.into_std()?;
+    stream.set_nonblocking(false)?;
+    let sent = tokio::task::spawn_blocking(move || send_listeners(&stream)).await??;
+    info!(
+        "Handed {} listening sockets to new process, draining connections",
+        sent
+    );
+    drain_token().cancel();
+    Ok(())
+}
+#[cfg(unix)]
+fn send_listeners(stream: &std::os::unix::net::UnixStream) -> Result<usize> {
+    use nix::sys::socket::{ControlMessage, MsgFlags, sendmsg};
+    use std::io::IoSlice;
+    use std::os::fd::AsRawFd;
+    let (entries, fds): (Vec<SocketEntry>, Vec<RawFd>) = state()
+        .active
+        .iter()
+        .map(|((kind, addr), (fd, instance_id))| {
+            (
+                SocketEntry {
+                    kind: *kind,
+                    addr: *addr,
+                    instance_id: *instance_id,
+                },
+                *fd,
+            )
+        })
+        .unzip();
+    if fds.len() > 253 {
+        warn!(
+            "Only the first 253 of {} listeners can be handed over",
+            fds.len()
+        );
+    }
+    let entries = &entries[..entries.len().min(253)];
+    let fds = &fds[..fds.len().min(253)];
+    let payload = serde_json::to_vec(entries)?;
+    let cmsgs = if fds.is_empty() {
+        Vec::new()
+    } else {
+        vec![ControlMessage::ScmRights(fds)]
+    };
+    sendmsg::<()>(
+        stream.as_raw_fd(),
+        &[IoSlice::new(&payload)],
+        &cmsgs,
+        MsgFlags::empty(),
+        None,
+    )
+    .context("Failed to send listening sockets")?;
+    Ok(entries.len())
+}
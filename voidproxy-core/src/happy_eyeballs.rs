@@ -0,0 +1,94 @@
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tracing::debug;
+/**
+ * Delay before the next connection attempt is started while earlier ones
+ * are still pending (RFC 8305 recommends 250 ms).
+ */
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+/**
+ * Orders addresses for connection attempts as RFC 8305 describes:
+ * alternating between address families, starting with the family of the
+ * first address.
+ */
+pub fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let Some(first) = addrs.first() else {
+        return Vec::new();
+    };
+    let (preferred, other): (Vec<SocketAddr>, Vec<SocketAddr>) = addrs
+        .iter()
+        .partition(|addr| addr.is_ipv4() == first.is_ipv4());
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+    let mut ordered = Vec::with_capacity(addrs.len());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => break,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+    ordered
+}
+/**
+ * Connects to whichever of `addrs` accepts first (Happy Eyeballs), opening
+ * each connection with `connector`.
+ *
+ * Attempts are started in `interleave` order, each one `attempt_delay`
+ * after the previous or as soon as it failed; the remaining attempts are
+ * cancelled once one succeeds. The error of the last failed attempt is
+ * returned when none does.
+ */
+pub async fn connect<F, Fut>(
+    addrs: &[SocketAddr],
+    attempt_delay: Duration,
+    connector: F,
+) -> io::Result<TcpStream>
+where
+    F: Fn(SocketAddr) -> Fut,
+    Fut: Future<Output = io::Result<TcpStream>> + Send + 'static,
+{
+    let attempt = |addr: SocketAddr| {
+        let connection = connector(addr);
+        async move { (addr, connection.await) }
+    };
+    let mut pending = interleave(addrs).into_iter().peekable();
+    let mut attempts = JoinSet::new();
+    let mut last_error = None;
+    loop {
+        if attempts.is_empty() {
+            match pending.next() {
+                Some(addr) => {
+                    attempts.spawn(attempt(addr));
+                }
+                None => {
+                    return Err(last_error.unwrap_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidInput, "No address to connect to")
+                    }));
+                }
+            }
+        }
+        tokio::select! {
+            Some(result) = attempts.join_next() => match result {
+                Ok((_, Ok(stream))) => return Ok(stream),
+                Ok((addr, Err(e))) => {
+                    debug!("Connection attempt to {} failed: {}", addr, e);
+                    last_error = Some(e);
+                    if let Some(addr) = pending.next() {
+                        attempts.spawn(attempt(addr));
+                    }
+                }
+                Err(e) => last_error = Some(io::Error::other(e)),
+            },
+            _ = tokio::time::sleep(attempt_delay), if pending.peek().is_some() => {
+                if let Some(addr) = pending.next() {
+                    debug!("Starting parallel connection attempt to {}", addr);
+                    attempts.spawn(attempt(addr));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::config::Config;
+use crate::ip_cache::IpCache;
+use hickory_resolver::TokioResolver;
+use hickory_resolver::config::LookupIpStrategy;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+#[derive(Debug, Clone, Default, PartialEq)]
+/**
+ * Addresses the `allow_hosts` and `deny_hosts` of an IP filter resolve to.
+ */
+pub struct ResolvedHosts {
+    pub allow: HashSet<IpAddr>,
+    pub deny: HashSet<IpAddr>,
+}
+/**
+ * Keeps the hostnames of an instance's IP filter resolved.
+ *
+ * The hostnames are resolved again when their records expire, at least
+ * every minute, so an entry such as a dynamic DNS name follows address
+ * changes. A hostname that fails to resolve keeps its previous addresses.
+ * Cached filter decisions are dropped whenever the addresses change.
+ */
+pub struct HostAcl {
+    allow_hosts: Vec<String>,
+    deny_hosts: Vec<String>,
+    addresses: Mutex<HashMap<String, Vec<IpAddr>>>,
+    resolved: RwLock<Arc<ResolvedHosts>>,
+}
+impl HostAcl {
+    pub fn for_config(config: &Config) -> Self {
+        let filter = config.ip_filter.as_ref();
+        Self {
+            allow_hosts: filter.map(|f| f.allow_hosts.clone()).unwrap_or_default(),
+            deny_hosts: filter.map(|f| f.deny_hosts.clone()).unwrap_or_default(),
+            addresses: Mutex::new(HashMap::new()),
+            resolved: RwLock::new(Arc::new(ResolvedHosts::default())),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.allow_hosts.is_empty() && self.deny_hosts.is_empty()
+    }
+    pub fn resolved(&self) -> Arc<ResolvedHosts> {
+        self.resolved
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+    /**
+     * Resolves every hostname once and returns the delay before the next
+     * refresh.
+     */
+    pub async fn refresh(&self) -> Duration {
+        let mut builder = match TokioResolver::builder_tokio() {
+            Ok(builder) => builder,
+            Err(e) => {
+                warn!("Failed to read system DNS configuration: {}", e);
+                return DEFAULT_REFRESH_INTERVAL;
+            }
+        };
+        builder.options_mut().ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+        let resolver = builder.build();
+        let mut next_refresh = DEFAULT_REFRESH_INTERVAL;
+        for host in self.allow_hosts.iter().chain(&self.deny_hosts) {
+            match resolver.lookup_ip(host.as_str()).await {
+                Ok(lookup) => {
+                    let ttl = lookup
+                        .valid_until()
+                        .saturating_duration_since(Instant::now());
+                    next_refresh = next_refresh.min(ttl.max(MIN_REFRESH_INTERVAL));
+                    let addresses: Vec<IpAddr> = lookup.iter().collect();
+                    debug!("Resolved IP filter host {} to {:?}", host, addresses);
+                    let mut known = self.addresses.lock().unwrap_or_else(|e| e.into_inner());
+                    known.insert(host.clone(), addresses);
+                }
+                Err(e) => warn!("Failed to resolve IP filter host {}: {}", host, e),
+            }
+        }
+        let known = self.addresses.lock().unwrap_or_else(|e| e.into_inner());
+        let collect = |hosts: &[String]| {
+            hosts
+                .iter()
+                .filter_map(|host| known.get(host))
+                .flatten()
+                .copied()
+                .collect()
+        };
+        let resolved = ResolvedHosts {
+            allow: collect(&self.allow_hosts),
+            deny: collect(&self.deny_hosts),
+        };
+        drop(known);
+        let mut current = self.resolved.write().unwrap_or_else(|e| e.into_inner());
+        if **current != resolved {
+            *current = Arc::new(resolved);
+        }
+        next_refresh
+    }
+    /**
+     * Resolves the hostnames once, then keeps them up to date in the
+     * background until the instance stops, clearing `ip_cache` whenever
+     * their addresses change.
+     */
+    pub async fn start_refresh(
+        self: &Arc<Self>,
+        ip_cache: Arc<IpCache>,
+        cancel_token: Arc<CancellationToken>,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        let mut delay = self.refresh().await;
+        let resolved = self.resolved();
+        info!(
+            "IP filter hosts resolved to {} addresses",
+            resolved.allow.len() + resolved.deny.len()
+        );
+        let acl = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = cancel_token.cancelled() => break,
+                    _ = tokio::time::sleep(delay) => {}
+                }
+                let before = acl.resolved();
+                delay = acl.refresh().await;
+                if !Arc::ptr_eq(&before, &acl.resolved()) {
+                    info!("IP filter host addresses changed");
+                    ip_cache.clear().await;
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,75 @@
+use crate::backend::{Backend, BackendProvider, Resolution, ResolveFuture};
+use anyhow::{Context, Result};
+use hickory_resolver::TokioResolver;
+use hickory_resolver::config::LookupIpStrategy;
+use std::net::SocketAddr;
+use std::time::Instant;
+/**
+ * Backend provider resolving a `host:port` entry to its A and AAAA records.
+ *
+ * Every address becomes a backend tagged with the hostname, so TCP
+ * connections can race the address families (Happy Eyeballs) instead of
+ * failing when one of them is unreachable. The set is resolved again once
+ * the record TTL expires.
+ */
+pub struct HostnameProvider {
+    host: String,
+    port: u16,
+}
+impl HostnameProvider {
+    pub fn from_entry(entry: &str) -> Result<Self> {
+        let (host, port) = entry
+            .rsplit_once(':')
+            .ok_or_else(|| anyhow::anyhow!("Backend {} must be host:port", entry))?;
+        let port: u16 = port
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid port in backend {}: {}", entry, e))?;
+        if !is_valid_hostname(host) || port == 0 {
+            return Err(anyhow::anyhow!("Invalid backend address {}", entry));
+        }
+        Ok(Self {
+            host: host.trim_end_matches('.').to_string(),
+            port,
+        })
+    }
+    async fn fetch(&self) -> Result<Resolution> {
+        let mut builder =
+            TokioResolver::builder_tokio().context("Failed to read system DNS configuration")?;
+        builder.options_mut().ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+        let addresses = builder
+            .build()
+            .lookup_ip(self.host.as_str())
+            .await
+            .with_context(|| format!("Failed to resolve {}", self.host))?;
+        let backends = addresses
+            .iter()
+            .map(|ip| Backend {
+                host: Some(self.host.clone()),
+                ..Backend::new(SocketAddr::new(ip, self.port))
+            })
+            .collect();
+        Ok(Resolution {
+            backends,
+            ttl: Some(addresses.valid_until().saturating_duration_since(Instant::now())),
+        })
+    }
+}
+impl BackendProvider for HostnameProvider {
+    fn resolve(&self) -> ResolveFuture<'_> {
+        Box::pin(self.fetch())
+    }
+    fn describe(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+/**
+ * Whether `host` looks like a DNS name: letters, digits, `-` and `.`, at
+ * most 253 characters.
+ */
+pub fn is_valid_hostname(host: &str) -> bool {
+    !host.is_empty()
+        && host.len() <= 253
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
@@ -0,0 +1,175 @@
+use anyhow::{Context, Result};
+use hyper::{Method, Request, Uri, header};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_util::rt::TokioIo;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, RootCertStore};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::timeout;
+use tokio_rustls::TlsConnector;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+#[derive(Debug, Clone)]
+/**
+ * Response returned by the internal HTTP client.
+ *
+ * Holds the status code and the fully buffered body.
+ */
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Bytes,
+}
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_slice(&self.body).context("Failed to decode JSON response")
+    }
+}
+/**
+ * Sends an HTTP/1.1 request to an `http://` or `https://` URL.
+ *
+ * Used for control-plane traffic (cluster sync, discovery, notifications),
+ * never on the proxy data path.
+ */
+pub async fn send(
+    method: Method,
+    url: &str,
+    headers: &[(&str, String)],
+    body: Option<Vec<u8>>,
+) -> Result<HttpResponse> {
+    send_with_tls(method, url, headers, body, None).await
+}
+/**
+ * Same as `send`, with an explicit TLS configuration for `https://` URLs
+ * (e.g. a private CA). Public web roots are trusted when none is given.
+ */
+pub async fn send_with_tls(
+    method: Method,
+    url: &str,
+    headers: &[(&str, String)],
+    body: Option<Vec<u8>>,
+    tls_config: Option<Arc<ClientConfig>>,
+) -> Result<HttpResponse> {
+    let uri: Uri = url.parse().context("Invalid URL")?;
+    let secure = match uri.scheme_str() {
+        Some("http") => false,
+        Some("https") => true,
+        _ => return Err(anyhow::anyhow!("Unsupported URL scheme in {}", url)),
+    };
+    let host = uri.host().context("URL has no host")?;
+    let host = host.trim_matches(['[', ']']);
+    let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+    let authority = uri
+        .authority()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|| host.to_string());
+    let path = uri
+        .path_and_query()
+        .map(|p| p.to_string())
+        .unwrap_or_else(|| "/".to_string());
+    let stream = timeout(REQUEST_TIMEOUT, tokio::net::TcpStream::connect((host, port)))
+        .await
+        .context("Connection timed out")?
+        .with_context(|| format!("Failed to connect to {}", authority))?;
+    if !secure {
+        return send_over(stream, method, &authority, &path, headers, body).await;
+    }
+    let server_name = ServerName::try_from(host.to_string()).context("Invalid TLS server name")?;
+    let connector = TlsConnector::from(tls_config.unwrap_or_else(default_tls_config));
+    let stream = timeout(REQUEST_TIMEOUT, connector.connect(server_name, stream))
+        .await
+        .context("TLS handshake timed out")?
+        .with_context(|| format!("TLS handshake with {} failed", authority))?;
+    send_over(stream, method, &authority, &path, headers, body).await
+}
+pub(crate) fn default_tls_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            Arc::new(client_config(roots).expect("Default TLS protocol versions are supported"))
+        })
+        .clone()
+}
+fn client_config(roots: RootCertStore) -> Result<ClientConfig> {
+    Ok(
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_root_certificates(roots)
+            .with_no_client_auth(),
+    )
+}
+/**
+ * Builds a TLS configuration trusting only the certificates of a PEM bundle.
+ */
+pub fn tls_config_with_ca(ca_pem_path: &std::path::Path) -> Result<Arc<ClientConfig>> {
+    let pem = std::fs::read(ca_pem_path)
+        .with_context(|| format!("Failed to read CA bundle {:?}", ca_pem_path))?;
+    let mut roots = RootCertStore::empty();
+    for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+        roots.add(cert.context("Invalid certificate in CA bundle")?)?;
+    }
+    Ok(Arc::new(client_config(roots)?))
+}
+/**
+ * Sends an HTTP/1.1 request over a unix domain socket, e.g. the Docker API.
+ */
+#[cfg(unix)]
+pub async fn send_unix(
+    socket_path: &std::path::Path,
+    method: Method,
+    path: &str,
+    headers: &[(&str, String)],
+    body: Option<Vec<u8>>,
+) -> Result<HttpResponse> {
+    let stream = timeout(REQUEST_TIMEOUT, tokio::net::UnixStream::connect(socket_path))
+        .await
+        .context("Connection timed out")?
+        .with_context(|| format!("Failed to connect to {:?}", socket_path))?;
+    send_over(stream, method, "localhost", path, headers, body).await
+}
+async fn send_over<S>(
+    stream: S,
+    method: Method,
+    authority: &str,
+    path: &str,
+    headers: &[(&str, String)],
+    body: Option<Vec<u8>>,
+) -> Result<HttpResponse>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+        .await
+        .context("HTTP handshake failed")?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::debug!("HTTP client connection closed: {}", e);
+        }
+    });
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(path)
+        .header(header::HOST, authority)
+        .header(header::USER_AGENT, concat!("void_proxy/", env!("CARGO_PKG_VERSION")));
+    for (name, value) in headers {
+        builder = builder.header(*name, value);
+    }
+    let request = builder
+        .body(Full::new(Bytes::from(body.unwrap_or_default())))
+        .context("Failed to build HTTP request")?;
+    let response = timeout(REQUEST_TIMEOUT, async {
+        let response = sender.send_request(request).await?;
+        let status = response.status().as_u16();
+        let body = response.into_body().collect().await?.to_bytes();
+        Ok::<_, hyper::Error>(HttpResponse { status, body })
+    })
+    .await
+    .context("HTTP request timed out")?
+    .context("HTTP request failed")?;
+    Ok(response)
+}
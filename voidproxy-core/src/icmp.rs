@@ -0,0 +1,97 @@
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+const UDP_PROTOCOL: u8 = 17;
+/** Type 3 (destination unreachable), code 3 (port unreachable). */
+const ICMP_PORT_UNREACHABLE: [u8; 8] = [3, 3, 0, 0, 0, 0, 0, 0];
+/** Type 1 (destination unreachable), code 4 (port unreachable). */
+const ICMPV6_PORT_UNREACHABLE: [u8; 8] = [1, 4, 0, 0, 0, 0, 0, 0];
+/**
+ * Builds the ICMP (IPv4) or ICMPv6 port unreachable message for a UDP
+ * datagram `client` sent to `proxy`.
+ *
+ * The message quotes the IP and UDP headers of that datagram, which is what
+ * the client's stack matches against its socket. The ICMPv6 checksum is left
+ * at 0 for the kernel to fill in.
+ */
+pub fn port_unreachable(client: SocketAddr, proxy: SocketAddr) -> std::io::Result<Vec<u8>> {
+    let udp_header = [
+        client.port().to_be_bytes(),
+        proxy.port().to_be_bytes(),
+        8u16.to_be_bytes(),
+        [0, 0],
+    ]
+    .concat();
+    match (client.ip().to_canonical(), proxy.ip().to_canonical()) {
+        (IpAddr::V4(client_ip), IpAddr::V4(proxy_ip)) => {
+            let mut ip_header = [
+                &[0x45, 0, 0, 28, 0, 0, 0, 0, 64, UDP_PROTOCOL, 0, 0][..],
+                &client_ip.octets(),
+                &proxy_ip.octets(),
+            ]
+            .concat();
+            let checksum = internet_checksum(&ip_header);
+            ip_header[10..12].copy_from_slice(&checksum.to_be_bytes());
+            let mut message = [&ICMP_PORT_UNREACHABLE[..], &ip_header, &udp_header].concat();
+            let checksum = internet_checksum(&message);
+            message[2..4].copy_from_slice(&checksum.to_be_bytes());
+            Ok(message)
+        }
+        (IpAddr::V6(client_ip), IpAddr::V6(proxy_ip)) => Ok([
+            &ICMPV6_PORT_UNREACHABLE[..],
+            &[0x60, 0, 0, 0, 0, 8, UDP_PROTOCOL, 64],
+            &client_ip.octets(),
+            &proxy_ip.octets(),
+            &udp_header,
+        ]
+        .concat()),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("{} and {} are of different address families", client, proxy),
+        )),
+    }
+}
+/**
+ * Tells `client` that the UDP port it reached the proxy on, `proxy`, is
+ * unreachable.
+ *
+ * Sending ICMP needs a raw socket, i.e. root or `CAP_NET_RAW`. An
+ * unspecified `proxy` IP is replaced by the address the host uses to reach
+ * the client.
+ */
+pub fn send_port_unreachable(client: SocketAddr, proxy: SocketAddr) -> std::io::Result<()> {
+    let client_ip = client.ip().to_canonical();
+    let proxy_ip = match proxy.ip().to_canonical() {
+        ip if ip.is_unspecified() => local_ip_towards(client_ip)?,
+        ip => ip,
+    };
+    let message = port_unreachable(
+        SocketAddr::new(client_ip, client.port()),
+        SocketAddr::new(proxy_ip, proxy.port()),
+    )?;
+    let socket = match client_ip {
+        IpAddr::V4(_) => Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?,
+        IpAddr::V6(_) => Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6))?,
+    };
+    socket.bind(&SocketAddr::new(proxy_ip, 0).into())?;
+    socket.send_to(&message, &SocketAddr::new(client_ip, 0).into())?;
+    Ok(())
+}
+fn local_ip_towards(client_ip: IpAddr) -> std::io::Result<IpAddr> {
+    let unspecified = match client_ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    };
+    let socket = UdpSocket::bind(SocketAddr::new(unspecified, 0))?;
+    socket.connect(SocketAddr::new(client_ip, 9))?;
+    Ok(socket.local_addr()?.ip())
+}
+fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
@@ -0,0 +1,724 @@
+use crate::config::{
+    Config, DestinationCheck, InstanceDefaults, LogLevel, LogLimitConfig, MirrorConfig,
+    ProfileOverride, Protocol, SlowLogConfig, TlsConfig, TunnelConfig,
+};
+use crate::metrics::InstanceMetrics;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Represents a proxy instance with its configuration and runtime state.
+ *
+ * Each proxy instance has a unique ID, name, configuration, current status,
+ * timestamps for creation and startup, and associated metrics. A locked
+ * instance is protected from being updated, stopped or deleted through the
+ * API unless an administrator forces the change. `deleted_at` is set while
+ * a deleted instance waits in the trash. `last_error` keeps the most recent
+ * error so a failing instance can be diagnosed without the daemon logs.
+ * `status_history` keeps the latest status transitions of the running
+ * daemon.
+ */
+pub struct ProxyInstance {
+    pub id: Uuid,
+    pub name: String,
+    pub config: Config,
+    pub status: InstanceStatus,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub auto_start: bool,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_error: ErrorSlot,
+    #[serde(skip)]
+    pub metrics: Arc<InstanceMetrics>,
+    #[serde(skip)]
+    pub status_history: VecDeque<StatusTransition>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/**
+ * Where an instance error came from: its configuration, a WASM filter that
+ * failed to load, its listener, or the destination it forwards to.
+ */
+pub enum ErrorCategory {
+    Config,
+    Filter,
+    Listener,
+    Upstream,
+}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastError {
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+    pub category: ErrorCategory,
+}
+#[derive(Debug, Clone, Default)]
+/**
+ * Most recent error of an instance, shared between the instance and its
+ * proxy tasks. Serialized as the error itself, or `null`.
+ */
+pub struct ErrorSlot(Arc<std::sync::Mutex<Option<LastError>>>);
+impl ErrorSlot {
+    pub fn set(&self, category: ErrorCategory, message: impl Into<String>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(LastError {
+            message: message.into(),
+            timestamp: Utc::now(),
+            category,
+        });
+    }
+    pub fn get(&self) -> Option<LastError> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+impl Serialize for ErrorSlot {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for ErrorSlot {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let last_error = Option::<LastError>::deserialize(deserializer)?;
+        Ok(Self(Arc::new(std::sync::Mutex::new(last_error))))
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+/**
+ * Runtime status of a proxy instance.
+ *
+ * Tracks the current operational state of a proxy instance through its lifecycle.
+ * `Error` is a running instance whose listener keeps failing and is being
+ * retried; it returns to `Running` once the listener recovers.
+ */
+pub enum InstanceStatus {
+    Stopped,
+    Running,
+    Error,
+    Starting,
+    Stopping,
+}
+impl InstanceStatus {
+    /**
+     * Whether the proxy tasks of the instance are up, failing or not.
+     */
+    pub fn is_up(self) -> bool {
+        matches!(self, Self::Running | Self::Error)
+    }
+    /**
+     * Whether an instance may go from this status to `next`. A stopped
+     * instance starts, a starting one runs, or goes back to stopped when
+     * its start fails, running and error alternate while its listener
+     * fails, and an up or starting instance stops through `Stopping`.
+     */
+    pub fn can_transition_to(self, next: Self) -> bool {
+        matches!(
+            (self, next),
+            (Self::Stopped, Self::Starting)
+                | (Self::Starting, Self::Running | Self::Stopped | Self::Stopping)
+                | (Self::Running, Self::Error | Self::Stopping)
+                | (Self::Error, Self::Running | Self::Stopping)
+                | (Self::Stopping, Self::Stopped)
+        )
+    }
+}
+impl std::fmt::Display for InstanceStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Stopped => "stopped",
+            Self::Running => "running",
+            Self::Error => "error",
+            Self::Starting => "starting",
+            Self::Stopping => "stopping",
+        })
+    }
+}
+/** Status transitions kept per instance. */
+pub const STATUS_HISTORY_LEN: usize = 50;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub from: InstanceStatus,
+    pub to: InstanceStatus,
+    pub at: DateTime<Utc>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/**
+ * Status change refused by the instance state machine, such as starting
+ * an instance that is still stopping.
+ */
+pub struct InvalidTransition {
+    pub from: InstanceStatus,
+    pub to: InstanceStatus,
+}
+impl std::fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Instance cannot go from {} to {}", self.from, self.to)
+    }
+}
+impl std::error::Error for InvalidTransition {}
+impl ProxyInstance {
+    pub fn new(name: String, config: Config, auto_start: bool) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            name,
+            config,
+            status: InstanceStatus::Stopped,
+            created_at: Utc::now(),
+            started_at: None,
+            auto_start,
+            locked: false,
+            deleted_at: None,
+            last_error: ErrorSlot::default(),
+            metrics: Arc::new(InstanceMetrics::new()),
+            status_history: VecDeque::new(),
+        }
+    }
+    pub fn state(&self) -> InstanceState {
+        InstanceState {
+            metrics: self.metrics.clone(),
+            last_error: self.last_error.clone(),
+        }
+    }
+    /**
+     * Moves the instance to status `to` if the state machine allows it
+     * (see `InstanceStatus::can_transition_to`) and records the change in
+     * its status history.
+     */
+    pub fn transition(
+        &mut self,
+        to: InstanceStatus,
+    ) -> Result<StatusTransition, InvalidTransition> {
+        let from = self.status;
+        if !from.can_transition_to(to) {
+            return Err(InvalidTransition { from, to });
+        }
+        let transition = StatusTransition {
+            from,
+            to,
+            at: Utc::now(),
+        };
+        self.status = to;
+        match to {
+            InstanceStatus::Starting => self.started_at = Some(transition.at),
+            InstanceStatus::Stopping | InstanceStatus::Stopped => self.started_at = None,
+            _ => {}
+        }
+        if self.status_history.len() == STATUS_HISTORY_LEN {
+            self.status_history.pop_front();
+        }
+        self.status_history.push_back(transition);
+        Ok(transition)
+    }
+    pub fn start(&mut self) -> Result<StatusTransition, InvalidTransition> {
+        self.transition(InstanceStatus::Starting)
+    }
+    pub fn set_running(&mut self) -> Result<StatusTransition, InvalidTransition> {
+        self.transition(InstanceStatus::Running)
+    }
+    pub fn stop(&mut self) -> Result<StatusTransition, InvalidTransition> {
+        self.transition(InstanceStatus::Stopping)
+    }
+    pub fn set_stopped(&mut self) -> Result<StatusTransition, InvalidTransition> {
+        self.transition(InstanceStatus::Stopped)
+    }
+    /**
+     * Puts an instance loaded from storage, the trash or a reload back to
+     * `Stopped`, whatever status it was saved with. No transition is
+     * recorded: nothing was running.
+     */
+    pub fn reset_status(&mut self) {
+        self.status = InstanceStatus::Stopped;
+        self.started_at = None;
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Request structure for creating a new proxy instance.
+ *
+ * Contains all the necessary parameters to create and configure a proxy instance.
+ */
+pub struct CreateInstanceRequest {
+    pub name: String,
+    pub listen_ip: IpAddr,
+    pub listen_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+    pub protocol: Protocol,
+    pub auto_start: bool,
+    pub allow_list: Option<Vec<IpAddr>>,
+    pub deny_list: Option<Vec<IpAddr>>,
+    #[serde(default)]
+    pub allow_hosts: Vec<String>,
+    #[serde(default)]
+    pub deny_hosts: Vec<String>,
+    pub connect_timeout_secs: u64,
+    pub idle_timeout_secs: u64,
+    pub log_level: LogLevel,
+    pub bind_device: Option<String>,
+    #[serde(default)]
+    pub bind_nonlocal: bool,
+    #[serde(default)]
+    pub backends: Vec<String>,
+    #[serde(default)]
+    pub wasm_filters: Vec<String>,
+    #[serde(default)]
+    pub listen_backlog: Option<u32>,
+    #[serde(default)]
+    pub bind_src_ip: Option<IpAddr>,
+    #[serde(default)]
+    pub bind_src_device: Option<String>,
+    #[serde(default)]
+    pub max_inflight_bytes: Option<u32>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub tunnel: Option<TunnelConfig>,
+    #[serde(default)]
+    pub slow_log: Option<SlowLogConfig>,
+    #[serde(default)]
+    pub max_concurrent_handlers: Option<u32>,
+    #[serde(default)]
+    pub buffer_pool_size: Option<u32>,
+    #[serde(default)]
+    pub max_concurrent_buffers: Option<u32>,
+    #[serde(default)]
+    pub ip_cache_size: Option<u32>,
+    #[serde(default)]
+    pub ip_cache_ttl_secs: Option<u64>,
+    #[serde(default)]
+    pub propagate_unreachable: bool,
+    #[serde(default)]
+    pub log_limit: Option<LogLimitConfig>,
+    #[serde(default)]
+    pub mirror: Option<MirrorConfig>,
+    #[serde(default)]
+    pub max_connection_lifetime_secs: Option<u64>,
+    #[serde(default)]
+    pub verify_destination_on_start: DestinationCheck,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * String-based request structure for creating a proxy instance.
+ *
+ * Used for API requests where IP addresses are provided as strings
+ * and need to be parsed and validated. The protocol, auto start, timeouts
+ * and log level are optional and taken from `InstanceDefaults` when left
+ * out.
+ */
+pub struct CreateInstanceRequestStrings {
+    pub name: String,
+    pub listen_ip: String,
+    pub listen_port: u16,
+    pub dst_ip: String,
+    pub dst_port: u16,
+    #[serde(default)]
+    pub protocol: Option<Protocol>,
+    #[serde(default)]
+    pub auto_start: Option<bool>,
+    pub allow_list: Option<Vec<String>>,
+    pub deny_list: Option<Vec<String>>,
+    #[serde(default)]
+    pub allow_hosts: Vec<String>,
+    #[serde(default)]
+    pub deny_hosts: Vec<String>,
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub idle_timeout_secs: Option<u64>,
+    #[serde(default)]
+    pub log_level: Option<LogLevel>,
+    pub bind_device: Option<String>,
+    #[serde(default)]
+    pub bind_nonlocal: bool,
+    #[serde(default)]
+    pub backends: Vec<String>,
+    #[serde(default)]
+    pub wasm_filters: Vec<String>,
+    #[serde(default)]
+    pub listen_backlog: Option<u32>,
+    #[serde(default)]
+    pub bind_src_ip: Option<String>,
+    #[serde(default)]
+    pub bind_src_device: Option<String>,
+    #[serde(default)]
+    pub max_inflight_bytes: Option<u32>,
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub tunnel: Option<TunnelConfig>,
+    #[serde(default)]
+    pub slow_log: Option<SlowLogConfig>,
+    #[serde(default)]
+    pub max_concurrent_handlers: Option<u32>,
+    #[serde(default)]
+    pub buffer_pool_size: Option<u32>,
+    #[serde(default)]
+    pub max_concurrent_buffers: Option<u32>,
+    #[serde(default)]
+    pub ip_cache_size: Option<u32>,
+    #[serde(default)]
+    pub ip_cache_ttl_secs: Option<u64>,
+    #[serde(default)]
+    pub propagate_unreachable: bool,
+    #[serde(default)]
+    pub log_limit: Option<LogLimitConfig>,
+    #[serde(default)]
+    pub mirror: Option<MirrorConfig>,
+    #[serde(default)]
+    pub max_connection_lifetime_secs: Option<u64>,
+    #[serde(default)]
+    pub verify_destination_on_start: DestinationCheck,
+}
+impl CreateInstanceRequestStrings {
+    pub fn to_typed(&self) -> Result<CreateInstanceRequest, String> {
+        self.to_typed_with(&InstanceDefaults::default())
+    }
+    pub fn to_typed_with(
+        &self,
+        defaults: &InstanceDefaults,
+    ) -> Result<CreateInstanceRequest, String> {
+        let listen_ip = self
+            .listen_ip
+            .parse()
+            .map_err(|e| format!("Invalid listen IP: {}", e))?;
+        let dst_ip = self
+            .dst_ip
+            .parse()
+            .map_err(|e| format!("Invalid destination IP: {}", e))?;
+        let bind_src_ip = self
+            .bind_src_ip
+            .as_deref()
+            .filter(|ip| !ip.is_empty())
+            .map(|ip| ip.parse())
+            .transpose()
+            .map_err(|e| format!("Invalid source IP: {}", e))?;
+        let allow_list = self
+            .allow_list
+            .as_ref()
+            .map(|list| {
+                list.iter()
+                    .map(|s| {
+                        s.parse()
+                            .map_err(|e| format!("Invalid allow IP {}: {}", s, e))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()
+            .map_err(|e| format!("Invalid allow list: {}", e))?;
+        let deny_list = self
+            .deny_list
+            .as_ref()
+            .map(|list| {
+                list.iter()
+                    .map(|s| {
+                        s.parse()
+                            .map_err(|e| format!("Invalid deny IP {}: {}", s, e))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()
+            .map_err(|e| format!("Invalid deny list: {}", e))?;
+        Ok(CreateInstanceRequest {
+            name: self.name.clone(),
+            listen_ip,
+            listen_port: self.listen_port,
+            dst_ip,
+            dst_port: self.dst_port,
+            protocol: self.protocol.unwrap_or(defaults.protocol),
+            auto_start: self.auto_start.unwrap_or(defaults.auto_start),
+            allow_list,
+            deny_list,
+            allow_hosts: self.allow_hosts.clone(),
+            deny_hosts: self.deny_hosts.clone(),
+            connect_timeout_secs: self
+                .connect_timeout_secs
+                .unwrap_or(defaults.connect_timeout_secs),
+            idle_timeout_secs: self.idle_timeout_secs.unwrap_or(defaults.idle_timeout_secs),
+            log_level: self.log_level.unwrap_or(defaults.log_level),
+            bind_device: self.bind_device.clone(),
+            bind_nonlocal: self.bind_nonlocal,
+            backends: self.backends.clone(),
+            wasm_filters: self.wasm_filters.clone(),
+            listen_backlog: self.listen_backlog,
+            bind_src_ip,
+            bind_src_device: self.bind_src_device.clone(),
+            max_inflight_bytes: self.max_inflight_bytes,
+            tls: self.tls.clone(),
+            tunnel: self.tunnel.clone(),
+            slow_log: self.slow_log,
+            max_concurrent_handlers: self.max_concurrent_handlers,
+            buffer_pool_size: self.buffer_pool_size,
+            max_concurrent_buffers: self.max_concurrent_buffers,
+            ip_cache_size: self.ip_cache_size,
+            ip_cache_ttl_secs: self.ip_cache_ttl_secs,
+            propagate_unreachable: self.propagate_unreachable,
+            log_limit: self.log_limit,
+            mirror: self.mirror,
+            max_connection_lifetime_secs: self.max_connection_lifetime_secs,
+            verify_destination_on_start: self.verify_destination_on_start,
+        })
+    }
+}
+impl CreateInstanceRequest {
+    pub fn to_config(&self) -> Config {
+        Config {
+            proxy: crate::config::ProxyConfig {
+                listen_ip: self.listen_ip,
+                listen_port: self.listen_port,
+                dst_ip: self.dst_ip,
+                dst_port: self.dst_port,
+                protocol: self.protocol,
+                connect_timeout_secs: self.connect_timeout_secs,
+                idle_timeout_secs: self.idle_timeout_secs,
+                log_level: self.log_level,
+                bind_device: self.bind_device.clone(),
+                bind_nonlocal: self.bind_nonlocal,
+                backends: self.backends.clone(),
+                wasm_filters: self.wasm_filters.clone(),
+                listen_backlog: self.listen_backlog,
+                bind_src_ip: self.bind_src_ip,
+                bind_src_device: self.bind_src_device.clone(),
+                max_inflight_bytes: self.max_inflight_bytes,
+                tls: self.tls.clone(),
+                tunnel: self.tunnel.clone(),
+                slow_log: self.slow_log,
+                max_concurrent_handlers: self.max_concurrent_handlers,
+                buffer_pool_size: self.buffer_pool_size,
+                max_concurrent_buffers: self.max_concurrent_buffers,
+                ip_cache_size: self.ip_cache_size,
+                ip_cache_ttl_secs: self.ip_cache_ttl_secs,
+                propagate_unreachable: self.propagate_unreachable,
+                log_limit: self.log_limit,
+                mirror: self.mirror,
+                max_connection_lifetime_secs: self.max_connection_lifetime_secs,
+                verify_destination_on_start: self.verify_destination_on_start,
+            },
+            ip_filter: if self.allow_list.is_some()
+                || self.deny_list.is_some()
+                || !self.allow_hosts.is_empty()
+                || !self.deny_hosts.is_empty()
+            {
+                Some(crate::config::IpFilterConfig {
+                    allow_list: self.allow_list.clone(),
+                    deny_list: self.deny_list.clone(),
+                    allow_hosts: self.allow_hosts.clone(),
+                    deny_hosts: self.deny_hosts.clone(),
+                })
+            } else {
+                None
+            },
+            profiles: BTreeMap::new(),
+            tags: BTreeMap::new(),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Request structure for updating an existing proxy instance.
+ *
+ * Contains optional fields for updating specific aspects of a proxy instance.
+ * Only provided fields will be updated. A `max_concurrent_buffers` or
+ * `ip_cache_size` of 0 returns the instance to the footprint's size. Any of
+ * `allow_list`, `deny_list`, `allow_hosts` and `deny_hosts` replaces the
+ * whole filter, with the ones left out emptied. A `mirror` with a
+ * `sample_percent` of 0 stops mirroring, and a `max_connection_lifetime_secs`
+ * of 0 removes the lifetime cap.
+ */
+pub struct UpdateInstanceRequest {
+    pub name: Option<String>,
+    pub listen_ip: Option<IpAddr>,
+    pub listen_port: Option<u16>,
+    pub dst_ip: Option<IpAddr>,
+    pub dst_port: Option<u16>,
+    pub protocol: Option<Protocol>,
+    pub auto_start: Option<bool>,
+    pub allow_list: Option<Vec<IpAddr>>,
+    pub deny_list: Option<Vec<IpAddr>>,
+    pub allow_hosts: Option<Vec<String>>,
+    pub deny_hosts: Option<Vec<String>>,
+    pub connect_timeout_secs: Option<u64>,
+    pub idle_timeout_secs: Option<u64>,
+    pub log_level: Option<LogLevel>,
+    pub bind_device: Option<String>,
+    pub bind_nonlocal: Option<bool>,
+    pub backends: Option<Vec<String>>,
+    pub wasm_filters: Option<Vec<String>>,
+    pub listen_backlog: Option<u32>,
+    pub bind_src_ip: Option<IpAddr>,
+    pub bind_src_device: Option<String>,
+    pub max_inflight_bytes: Option<u32>,
+    pub tls: Option<TlsConfig>,
+    pub tunnel: Option<TunnelConfig>,
+    pub slow_log: Option<SlowLogConfig>,
+    pub max_concurrent_handlers: Option<u32>,
+    pub buffer_pool_size: Option<u32>,
+    pub max_concurrent_buffers: Option<u32>,
+    pub ip_cache_size: Option<u32>,
+    pub ip_cache_ttl_secs: Option<u64>,
+    pub propagate_unreachable: Option<bool>,
+    pub log_limit: Option<LogLimitConfig>,
+    pub mirror: Option<MirrorConfig>,
+    pub max_connection_lifetime_secs: Option<u64>,
+    pub verify_destination_on_start: Option<DestinationCheck>,
+    pub profiles: Option<BTreeMap<String, ProfileOverride>>,
+    pub tags: Option<BTreeMap<String, String>>,
+    pub locked: Option<bool>,
+}
+impl UpdateInstanceRequest {
+    pub fn apply_to(&self, instance: &mut ProxyInstance) {
+        if let Some(name) = &self.name {
+            instance.name = name.clone();
+        }
+        if let Some(listen_ip) = self.listen_ip {
+            instance.config.proxy.listen_ip = listen_ip;
+        }
+        if let Some(listen_port) = self.listen_port {
+            instance.config.proxy.listen_port = listen_port;
+        }
+        if let Some(dst_ip) = self.dst_ip {
+            instance.config.proxy.dst_ip = dst_ip;
+        }
+        if let Some(dst_port) = self.dst_port {
+            instance.config.proxy.dst_port = dst_port;
+        }
+        if let Some(protocol) = self.protocol {
+            instance.config.proxy.protocol = protocol;
+        }
+        if let Some(auto_start) = self.auto_start {
+            instance.auto_start = auto_start;
+        }
+        if self.allow_list.is_some()
+            || self.deny_list.is_some()
+            || self.allow_hosts.is_some()
+            || self.deny_hosts.is_some()
+        {
+            instance.config.ip_filter = Some(crate::config::IpFilterConfig {
+                allow_list: self.allow_list.clone(),
+                deny_list: self.deny_list.clone(),
+                allow_hosts: self.allow_hosts.clone().unwrap_or_default(),
+                deny_hosts: self.deny_hosts.clone().unwrap_or_default(),
+            });
+        }
+        if let Some(connect_timeout_secs) = self.connect_timeout_secs {
+            instance.config.proxy.connect_timeout_secs = connect_timeout_secs;
+        }
+        if let Some(idle_timeout_secs) = self.idle_timeout_secs {
+            instance.config.proxy.idle_timeout_secs = idle_timeout_secs;
+        }
+        if let Some(log_level) = self.log_level {
+            instance.config.proxy.log_level = log_level;
+        }
+        if let Some(bind_device) = &self.bind_device {
+            instance.config.proxy.bind_device = if bind_device.is_empty() {
+                None
+            } else {
+                Some(bind_device.clone())
+            };
+        }
+        if let Some(bind_nonlocal) = self.bind_nonlocal {
+            instance.config.proxy.bind_nonlocal = bind_nonlocal;
+        }
+        if let Some(backends) = &self.backends {
+            instance.config.proxy.backends = backends.clone();
+        }
+        if let Some(wasm_filters) = &self.wasm_filters {
+            instance.config.proxy.wasm_filters = wasm_filters.clone();
+        }
+        if let Some(listen_backlog) = self.listen_backlog {
+            instance.config.proxy.listen_backlog = Some(listen_backlog);
+        }
+        if let Some(bind_src_ip) = self.bind_src_ip {
+            instance.config.proxy.bind_src_ip = Some(bind_src_ip);
+        }
+        if let Some(bind_src_device) = &self.bind_src_device {
+            instance.config.proxy.bind_src_device = if bind_src_device.is_empty() {
+                None
+            } else {
+                Some(bind_src_device.clone())
+            };
+        }
+        if let Some(max_inflight_bytes) = self.max_inflight_bytes {
+            instance.config.proxy.max_inflight_bytes = Some(max_inflight_bytes);
+        }
+        if let Some(tls) = &self.tls {
+            instance.config.proxy.tls = if tls.cert_path.is_empty() {
+                None
+            } else {
+                Some(tls.clone())
+            };
+        }
+        if let Some(tunnel) = &self.tunnel {
+            instance.config.proxy.tunnel = if tunnel.token.is_empty() {
+                None
+            } else {
+                Some(tunnel.clone())
+            };
+        }
+        if let Some(slow_log) = self.slow_log {
+            instance.config.proxy.slow_log = if slow_log.capacity == 0 {
+                None
+            } else {
+                Some(slow_log)
+            };
+        }
+        if let Some(max_concurrent_handlers) = self.max_concurrent_handlers {
+            instance.config.proxy.max_concurrent_handlers = Some(max_concurrent_handlers);
+        }
+        if let Some(buffer_pool_size) = self.buffer_pool_size {
+            instance.config.proxy.buffer_pool_size = Some(buffer_pool_size);
+        }
+        if let Some(max_concurrent_buffers) = self.max_concurrent_buffers {
+            instance.config.proxy.max_concurrent_buffers =
+                (max_concurrent_buffers > 0).then_some(max_concurrent_buffers);
+        }
+        if let Some(ip_cache_size) = self.ip_cache_size {
+            instance.config.proxy.ip_cache_size = (ip_cache_size > 0).then_some(ip_cache_size);
+        }
+        if let Some(ip_cache_ttl_secs) = self.ip_cache_ttl_secs {
+            instance.config.proxy.ip_cache_ttl_secs = Some(ip_cache_ttl_secs);
+        }
+        if let Some(propagate_unreachable) = self.propagate_unreachable {
+            instance.config.proxy.propagate_unreachable = propagate_unreachable;
+        }
+        if let Some(log_limit) = self.log_limit {
+            instance.config.proxy.log_limit = Some(log_limit);
+        }
+        if let Some(mirror) = self.mirror {
+            instance.config.proxy.mirror = (mirror.sample_percent > 0).then_some(mirror);
+        }
+        if let Some(max_connection_lifetime_secs) = self.max_connection_lifetime_secs {
+            instance.config.proxy.max_connection_lifetime_secs =
+                (max_connection_lifetime_secs > 0).then_some(max_connection_lifetime_secs);
+        }
+        if let Some(verify_destination_on_start) = self.verify_destination_on_start {
+            instance.config.proxy.verify_destination_on_start = verify_destination_on_start;
+        }
+        if let Some(profiles) = &self.profiles {
+            instance.config.profiles = profiles.clone();
+        }
+        if let Some(tags) = &self.tags {
+            instance.config.tags = tags.clone();
+        }
+        if let Some(locked) = self.locked {
+            instance.locked = locked;
+        }
+    }
+}
+pub type InstanceManager = Arc<RwLock<HashMap<Uuid, ProxyInstance>>>;
+#[derive(Clone)]
+/**
+ * Shared state a running proxy records into. Proxies look it up once when
+ * they start and keep it, so counting traffic never waits on the instance
+ * map the API write-locks for starts, stops and updates.
+ */
+pub struct InstanceState {
+    pub metrics: Arc<InstanceMetrics>,
+    pub last_error: ErrorSlot,
+}
@@ -0,0 +1,1113 @@
+use crate::benchmark::{BenchmarkOptions, BenchmarkReport};
+use crate::config::{DestinationCheck, InstanceDefaults};
+use crate::config_diff::{ConfigDiff, ImportPlan, diff_instances, plan_import};
+use crate::events::{Event, EventKind};
+use crate::footprint::Footprint;
+use crate::global_filter::GlobalFilterConfig;
+use crate::instance::{
+    CreateInstanceRequest, ErrorCategory, InstanceManager, InstanceStatus, InvalidTransition,
+    ProxyInstance, StatusTransition, UpdateInstanceRequest,
+};
+use crate::metrics::{BackendStats, ClosedConnection, MetricsManager, SlowLogEntry};
+use crate::middleware::{ConnectionMiddleware, MiddlewareChain};
+use crate::naming::{NameError, NamingPolicy};
+use crate::replay::{ConnectionRecorder, RecordOptions, Recording, ReplayOptions, ReplayReport};
+use crate::storage::StorageManager;
+use crate::tcp_proxy::TcpProxy;
+use crate::udp_proxy::UdpProxy;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+pub struct InstanceService {
+    instances: InstanceManager,
+    running_instances: Arc<RwLock<HashMap<Uuid, InstanceHandle>>>,
+    lifecycle: Arc<std::sync::Mutex<HashMap<Uuid, Arc<tokio::sync::Mutex<()>>>>>,
+    storage: Arc<StorageManager>,
+    metrics_manager: Arc<MetricsManager>,
+    events: tokio::sync::broadcast::Sender<Event>,
+    middleware: Arc<std::sync::RwLock<Vec<Arc<dyn ConnectionMiddleware>>>>,
+    profile: Option<String>,
+    duration_buckets: Vec<f64>,
+    recorder: Arc<ConnectionRecorder>,
+}
+struct InstanceHandle {
+    tcp_handle: Option<tokio::task::JoinHandle<()>>,
+    udp_handle: Option<tokio::task::JoinHandle<()>>,
+    tcp_proxy: Option<std::sync::Arc<crate::tcp_proxy::TcpProxy>>,
+    udp_proxy: Option<std::sync::Arc<crate::udp_proxy::UdpProxy>>,
+    cancel_token: Option<Arc<tokio_util::sync::CancellationToken>>,
+}
+pub type PerformanceMetrics = crate::metrics::SystemMetrics;
+impl InstanceService {
+    pub fn with_storage(storage: Arc<StorageManager>) -> Self {
+        Self {
+            instances: Arc::new(RwLock::new(HashMap::new())),
+            running_instances: Arc::new(RwLock::new(HashMap::new())),
+            lifecycle: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            storage,
+            metrics_manager: Arc::new(MetricsManager::new()),
+            events: tokio::sync::broadcast::channel(Footprint::current().event_queue()).0,
+            middleware: Arc::new(std::sync::RwLock::new(Vec::new())),
+            profile: None,
+            duration_buckets: crate::metrics::DEFAULT_DURATION_BUCKETS.to_vec(),
+            recorder: Arc::new(ConnectionRecorder::new()),
+        }
+    }
+    /**
+     * Selects the profile whose destination overrides are applied when
+     * instances start. Stored configurations are left untouched.
+     */
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+    /**
+     * Defaults for the fields create requests leave out, from the
+     * configuration file.
+     */
+    pub async fn instance_defaults(&self) -> InstanceDefaults {
+        self.storage.instance_defaults().await
+    }
+    /**
+     * Applies the global IP filter of the configuration file.
+     */
+    pub async fn apply_global_filter(&self) {
+        let global_filter = self.storage.global_filter().await;
+        if !global_filter.is_empty() {
+            info!(
+                "Global IP filter: {} allowed and {} denied networks",
+                global_filter.allow.len(),
+                global_filter.deny.len()
+            );
+        }
+        crate::global_filter::global().set(global_filter);
+    }
+    /**
+     * Replaces the global IP filter. It applies to new connections of all
+     * running instances right away.
+     */
+    pub async fn set_global_filter(&self, global_filter: GlobalFilterConfig) {
+        crate::global_filter::global().set(global_filter.clone());
+        info!(
+            "Updated global IP filter: {} allowed and {} denied networks",
+            global_filter.allow.len(),
+            global_filter.deny.len()
+        );
+        if let Err(e) = self.storage.set_global_filter(global_filter).await {
+            error!("Failed to save global IP filter to storage: {}", e);
+        }
+    }
+    /**
+     * Sets the bounds, in seconds, of the connection duration histograms.
+     */
+    pub fn with_duration_buckets(mut self, bounds: Vec<f64>) -> Result<Self> {
+        crate::metrics::validate_buckets(&bounds)?;
+        self.duration_buckets = bounds;
+        Ok(self)
+    }
+    /**
+     * Appends a middleware to the chain applied to every instance. Instances
+     * that are already running keep the chain they were started with.
+     */
+    pub fn register_middleware(&self, middleware: Arc<dyn ConnectionMiddleware>) {
+        info!("Registered connection middleware: {}", middleware.name());
+        self.middleware
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(middleware);
+    }
+    /**
+     * Middleware of an instance. The mirror, fed with `metrics`, comes last
+     * so it copies the data as forwarded; benchmarks run without it.
+     */
+    fn middleware_chain(
+        &self,
+        config: &crate::config::Config,
+        metrics: Option<&Arc<crate::metrics::InstanceMetrics>>,
+    ) -> Result<MiddlewareChain> {
+        let mut middleware: Vec<Arc<dyn ConnectionMiddleware>> = vec![self.recorder.clone()];
+        middleware.extend(self.middleware.read().unwrap_or_else(|e| e.into_inner()).iter().cloned());
+        middleware.extend(crate::wasm_filter::load_wasm_filters(&config.proxy.wasm_filters)?);
+        middleware.extend(
+            metrics.and_then(|metrics| crate::mirror::mirror_middleware(config, metrics)),
+        );
+        Ok(MiddlewareChain::new(middleware))
+    }
+    /**
+     * Subscribes to lifecycle events. Slow subscribers miss the oldest
+     * events instead of holding back the service.
+     */
+    pub fn subscribe_events(&self) -> tokio::sync::broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+    fn emit(&self, kind: EventKind) {
+        let _ = self.events.send(Event::new(kind));
+    }
+    /**
+     * Moves `instance` to status `to` and announces the change, refusing
+     * transitions the state machine does not allow.
+     */
+    fn transition(
+        &self,
+        instance: &mut ProxyInstance,
+        to: InstanceStatus,
+    ) -> Result<(), InvalidTransition> {
+        let transition = instance.transition(to)?;
+        self.emit(EventKind::InstanceStatusChanged {
+            instance_id: instance.id,
+            from: transition.from,
+            to: transition.to,
+        });
+        Ok(())
+    }
+    pub async fn naming_policy(&self) -> NamingPolicy {
+        self.storage.naming_policy().await
+    }
+    /**
+     * Checks a new name for instance `id` against the naming policy and the
+     * names of the other active instances.
+     */
+    fn check_name(
+        policy: &NamingPolicy,
+        instances: &HashMap<Uuid, ProxyInstance>,
+        id: Uuid,
+        name: &str,
+    ) -> Result<(), NameError> {
+        let others = instances
+            .values()
+            .filter(|instance| instance.id != id)
+            .map(|instance| instance.name.as_str());
+        policy.check(name, others)
+    }
+    pub async fn get_instance_by_name(&self, name: &str) -> Option<ProxyInstance> {
+        let instances = self.instances.read().await;
+        instances
+            .values()
+            .find(|instance| instance.name == name)
+            .cloned()
+    }
+    pub async fn create_instance(&self, request: CreateInstanceRequest) -> Result<ProxyInstance> {
+        let config = request.to_config();
+        config.validate()?;
+        let policy = self.naming_policy().await;
+        let instance = ProxyInstance::new(request.name, config, request.auto_start);
+        let mut instances = self.instances.write().await;
+        Self::check_name(&policy, &instances, instance.id, &instance.name)?;
+        instances.insert(instance.id, instance.clone());
+        drop(instances);
+        self.metrics_manager
+            .register_instance(instance.id, instance.metrics.clone())
+            .await;
+        if let Err(e) = self.storage.add_instance(&instance).await {
+            error!("Failed to save instance to storage: {}", e);
+        }
+        info!("Created proxy instance: {}", instance.name);
+        if request.auto_start {
+            self.start_instance(instance.id).await?;
+        }
+        Ok(instance)
+    }
+    pub async fn restore_instance(&self, mut instance: ProxyInstance) -> Result<()> {
+        instance.reset_status();
+        let mut instances = self.instances.write().await;
+        instances.insert(instance.id, instance.clone());
+        drop(instances);
+        self.metrics_manager
+            .register_instance(instance.id, instance.metrics.clone())
+            .await;
+        info!("Restored proxy instance: {}", instance.name);
+        Ok(())
+    }
+    pub async fn get_instances(&self) -> Vec<ProxyInstance> {
+        let instances = self.instances.read().await;
+        instances.values().cloned().collect()
+    }
+    pub async fn get_instance(&self, id: Uuid) -> Option<ProxyInstance> {
+        let instances = self.instances.read().await;
+        instances.get(&id).cloned()
+    }
+    /**
+     * Runs a loopback throughput test through the data path of an instance
+     * (see `benchmark::run`). Returns `None` when the instance does not
+     * exist; it does not need to be running.
+     */
+    pub async fn benchmark_instance(
+        &self,
+        id: Uuid,
+        options: BenchmarkOptions,
+    ) -> Result<Option<BenchmarkReport>> {
+        let Some(instance) = self.get_instance(id).await else {
+            return Ok(None);
+        };
+        let config = instance.config.for_profile(self.profile.as_deref());
+        let middleware = self.middleware_chain(&config, None)?;
+        crate::benchmark::run(&config, middleware, options).await.map(Some)
+    }
+    /**
+     * Records the next TCP connection accepted by an instance (see
+     * `replay::ConnectionRecorder`). Only instances started after the
+     * service was created can be recorded.
+     */
+    pub async fn record_connection(
+        &self,
+        id: Uuid,
+        options: RecordOptions,
+    ) -> Result<Option<Recording>> {
+        if self.get_instance(id).await.is_none() {
+            return Ok(None);
+        }
+        self.recorder.arm(id, options).map(Some)
+    }
+    pub fn recordings(&self) -> Vec<Recording> {
+        self.recorder.recordings()
+    }
+    pub fn recording(&self, id: Uuid) -> Option<Recording> {
+        self.recorder.recording(id)
+    }
+    /**
+     * Replays the client side of a recording against `target`, by default
+     * the upstream the recorded connection went to.
+     */
+    pub async fn replay_recording(
+        &self,
+        id: Uuid,
+        target: Option<std::net::SocketAddr>,
+        options: ReplayOptions,
+    ) -> Result<Option<ReplayReport>> {
+        let Some(recording) = self.recorder.recording(id) else {
+            return Ok(None);
+        };
+        let Some(target) = target.or(recording.upstream_addr) else {
+            anyhow::bail!("Recording {} has no upstream to replay against", id);
+        };
+        crate::replay::replay(&recording, target, options)
+            .await
+            .map(Some)
+    }
+    /**
+     * Recently closed connections and UDP sessions of an instance, newest
+     * first.
+     */
+    pub async fn connection_history(&self, id: Uuid) -> Option<Vec<ClosedConnection>> {
+        let instances = self.instances.read().await;
+        instances
+            .get(&id)
+            .map(|instance| instance.metrics.connection_history())
+    }
+    /**
+     * Latest status transitions of an instance, oldest first.
+     */
+    pub async fn status_history(&self, id: Uuid) -> Option<Vec<StatusTransition>> {
+        let instances = self.instances.read().await;
+        instances
+            .get(&id)
+            .map(|instance| instance.status_history.iter().copied().collect())
+    }
+    /**
+     * Traffic and health of each backend of a load-balanced instance, empty
+     * until it has been started with `backends`.
+     */
+    pub async fn backend_stats(&self, id: Uuid) -> Option<Vec<BackendStats>> {
+        let instances = self.instances.read().await;
+        instances
+            .get(&id)
+            .map(|instance| instance.metrics.backend_stats())
+    }
+    /**
+     * Connections of an instance that exceeded its slow-log thresholds,
+     * newest first.
+     */
+    pub async fn slow_log(&self, id: Uuid) -> Option<Vec<SlowLogEntry>> {
+        let instances = self.instances.read().await;
+        instances.get(&id).map(|instance| instance.metrics.slow_log())
+    }
+    pub async fn reset_slow_log(&self, id: Uuid) -> Option<usize> {
+        let instances = self.instances.read().await;
+        instances.get(&id).map(|instance| instance.metrics.reset_slow_log())
+    }
+    pub async fn update_instance(
+        &self,
+        id: Uuid,
+        request: UpdateInstanceRequest,
+    ) -> Result<Option<ProxyInstance>> {
+        let policy = self.naming_policy().await;
+        let mut instances = self.instances.write().await;
+        let Some(instance) = instances.get(&id) else {
+            return Ok(None);
+        };
+        let was_running = instance.status.is_up();
+        let mut updated = instance.clone();
+        request.apply_to(&mut updated);
+        updated.config.validate()?;
+        if updated.name != instance.name {
+            Self::check_name(&policy, &instances, id, &updated.name)?;
+        }
+        instances.insert(id, updated.clone());
+        drop(instances);
+        if let Err(e) = self.storage.update_instance(&updated).await {
+            error!("Failed to update instance in storage: {}", e);
+        }
+        if was_running {
+            warn!("Restarting instance {} due to configuration update", id);
+            self.stop_instance_internal(id).await?;
+            self.start_instance_internal(id).await?;
+        }
+        info!("Updated proxy instance: {}", updated.name);
+        Ok(self.get_instance(id).await)
+    }
+    /**
+     * Creates the instance named in the request, or brings the existing
+     * instance with that name in line with it.
+     *
+     * Applying the same request twice leaves the instance untouched, which
+     * lets declarative tooling address instances by name instead of UUID.
+     */
+    pub async fn upsert_instance(&self, request: CreateInstanceRequest) -> Result<UpsertResult> {
+        let Some(existing) = self.get_instance_by_name(&request.name).await else {
+            let instance = self.create_instance(request).await?;
+            return Ok(UpsertResult {
+                instance,
+                created: true,
+                changed: true,
+            });
+        };
+        let mut config = request.to_config();
+        config.profiles = existing.config.profiles.clone();
+        config.tags = existing.config.tags.clone();
+        if existing.config == config && existing.auto_start == request.auto_start {
+            return Ok(UpsertResult {
+                instance: existing,
+                created: false,
+                changed: false,
+            });
+        }
+        config.validate()?;
+        let id = existing.id;
+        let was_running = existing.status.is_up();
+        let config_changed = existing.config != config;
+        if was_running && config_changed {
+            self.stop_instance_internal(id).await?;
+        }
+        let updated = {
+            let mut instances = self.instances.write().await;
+            let instance = instances
+                .get_mut(&id)
+                .ok_or_else(|| anyhow::anyhow!("Instance {} disappeared during upsert", id))?;
+            instance.config = config;
+            instance.auto_start = request.auto_start;
+            instance.clone()
+        };
+        if let Err(e) = self.storage.update_instance(&updated).await {
+            error!("Failed to update instance in storage: {}", e);
+        }
+        if was_running && config_changed {
+            self.start_instance_internal(id).await?;
+        }
+        info!("Upserted proxy instance: {}", updated.name);
+        Ok(UpsertResult {
+            instance: self.get_instance(id).await.unwrap_or(updated),
+            created: false,
+            changed: true,
+        })
+    }
+    /**
+     * Stops an instance and moves it to the trash, from which it can be
+     * restored until it is purged.
+     */
+    pub async fn delete_instance(&self, id: Uuid) -> Result<bool> {
+        self.stop_instance_internal(id).await?;
+        let mut instances = self.instances.write().await;
+        let removed = instances.remove(&id);
+        drop(instances);
+        self.forget_lifecycle_lock(&id);
+        let Some(mut instance) = removed else {
+            return Ok(false);
+        };
+        self.metrics_manager.unregister_instance(&id).await;
+        instance.reset_status();
+        if let Err(e) = self.storage.trash_instance(&instance).await {
+            error!("Failed to move instance to the trash: {}", e);
+        }
+        info!("Deleted proxy instance: {}", id);
+        Ok(true)
+    }
+    pub async fn get_deleted_instances(&self) -> Result<Vec<ProxyInstance>> {
+        self.storage.trashed_instances().await
+    }
+    /**
+     * Brings a deleted instance back, stopped. Fails if an active instance
+     * took its name in the meantime.
+     */
+    pub async fn restore_deleted_instance(&self, id: Uuid) -> Result<Option<ProxyInstance>> {
+        let Some(trashed) = self
+            .storage
+            .trashed_instances()
+            .await?
+            .into_iter()
+            .find(|instance| instance.id == id)
+        else {
+            return Ok(None);
+        };
+        let mut instances = self.instances.write().await;
+        if instances.values().any(|instance| instance.name == trashed.name) {
+            return Err(NameError::Duplicate(trashed.name).into());
+        }
+        let Some(mut instance) = self.storage.take_from_trash(Some(id), None).await?.pop() else {
+            return Ok(None);
+        };
+        instance.deleted_at = None;
+        instances.insert(id, instance.clone());
+        drop(instances);
+        self.metrics_manager
+            .register_instance(id, instance.metrics.clone())
+            .await;
+        if let Err(e) = self.storage.add_instance(&instance).await {
+            error!("Failed to save restored instance to storage: {}", e);
+        }
+        info!("Restored deleted proxy instance: {}", instance.name);
+        Ok(Some(instance))
+    }
+    /**
+     * Permanently removes a deleted instance, or the whole trash when `id`
+     * is `None`. Returns the number of purged instances.
+     */
+    pub async fn purge_deleted_instances(&self, id: Option<Uuid>) -> Result<usize> {
+        let purged = self.storage.take_from_trash(id, None).await?;
+        for instance in &purged {
+            info!("Purged deleted proxy instance: {}", instance.name);
+        }
+        Ok(purged.len())
+    }
+    pub async fn purge_expired_instances(&self, retention: std::time::Duration) -> Result<usize> {
+        let cutoff = chrono::Utc::now() - chrono::Duration::from_std(retention)?;
+        let purged = self.storage.take_from_trash(None, Some(cutoff)).await?;
+        for instance in &purged {
+            info!("Purged expired deleted proxy instance: {}", instance.name);
+        }
+        Ok(purged.len())
+    }
+    pub async fn start_instance(&self, id: Uuid) -> Result<bool> {
+        self.start_instance_internal(id).await
+    }
+    /**
+     * Starts the instances concurrently and returns the result of each, in
+     * the order of `ids`.
+     */
+    pub async fn start_instances(&self, ids: &[Uuid]) -> Vec<Result<bool>> {
+        futures::future::join_all(ids.iter().map(|&id| self.start_instance_internal(id))).await
+    }
+    /**
+     * Lock held while one instance starts or stops. Starts and stops of
+     * different instances only share the maps for short updates, so they
+     * run concurrently.
+     */
+    fn lifecycle_lock(&self, id: Uuid) -> Arc<tokio::sync::Mutex<()>> {
+        self.lifecycle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(id)
+            .or_default()
+            .clone()
+    }
+    fn forget_lifecycle_lock(&self, id: &Uuid) {
+        self.lifecycle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(id);
+    }
+    async fn start_instance_internal(&self, id: Uuid) -> Result<bool> {
+        let lifecycle = self.lifecycle_lock(id);
+        let _lifecycle = lifecycle.lock().await;
+        self.check_destination(id).await?;
+        let (config, middleware, state) = {
+            let mut instances = self.instances.write().await;
+            let Some(instance) = instances.get_mut(&id) else {
+                return Ok(false);
+            };
+            if instance.status.is_up() {
+                return Ok(true);
+            }
+            let config = instance.config.for_profile(self.profile.as_deref());
+            if let Err(e) = config.validate() {
+                instance.last_error.set(ErrorCategory::Config, e.to_string());
+                return Err(e);
+            }
+            let middleware = match self.middleware_chain(&config, Some(&instance.metrics)) {
+                Ok(middleware) => middleware,
+                Err(e) => {
+                    instance.last_error.set(ErrorCategory::Filter, format!("{:#}", e));
+                    return Err(e);
+                }
+            };
+            instance.metrics.set_duration_buckets(&self.duration_buckets);
+            self.transition(instance, InstanceStatus::Starting)?;
+            (Arc::new(config), middleware, instance.state())
+        };
+        let cancel_token = Arc::new(tokio_util::sync::CancellationToken::new());
+        let (tcp_handle, tcp_proxy) = if matches!(
+            config.proxy.protocol,
+            crate::config::Protocol::Tcp | crate::config::Protocol::Both
+        ) {
+            let instances = self.instances.clone();
+            let tcp_proxy = std::sync::Arc::new(
+                TcpProxy::new(config.clone(), id, instances)
+                    .with_middleware(middleware.clone())
+                    .with_state(state.clone())
+                    .with_events(self.events.clone()),
+            );
+            let token_clone = cancel_token.clone();
+            let events = self.events.clone();
+            let last_error = state.last_error.clone();
+            let handle = Some(tokio::spawn({
+                let tcp_proxy_clone = tcp_proxy.clone();
+                async move {
+                    if let Err(e) = tcp_proxy_clone.run_with_token(token_clone).await {
+                        error!("TCP proxy error for instance {}: {}", id, e);
+                        last_error.set(ErrorCategory::Listener, format!("TCP proxy: {:#}", e));
+                        let _ = events.send(Event::new(EventKind::InstanceFailed {
+                            instance_id: id,
+                            error: format!("TCP proxy: {:#}", e),
+                        }));
+                    }
+                }
+            }));
+            (handle, Some(tcp_proxy))
+        } else {
+            (None, None)
+        };
+        let (udp_handle, udp_proxy) = if matches!(
+            config.proxy.protocol,
+            crate::config::Protocol::Udp | crate::config::Protocol::Both
+        ) {
+            let instances = self.instances.clone();
+            let udp_proxy = std::sync::Arc::new(
+                UdpProxy::new(config.clone(), id, instances)
+                    .with_middleware(middleware.clone())
+                    .with_state(state.clone()),
+            );
+            let token_clone = cancel_token.clone();
+            let events = self.events.clone();
+            let last_error = state.last_error.clone();
+            let handle = Some(tokio::spawn({
+                let udp_proxy_clone = udp_proxy.clone();
+                async move {
+                    if let Err(e) = udp_proxy_clone.run_with_token(token_clone).await {
+                        error!("UDP proxy error for instance {}: {}", id, e);
+                        last_error.set(ErrorCategory::Listener, format!("UDP proxy: {:#}", e));
+                        let _ = events.send(Event::new(EventKind::InstanceFailed {
+                            instance_id: id,
+                            error: format!("UDP proxy: {:#}", e),
+                        }));
+                    }
+                }
+            }));
+            (handle, Some(udp_proxy))
+        } else {
+            (None, None)
+        };
+        self.running_instances.write().await.insert(
+            id,
+            InstanceHandle {
+                tcp_handle,
+                udp_handle,
+                tcp_proxy,
+                udp_proxy,
+                cancel_token: Some(cancel_token.clone()),
+            },
+        );
+        let mut instances = self.instances.write().await;
+        let Some(instance) = instances.get_mut(&id) else {
+            return Ok(false);
+        };
+        self.transition(instance, InstanceStatus::Running)?;
+        info!("Started proxy instance: {}", instance.name);
+        self.emit(EventKind::InstanceStarted {
+            instance_id: id,
+            name: instance.name.clone(),
+        });
+        Ok(true)
+    }
+    /**
+     * Probes the destination of a stopped instance before it starts, as
+     * its `verify_destination_on_start` asks. Only `fail` turns an
+     * unreachable destination into an error.
+     */
+    async fn check_destination(&self, id: Uuid) -> Result<()> {
+        let (config, last_error) = {
+            let instances = self.instances.read().await;
+            let Some(instance) = instances.get(&id) else {
+                return Ok(());
+            };
+            let config = instance.config.for_profile(self.profile.as_deref());
+            if instance.status.is_up()
+                || config.proxy.verify_destination_on_start.is_off()
+                || config.validate().is_err()
+            {
+                return Ok(());
+            }
+            (config, instance.last_error.clone())
+        };
+        let Err(e) = crate::preflight::check_destination(&config).await else {
+            return Ok(());
+        };
+        let message = format!("Destination unreachable: {:#}", e);
+        last_error.set(ErrorCategory::Upstream, message.clone());
+        match config.proxy.verify_destination_on_start {
+            DestinationCheck::Fail => return Err(anyhow::anyhow!(message)),
+            DestinationCheck::Warn => warn!("Starting instance {} anyway: {}", id, message),
+            _ => debug!("Starting instance {} anyway: {}", id, message),
+        }
+        Ok(())
+    }
+    pub async fn stop_instance(&self, id: Uuid) -> Result<bool> {
+        self.stop_instance_internal(id).await
+    }
+    /**
+     * Stops the instances concurrently and returns the result of each, in
+     * the order of `ids`.
+     */
+    pub async fn stop_instances(&self, ids: &[Uuid]) -> Vec<Result<bool>> {
+        futures::future::join_all(ids.iter().map(|&id| self.stop_instance_internal(id))).await
+    }
+    async fn stop_instance_internal(&self, id: Uuid) -> Result<bool> {
+        let lifecycle = self.lifecycle_lock(id);
+        let _lifecycle = lifecycle.lock().await;
+        let name = {
+            let mut instances = self.instances.write().await;
+            let Some(instance) = instances.get_mut(&id) else {
+                return Ok(false);
+            };
+            if !instance.status.is_up() {
+                return Ok(true);
+            }
+            self.transition(instance, InstanceStatus::Stopping)?;
+            instance.name.clone()
+        };
+        let handle = self.running_instances.write().await.remove(&id);
+        if let Some(handle) = handle {
+            if let Some(cancel_token) = handle.cancel_token {
+                cancel_token.cancel();
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+            if let Some(tcp_handle) = handle.tcp_handle
+                && !tcp_handle.is_finished()
+            {
+                tcp_handle.abort();
+            }
+            if let Some(udp_handle) = handle.udp_handle
+                && !udp_handle.is_finished()
+            {
+                udp_handle.abort();
+            }
+        }
+        if let Some(instance) = self.instances.write().await.get_mut(&id) {
+            self.transition(instance, InstanceStatus::Stopped)?;
+        }
+        info!("Stopped proxy instance: {}", name);
+        self.emit(EventKind::InstanceStopped {
+            instance_id: id,
+            name,
+        });
+        Ok(true)
+    }
+    pub async fn start_auto_instances(&self) -> Result<()> {
+        let auto_start_instances: Vec<Uuid> = self
+            .instances
+            .read()
+            .await
+            .values()
+            .filter(|instance| instance.auto_start)
+            .map(|instance| instance.id)
+            .collect();
+        let results = self.start_instances(&auto_start_instances).await;
+        for (id, result) in auto_start_instances.iter().zip(results) {
+            if let Err(e) = result {
+                error!("Failed to start auto-start instance {}: {}", id, e);
+            }
+        }
+        Ok(())
+    }
+    pub async fn get_instance_stats(&self) -> HashMap<Uuid, InstanceStats> {
+        let instances = self.instances.read().await;
+        let running_instances = self.running_instances.read().await;
+        let mut stats = HashMap::new();
+        let mut started_times = HashMap::new();
+        let tcp_tables = crate::listener::read_tcp_tables();
+        for (id, instance) in instances.iter() {
+            let is_running = running_instances.contains_key(id);
+            started_times.insert(*id, instance.started_at);
+            let instance_metrics = instance.metrics.get_stats(instance.started_at).await;
+            if instance_metrics.bytes_sent > 0 || instance_metrics.bytes_received > 0 {
+                debug!(
+                    "Instance {} ({}) - Sent: {} bytes, Received: {} bytes",
+                    id, instance.name, instance_metrics.bytes_sent, instance_metrics.bytes_received
+                );
+            }
+            stats.insert(
+                *id,
+                InstanceStats {
+                    id: *id,
+                    name: instance.name.clone(),
+                    tags: instance.config.tags.clone(),
+                    status: instance.status,
+                    is_running,
+                    uptime: instance.started_at.map(|started| {
+                        started
+                            .signed_duration_since(chrono::Utc::now())
+                            .num_seconds()
+                            .abs()
+                    }),
+                    bytes_sent: instance_metrics.bytes_sent,
+                    bytes_received: instance_metrics.bytes_received,
+                    connections_active: instance_metrics.connections_active,
+                    connections_total: instance_metrics.connections_total,
+                    errors: instance_metrics.errors,
+                    bytes_sent_per_sec: instance_metrics.bytes_sent_per_sec,
+                    bytes_received_per_sec: instance_metrics.bytes_received_per_sec,
+                    error_rate: instance_metrics.error_rate,
+                    connections_stalled: instance_metrics.connections_stalled,
+                    stalls_total: instance_metrics.stalls_total,
+                    handler_waits: instance_metrics.handler_waits,
+                    accept_errors: instance_metrics.accept_errors,
+                    udp_unreachable: instance_metrics.udp_unreachable,
+                    certificate_expires_at: instance_metrics.certificate_expires_at,
+                    tunnel_bytes_uncompressed: instance_metrics.tunnel_bytes_uncompressed,
+                    tunnel_bytes_compressed: instance_metrics.tunnel_bytes_compressed,
+                    tunnel_compression_ratio: instance_metrics.tunnel_compression_ratio,
+                    mirrored_connections: instance_metrics.mirrored_connections,
+                    mirrored_bytes: instance_metrics.mirrored_bytes,
+                    mirror_dropped_bytes: instance_metrics.mirror_dropped_bytes,
+                    tcp_connection_duration: instance_metrics.tcp_connection_duration,
+                    udp_session_duration: instance_metrics.udp_session_duration,
+                    tcp_disconnects: instance_metrics.tcp_disconnects,
+                    udp_disconnects: instance_metrics.udp_disconnects,
+                    memory: instance_metrics.memory,
+                    tcp: instance_metrics.tcp,
+                    udp: instance_metrics.udp,
+                    accept_queue: tcp_tables.as_deref().filter(|_| is_running).and_then(|tables| {
+                        crate::listener::find_accept_queue(tables, &instance.config.proxy)
+                    }),
+                },
+            );
+        }
+        debug!(
+            "get_instance_stats returning stats for {} instances",
+            stats.len()
+        );
+        stats
+    }
+}
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InstanceStats {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub tags: std::collections::BTreeMap<String, String>,
+    pub status: crate::instance::InstanceStatus,
+    pub is_running: bool,
+    pub uptime: Option<i64>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub connections_active: u32,
+    pub connections_total: u32,
+    pub errors: u32,
+    pub bytes_sent_per_sec: f64,
+    pub bytes_received_per_sec: f64,
+    pub error_rate: f64,
+    pub connections_stalled: u32,
+    pub stalls_total: u64,
+    pub handler_waits: u64,
+    pub accept_errors: u64,
+    pub udp_unreachable: u64,
+    pub certificate_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub tunnel_bytes_uncompressed: u64,
+    pub tunnel_bytes_compressed: u64,
+    pub tunnel_compression_ratio: Option<f64>,
+    pub mirrored_connections: u64,
+    pub mirrored_bytes: u64,
+    pub mirror_dropped_bytes: u64,
+    pub tcp_connection_duration: crate::metrics::HistogramSnapshot,
+    pub udp_session_duration: crate::metrics::HistogramSnapshot,
+    pub tcp_disconnects: std::collections::BTreeMap<String, u64>,
+    pub udp_disconnects: std::collections::BTreeMap<String, u64>,
+    pub memory: crate::metrics::MemoryUsage,
+    pub tcp: crate::metrics::TransportStats,
+    pub udp: crate::metrics::TransportStats,
+    pub accept_queue: Option<crate::listener::AcceptQueue>,
+}
+#[derive(Debug, Clone, Default, serde::Serialize)]
+/**
+ * Summary of the changes applied by a configuration reload.
+ */
+pub struct ReloadReport {
+    pub added: usize,
+    pub removed: usize,
+    pub updated: usize,
+    pub restarted: usize,
+}
+#[derive(Debug, Clone, serde::Serialize)]
+/**
+ * Outcome of an idempotent create-or-update by instance name.
+ */
+pub struct UpsertResult {
+    pub instance: ProxyInstance,
+    pub created: bool,
+    pub changed: bool,
+}
+impl InstanceService {
+    pub async fn export_config(&self) -> Result<String> {
+        self.storage.export_config().await
+    }
+    /**
+     * Parses and validates a configuration as `import_config` would apply
+     * it, without touching the running state or the stored configuration.
+     */
+    pub async fn plan_import(&self, config_content: &str) -> Result<ImportPlan> {
+        let candidate = StorageManager::parse_instances(config_content)?;
+        let mut plan = plan_import(&self.get_instances().await, &candidate);
+        plan.unknown_fields = StorageManager::unknown_fields(config_content)?;
+        Ok(plan)
+    }
+    pub async fn import_config(&self, config_content: &str) -> Result<()> {
+        let current_ids: Vec<Uuid> = self
+            .get_instances()
+            .await
+            .iter()
+            .map(|instance| instance.id)
+            .collect();
+        for result in self.stop_instances(&current_ids).await {
+            result?;
+        }
+        let mut instances = self.instances.write().await;
+        for id in &current_ids {
+            instances.remove(id);
+            self.forget_lifecycle_lock(id);
+        }
+        drop(instances);
+        for id in &current_ids {
+            self.metrics_manager.unregister_instance(id).await;
+        }
+        self.storage.import_config(config_content).await?;
+        self.apply_global_filter().await;
+        match self.storage.load().await {
+            Ok(imported_instances) => {
+                let count = imported_instances.len();
+                for instance in imported_instances {
+                    let mut instances_map = self.instances.write().await;
+                    instances_map.insert(instance.id, instance.clone());
+                    drop(instances_map);
+                    self.metrics_manager
+                        .register_instance(instance.id, instance.metrics.clone())
+                        .await;
+                }
+                info!("Imported {} instances", count);
+                self.emit(EventKind::ConfigImported { instances: count });
+            }
+            Err(e) => {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+    pub async fn config_version(&self) -> String {
+        self.storage.updated_at().await
+    }
+    pub async fn apply_replicated_config(&self, config_content: &str) -> Result<ReloadReport> {
+        self.storage.import_config(config_content).await?;
+        self.reload_config().await
+    }
+    pub async fn reload_config(&self) -> Result<ReloadReport> {
+        let loaded_instances = self.storage.load().await?;
+        let current_instances = self.get_instances().await;
+        let mut report = ReloadReport::default();
+        let loaded_ids: std::collections::HashSet<Uuid> =
+            loaded_instances.iter().map(|instance| instance.id).collect();
+        let removed: Vec<&ProxyInstance> = current_instances
+            .iter()
+            .filter(|instance| !loaded_ids.contains(&instance.id))
+            .collect();
+        let removed_ids: Vec<Uuid> = removed.iter().map(|instance| instance.id).collect();
+        for result in self.stop_instances(&removed_ids).await {
+            result?;
+        }
+        for instance in removed {
+            self.instances.write().await.remove(&instance.id);
+            self.forget_lifecycle_lock(&instance.id);
+            self.metrics_manager.unregister_instance(&instance.id).await;
+            info!("Reload removed proxy instance: {}", instance.name);
+            report.removed += 1;
+        }
+        for mut loaded in loaded_instances {
+            let existing = current_instances.iter().find(|i| i.id == loaded.id);
+            match existing {
+                None => {
+                    let id = loaded.id;
+                    let auto_start = loaded.auto_start;
+                    loaded.reset_status();
+                    info!("Reload added proxy instance: {}", loaded.name);
+                    let metrics = loaded.metrics.clone();
+                    self.instances.write().await.insert(id, loaded);
+                    self.metrics_manager.register_instance(id, metrics).await;
+                    if auto_start {
+                        self.start_instance_internal(id).await?;
+                    }
+                    report.added += 1;
+                }
+                Some(current) => {
+                    if current.name == loaded.name
+                        && current.config == loaded.config
+                        && current.auto_start == loaded.auto_start
+                        && current.locked == loaded.locked
+                    {
+                        continue;
+                    }
+                    loaded.config.validate()?;
+                    let id = loaded.id;
+                    let was_running = current.status.is_up();
+                    let config_changed = current.config != loaded.config;
+                    if was_running && config_changed {
+                        self.stop_instance_internal(id).await?;
+                    }
+                    {
+                        let mut instances = self.instances.write().await;
+                        if let Some(instance) = instances.get_mut(&id) {
+                            instance.name = loaded.name.clone();
+                            instance.config = loaded.config;
+                            instance.auto_start = loaded.auto_start;
+                            instance.locked = loaded.locked;
+                        }
+                    }
+                    if was_running && config_changed {
+                        self.start_instance_internal(id).await?;
+                        report.restarted += 1;
+                    }
+                    info!("Reload updated proxy instance: {}", loaded.name);
+                    report.updated += 1;
+                }
+            }
+        }
+        info!(
+            "Configuration reloaded: {} added, {} removed, {} updated ({} restarted)",
+            report.added, report.removed, report.updated, report.restarted
+        );
+        Ok(report)
+    }
+    pub async fn wait_for_drain(&self, timeout: std::time::Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let active: u32 = {
+                let instances = self.instances.read().await;
+                instances
+                    .values()
+                    .map(|instance| {
+                        instance
+                            .metrics
+                            .connections_active
+                            .load(std::sync::atomic::Ordering::Relaxed)
+                    })
+                    .sum()
+            };
+            if active == 0 {
+                info!("All connections drained");
+                return true;
+            }
+            if tokio::time::Instant::now() >= deadline {
+                warn!("Drain timeout reached with {} connections still active", active);
+                return false;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        }
+    }
+    pub async fn create_backup(&self) -> Result<std::path::PathBuf> {
+        self.storage.create_backup().await
+    }
+    pub async fn prune_backups(&self, keep: usize) -> Result<Vec<std::path::PathBuf>> {
+        self.storage.prune_backups(keep).await
+    }
+    pub async fn backup_schedule(&self) -> crate::backup::BackupSchedule {
+        self.storage.backup_schedule().await
+    }
+    /**
+     * Replaces the backup schedule. The scheduler picks it up at its next
+     * check.
+     */
+    pub async fn set_backup_schedule(&self, schedule: crate::backup::BackupSchedule) -> Result<()> {
+        schedule.validate()?;
+        info!(
+            "Updated backup schedule: {:?} at {}",
+            schedule.frequency, schedule.at
+        );
+        self.storage.set_backup_schedule(schedule).await
+    }
+    /**
+     * Compares the running instances with the configuration file
+     * (`backup_id` = `None`) or a backup. Returns `None` when the file or
+     * backup does not exist.
+     */
+    pub async fn diff_config(&self, backup_id: Option<&str>) -> Result<Option<ConfigDiff>> {
+        let Some(content) = self.storage.read_stored_config(backup_id).await? else {
+            return Ok(None);
+        };
+        let candidate = StorageManager::parse_instances(&content)?;
+        Ok(Some(diff_instances(&self.get_instances().await, &candidate)))
+    }
+    /**
+     * Adds the persisted lifetime counters to the loaded instances. Returns
+     * how many instances had stored counters.
+     */
+    pub async fn restore_stats(&self) -> Result<usize> {
+        let stats = self.storage.load_stats().await?;
+        let instances = self.instances.read().await;
+        let mut restored = 0;
+        for (id, snapshot) in &stats {
+            if let Some(instance) = instances.get(id) {
+                instance.metrics.restore(snapshot);
+                restored += 1;
+            }
+        }
+        Ok(restored)
+    }
+    pub async fn persist_stats(&self) -> Result<()> {
+        let stats = self
+            .instances
+            .read()
+            .await
+            .values()
+            .map(|instance| (instance.id, instance.metrics.snapshot()))
+            .collect();
+        self.storage.save_stats(stats).await
+    }
+    pub async fn get_performance_metrics(&self) -> PerformanceMetrics {
+        self.metrics_manager.get_system_metrics().await
+    }
+    /**
+     * Session metrics of a running instance. A `both` instance adds its TCP
+     * connections to its UDP sessions and reports the UDP timeouts.
+     */
+    pub async fn get_instance_session_metrics(
+        &self,
+        instance_id: &Uuid,
+    ) -> Option<crate::metrics::SessionMetrics> {
+        let running_instances = self.running_instances.read().await;
+        let handle = running_instances.get(instance_id)?;
+        let tcp = handle.tcp_proxy.as_ref().map(|proxy| proxy.get_session_metrics());
+        let udp = match handle.udp_proxy {
+            Some(ref udp_proxy) => Some(udp_proxy.get_session_metrics().await),
+            None => None,
+        };
+        match (tcp, udp) {
+            (Some(tcp), Some(udp)) => Some(crate::metrics::SessionMetrics {
+                active_sessions: tcp.active_sessions + udp.active_sessions,
+                longest_idle_seconds: tcp.longest_idle_seconds.max(udp.longest_idle_seconds),
+                ..udp
+            }),
+            (tcp, udp) => tcp.or(udp),
+        }
+    }
+}
@@ -22,6 +22,21 @@ impl IpCache {
             ttl,
         }
     }
+    /**
+     * Cache sized by the instance's `ip_cache_size` and `ip_cache_ttl_secs`,
+     * falling back to the footprint and the idle timeout.
+     */
+    pub fn for_proxy(proxy: &crate::config::ProxyConfig) -> Self {
+        let capacity = proxy.ip_cache_size.map_or_else(
+            || crate::footprint::Footprint::current().ip_cache_entries(),
+            |size| size as usize,
+        );
+        let ttl = proxy.ip_cache_ttl_secs.unwrap_or(proxy.idle_timeout_secs);
+        Self::new(capacity, Duration::from_secs(ttl))
+    }
+    pub async fn clear(&self) {
+        self.cache.write().await.clear();
+    }
     pub async fn check_ip(&self, ip: &IpAddr, checker: impl Fn(&IpAddr) -> bool) -> bool {
         let mut cache = self.cache.write().await;
         if let Some(entry) = cache.get(ip) {
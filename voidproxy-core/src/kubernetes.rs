@@ -0,0 +1,177 @@
+use crate::backend::{Backend, BackendProvider, Resolution, ResolveFuture};
+use anyhow::{Context, Result};
+use hyper::{Method, header};
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::time::Duration;
+const SERVICE_ACCOUNT_DIR: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+#[derive(Debug, Clone, PartialEq, Eq)]
+/**
+ * Port of the Kubernetes Service to forward to, by name or by number.
+ */
+pub enum ServicePort {
+    Any,
+    Named(String),
+    Number(u16),
+}
+/**
+ * Backend provider resolving a Kubernetes Service through its EndpointSlices.
+ *
+ * Configured as `k8s://<namespace>/<service>[:<port>]` and authenticated with
+ * the in-cluster service account, so VoidProxy can act as an external L4
+ * entry point into a cluster. Endpoints that are not ready are kept but
+ * marked unhealthy.
+ */
+pub struct KubernetesProvider {
+    namespace: String,
+    service: String,
+    port: ServicePort,
+    api_server: String,
+    credentials_dir: PathBuf,
+}
+#[derive(Debug, Deserialize)]
+struct EndpointSliceList {
+    #[serde(default)]
+    items: Vec<EndpointSlice>,
+}
+#[derive(Debug, Deserialize)]
+struct EndpointSlice {
+    #[serde(default)]
+    endpoints: Vec<Endpoint>,
+    #[serde(default)]
+    ports: Vec<EndpointPort>,
+}
+#[derive(Debug, Deserialize)]
+struct Endpoint {
+    #[serde(default)]
+    addresses: Vec<String>,
+    #[serde(default)]
+    conditions: Option<EndpointConditions>,
+}
+#[derive(Debug, Deserialize)]
+struct EndpointConditions {
+    ready: Option<bool>,
+}
+#[derive(Debug, Deserialize)]
+struct EndpointPort {
+    name: Option<String>,
+    port: Option<u16>,
+}
+impl KubernetesProvider {
+    pub fn from_target(target: &str) -> Result<Self> {
+        let (namespace, service) = target
+            .split_once('/')
+            .context("Kubernetes backend must be k8s://<namespace>/<service>[:<port>]")?;
+        let (service, port) = match service.split_once(':') {
+            Some((service, port)) => (
+                service,
+                port.parse()
+                    .map(ServicePort::Number)
+                    .unwrap_or_else(|_| ServicePort::Named(port.to_string())),
+            ),
+            None => (service, ServicePort::Any),
+        };
+        if namespace.is_empty() || service.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Kubernetes backend needs both a namespace and a service name"
+            ));
+        }
+        let host = std::env::var("KUBERNETES_SERVICE_HOST")
+            .unwrap_or_else(|_| "kubernetes.default.svc".to_string());
+        let port_number =
+            std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+        let host = match host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+            _ => host,
+        };
+        Ok(Self {
+            namespace: namespace.to_string(),
+            service: service.to_string(),
+            port,
+            api_server: format!("https://{}:{}", host, port_number),
+            credentials_dir: PathBuf::from(SERVICE_ACCOUNT_DIR),
+        })
+    }
+    fn slice_port(&self, slice: &EndpointSlice) -> Option<u16> {
+        slice
+            .ports
+            .iter()
+            .find(|port| match &self.port {
+                ServicePort::Any => true,
+                ServicePort::Named(name) => port.name.as_deref() == Some(name.as_str()),
+                ServicePort::Number(number) => port.port == Some(*number),
+            })
+            .and_then(|port| port.port)
+    }
+    async fn fetch(&self) -> Result<Resolution> {
+        let token = tokio::fs::read_to_string(self.credentials_dir.join("token"))
+            .await
+            .context("Failed to read service account token")?;
+        let ca_path = self.credentials_dir.join("ca.crt");
+        let tls_config = if ca_path.exists() {
+            Some(crate::http_client::tls_config_with_ca(&ca_path)?)
+        } else {
+            None
+        };
+        let url = format!(
+            "{}/apis/discovery.k8s.io/v1/namespaces/{}/endpointslices?labelSelector=kubernetes.io%2Fservice-name%3D{}",
+            self.api_server, self.namespace, self.service
+        );
+        let response = crate::http_client::send_with_tls(
+            Method::GET,
+            &url,
+            &[(
+                header::AUTHORIZATION.as_str(),
+                format!("Bearer {}", token.trim()),
+            )],
+            None,
+            tls_config,
+        )
+        .await?;
+        if !response.is_success() {
+            return Err(anyhow::anyhow!(
+                "Kubernetes API responded with status {}",
+                response.status
+            ));
+        }
+        let slices: EndpointSliceList = response.json()?;
+        Ok(Resolution {
+            backends: self.backends_from(&slices),
+            ttl: Some(REFRESH_INTERVAL),
+        })
+    }
+    fn backends_from(&self, slices: &EndpointSliceList) -> Vec<Backend> {
+        let mut backends = Vec::new();
+        for slice in &slices.items {
+            let Some(port) = self.slice_port(slice) else {
+                continue;
+            };
+            for endpoint in &slice.endpoints {
+                let healthy = endpoint
+                    .conditions
+                    .as_ref()
+                    .and_then(|conditions| conditions.ready)
+                    .unwrap_or(true);
+                for address in &endpoint.addresses {
+                    if let Ok(ip) = address.parse::<IpAddr>() {
+                        backends.push(Backend {
+                            healthy,
+                            ..Backend::new(SocketAddr::new(ip, port))
+                        });
+                    }
+                }
+            }
+        }
+        backends
+    }
+}
+impl BackendProvider for KubernetesProvider {
+    fn resolve(&self) -> ResolveFuture<'_> {
+        Box::pin(self.fetch())
+    }
+    fn describe(&self) -> String {
+        format!("k8s://{}/{}", self.namespace, self.service)
+    }
+}
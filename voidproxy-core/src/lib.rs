@@ -0,0 +1,52 @@
+/*!
+ * Forwarding engine of VoidProxy.
+ *
+ * Holds the TCP/UDP proxies, instance lifecycle management, configuration
+ * and persistence, without the web stack of the `void_proxy` daemon. Use
+ * `builder::ProxyBuilder` to run a single forwarder from another program, or
+ * `instance_manager::InstanceService` to manage many of them.
+ */
+pub mod backend;
+pub mod benchmark;
+pub mod backpressure;
+pub mod backup;
+pub mod blocklist;
+pub mod buffer_pool;
+pub mod builder;
+pub mod config;
+pub mod config_diff;
+pub mod connection_registry;
+pub mod consul;
+pub mod events;
+pub mod fd_limit;
+pub mod footprint;
+pub mod geoip;
+pub mod global_filter;
+pub mod handoff;
+pub mod happy_eyeballs;
+pub mod host_acl;
+pub mod hostname;
+pub mod http_client;
+pub mod icmp;
+pub mod instance;
+pub mod instance_manager;
+pub mod ip_cache;
+pub mod kubernetes;
+pub mod listener;
+pub mod log_limit;
+pub mod metrics;
+pub mod middleware;
+pub mod mirror;
+pub mod naming;
+pub mod preflight;
+pub mod replay;
+pub mod srv;
+pub mod storage;
+pub mod tcp_proxy;
+pub mod test_server;
+pub mod tls;
+pub mod traffic_report;
+pub mod tunnel;
+pub mod udp_batch;
+pub mod udp_proxy;
+pub mod wasm_filter;
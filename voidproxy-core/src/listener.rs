@@ -0,0 +1,223 @@
+use crate::config::ProxyConfig;
+use anyhow::{Context, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use serde::Serialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+const LISTEN_BACKLOG: u32 = 1024;
+const TCP_LISTEN_STATE: &str = "0A";
+/**
+ * Binds the TCP listener of a proxy instance.
+ *
+ * Applies the per-instance bind options (interface binding and non-local
+ * binding for floating IPs) before the address is bound, and listens with
+ * the instance's `listen_backlog`.
+ */
+pub fn bind_tcp_listener(addr: SocketAddr, proxy: &ProxyConfig) -> Result<tokio::net::TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))
+        .context("Failed to create TCP socket")?;
+    socket.set_reuse_address(true)?;
+    apply_bind_options(&socket, addr, proxy)?;
+    socket
+        .bind(&addr.into())
+        .with_context(|| format!("Failed to bind TCP listener on {}", addr))?;
+    socket.listen(proxy.listen_backlog.unwrap_or(LISTEN_BACKLOG) as i32)?;
+    socket.set_nonblocking(true)?;
+    Ok(tokio::net::TcpListener::from_std(socket.into())?)
+}
+/**
+ * Binds the UDP socket of a proxy instance with the same bind options as TCP.
+ */
+pub fn bind_udp_socket(addr: SocketAddr, proxy: &ProxyConfig) -> Result<tokio::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))
+        .context("Failed to create UDP socket")?;
+    apply_bind_options(&socket, addr, proxy)?;
+    socket
+        .bind(&addr.into())
+        .with_context(|| format!("Failed to bind UDP socket on {}", addr))?;
+    socket.set_nonblocking(true)?;
+    Ok(tokio::net::UdpSocket::from_std(socket.into())?)
+}
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn apply_bind_options(socket: &Socket, addr: SocketAddr, proxy: &ProxyConfig) -> Result<()> {
+    if proxy.bind_nonlocal {
+        match addr {
+            SocketAddr::V4(_) => socket.set_freebind_v4(true),
+            SocketAddr::V6(_) => socket.set_freebind_v6(true),
+        }
+        .context("Failed to enable non-local bind (IP_FREEBIND)")?;
+    }
+    if let Some(ref device) = proxy.bind_device {
+        socket
+            .bind_device(Some(device.as_bytes()))
+            .with_context(|| format!("Failed to bind socket to device {}", device))?;
+    }
+    Ok(())
+}
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn apply_bind_options(_socket: &Socket, _addr: SocketAddr, proxy: &ProxyConfig) -> Result<()> {
+    if proxy.bind_nonlocal || proxy.bind_device.is_some() {
+        return Err(anyhow::anyhow!(
+            "bind_device and bind_nonlocal are only supported on Linux"
+        ));
+    }
+    Ok(())
+}
+/**
+ * Connects to an upstream over TCP, leaving from the instance's
+ * `bind_src_ip` and `bind_src_device` when they are set.
+ */
+pub async fn connect_upstream(
+    addr: SocketAddr,
+    proxy: &ProxyConfig,
+) -> std::io::Result<tokio::net::TcpStream> {
+    if proxy.bind_src_ip.is_none() && proxy.bind_src_device.is_none() {
+        return tokio::net::TcpStream::connect(addr).await;
+    }
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    apply_source_options(&socket, proxy)?;
+    if let Some(src_ip) = proxy.bind_src_ip {
+        socket.bind(&source_addr(src_ip, addr)?.into())?;
+    }
+    socket.set_nonblocking(true)?;
+    tokio::net::TcpSocket::from_std_stream(socket.into())
+        .connect(addr)
+        .await
+}
+/**
+ * Binds the socket a UDP session uses to reach `dst`, with the same source
+ * options as TCP upstream connections. The socket is connected to `dst` so
+ * that ICMP errors of the destination surface on its next receive.
+ */
+pub fn bind_upstream_udp_socket(
+    dst: SocketAddr,
+    proxy: &ProxyConfig,
+) -> std::io::Result<tokio::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(dst), Type::DGRAM, Some(Protocol::UDP))?;
+    apply_source_options(&socket, proxy)?;
+    let src_addr = match proxy.bind_src_ip {
+        Some(src_ip) => source_addr(src_ip, dst)?,
+        None if dst.is_ipv4() => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        None => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+    socket.bind(&src_addr.into())?;
+    socket.connect(&dst.into())?;
+    socket.set_nonblocking(true)?;
+    tokio::net::UdpSocket::from_std(socket.into())
+}
+fn source_addr(src_ip: IpAddr, dst: SocketAddr) -> std::io::Result<SocketAddr> {
+    if src_ip.is_ipv4() != dst.is_ipv4() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Source address {} cannot reach {}", src_ip, dst),
+        ));
+    }
+    Ok(SocketAddr::new(src_ip, 0))
+}
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn apply_source_options(socket: &Socket, proxy: &ProxyConfig) -> std::io::Result<()> {
+    if let Some(ref device) = proxy.bind_src_device {
+        socket.bind_device(Some(device.as_bytes())).map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("Failed to bind socket to device {}: {}", device, e),
+            )
+        })?;
+    }
+    Ok(())
+}
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn apply_source_options(_socket: &Socket, proxy: &ProxyConfig) -> std::io::Result<()> {
+    if proxy.bind_src_device.is_some() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "bind_src_device is only supported on Linux",
+        ));
+    }
+    Ok(())
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/**
+ * Accept queue of a listening TCP socket: connections waiting to be
+ * accepted and the effective backlog.
+ */
+pub struct AcceptQueue {
+    pub queued: u32,
+    pub backlog: u32,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+/**
+ * System-wide counters of connections dropped because an accept queue was
+ * full (`ListenOverflows`) or for any listen-related reason (`ListenDrops`).
+ */
+pub struct ListenOverflows {
+    pub overflows: u64,
+    pub drops: u64,
+}
+fn parse_proc_addr(value: &str) -> Option<SocketAddr> {
+    let (ip, port) = value.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for word in (0..ip.len()).step_by(8) {
+        let word = u32::from_str_radix(ip.get(word..word + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match bytes.len() {
+        4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+/**
+ * Reads the TCP socket tables (`/proc/net/tcp` and `/proc/net/tcp6`), or
+ * `None` where they are not available.
+ */
+pub fn read_tcp_tables() -> Option<String> {
+    let tables: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .collect();
+    (!tables.is_empty()).then(|| tables.concat())
+}
+/**
+ * Finds the TCP listener of a proxy instance in TCP socket tables. For
+ * listening sockets the kernel reports the accept queue length as
+ * `rx_queue`; the backlog is not exposed there, so the configured one is
+ * reported.
+ */
+pub fn find_accept_queue(tables: &str, proxy: &ProxyConfig) -> Option<AcceptQueue> {
+    let addr = SocketAddr::new(proxy.listen_ip, proxy.listen_port);
+    tables.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.get(3) != Some(&TCP_LISTEN_STATE) || parse_proc_addr(fields.get(1)?)? != addr {
+            return None;
+        }
+        let (_, queued) = fields.get(4)?.split_once(':')?;
+        Some(AcceptQueue {
+            queued: u32::from_str_radix(queued, 16).ok()?,
+            backlog: proxy.listen_backlog.unwrap_or(LISTEN_BACKLOG),
+        })
+    })
+}
+/**
+ * Reads the `ListenOverflows` and `ListenDrops` counters from
+ * `/proc/net/netstat`, where available.
+ */
+pub fn listen_overflows() -> Option<ListenOverflows> {
+    let netstat = std::fs::read_to_string("/proc/net/netstat").ok()?;
+    let mut lines = netstat.lines();
+    while let (Some(header), Some(values)) = (lines.next(), lines.next()) {
+        if !header.starts_with("TcpExt:") {
+            continue;
+        }
+        let counter = |name: &str| {
+            let index = header.split_whitespace().position(|field| field == name)?;
+            values.split_whitespace().nth(index)?.parse().ok()
+        };
+        return Some(ListenOverflows {
+            overflows: counter("ListenOverflows")?,
+            drops: counter("ListenDrops")?,
+        });
+    }
+    None
+}
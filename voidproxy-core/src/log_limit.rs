@@ -0,0 +1,121 @@
+use crate::config::LogLimitConfig;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Instant;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/**
+ * Kind of hot-path warning or error, rate limited independently of the
+ * others.
+ */
+pub enum LogClass {
+    /** Client rejected by the global or the instance IP filter. */
+    Rejected,
+    /** No healthy backend to forward a connection or datagram to. */
+    NoBackend,
+    /** Accepting or receiving on the listener failed. */
+    Listener,
+    /** A TLS handshake failed or timed out. */
+    Tls,
+    /** Handling a connection or datagram failed. */
+    Handler,
+    /** Reading from or writing to a peer failed mid-stream. */
+    Relay,
+}
+impl LogClass {
+    const COUNT: usize = 6;
+    fn index(self) -> usize {
+        match self {
+            LogClass::Rejected => 0,
+            LogClass::NoBackend => 1,
+            LogClass::Listener => 2,
+            LogClass::Tls => 3,
+            LogClass::Handler => 4,
+            LogClass::Relay => 5,
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/**
+ * Messages of a class suppressed since the previous one was logged. Renders
+ * as ` (N similar messages suppressed)` to append to the logged message, or
+ * as nothing when none were.
+ */
+pub struct Suppressed(pub u64);
+impl fmt::Display for Suppressed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            0 => Ok(()),
+            1 => f.write_str(" (1 similar message suppressed)"),
+            count => write!(f, " ({} similar messages suppressed)", count),
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, Default)]
+struct ClassState {
+    window: u64,
+    logged: u32,
+    over_limit: u64,
+    suppressed: u64,
+}
+#[derive(Debug)]
+/**
+ * Per-class log rate limiter of an instance.
+ *
+ * Each class logs at most `per_sec` messages per second; beyond that only
+ * every `sample_every`-th message is logged, if sampling is enabled. The
+ * next message logged for a class carries the count of those suppressed
+ * before it.
+ */
+pub struct LogLimiter {
+    config: LogLimitConfig,
+    started: Instant,
+    classes: Mutex<[ClassState; LogClass::COUNT]>,
+}
+impl LogLimiter {
+    pub fn new(config: LogLimitConfig) -> Self {
+        Self {
+            config,
+            started: Instant::now(),
+            classes: Mutex::new([ClassState::default(); LogClass::COUNT]),
+        }
+    }
+    pub fn for_proxy(proxy: &crate::config::ProxyConfig) -> Self {
+        Self::new(proxy.log_limit.unwrap_or_default())
+    }
+    /**
+     * Whether a message of `class` should be logged now, with the count of
+     * messages suppressed since the last one that was.
+     */
+    pub fn admit(&self, class: LogClass) -> Option<Suppressed> {
+        if self.config.per_sec == 0 {
+            return Some(Suppressed(0));
+        }
+        let window = self.started.elapsed().as_secs();
+        let mut classes = self.classes.lock().unwrap_or_else(|e| e.into_inner());
+        let state = &mut classes[class.index()];
+        if state.window != window {
+            state.window = window;
+            state.logged = 0;
+            state.over_limit = 0;
+        }
+        if state.logged < self.config.per_sec {
+            state.logged += 1;
+        } else {
+            state.over_limit += 1;
+            let sampled = self.config.sample_every > 0
+                && state
+                    .over_limit
+                    .is_multiple_of(u64::from(self.config.sample_every));
+            if !sampled {
+                state.suppressed += 1;
+                return None;
+            }
+        }
+        Some(Suppressed(std::mem::take(&mut state.suppressed)))
+    }
+}
+impl Default for LogLimiter {
+    fn default() -> Self {
+        Self::new(LogLimitConfig::default())
+    }
+}
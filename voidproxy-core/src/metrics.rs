@@ -0,0 +1,999 @@
+use crate::middleware::{CloseReason, ConnectionContext, ConnectionId, Transport};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+/**
+ * Default upper bounds, in seconds, of the connection duration histograms.
+ */
+pub const DEFAULT_DURATION_BUCKETS: [f64; 10] =
+    [0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0];
+/**
+ * Number of recently closed connections kept per instance.
+ */
+pub const CONNECTION_HISTORY_CAPACITY: usize = 256;
+/**
+ * Tracing target of the access log line emitted for every closed connection
+ * (or session). No subscriber enables it unless access logging is configured.
+ */
+pub const ACCESS_LOG_TARGET: &str = "voidproxy::access";
+/**
+ * Estimated bytes held by an open TCP connection besides its pooled buffers:
+ * relay task state, socket halves and the middleware context.
+ */
+pub const TCP_CONNECTION_BYTES: u64 = 4096;
+/**
+ * Estimated bytes held by a UDP session: its 64 KiB response buffer, the
+ * session table entry and the response task.
+ */
+pub const UDP_SESSION_BYTES: u64 = 65535 + 1024;
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+/**
+ * Approximate memory attributable to an instance, in bytes. Buffers count
+ * allocated capacity (pooled TCP buffers and UDP receive batches), sessions
+ * and connections a fixed estimate per entry; allocator overhead is ignored.
+ */
+pub struct MemoryUsage {
+    pub buffers: u64,
+    pub udp_sessions: u64,
+    pub connections: u64,
+    pub total: u64,
+}
+#[derive(Debug, Clone, Default)]
+/**
+ * Traffic counters of one transport of an instance. For UDP a connection is
+ * a client session.
+ */
+pub struct TransportMetrics {
+    pub bytes_sent: Arc<AtomicU64>,
+    pub bytes_received: Arc<AtomicU64>,
+    pub connections_active: Arc<AtomicU32>,
+    pub connections_total: Arc<AtomicU32>,
+    pub errors: Arc<AtomicU32>,
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+/**
+ * Point-in-time values of a `TransportMetrics`.
+ */
+pub struct TransportStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub connections_active: u32,
+    pub connections_total: u32,
+    pub errors: u32,
+}
+impl TransportMetrics {
+    pub fn add_sent(&self, bytes: u64) {
+        saturating_add(&self.bytes_sent, bytes);
+    }
+    pub fn add_received(&self, bytes: u64) {
+        saturating_add(&self.bytes_received, bytes);
+    }
+    pub fn opened(&self) {
+        self.connections_active.fetch_add(1, Ordering::Relaxed);
+        self.connections_total.fetch_add(1, Ordering::Relaxed);
+    }
+    /**
+     * Counts a connection as closed, and as an error when `failed`.
+     */
+    pub fn closed(&self, failed: bool) {
+        self.connections_active.fetch_sub(1, Ordering::Relaxed);
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    pub fn stats(&self) -> TransportStats {
+        TransportStats {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            connections_active: self.connections_active.load(Ordering::Relaxed),
+            connections_total: self.connections_total.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+/**
+ * Traffic and health of one backend of a load-balanced instance.
+ *
+ * Traffic counters are those of `TransportStats`, where `errors` counts
+ * failed connects and connections closed by an error. `healthy_secs` and
+ * `unhealthy_secs` add up the time spent in each health state since the
+ * backend was first seen, `health_changes` counts how often it flipped and
+ * `state_since` tells when the current state started. A backend no longer
+ * returned by discovery counts as unhealthy.
+ */
+pub struct BackendStats {
+    pub addr: SocketAddr,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    pub healthy: bool,
+    #[serde(flatten)]
+    pub traffic: TransportStats,
+    pub healthy_secs: u64,
+    pub unhealthy_secs: u64,
+    pub health_changes: u64,
+    pub state_since: DateTime<Utc>,
+}
+#[derive(Debug)]
+struct BackendEntry {
+    host: Option<String>,
+    traffic: TransportMetrics,
+    healthy: bool,
+    state_since: DateTime<Utc>,
+    healthy_time: chrono::Duration,
+    unhealthy_time: chrono::Duration,
+    health_changes: u64,
+}
+impl BackendEntry {
+    fn set_healthy(&mut self, healthy: bool, now: DateTime<Utc>) {
+        if self.healthy == healthy {
+            return;
+        }
+        let elapsed = now - self.state_since;
+        if self.healthy {
+            self.healthy_time += elapsed;
+        } else {
+            self.unhealthy_time += elapsed;
+        }
+        self.healthy = healthy;
+        self.state_since = now;
+        self.health_changes += 1;
+    }
+    fn stats(&self, addr: SocketAddr, now: DateTime<Utc>) -> BackendStats {
+        let current = (now - self.state_since).max(chrono::Duration::zero());
+        let (healthy_time, unhealthy_time) = if self.healthy {
+            (self.healthy_time + current, self.unhealthy_time)
+        } else {
+            (self.healthy_time, self.unhealthy_time + current)
+        };
+        BackendStats {
+            addr,
+            host: self.host.clone(),
+            healthy: self.healthy,
+            traffic: self.traffic.stats(),
+            healthy_secs: healthy_time.num_seconds().max(0) as u64,
+            unhealthy_secs: unhealthy_time.num_seconds().max(0) as u64,
+            health_changes: self.health_changes,
+            state_since: self.state_since,
+        }
+    }
+}
+fn saturating_add(counter: &AtomicU64, bytes: u64) {
+    counter
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+            current.checked_add(bytes).or(Some(u64::MAX))
+        })
+        .ok();
+}
+#[derive(Debug, Clone, PartialEq)]
+/**
+ * Histogram of observed values with fixed upper bounds, Prometheus style.
+ * Values above the last bound are only counted in the total.
+ */
+pub struct Histogram {
+    bounds: Vec<f64>,
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HistogramBucket {
+    pub le: f64,
+    pub count: u64,
+}
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+/**
+ * Cumulative bucket counts of a histogram: each bucket counts the values
+ * lower than or equal to `le`.
+ */
+pub struct HistogramSnapshot {
+    pub buckets: Vec<HistogramBucket>,
+    pub count: u64,
+    pub sum: f64,
+}
+impl Histogram {
+    pub fn new(bounds: &[f64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len()],
+            count: 0,
+            sum: 0.0,
+        }
+    }
+    pub fn bounds(&self) -> &[f64] {
+        &self.bounds
+    }
+    pub fn observe(&mut self, value: f64) {
+        if let Some(index) = self.bounds.iter().position(|bound| value <= *bound) {
+            self.counts[index] += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds
+            .iter()
+            .zip(&self.counts)
+            .map(|(bound, count)| {
+                cumulative += count;
+                HistogramBucket {
+                    le: *bound,
+                    count: cumulative,
+                }
+            })
+            .collect();
+        HistogramSnapshot {
+            buckets,
+            count: self.count,
+            sum: self.sum,
+        }
+    }
+}
+/**
+ * Checks that histogram bounds are finite, positive and strictly increasing.
+ */
+pub fn validate_buckets(bounds: &[f64]) -> anyhow::Result<()> {
+    if bounds.is_empty() {
+        return Err(anyhow::anyhow!("At least one histogram bucket is required"));
+    }
+    if bounds.iter().any(|bound| !bound.is_finite() || *bound <= 0.0) {
+        return Err(anyhow::anyhow!("Histogram buckets must be positive numbers"));
+    }
+    if bounds.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err(anyhow::anyhow!("Histogram buckets must be strictly increasing"));
+    }
+    Ok(())
+}
+#[derive(Debug, Clone)]
+/**
+ * Metrics tracking for a single proxy instance.
+ *
+ * Tracks various performance and usage metrics for a proxy instance
+ * including traffic statistics and error counts. The durations of completed
+ * TCP connections and UDP sessions are recorded into histograms.
+ * `connections_stalled` counts TCP connections currently paused because
+ * their peer does not drain its send queue, `stalls_total` how often that
+ * happened. `handler_waits` counts the connections or datagrams that
+ * waited for a free handler of the instance. `accept_errors` counts failed
+ * accepts of the TCP listener, `udp_unreachable` the ICMP port unreachable
+ * errors received from UDP destinations. `certificate_expires_at` holds the expiry of the TLS
+ * certificate currently served, as a unix timestamp (0 without TLS).
+ * `tunnel_bytes_uncompressed` and `tunnel_bytes_compressed` count the
+ * traffic of compressed tunnel links before and after compression.
+ * `mirrored_connections` and `mirrored_bytes` count the connections and
+ * client bytes copied to the instance's mirror, `mirror_dropped_bytes` the
+ * bytes left out by the hourly cap or a mirror falling behind.
+ * Closed connections and sessions are counted by close reason, and the
+ * latest of them are kept in the connection history. The slow log keeps the latest TCP connections that exceeded the instance's
+ * slow-log thresholds. `buffer_bytes` feeds the memory accounting with
+ * the buffer capacity held for the instance. Load-balanced instances also
+ * count traffic and health per backend.
+ *
+ * `tcp` and `udp` split the traffic by transport, which matters for
+ * instances serving both. The byte totals cover both transports, while
+ * `connections_active`, `connections_total` and `errors` keep counting TCP
+ * connections only; UDP sessions and their errors are in `udp`.
+ */
+pub struct InstanceMetrics {
+    pub bytes_sent: Arc<AtomicU64>,
+    pub bytes_received: Arc<AtomicU64>,
+    pub connections_active: Arc<AtomicU32>,
+    pub connections_total: Arc<AtomicU32>,
+    pub errors: Arc<AtomicU32>,
+    pub connections_stalled: Arc<AtomicU32>,
+    pub stalls_total: Arc<AtomicU64>,
+    pub handler_waits: Arc<AtomicU64>,
+    pub accept_errors: Arc<AtomicU64>,
+    pub udp_unreachable: Arc<AtomicU64>,
+    pub buffer_bytes: Arc<AtomicU64>,
+    pub tcp: TransportMetrics,
+    pub udp: TransportMetrics,
+    pub certificate_expires_at: Arc<AtomicI64>,
+    pub tunnel_bytes_uncompressed: Arc<AtomicU64>,
+    pub tunnel_bytes_compressed: Arc<AtomicU64>,
+    pub mirrored_connections: Arc<AtomicU64>,
+    pub mirrored_bytes: Arc<AtomicU64>,
+    pub mirror_dropped_bytes: Arc<AtomicU64>,
+    tcp_durations: Arc<std::sync::Mutex<Histogram>>,
+    udp_durations: Arc<std::sync::Mutex<Histogram>>,
+    disconnects: Arc<std::sync::Mutex<HashMap<(Transport, CloseReason), u64>>>,
+    history: Arc<std::sync::Mutex<VecDeque<ClosedConnection>>>,
+    slow_log: Arc<std::sync::Mutex<SlowLog>>,
+    backends: Arc<std::sync::Mutex<BTreeMap<SocketAddr, BackendEntry>>>,
+    last_update: Arc<RwLock<Instant>>,
+}
+impl Default for InstanceMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl InstanceMetrics {
+    pub fn new() -> Self {
+        Self {
+            bytes_sent: Arc::new(AtomicU64::new(0)),
+            bytes_received: Arc::new(AtomicU64::new(0)),
+            connections_active: Arc::new(AtomicU32::new(0)),
+            connections_total: Arc::new(AtomicU32::new(0)),
+            errors: Arc::new(AtomicU32::new(0)),
+            connections_stalled: Arc::new(AtomicU32::new(0)),
+            stalls_total: Arc::new(AtomicU64::new(0)),
+            handler_waits: Arc::new(AtomicU64::new(0)),
+            accept_errors: Arc::new(AtomicU64::new(0)),
+            udp_unreachable: Arc::new(AtomicU64::new(0)),
+            buffer_bytes: Arc::new(AtomicU64::new(0)),
+            tcp: TransportMetrics::default(),
+            udp: TransportMetrics::default(),
+            certificate_expires_at: Arc::new(AtomicI64::new(0)),
+            tunnel_bytes_uncompressed: Arc::new(AtomicU64::new(0)),
+            tunnel_bytes_compressed: Arc::new(AtomicU64::new(0)),
+            mirrored_connections: Arc::new(AtomicU64::new(0)),
+            mirrored_bytes: Arc::new(AtomicU64::new(0)),
+            mirror_dropped_bytes: Arc::new(AtomicU64::new(0)),
+            tcp_durations: Arc::new(std::sync::Mutex::new(Histogram::new(
+                &DEFAULT_DURATION_BUCKETS,
+            ))),
+            udp_durations: Arc::new(std::sync::Mutex::new(Histogram::new(
+                &DEFAULT_DURATION_BUCKETS,
+            ))),
+            disconnects: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            history: Arc::new(std::sync::Mutex::new(VecDeque::new())),
+            slow_log: Arc::new(std::sync::Mutex::new(SlowLog::default())),
+            backends: Arc::new(std::sync::Mutex::new(BTreeMap::new())),
+            last_update: Arc::new(RwLock::new(Instant::now())),
+        }
+    }
+    pub fn add_bytes_sent(&self, bytes: u64) {
+        saturating_add(&self.bytes_sent, bytes);
+        self.update_timestamp();
+    }
+    pub fn add_bytes_received(&self, bytes: u64) {
+        saturating_add(&self.bytes_received, bytes);
+        self.update_timestamp();
+    }
+    pub fn transport(&self, transport: Transport) -> &TransportMetrics {
+        match transport {
+            Transport::Tcp => &self.tcp,
+            Transport::Udp => &self.udp,
+        }
+    }
+    /**
+     * Counts bytes sent to clients over `transport`, in the totals and in
+     * the transport's counters.
+     */
+    pub fn record_sent(&self, transport: Transport, bytes: u64) {
+        saturating_add(&self.transport(transport).bytes_sent, bytes);
+        self.add_bytes_sent(bytes);
+    }
+    /**
+     * Counts bytes received from clients over `transport`.
+     */
+    pub fn record_received(&self, transport: Transport, bytes: u64) {
+        saturating_add(&self.transport(transport).bytes_received, bytes);
+        self.add_bytes_received(bytes);
+    }
+    pub fn connection_opened(&self, transport: Transport) {
+        let counters = self.transport(transport);
+        counters.connections_active.fetch_add(1, Ordering::Relaxed);
+        counters.connections_total.fetch_add(1, Ordering::Relaxed);
+        if transport == Transport::Tcp {
+            self.connections_active.fetch_add(1, Ordering::Relaxed);
+            self.connections_total.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    pub fn connection_closed(&self, transport: Transport) {
+        self.transport(transport)
+            .connections_active
+            .fetch_sub(1, Ordering::Relaxed);
+        if transport == Transport::Tcp {
+            self.connections_active.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+    pub fn record_error(&self, transport: Transport) {
+        self.transport(transport)
+            .errors
+            .fetch_add(1, Ordering::Relaxed);
+        if transport == Transport::Tcp {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    pub fn snapshot(&self) -> CounterSnapshot {
+        CounterSnapshot {
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            connections_total: self.connections_total.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+    /**
+     * Adds previously persisted totals to the counters. Adding rather than
+     * overwriting keeps traffic counted before the restore.
+     */
+    pub fn restore(&self, snapshot: &CounterSnapshot) {
+        self.add_bytes_sent(snapshot.bytes_sent);
+        self.add_bytes_received(snapshot.bytes_received);
+        for (counter, value) in [
+            (&self.connections_total, snapshot.connections_total),
+            (&self.errors, snapshot.errors),
+        ] {
+            counter
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                    Some(current.saturating_add(value))
+                })
+                .ok();
+        }
+    }
+    fn durations(&self, transport: Transport) -> &std::sync::Mutex<Histogram> {
+        match transport {
+            Transport::Tcp => &self.tcp_durations,
+            Transport::Udp => &self.udp_durations,
+        }
+    }
+    /**
+     * Switches the duration histograms to new bounds. Histograms whose bounds
+     * change start over empty.
+     */
+    pub fn set_duration_buckets(&self, bounds: &[f64]) {
+        for transport in [Transport::Tcp, Transport::Udp] {
+            let mut histogram = self
+                .durations(transport)
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if histogram.bounds() != bounds {
+                *histogram = Histogram::new(bounds);
+            }
+        }
+    }
+    pub fn record_duration(&self, transport: Transport, duration: Duration) {
+        self.durations(transport)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .observe(duration.as_secs_f64());
+    }
+    pub fn duration_histogram(&self, transport: Transport) -> HistogramSnapshot {
+        self.durations(transport)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .snapshot()
+    }
+    pub fn record_disconnect(&self, transport: Transport, reason: CloseReason) {
+        *self
+            .disconnects
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry((transport, reason))
+            .or_default() += 1;
+    }
+    /**
+     * Counts a closed connection (or session) by its close reason, adds it
+     * to the connection history, accounts its traffic to the client and
+     * writes its access log line.
+     */
+    pub fn record_close(
+        &self,
+        ctx: &ConnectionContext,
+        reason: CloseReason,
+        bytes_received: u64,
+        bytes_sent: u64,
+    ) {
+        self.record_disconnect(ctx.transport, reason);
+        crate::traffic_report::global().record(ctx.peer_addr.ip(), bytes_received, bytes_sent);
+        let entry = ClosedConnection {
+            connection_id: ctx.connection_id,
+            transport: ctx.transport,
+            client: ctx.peer_addr,
+            backend: ctx.upstream_addr,
+            closed_at: Utc::now(),
+            duration_ms: ctx.accepted_at.elapsed().as_millis() as u64,
+            bytes_received,
+            bytes_sent,
+            reason,
+        };
+        tracing::info!(
+            target: ACCESS_LOG_TARGET,
+            "connection={} instance={} transport={} client={} backend={} duration_ms={} bytes_received={} bytes_sent={} reason={}",
+            ctx.connection_id,
+            ctx.instance_id,
+            match ctx.transport {
+                Transport::Tcp => "tcp",
+                Transport::Udp => "udp",
+            },
+            entry.client,
+            entry.backend,
+            entry.duration_ms,
+            bytes_received,
+            bytes_sent,
+            reason
+        );
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        if history.len() == CONNECTION_HISTORY_CAPACITY {
+            history.pop_front();
+        }
+        history.push_back(entry);
+    }
+    /**
+     * Recently closed connections, newest first.
+     */
+    pub fn connection_history(&self) -> Vec<ClosedConnection> {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history.iter().rev().cloned().collect()
+    }
+    /**
+     * Closed connections (or sessions) of a transport per close reason.
+     */
+    pub fn disconnects(&self, transport: Transport) -> BTreeMap<String, u64> {
+        self.disconnects
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|((counted, _), _)| *counted == transport)
+            .map(|((_, reason), count)| (reason.to_string(), *count))
+            .collect()
+    }
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let buffers = self.buffer_bytes.load(Ordering::Relaxed);
+        let udp_sessions =
+            self.udp.connections_active.load(Ordering::Relaxed) as u64 * UDP_SESSION_BYTES;
+        let connections =
+            self.tcp.connections_active.load(Ordering::Relaxed) as u64 * TCP_CONNECTION_BYTES;
+        MemoryUsage {
+            buffers,
+            udp_sessions,
+            connections,
+            total: buffers + udp_sessions + connections,
+        }
+    }
+    /**
+     * Appends a connection to the slow log, numbering it and dropping the
+     * oldest entries beyond `capacity`.
+     */
+    pub fn record_slow_connection(&self, mut entry: SlowLogEntry, capacity: usize) {
+        let mut slow_log = self.slow_log.lock().unwrap_or_else(|e| e.into_inner());
+        slow_log.next_id += 1;
+        entry.id = slow_log.next_id;
+        slow_log.entries.push_back(entry);
+        while slow_log.entries.len() > capacity {
+            slow_log.entries.pop_front();
+        }
+    }
+    /**
+     * Slow-log entries, newest first.
+     */
+    pub fn slow_log(&self) -> Vec<SlowLogEntry> {
+        let slow_log = self.slow_log.lock().unwrap_or_else(|e| e.into_inner());
+        slow_log.entries.iter().rev().cloned().collect()
+    }
+    pub fn reset_slow_log(&self) -> usize {
+        let mut slow_log = self.slow_log.lock().unwrap_or_else(|e| e.into_inner());
+        let removed = slow_log.entries.len();
+        slow_log.entries.clear();
+        removed
+    }
+    /**
+     * Records the current backend set of a load-balanced instance: backends
+     * seen for the first time start being tracked, and every tracked
+     * backend takes the health it is reported with, or unhealthy when it is
+     * no longer listed.
+     */
+    pub fn report_backends(&self, backends: &[crate::backend::Backend]) {
+        let now = Utc::now();
+        let mut reported: BTreeMap<SocketAddr, (bool, Option<String>)> = BTreeMap::new();
+        for backend in backends {
+            let entry = reported.entry(backend.addr).or_default();
+            entry.0 |= backend.healthy;
+            if backend.host.is_some() {
+                entry.1 = backend.host.clone();
+            }
+        }
+        let mut tracked = self.backends.lock().unwrap_or_else(|e| e.into_inner());
+        for (addr, entry) in tracked.iter_mut() {
+            if !reported.contains_key(addr) {
+                entry.set_healthy(false, now);
+            }
+        }
+        for (addr, (healthy, host)) in reported {
+            let entry = tracked.entry(addr).or_insert_with(|| BackendEntry {
+                host: None,
+                traffic: TransportMetrics::default(),
+                healthy,
+                state_since: now,
+                healthy_time: chrono::Duration::zero(),
+                unhealthy_time: chrono::Duration::zero(),
+                health_changes: 0,
+            });
+            if host.is_some() {
+                entry.host = host;
+            }
+            entry.set_healthy(healthy, now);
+        }
+    }
+    /**
+     * Traffic counters of a tracked backend, which connections to it keep
+     * for their lifetime. `None` when the instance does not balance across
+     * `addr`.
+     */
+    pub fn backend(&self, addr: SocketAddr) -> Option<TransportMetrics> {
+        self.backends
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&addr)
+            .map(|entry| entry.traffic.clone())
+    }
+    /**
+     * Counts a failed connection attempt to a tracked backend.
+     */
+    pub fn record_backend_error(&self, addr: SocketAddr) {
+        if let Some(traffic) = self.backend(addr) {
+            traffic.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    /**
+     * Statistics of the tracked backends, ordered by address.
+     */
+    pub fn backend_stats(&self) -> Vec<BackendStats> {
+        let now = Utc::now();
+        self.backends
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(addr, entry)| entry.stats(*addr, now))
+            .collect()
+    }
+    fn update_timestamp(&self) {
+        if let Ok(mut last_update) = self.last_update.try_write() {
+            *last_update = Instant::now();
+        }
+    }
+    pub async fn get_stats(&self, started_at: Option<DateTime<Utc>>) -> InstanceStats {
+        let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
+        let bytes_received = self.bytes_received.load(Ordering::Relaxed);
+        let connections_active = self.connections_active.load(Ordering::Relaxed);
+        let connections_total = self.connections_total.load(Ordering::Relaxed);
+        let errors = self.errors.load(Ordering::Relaxed);
+        let tunnel_bytes_uncompressed = self.tunnel_bytes_uncompressed.load(Ordering::Relaxed);
+        let tunnel_bytes_compressed = self.tunnel_bytes_compressed.load(Ordering::Relaxed);
+        let (bytes_sent_per_sec, bytes_received_per_sec) = if let Some(started) = started_at {
+            let duration = Utc::now().signed_duration_since(started);
+            let seconds = duration.num_seconds().max(1) as f64;
+            (bytes_sent as f64 / seconds, bytes_received as f64 / seconds)
+        } else {
+            (0.0, 0.0)
+        };
+        let error_rate = if connections_total > 0 {
+            errors as f64 / connections_total as f64
+        } else {
+            0.0
+        };
+        InstanceStats {
+            bytes_sent,
+            bytes_received,
+            connections_active,
+            connections_total,
+            errors,
+            bytes_sent_per_sec,
+            bytes_received_per_sec,
+            error_rate,
+            connections_stalled: self.connections_stalled.load(Ordering::Relaxed),
+            stalls_total: self.stalls_total.load(Ordering::Relaxed),
+            handler_waits: self.handler_waits.load(Ordering::Relaxed),
+            accept_errors: self.accept_errors.load(Ordering::Relaxed),
+            udp_unreachable: self.udp_unreachable.load(Ordering::Relaxed),
+            certificate_expires_at: match self.certificate_expires_at.load(Ordering::Relaxed) {
+                0 => None,
+                timestamp => DateTime::from_timestamp(timestamp, 0),
+            },
+            tunnel_bytes_uncompressed,
+            tunnel_bytes_compressed,
+            tunnel_compression_ratio: (tunnel_bytes_compressed > 0)
+                .then(|| tunnel_bytes_uncompressed as f64 / tunnel_bytes_compressed as f64),
+            mirrored_connections: self.mirrored_connections.load(Ordering::Relaxed),
+            mirrored_bytes: self.mirrored_bytes.load(Ordering::Relaxed),
+            mirror_dropped_bytes: self.mirror_dropped_bytes.load(Ordering::Relaxed),
+            tcp_connection_duration: self.duration_histogram(Transport::Tcp),
+            udp_session_duration: self.duration_histogram(Transport::Udp),
+            tcp_disconnects: self.disconnects(Transport::Tcp),
+            udp_disconnects: self.disconnects(Transport::Udp),
+            memory: self.memory_usage(),
+            tcp: self.tcp.stats(),
+            udp: self.udp.stats(),
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+/**
+ * Cumulative counters of an instance, persisted so lifetime totals survive
+ * restarts.
+ */
+pub struct CounterSnapshot {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub connections_total: u32,
+    pub errors: u32,
+}
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+/**
+ * Connection or UDP session that has been closed. `bytes_received` counts
+ * the bytes read from the client, `bytes_sent` those sent to it.
+ */
+pub struct ClosedConnection {
+    pub connection_id: ConnectionId,
+    pub transport: Transport,
+    pub client: SocketAddr,
+    pub backend: SocketAddr,
+    pub closed_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+    pub reason: CloseReason,
+}
+#[derive(Debug, Default)]
+struct SlowLog {
+    next_id: u64,
+    entries: VecDeque<SlowLogEntry>,
+}
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+/**
+ * TCP connection that was slow to reach or hear back from its destination.
+ *
+ * `connect_ms` is the time spent connecting to `backend`, `first_byte_ms`
+ * the time from the established connection to the first byte the backend
+ * sent (unset when it sent nothing) and `duration_ms` the lifetime of the
+ * client connection. `error` is set when connecting failed.
+ */
+pub struct SlowLogEntry {
+    pub id: u64,
+    pub connection_id: ConnectionId,
+    pub timestamp: DateTime<Utc>,
+    pub client: SocketAddr,
+    pub backend: SocketAddr,
+    pub connect_ms: u64,
+    pub first_byte_ms: Option<u64>,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+#[derive(Debug, Clone, serde::Serialize)]
+/**
+ * Statistical summary of instance metrics.
+ *
+ * Contains computed statistics derived from raw metrics including
+ * throughput rates and error percentages.
+ */
+pub struct InstanceStats {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub connections_active: u32,
+    pub connections_total: u32,
+    pub errors: u32,
+    pub bytes_sent_per_sec: f64,
+    pub bytes_received_per_sec: f64,
+    pub error_rate: f64,
+    pub connections_stalled: u32,
+    pub stalls_total: u64,
+    pub handler_waits: u64,
+    pub accept_errors: u64,
+    pub udp_unreachable: u64,
+    pub certificate_expires_at: Option<DateTime<Utc>>,
+    pub tunnel_bytes_uncompressed: u64,
+    pub tunnel_bytes_compressed: u64,
+    pub tunnel_compression_ratio: Option<f64>,
+    pub mirrored_connections: u64,
+    pub mirrored_bytes: u64,
+    pub mirror_dropped_bytes: u64,
+    pub tcp_connection_duration: HistogramSnapshot,
+    pub udp_session_duration: HistogramSnapshot,
+    pub tcp_disconnects: BTreeMap<String, u64>,
+    pub udp_disconnects: BTreeMap<String, u64>,
+    pub memory: MemoryUsage,
+    pub tcp: TransportStats,
+    pub udp: TransportStats,
+}
+/**
+ * Manages metrics collection for all proxy instances.
+ *
+ * Holds the live metrics of every registered instance and periodically
+ * folds them into the system-wide totals and rates.
+ */
+pub struct MetricsManager {
+    instances: Arc<RwLock<HashMap<Uuid, Arc<InstanceMetrics>>>>,
+    system_metrics: Arc<RwLock<SystemMetrics>>,
+    traffic: Arc<std::sync::Mutex<TrafficSample>>,
+    started_at: Instant,
+}
+/**
+ * Byte counters of each instance at the last collection, which the next
+ * one subtracts to get the system-wide rates.
+ */
+struct TrafficSample {
+    taken_at: Instant,
+    bytes: HashMap<Uuid, (u64, u64)>,
+}
+/**
+ * How often the system metrics are recomputed.
+ */
+pub const SYSTEM_METRICS_INTERVAL: Duration = Duration::from_secs(10);
+#[derive(Debug, Clone, serde::Serialize)]
+/**
+ * System-wide performance metrics.
+ *
+ * Tracks overall system performance including memory usage,
+ * CPU utilization, and connection statistics.
+ */
+pub struct SystemMetrics {
+    pub uptime_seconds: u64,
+    pub total_memory_mb: u64,
+    pub used_memory_mb: u64,
+    pub cpu_usage_percent: f64,
+    pub active_connections: u32,
+    pub bytes_sent_per_sec: f64,
+    pub bytes_received_per_sec: f64,
+    pub open_files: Option<u64>,
+    pub open_files_limit: Option<u64>,
+    pub listen_overflows: Option<crate::listener::ListenOverflows>,
+    pub last_updated: DateTime<Utc>,
+}
+#[derive(Debug, Clone, serde::Serialize)]
+/**
+ * Session-specific metrics of a proxy: open UDP sessions or TCP
+ * connections, the idle timeout closing them and how long the most idle of
+ * them has been without traffic. `cleanup_interval_seconds` is how often
+ * expired UDP sessions are swept; TCP connections time out on their own
+ * timers and report 0.
+ */
+pub struct SessionMetrics {
+    pub session_timeout_seconds: u64,
+    pub cleanup_interval_seconds: u64,
+    pub active_sessions: usize,
+    pub longest_idle_seconds: u64,
+}
+impl Default for MetricsManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MetricsManager {
+    pub fn new() -> Self {
+        let manager = Self {
+            instances: Arc::new(RwLock::new(HashMap::new())),
+            system_metrics: Arc::new(RwLock::new(SystemMetrics {
+                uptime_seconds: 0,
+                total_memory_mb: 0,
+                used_memory_mb: 0,
+                cpu_usage_percent: 0.0,
+                active_connections: 0,
+                bytes_sent_per_sec: 0.0,
+                bytes_received_per_sec: 0.0,
+                open_files: None,
+                open_files_limit: None,
+                listen_overflows: None,
+                last_updated: Utc::now(),
+            })),
+            traffic: Arc::new(std::sync::Mutex::new(TrafficSample {
+                taken_at: Instant::now(),
+                bytes: HashMap::new(),
+            })),
+            started_at: Instant::now(),
+        };
+        manager.start_system_metrics_collection();
+        manager
+    }
+    fn start_system_metrics_collection(&self) {
+        let manager = Self {
+            instances: self.instances.clone(),
+            system_metrics: self.system_metrics.clone(),
+            traffic: self.traffic.clone(),
+            started_at: self.started_at,
+        };
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SYSTEM_METRICS_INTERVAL);
+            loop {
+                interval.tick().await;
+                manager.refresh().await;
+            }
+        });
+    }
+    /**
+     * Recomputes the system metrics from the registered instances. Active
+     * connections count open TCP connections and UDP sessions; the byte
+     * rates cover the time since the previous refresh. Warns when the open
+     * descriptors (estimated from the connections where they cannot be
+     * counted) near the open file limit.
+     */
+    pub async fn refresh(&self) {
+        let (total_memory, used_memory) = if let Ok(mem_info) = sys_info::mem_info() {
+            (
+                mem_info.total / (1024 * 1024),
+                (mem_info.total - mem_info.free) / (1024 * 1024),
+            )
+        } else {
+            (0, 0)
+        };
+        let (active_connections, bytes) = {
+            let instances = self.instances.read().await;
+            let active_connections = instances
+                .values()
+                .map(|metrics| {
+                    metrics.tcp.connections_active.load(Ordering::Relaxed)
+                        + metrics.udp.connections_active.load(Ordering::Relaxed)
+                })
+                .sum();
+            let bytes: HashMap<Uuid, (u64, u64)> = instances
+                .iter()
+                .map(|(id, metrics)| {
+                    (
+                        *id,
+                        (
+                            metrics.bytes_sent.load(Ordering::Relaxed),
+                            metrics.bytes_received.load(Ordering::Relaxed),
+                        ),
+                    )
+                })
+                .collect();
+            (active_connections, bytes)
+        };
+        let (bytes_sent_per_sec, bytes_received_per_sec) = {
+            let mut traffic = self.traffic.lock().unwrap_or_else(|e| e.into_inner());
+            let seconds = traffic.taken_at.elapsed().as_secs_f64();
+            let (mut sent, mut received) = (0u64, 0u64);
+            for (id, (bytes_sent, bytes_received)) in &bytes {
+                if let Some((previous_sent, previous_received)) = traffic.bytes.get(id) {
+                    sent += bytes_sent.saturating_sub(*previous_sent);
+                    received += bytes_received.saturating_sub(*previous_received);
+                }
+            }
+            *traffic = TrafficSample {
+                taken_at: Instant::now(),
+                bytes,
+            };
+            if seconds > 0.0 {
+                (sent as f64 / seconds, received as f64 / seconds)
+            } else {
+                (0.0, 0.0)
+            }
+        };
+        let open_files = crate::fd_limit::open_files();
+        let open_files_limit = crate::fd_limit::current_limit();
+        if let Some(limit) = open_files_limit {
+            let in_use = open_files.unwrap_or(2 * u64::from(active_connections));
+            if in_use as f64 >= limit as f64 * crate::fd_limit::WARN_RATIO {
+                tracing::warn!(
+                    "{} of {} file descriptors in use with {} active connections; new connections will fail at the limit",
+                    in_use,
+                    limit,
+                    active_connections
+                );
+            }
+        }
+        let mut metrics_guard = self.system_metrics.write().await;
+        metrics_guard.uptime_seconds = self.started_at.elapsed().as_secs();
+        metrics_guard.total_memory_mb = total_memory;
+        metrics_guard.used_memory_mb = used_memory;
+        metrics_guard.active_connections = active_connections;
+        metrics_guard.bytes_sent_per_sec = bytes_sent_per_sec;
+        metrics_guard.bytes_received_per_sec = bytes_received_per_sec;
+        metrics_guard.open_files = open_files;
+        metrics_guard.open_files_limit = open_files_limit;
+        metrics_guard.listen_overflows = crate::listener::listen_overflows();
+        metrics_guard.last_updated = Utc::now();
+        metrics_guard.cpu_usage_percent = 0.0;
+    }
+    /**
+     * Adds an instance's live metrics to the system totals.
+     */
+    pub async fn register_instance(&self, instance_id: Uuid, metrics: Arc<InstanceMetrics>) {
+        let mut instances = self.instances.write().await;
+        instances.insert(instance_id, metrics);
+    }
+    pub async fn unregister_instance(&self, instance_id: &Uuid) {
+        let mut instances = self.instances.write().await;
+        instances.remove(instance_id);
+    }
+    pub async fn get_system_metrics(&self) -> SystemMetrics {
+        self.system_metrics.read().await.clone()
+    }
+}
@@ -0,0 +1,243 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+pub type MiddlewareFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/**
+ * Why a TCP connection or UDP session ended. `Error` names the step that
+ * failed, e.g. `server_read`, and is rendered as `error:server_read`.
+ */
+pub enum CloseReason {
+    IdleTimeout,
+    MaxLifetime,
+    ClientEof,
+    ServerEof,
+    Cancelled,
+    Rejected,
+    Error(&'static str),
+}
+impl std::fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CloseReason::IdleTimeout => f.write_str("idle_timeout"),
+            CloseReason::MaxLifetime => f.write_str("max_lifetime"),
+            CloseReason::ClientEof => f.write_str("client_eof"),
+            CloseReason::ServerEof => f.write_str("server_eof"),
+            CloseReason::Cancelled => f.write_str("cancelled"),
+            CloseReason::Rejected => f.write_str("rejected"),
+            CloseReason::Error(category) => write!(f, "error:{}", category),
+        }
+    }
+}
+impl serde::Serialize for CloseReason {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+/**
+ * Decision returned by a middleware hook. `Reject` closes the TCP
+ * connection or drops the UDP datagram.
+ */
+pub enum Verdict {
+    Continue,
+    Reject(String),
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/**
+ * Short ID of a TCP connection or UDP session, rendered as 12 hex digits in
+ * log lines and API objects. IDs count up from a random start, so they are
+ * unique within a run and unlikely to repeat across restarts.
+ */
+pub struct ConnectionId(u64);
+impl ConnectionId {
+    const MASK: u64 = 0xffff_ffff_ffff;
+    pub fn next() -> Self {
+        static NEXT: OnceLock<AtomicU64> = OnceLock::new();
+        let next = NEXT.get_or_init(|| AtomicU64::new(Uuid::new_v4().as_u64_pair().0));
+        Self(next.fetch_add(1, Ordering::Relaxed) & Self::MASK)
+    }
+}
+impl std::fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:012x}", self.0)
+    }
+}
+impl std::str::FromStr for ConnectionId {
+    type Err = std::num::ParseIntError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        u64::from_str_radix(s, 16).map(|id| Self(id & Self::MASK))
+    }
+}
+impl serde::Serialize for ConnectionId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> serde::Deserialize<'de> for ConnectionId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map_err(serde::de::Error::custom)
+    }
+}
+#[derive(Debug, Clone)]
+/**
+ * Connection (or UDP session) a middleware hook is invoked for.
+ */
+pub struct ConnectionContext {
+    pub connection_id: ConnectionId,
+    pub instance_id: Uuid,
+    pub transport: Transport,
+    pub peer_addr: SocketAddr,
+    pub listen_addr: SocketAddr,
+    pub upstream_addr: SocketAddr,
+    pub accepted_at: Instant,
+}
+impl ConnectionContext {
+    pub fn new(
+        instance_id: Uuid,
+        transport: Transport,
+        peer_addr: SocketAddr,
+        listen_addr: SocketAddr,
+        upstream_addr: SocketAddr,
+    ) -> Self {
+        Self {
+            connection_id: ConnectionId::next(),
+            instance_id,
+            transport,
+            peer_addr,
+            listen_addr,
+            upstream_addr,
+            accepted_at: Instant::now(),
+        }
+    }
+    /**
+     * Keeps the ID already assigned to the connection when it was accepted.
+     */
+    pub fn with_connection_id(mut self, connection_id: ConnectionId) -> Self {
+        self.connection_id = connection_id;
+        self
+    }
+    /**
+     * Completes once the connection has been open for `max_lifetime`
+     * since it was accepted, never without a limit.
+     */
+    pub async fn lifetime_expired(&self, max_lifetime: Option<Duration>) {
+        match max_lifetime {
+            Some(max_lifetime) => {
+                tokio::time::sleep_until((self.accepted_at + max_lifetime).into()).await
+            }
+            None => std::future::pending().await,
+        }
+    }
+}
+/**
+ * Hooks invoked by the TCP and UDP proxies around each connection.
+ *
+ * `on_accept` runs before the upstream is contacted, `on_connect` once it
+ * is reachable, `on_data` for every chunk in either direction (the chunk
+ * may be rewritten in place) and `on_close` when the connection ends. For
+ * UDP, a session starts with the first datagram of a peer. All hooks
+ * default to letting traffic through untouched.
+ */
+pub trait ConnectionMiddleware: Send + Sync {
+    fn name(&self) -> &str;
+    fn on_accept<'a>(&'a self, _ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async { Verdict::Continue })
+    }
+    fn on_connect<'a>(&'a self, _ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async { Verdict::Continue })
+    }
+    fn on_data<'a>(
+        &'a self,
+        _ctx: &'a ConnectionContext,
+        _direction: Direction,
+        _data: &'a mut Vec<u8>,
+    ) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async { Verdict::Continue })
+    }
+    fn on_close<'a>(&'a self, _ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, ()> {
+        Box::pin(async {})
+    }
+    /**
+     * Whether `on_data` needs to see the chunks of a TCP connection. Asked
+     * once after `on_connect`; when no middleware does, the connection is
+     * relayed without copying its data.
+     */
+    fn inspects_data(&self, _ctx: &ConnectionContext) -> bool {
+        true
+    }
+}
+#[derive(Clone, Default)]
+/**
+ * Ordered list of middleware applied to an instance's connections.
+ *
+ * Hooks run in registration order and the first `Reject` wins.
+ */
+pub struct MiddlewareChain {
+    middleware: Arc<Vec<Arc<dyn ConnectionMiddleware>>>,
+}
+impl MiddlewareChain {
+    pub fn new(middleware: Vec<Arc<dyn ConnectionMiddleware>>) -> Self {
+        Self {
+            middleware: Arc::new(middleware),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.middleware.is_empty()
+    }
+    pub async fn on_accept(&self, ctx: &ConnectionContext) -> Verdict {
+        for middleware in self.middleware.iter() {
+            if let Verdict::Reject(reason) = middleware.on_accept(ctx).await {
+                return Verdict::Reject(format!("{}: {}", middleware.name(), reason));
+            }
+        }
+        Verdict::Continue
+    }
+    pub async fn on_connect(&self, ctx: &ConnectionContext) -> Verdict {
+        for middleware in self.middleware.iter() {
+            if let Verdict::Reject(reason) = middleware.on_connect(ctx).await {
+                return Verdict::Reject(format!("{}: {}", middleware.name(), reason));
+            }
+        }
+        Verdict::Continue
+    }
+    pub async fn on_data(
+        &self,
+        ctx: &ConnectionContext,
+        direction: Direction,
+        data: &mut Vec<u8>,
+    ) -> Verdict {
+        for middleware in self.middleware.iter() {
+            if let Verdict::Reject(reason) = middleware.on_data(ctx, direction, data).await {
+                return Verdict::Reject(format!("{}: {}", middleware.name(), reason));
+            }
+        }
+        Verdict::Continue
+    }
+    pub fn inspects_data(&self, ctx: &ConnectionContext) -> bool {
+        self.middleware
+            .iter()
+            .any(|middleware| middleware.inspects_data(ctx))
+    }
+    pub async fn on_close(&self, ctx: &ConnectionContext) {
+        for middleware in self.middleware.iter() {
+            middleware.on_close(ctx).await;
+        }
+    }
+}
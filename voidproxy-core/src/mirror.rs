@@ -0,0 +1,235 @@
+use crate::config::MirrorConfig;
+use crate::metrics::InstanceMetrics;
+use crate::middleware::{
+    ConnectionContext, ConnectionId, ConnectionMiddleware, Direction, MiddlewareFuture, Transport,
+    Verdict,
+};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tracing::debug;
+/** Chunks queued per mirrored connection before further ones are dropped. */
+const MIRROR_QUEUE_CHUNKS: usize = 64;
+/**
+ * Bytes mirrored during the current clock hour, reset when the next one
+ * starts.
+ */
+struct HourlyBudget {
+    hour: i64,
+    used: u64,
+}
+/**
+ * Middleware copying the client side of sampled connections to the mirror
+ * of an instance (see `MirrorConfig`).
+ *
+ * Every sampled connection gets its own link to the mirror, fed through a
+ * short queue by a background task so a slow or unreachable mirror never
+ * holds back the proxied traffic. Sampling is spread evenly: with
+ * `sample_percent` 25, every fourth connection is mirrored.
+ */
+pub struct TrafficMirror {
+    config: MirrorConfig,
+    connect_timeout: Duration,
+    metrics: Arc<InstanceMetrics>,
+    seen: AtomicU64,
+    budget: Mutex<HourlyBudget>,
+    links: Mutex<HashMap<ConnectionId, mpsc::Sender<Vec<u8>>>>,
+}
+impl TrafficMirror {
+    pub fn new(
+        config: MirrorConfig,
+        connect_timeout: Duration,
+        metrics: Arc<InstanceMetrics>,
+    ) -> Self {
+        Self {
+            config,
+            connect_timeout,
+            metrics,
+            seen: AtomicU64::new(0),
+            budget: Mutex::new(HourlyBudget { hour: 0, used: 0 }),
+            links: Mutex::new(HashMap::new()),
+        }
+    }
+    fn sample(&self) -> bool {
+        let seen = self.seen.fetch_add(1, Ordering::Relaxed);
+        let percent = u64::from(self.config.sample_percent);
+        (seen + 1) * percent / 100 != seen * percent / 100
+    }
+    /**
+     * Charges `len` bytes to the budget of the current hour, refusing them
+     * when they would exceed `max_bytes_per_hour`.
+     */
+    fn charge(&self, len: u64) -> bool {
+        let Some(max) = self.config.max_bytes_per_hour else {
+            return true;
+        };
+        let hour = chrono::Utc::now().timestamp() / 3600;
+        let mut budget = self.budget.lock().unwrap_or_else(|e| e.into_inner());
+        if budget.hour != hour {
+            *budget = HourlyBudget { hour, used: 0 };
+        }
+        if budget.used + len > max {
+            return false;
+        }
+        budget.used += len;
+        true
+    }
+    fn drop_bytes(metrics: &InstanceMetrics, len: usize) {
+        metrics
+            .mirror_dropped_bytes
+            .fetch_add(len as u64, Ordering::Relaxed);
+    }
+}
+/**
+ * Sends the chunks of one connection to the mirror until the connection
+ * ends. Chunks that cannot be delivered are counted as dropped.
+ */
+async fn forward(
+    addr: SocketAddr,
+    transport: Transport,
+    connect_timeout: Duration,
+    metrics: Arc<InstanceMetrics>,
+    mut chunks: mpsc::Receiver<Vec<u8>>,
+) {
+    match transport {
+        Transport::Tcp => {
+            match tokio::time::timeout(connect_timeout, TcpStream::connect(addr)).await {
+                Ok(Ok(stream)) => {
+                    let (mut reader, mut writer) = stream.into_split();
+                    let discard = tokio::spawn(async move {
+                        let _ = tokio::io::copy(&mut reader, &mut tokio::io::sink()).await;
+                    });
+                    while let Some(chunk) = chunks.recv().await {
+                        if let Err(e) = writer.write_all(&chunk).await {
+                            debug!("Writing to mirror {} failed: {}", addr, e);
+                            TrafficMirror::drop_bytes(&metrics, chunk.len());
+                            break;
+                        }
+                        metrics
+                            .mirrored_bytes
+                            .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                    }
+                    let _ = writer.shutdown().await;
+                    discard.abort();
+                }
+                Ok(Err(e)) => debug!("Connecting to mirror {} failed: {}", addr, e),
+                Err(_) => debug!("Connecting to mirror {} timed out", addr),
+            }
+        }
+        Transport::Udp => {
+            let local: SocketAddr = if addr.is_ipv4() {
+                (Ipv4Addr::UNSPECIFIED, 0).into()
+            } else {
+                (Ipv6Addr::UNSPECIFIED, 0).into()
+            };
+            match UdpSocket::bind(local).await {
+                Ok(socket) => {
+                    while let Some(chunk) = chunks.recv().await {
+                        match socket.send_to(&chunk, addr).await {
+                            Ok(_) => {
+                                metrics
+                                    .mirrored_bytes
+                                    .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                            }
+                            Err(e) => {
+                                debug!("Sending to mirror {} failed: {}", addr, e);
+                                TrafficMirror::drop_bytes(&metrics, chunk.len());
+                            }
+                        }
+                    }
+                }
+                Err(e) => debug!("Binding a socket for mirror {} failed: {}", addr, e),
+            }
+        }
+    }
+    while let Some(chunk) = chunks.recv().await {
+        TrafficMirror::drop_bytes(&metrics, chunk.len());
+    }
+}
+impl ConnectionMiddleware for TrafficMirror {
+    fn name(&self) -> &str {
+        "mirror"
+    }
+    fn on_connect<'a>(&'a self, ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async move {
+            if self.sample() {
+                let (sender, receiver) = mpsc::channel(MIRROR_QUEUE_CHUNKS);
+                tokio::spawn(forward(
+                    self.config.addr,
+                    ctx.transport,
+                    self.connect_timeout,
+                    self.metrics.clone(),
+                    receiver,
+                ));
+                self.links
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(ctx.connection_id, sender);
+                self.metrics
+                    .mirrored_connections
+                    .fetch_add(1, Ordering::Relaxed);
+            }
+            Verdict::Continue
+        })
+    }
+    fn on_data<'a>(
+        &'a self,
+        ctx: &'a ConnectionContext,
+        direction: Direction,
+        data: &'a mut Vec<u8>,
+    ) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async move {
+            if direction != Direction::ClientToServer || data.is_empty() {
+                return Verdict::Continue;
+            }
+            let Some(sender) = self
+                .links
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(&ctx.connection_id)
+                .cloned()
+            else {
+                return Verdict::Continue;
+            };
+            if !self.charge(data.len() as u64) || sender.try_send(data.clone()).is_err() {
+                Self::drop_bytes(&self.metrics, data.len());
+            }
+            Verdict::Continue
+        })
+    }
+    fn on_close<'a>(&'a self, ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, ()> {
+        Box::pin(async move {
+            self.links
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&ctx.connection_id);
+        })
+    }
+    fn inspects_data(&self, ctx: &ConnectionContext) -> bool {
+        self.links
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(&ctx.connection_id)
+    }
+}
+/**
+ * Mirror middleware for an instance configuration, when it has a mirror.
+ */
+pub fn mirror_middleware(
+    config: &crate::config::Config,
+    metrics: &Arc<InstanceMetrics>,
+) -> Option<Arc<dyn ConnectionMiddleware>> {
+    config.proxy.mirror.map(|mirror| {
+        Arc::new(TrafficMirror::new(
+            mirror,
+            Duration::from_secs(config.proxy.connect_timeout_secs),
+            metrics.clone(),
+        )) as Arc<dyn ConnectionMiddleware>
+    })
+}
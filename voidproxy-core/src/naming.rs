@@ -0,0 +1,94 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+/**
+ * Rules instance names follow, configured in the `[naming]` section of the
+ * configuration file.
+ *
+ * With `unique`, a name already used by an active instance is refused.
+ * `pattern` is a regular expression the whole name must match, such as
+ * `[a-z0-9-]+`.
+ */
+pub struct NamingPolicy {
+    pub unique: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+impl Default for NamingPolicy {
+    fn default() -> Self {
+        Self {
+            unique: true,
+            pattern: None,
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+/**
+ * Why a name was refused by the naming policy.
+ */
+pub enum NameError {
+    Empty,
+    Pattern { name: String, pattern: String },
+    Duplicate(String),
+}
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("Instance name must not be empty"),
+            Self::Pattern { name, pattern } => write!(
+                f,
+                "Instance name {} does not match the naming pattern {}",
+                name, pattern
+            ),
+            Self::Duplicate(name) => write!(f, "An instance named {} already exists", name),
+        }
+    }
+}
+impl std::error::Error for NameError {}
+impl NamingPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+    fn regex(&self) -> Option<Result<Regex, regex::Error>> {
+        self.pattern
+            .as_ref()
+            .map(|pattern| Regex::new(&format!("^(?:{})$", pattern)))
+    }
+    /**
+     * Checks that the pattern is a valid regular expression.
+     */
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(Err(e)) = self.regex() {
+            anyhow::bail!("Invalid naming pattern: {}", e);
+        }
+        Ok(())
+    }
+    /**
+     * Checks `name` against the policy, given the names of the other active
+     * instances. An invalid pattern lets every name through; it is refused
+     * when the configuration is loaded.
+     */
+    pub fn check<'a>(
+        &self,
+        name: &str,
+        others: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), NameError> {
+        if name.trim().is_empty() {
+            return Err(NameError::Empty);
+        }
+        if let Some(Ok(regex)) = self.regex()
+            && !regex.is_match(name)
+        {
+            return Err(NameError::Pattern {
+                name: name.to_string(),
+                pattern: self.pattern.clone().unwrap_or_default(),
+            });
+        }
+        if self.unique && others.into_iter().any(|other| other == name) {
+            return Err(NameError::Duplicate(name.to_string()));
+        }
+        Ok(())
+    }
+}
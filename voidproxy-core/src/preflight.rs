@@ -0,0 +1,59 @@
+use crate::backend::{BackendEntry, parse_backend_entry};
+use crate::config::{Config, Protocol, TunnelMode};
+use anyhow::{Result, anyhow};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+/**
+ * Addresses `check_destination` probes: the static backends of a
+ * load-balanced instance, or its destination. Discovered backends are not
+ * known before the instance starts and are left out.
+ */
+fn destinations(config: &Config) -> Vec<SocketAddr> {
+    if config.proxy.backends.is_empty() {
+        return vec![SocketAddr::new(config.proxy.dst_ip, config.proxy.dst_port)];
+    }
+    config
+        .proxy
+        .backends
+        .iter()
+        .filter_map(|entry| match parse_backend_entry(entry) {
+            Ok(BackendEntry::Static(addr)) => Some(addr),
+            _ => None,
+        })
+        .collect()
+}
+/**
+ * Checks that the destination of an instance accepts TCP connections
+ * within its connect timeout; with backends, one reachable backend is
+ * enough. UDP-only instances and the tunnel ends that forward over the
+ * tunnel (`edge`, `gateway`) have nothing to probe and always pass.
+ */
+pub async fn check_destination(config: &Config) -> Result<()> {
+    if config.proxy.protocol == Protocol::Udp
+        || config
+            .proxy
+            .tunnel
+            .as_ref()
+            .is_some_and(|tunnel| matches!(tunnel.mode, TunnelMode::Edge | TunnelMode::Gateway))
+    {
+        return Ok(());
+    }
+    let connect_timeout = Duration::from_secs(config.proxy.connect_timeout_secs);
+    let mut last_error = None;
+    for addr in destinations(config) {
+        match timeout(connect_timeout, TcpStream::connect(addr)).await {
+            Ok(Ok(_)) => return Ok(()),
+            Ok(Err(e)) => last_error = Some(anyhow!("{}: {}", addr, e)),
+            Err(_) => {
+                last_error = Some(anyhow!(
+                    "{}: no answer within {}s",
+                    addr,
+                    connect_timeout.as_secs()
+                ))
+            }
+        }
+    }
+    last_error.map_or(Ok(()), Err)
+}
@@ -0,0 +1,392 @@
+use crate::middleware::{
+    ConnectionContext, ConnectionId, ConnectionMiddleware, Direction, MiddlewareFuture, Transport, Verdict,
+};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::info;
+use uuid::Uuid;
+pub const DEFAULT_MAX_BYTES: usize = 1024 * 1024;
+pub const MAX_MAX_BYTES: usize = 16 * 1024 * 1024;
+/**
+ * Completed recordings kept in memory; older ones are dropped first.
+ */
+pub const MAX_RECORDINGS: usize = 32;
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/**
+ * Which connection to record and how much of it. `max_bytes` bounds both
+ * directions together.
+ */
+pub struct RecordOptions {
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: usize,
+    #[serde(default)]
+    pub peer_ip: Option<IpAddr>,
+}
+fn default_max_bytes() -> usize {
+    DEFAULT_MAX_BYTES
+}
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MAX_BYTES,
+            peer_ip: None,
+        }
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingState {
+    Armed,
+    Recording,
+    Complete,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Data read from one side of a recorded connection, `offset_ms` after it
+ * was accepted.
+ */
+pub struct RecordedChunk {
+    pub direction: RecordedDirection,
+    pub offset_ms: u64,
+    #[serde(with = "base64_data")]
+    pub data: Vec<u8>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedDirection {
+    ClientToServer,
+    ServerToClient,
+}
+impl From<Direction> for RecordedDirection {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::ClientToServer => RecordedDirection::ClientToServer,
+            Direction::ServerToClient => RecordedDirection::ServerToClient,
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Byte stream of a single TCP connection, as read from the client and the
+ * upstream before other middleware could rewrite it. `truncated` is set once
+ * `max_bytes` was reached; later data is not recorded.
+ */
+pub struct Recording {
+    pub id: Uuid,
+    pub instance_id: Uuid,
+    pub state: RecordingState,
+    pub max_bytes: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_ip: Option<IpAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connection_id: Option<ConnectionId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peer_addr: Option<SocketAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upstream_addr: Option<SocketAddr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    pub bytes: usize,
+    pub truncated: bool,
+    #[serde(default)]
+    pub chunks: Vec<RecordedChunk>,
+}
+impl Recording {
+    pub fn bytes_in(&self, direction: RecordedDirection) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .filter(|chunk| chunk.direction == direction)
+            .flat_map(|chunk| chunk.data.iter().copied())
+            .collect()
+    }
+    /**
+     * Copy without the recorded data, for listings.
+     */
+    pub fn summary(&self) -> Recording {
+        Recording {
+            chunks: Vec::new(),
+            ..self.clone()
+        }
+    }
+}
+#[derive(Default)]
+struct RecorderState {
+    armed: HashMap<Uuid, Recording>,
+    active: HashMap<ConnectionId, (Recording, std::time::Instant)>,
+    complete: VecDeque<Recording>,
+}
+#[derive(Default)]
+/**
+ * Middleware recording single connections on request.
+ *
+ * `arm` selects the next TCP connection accepted by an instance (optionally
+ * only from one client IP); its data is kept until it closes and can then
+ * be replayed with `replay`. Connections that are not recorded skip
+ * `on_data` entirely.
+ */
+pub struct ConnectionRecorder {
+    state: Mutex<RecorderState>,
+}
+impl ConnectionRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn arm(&self, instance_id: Uuid, options: RecordOptions) -> Result<Recording> {
+        if !(1..=MAX_MAX_BYTES).contains(&options.max_bytes) {
+            bail!(
+                "Recording size must be between 1 and {} bytes",
+                MAX_MAX_BYTES
+            );
+        }
+        let recording = Recording {
+            id: Uuid::new_v4(),
+            instance_id,
+            state: RecordingState::Armed,
+            max_bytes: options.max_bytes,
+            peer_ip: options.peer_ip,
+            connection_id: None,
+            peer_addr: None,
+            upstream_addr: None,
+            started_at: None,
+            bytes: 0,
+            truncated: false,
+            chunks: Vec::new(),
+        };
+        info!(
+            "Recording the next connection of instance {} as {}",
+            instance_id, recording.id
+        );
+        self.lock().armed.insert(instance_id, recording.clone());
+        Ok(recording)
+    }
+    /**
+     * All armed, running and complete recordings, without their data.
+     */
+    pub fn recordings(&self) -> Vec<Recording> {
+        let state = self.lock();
+        state
+            .armed
+            .values()
+            .chain(state.active.values().map(|(recording, _)| recording))
+            .chain(state.complete.iter())
+            .map(Recording::summary)
+            .collect()
+    }
+    pub fn recording(&self, id: Uuid) -> Option<Recording> {
+        let state = self.lock();
+        state
+            .armed
+            .values()
+            .chain(state.active.values().map(|(recording, _)| recording))
+            .chain(state.complete.iter())
+            .find(|recording| recording.id == id)
+            .cloned()
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, RecorderState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+impl ConnectionMiddleware for ConnectionRecorder {
+    fn name(&self) -> &str {
+        "recorder"
+    }
+    fn on_accept<'a>(&'a self, ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, Verdict> {
+        if ctx.transport == Transport::Tcp {
+            let mut state = self.lock();
+            let selected = state.armed.get(&ctx.instance_id).is_some_and(|recording| {
+                recording.peer_ip.is_none_or(|ip| ip == ctx.peer_addr.ip())
+            });
+            if selected && let Some(mut recording) = state.armed.remove(&ctx.instance_id) {
+                recording.state = RecordingState::Recording;
+                recording.connection_id = Some(ctx.connection_id);
+                recording.peer_addr = Some(ctx.peer_addr);
+                recording.upstream_addr = Some(ctx.upstream_addr);
+                recording.started_at = Some(Utc::now());
+                state
+                    .active
+                    .insert(ctx.connection_id, (recording, ctx.accepted_at));
+            }
+        }
+        Box::pin(async { Verdict::Continue })
+    }
+    fn on_data<'a>(
+        &'a self,
+        ctx: &'a ConnectionContext,
+        direction: Direction,
+        data: &'a mut Vec<u8>,
+    ) -> MiddlewareFuture<'a, Verdict> {
+        if let Some((recording, accepted_at)) = self.lock().active.get_mut(&ctx.connection_id) {
+            let room = recording.max_bytes - recording.bytes;
+            if data.len() > room {
+                recording.truncated = true;
+            }
+            let kept = &data[..data.len().min(room)];
+            if !kept.is_empty() {
+                recording.bytes += kept.len();
+                recording.chunks.push(RecordedChunk {
+                    direction: direction.into(),
+                    offset_ms: accepted_at.elapsed().as_millis() as u64,
+                    data: kept.to_vec(),
+                });
+            }
+        }
+        Box::pin(async { Verdict::Continue })
+    }
+    fn on_close<'a>(&'a self, ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, ()> {
+        let mut state = self.lock();
+        if let Some((mut recording, _)) = state.active.remove(&ctx.connection_id) {
+            info!(
+                "Recorded {} bytes of connection {} as {}",
+                recording.bytes, ctx.peer_addr, recording.id
+            );
+            recording.state = RecordingState::Complete;
+            if state.complete.len() >= MAX_RECORDINGS {
+                state.complete.pop_front();
+            }
+            state.complete.push_back(recording);
+        }
+        Box::pin(async {})
+    }
+    fn inspects_data(&self, ctx: &ConnectionContext) -> bool {
+        self.lock().active.contains_key(&ctx.connection_id)
+    }
+}
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/**
+ * How a recording is replayed. With `preserve_timing`, client chunks are
+ * sent at their recorded offsets instead of back to back. The upstream
+ * response is collected until it closes the connection or `timeout_secs`
+ * have passed.
+ */
+pub struct ReplayOptions {
+    #[serde(default)]
+    pub preserve_timing: bool,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+fn default_timeout_secs() -> u64 {
+    5
+}
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            preserve_timing: false,
+            timeout_secs: default_timeout_secs(),
+        }
+    }
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Outcome of a replay. `matches_recording` compares the response with the
+ * data the upstream sent during the recorded connection.
+ */
+pub struct ReplayReport {
+    pub recording_id: Uuid,
+    pub target: SocketAddr,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub closed_by_server: bool,
+    pub matches_recording: bool,
+    #[serde(with = "base64_data")]
+    pub response: Vec<u8>,
+}
+/**
+ * Sends the client side of a complete recording to `target` and collects
+ * the response, up to the recording's `max_bytes`.
+ */
+pub async fn replay(
+    recording: &Recording,
+    target: SocketAddr,
+    options: ReplayOptions,
+) -> Result<ReplayReport> {
+    if recording.state != RecordingState::Complete {
+        bail!("Recording {} is not complete yet", recording.id);
+    }
+    let timeout = Duration::from_secs(options.timeout_secs.max(1));
+    let deadline = tokio::time::Instant::now() + timeout;
+    let stream = tokio::time::timeout_at(deadline, TcpStream::connect(target))
+        .await
+        .with_context(|| format!("Timed out connecting to {}", target))?
+        .with_context(|| format!("Failed to connect to {}", target))?;
+    let (mut reader, mut writer) = stream.into_split();
+    let started = tokio::time::Instant::now();
+    let mut response = Vec::new();
+    let mut closed_by_server = false;
+    let mut bytes_sent = 0;
+    {
+        let send = async {
+            let mut sent = 0;
+            for chunk in &recording.chunks {
+                if chunk.direction != RecordedDirection::ClientToServer {
+                    continue;
+                }
+                if options.preserve_timing {
+                    tokio::time::sleep_until(started + Duration::from_millis(chunk.offset_ms))
+                        .await;
+                }
+                writer.write_all(&chunk.data).await?;
+                sent += chunk.data.len();
+            }
+            Ok::<_, std::io::Error>(sent)
+        };
+        let receive = async {
+            let mut buffer = vec![0u8; 16 * 1024];
+            loop {
+                let room = recording.max_bytes - response.len();
+                if room == 0 {
+                    return Ok::<_, std::io::Error>(false);
+                }
+                let limit = buffer.len().min(room);
+                match reader.read(&mut buffer[..limit]).await? {
+                    0 => return Ok(true),
+                    read => response.extend_from_slice(&buffer[..read]),
+                }
+            }
+        };
+        tokio::pin!(send, receive);
+        let mut sending = true;
+        loop {
+            tokio::select! {
+                sent = &mut send, if sending => {
+                    bytes_sent = sent.context("Failed to send recorded data")?;
+                    sending = false;
+                }
+                received = &mut receive => {
+                    closed_by_server = received.context("Failed to read response")?;
+                    break;
+                }
+                _ = tokio::time::sleep_until(deadline) => break,
+            }
+        }
+    }
+    let matches_recording =
+        !recording.truncated && response == recording.bytes_in(RecordedDirection::ServerToClient);
+    Ok(ReplayReport {
+        recording_id: recording.id,
+        target,
+        bytes_sent,
+        bytes_received: response.len(),
+        closed_by_server,
+        matches_recording,
+        response,
+    })
+}
+mod base64_data {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+    use serde::{Deserialize, Deserializer, Serializer};
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
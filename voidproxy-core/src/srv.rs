@@ -0,0 +1,68 @@
+use crate::backend::{Backend, BackendProvider, Resolution, ResolveFuture};
+use anyhow::{Context, Result};
+use hickory_resolver::TokioResolver;
+use std::net::SocketAddr;
+use std::time::Instant;
+/**
+ * Backend provider resolving DNS SRV records.
+ *
+ * Configured as `srv://_service._proto.example.com`. Record priority and
+ * weight are carried over to the backends, and the set is resolved again
+ * once the record TTL expires.
+ */
+pub struct SrvProvider {
+    name: String,
+}
+impl SrvProvider {
+    pub fn from_target(target: &str) -> Result<Self> {
+        let name = target.trim_end_matches('/');
+        if name.is_empty() || name.contains('/') {
+            return Err(anyhow::anyhow!(
+                "SRV backend must be srv://_service._proto.example.com"
+            ));
+        }
+        Ok(Self {
+            name: name.to_string(),
+        })
+    }
+    async fn fetch(&self) -> Result<Resolution> {
+        let resolver = TokioResolver::builder_tokio()
+            .context("Failed to read system DNS configuration")?
+            .build();
+        let records = resolver
+            .srv_lookup(self.name.as_str())
+            .await
+            .with_context(|| format!("SRV lookup for {} failed", self.name))?;
+        let mut valid_until = records.as_lookup().valid_until();
+        let mut backends = Vec::new();
+        for record in records.iter() {
+            let addresses = match resolver.lookup_ip(record.target().clone()).await {
+                Ok(addresses) => addresses,
+                Err(e) => {
+                    tracing::warn!("Failed to resolve SRV target {}: {}", record.target(), e);
+                    continue;
+                }
+            };
+            valid_until = valid_until.min(addresses.valid_until());
+            for ip in addresses.iter() {
+                backends.push(Backend {
+                    weight: u32::from(record.weight()),
+                    priority: record.priority(),
+                    ..Backend::new(SocketAddr::new(ip, record.port()))
+                });
+            }
+        }
+        Ok(Resolution {
+            backends,
+            ttl: Some(valid_until.saturating_duration_since(Instant::now())),
+        })
+    }
+}
+impl BackendProvider for SrvProvider {
+    fn resolve(&self) -> ResolveFuture<'_> {
+        Box::pin(self.fetch())
+    }
+    fn describe(&self) -> String {
+        format!("srv://{}", self.name)
+    }
+}
@@ -0,0 +1,819 @@
+use crate::backup::BackupSchedule;
+use crate::config::InstanceDefaults;
+use crate::global_filter::GlobalFilterConfig;
+use crate::naming::NamingPolicy;
+use crate::instance::{InstanceStatus, ProxyInstance};
+use crate::metrics::CounterSnapshot;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Persistent data structure for storing proxy instance configurations.
+ *
+ * Contains all instances along with metadata about the configuration
+ * including version information and timestamps. `profiles` declares the
+ * environment names instances may carry destination overrides for,
+ * `trash` keeps deleted instances until they are restored or purged,
+ * `defaults` fills in the fields API clients leave out, `global_filter`
+ * is checked before the IP filter of every instance and `naming` sets the
+ * rules instance names follow.
+ */
+pub struct PersistentData {
+    pub instances: Vec<PersistentInstance>,
+    #[serde(default)]
+    pub trash: Vec<PersistentInstance>,
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    #[serde(default, skip_serializing_if = "InstanceDefaults::is_default")]
+    pub defaults: InstanceDefaults,
+    #[serde(default, skip_serializing_if = "GlobalFilterConfig::is_empty")]
+    pub global_filter: GlobalFilterConfig,
+    #[serde(default, skip_serializing_if = "NamingPolicy::is_default")]
+    pub naming: NamingPolicy,
+    #[serde(default, skip_serializing_if = "BackupSchedule::is_default")]
+    pub backup: BackupSchedule,
+    pub version: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/**
+ * Persistent representation of a proxy instance for storage.
+ *
+ * Stores the essential configuration and state information for a proxy
+ * instance that can be serialized to and deserialized from storage.
+ */
+pub struct PersistentInstance {
+    pub id: Uuid,
+    pub name: String,
+    pub config: crate::config::Config,
+    pub status: InstanceStatus,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub auto_start: bool,
+    #[serde(default)]
+    pub locked: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+}
+impl From<ProxyInstance> for PersistentInstance {
+    fn from(instance: ProxyInstance) -> Self {
+        Self {
+            id: instance.id,
+            name: instance.name,
+            config: instance.config,
+            status: instance.status,
+            created_at: instance.created_at.to_rfc3339(),
+            started_at: instance.started_at.map(|dt| dt.to_rfc3339()),
+            auto_start: instance.auto_start,
+            locked: instance.locked,
+            deleted_at: instance.deleted_at.map(|dt| dt.to_rfc3339()),
+        }
+    }
+}
+impl TryFrom<PersistentInstance> for ProxyInstance {
+    type Error = anyhow::Error;
+    fn try_from(persistent: PersistentInstance) -> Result<Self> {
+        let instance = Self {
+            id: persistent.id,
+            name: persistent.name,
+            config: persistent.config,
+            status: persistent.status,
+            created_at: chrono::DateTime::parse_from_rfc3339(&persistent.created_at)?
+                .with_timezone(&chrono::Utc),
+            started_at: persistent
+                .started_at
+                .map(|s| chrono::DateTime::parse_from_rfc3339(&s))
+                .transpose()?
+                .map(|dt| dt.with_timezone(&chrono::Utc)),
+            auto_start: persistent.auto_start,
+            locked: persistent.locked,
+            deleted_at: persistent
+                .deleted_at
+                .map(|s| chrono::DateTime::parse_from_rfc3339(&s))
+                .transpose()?
+                .map(|dt| dt.with_timezone(&chrono::Utc)),
+            last_error: crate::instance::ErrorSlot::default(),
+            metrics: Arc::new(crate::metrics::InstanceMetrics::new()),
+            status_history: std::collections::VecDeque::new(),
+        };
+        Ok(instance)
+    }
+}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+/**
+ * Lifetime traffic counters per instance, kept in a file next to the
+ * configuration so periodic saves do not rewrite it.
+ */
+pub struct PersistentStats {
+    pub updated_at: String,
+    pub instances: BTreeMap<Uuid, CounterSnapshot>,
+}
+/**
+ * Manages persistent storage of proxy instance configurations.
+ *
+ * Handles loading, saving, and managing proxy instance configurations
+ * in a persistent storage format with backup capabilities.
+ */
+pub struct StorageManager {
+    config_path: PathBuf,
+    data: RwLock<PersistentData>,
+}
+impl StorageManager {
+    pub fn new(config_path: PathBuf) -> Self {
+        Self {
+            config_path,
+            data: RwLock::new(PersistentData {
+                instances: Vec::new(),
+                trash: Vec::new(),
+                profiles: Vec::new(),
+                defaults: InstanceDefaults::default(),
+                global_filter: GlobalFilterConfig::default(),
+                naming: NamingPolicy::default(),
+                backup: BackupSchedule::default(),
+                version: "1.0".to_string(),
+                created_at: chrono::Utc::now().to_rfc3339(),
+                updated_at: chrono::Utc::now().to_rfc3339(),
+            }),
+        }
+    }
+    pub async fn load(&self) -> Result<Vec<ProxyInstance>> {
+        if !self.config_path.exists() {
+            info!("No existing configuration file found, starting fresh");
+            return Ok(Vec::new());
+        }
+        debug!("Loading configuration from: {:?}", self.config_path);
+        let content = fs::read_to_string(&self.config_path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read config file: {}", e))?;
+        let persistent_data: PersistentData = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse config file: {}", e))?;
+        persistent_data.naming.validate()?;
+        persistent_data.backup.validate()?;
+        for field in Self::unknown_fields(&content).unwrap_or_default() {
+            warn!(
+                "Ignoring unknown setting {} in {:?}",
+                field, self.config_path
+            );
+        }
+        let mut data = self.data.write().await;
+        *data = persistent_data.clone();
+        let instances: Result<Vec<ProxyInstance>> = persistent_data
+            .instances
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect();
+        info!(
+            "Loaded {} instances from configuration",
+            instances.as_ref().map_or(0, |v| v.len())
+        );
+        instances
+    }
+    pub async fn add_instance(&self, instance: &ProxyInstance) -> Result<()> {
+        let mut data = self.data.write().await;
+        data.instances.push(instance.clone().into());
+        data.updated_at = chrono::Utc::now().to_rfc3339();
+        let content = toml::to_string_pretty(&*data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize configuration: {}", e))?;
+        fs::write(&self.config_path, content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write config file: {}", e))?;
+        debug!("Added instance {} to configuration", instance.name);
+        Ok(())
+    }
+    pub async fn update_instance(&self, instance: &ProxyInstance) -> Result<()> {
+        let mut data = self.data.write().await;
+        if let Some(index) = data.instances.iter().position(|i| i.id == instance.id) {
+            data.instances[index] = instance.clone().into();
+        } else {
+            data.instances.push(instance.clone().into());
+        }
+        data.updated_at = chrono::Utc::now().to_rfc3339();
+        let content = toml::to_string_pretty(&*data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize configuration: {}", e))?;
+        fs::write(&self.config_path, content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write config file: {}", e))?;
+        debug!("Updated instance {} in configuration", instance.name);
+        Ok(())
+    }
+    pub async fn remove_instance(&self, instance_id: Uuid) -> Result<()> {
+        let mut data = self.data.write().await;
+        let initial_len = data.instances.len();
+        data.instances.retain(|i| i.id != instance_id);
+        if data.instances.len() < initial_len {
+            data.updated_at = chrono::Utc::now().to_rfc3339();
+            let content = toml::to_string_pretty(&*data)
+                .map_err(|e| anyhow::anyhow!("Failed to serialize configuration: {}", e))?;
+            fs::write(&self.config_path, content)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to write config file: {}", e))?;
+            debug!("Removed instance {} from configuration", instance_id);
+        } else {
+            debug!("Instance {} not found for removal", instance_id);
+        }
+        Ok(())
+    }
+    /**
+     * Moves an instance from the active list to the trash, stamping it with
+     * the deletion time.
+     */
+    pub async fn trash_instance(&self, instance: &ProxyInstance) -> Result<()> {
+        let mut data = self.data.write().await;
+        data.instances.retain(|i| i.id != instance.id);
+        data.trash.retain(|i| i.id != instance.id);
+        let mut trashed = instance.clone();
+        trashed.deleted_at = Some(chrono::Utc::now());
+        data.trash.push(trashed.into());
+        data.updated_at = chrono::Utc::now().to_rfc3339();
+        let content = toml::to_string_pretty(&*data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize configuration: {}", e))?;
+        fs::write(&self.config_path, content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write config file: {}", e))?;
+        debug!("Moved instance {} to the trash", instance.name);
+        Ok(())
+    }
+    pub async fn trashed_instances(&self) -> Result<Vec<ProxyInstance>> {
+        let data = self.data.read().await;
+        data.trash.iter().cloned().map(TryInto::try_into).collect()
+    }
+    /**
+     * Removes instances from the trash, either the given one or those
+     * deleted before `deleted_before`, and returns them.
+     */
+    pub async fn take_from_trash(
+        &self,
+        id: Option<Uuid>,
+        deleted_before: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<ProxyInstance>> {
+        let mut data = self.data.write().await;
+        let trash = std::mem::take(&mut data.trash);
+        let mut taken = Vec::new();
+        for persistent in trash {
+            let instance: ProxyInstance = persistent.clone().try_into()?;
+            let matches_id = id.is_none_or(|id| instance.id == id);
+            let expired = deleted_before
+                .is_none_or(|cutoff| instance.deleted_at.is_some_and(|at| at < cutoff));
+            if matches_id && expired {
+                taken.push(instance);
+            } else {
+                data.trash.push(persistent);
+            }
+        }
+        if !taken.is_empty() {
+            data.updated_at = chrono::Utc::now().to_rfc3339();
+            let content = toml::to_string_pretty(&*data)
+                .map_err(|e| anyhow::anyhow!("Failed to serialize configuration: {}", e))?;
+            fs::write(&self.config_path, content)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to write config file: {}", e))?;
+        }
+        Ok(taken)
+    }
+    pub async fn export_config(&self) -> Result<String> {
+        let data = self.data.read().await;
+        let content = toml::to_string_pretty(&*data)
+            .map_err(|e| anyhow::anyhow!("Failed to export configuration: {}", e))?;
+        Ok(content)
+    }
+    pub async fn import_config(&self, config_content: &str) -> Result<()> {
+        let persistent_data: PersistentData = toml::from_str(config_content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse imported configuration: {}", e))?;
+        persistent_data.naming.validate()?;
+        for field in Self::unknown_fields(config_content).unwrap_or_default() {
+            warn!(
+                "Ignoring unknown setting {} in imported configuration",
+                field
+            );
+        }
+        let mut data = self.data.write().await;
+        *data = persistent_data;
+        data.updated_at = chrono::Utc::now().to_rfc3339();
+        let content = toml::to_string_pretty(&*data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize imported configuration: {}", e))?;
+        fs::write(&self.config_path, content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write imported configuration: {}", e))?;
+        info!(
+            "Imported configuration with {} instances",
+            data.instances.len()
+        );
+        Ok(())
+    }
+    /**
+     * Reads the profile names declared in the configuration file, without
+     * loading its instances.
+     */
+    pub async fn declared_profiles(&self) -> Result<Vec<String>> {
+        if !self.config_path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = fs::read_to_string(&self.config_path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read config file: {}", e))?;
+        let persistent_data: PersistentData = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse config file: {}", e))?;
+        Ok(persistent_data.profiles)
+    }
+    pub async fn instance_defaults(&self) -> InstanceDefaults {
+        self.data.read().await.defaults.clone()
+    }
+    pub async fn naming_policy(&self) -> NamingPolicy {
+        self.data.read().await.naming.clone()
+    }
+    pub async fn global_filter(&self) -> GlobalFilterConfig {
+        self.data.read().await.global_filter.clone()
+    }
+    pub async fn set_global_filter(&self, global_filter: GlobalFilterConfig) -> Result<()> {
+        let mut data = self.data.write().await;
+        data.global_filter = global_filter;
+        data.updated_at = chrono::Utc::now().to_rfc3339();
+        let content = toml::to_string_pretty(&*data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize configuration: {}", e))?;
+        fs::write(&self.config_path, content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write config file: {}", e))?;
+        debug!("Updated the global IP filter in configuration");
+        Ok(())
+    }
+    pub async fn backup_schedule(&self) -> BackupSchedule {
+        self.data.read().await.backup.clone()
+    }
+    pub async fn set_backup_schedule(&self, backup: BackupSchedule) -> Result<()> {
+        let mut data = self.data.write().await;
+        data.backup = backup;
+        data.updated_at = chrono::Utc::now().to_rfc3339();
+        let content = toml::to_string_pretty(&*data)
+            .map_err(|e| anyhow::anyhow!("Failed to serialize configuration: {}", e))?;
+        fs::write(&self.config_path, content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write config file: {}", e))?;
+        debug!("Updated the backup schedule in configuration");
+        Ok(())
+    }
+    pub async fn updated_at(&self) -> String {
+        self.data.read().await.updated_at.clone()
+    }
+    pub async fn get_backup_path(&self) -> PathBuf {
+        let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S").to_string();
+        self.backup_path_for(&timestamp)
+            .expect("timestamp is a valid backup ID")
+    }
+    /**
+     * Path of the backup with the given ID, the timestamp part of its file
+     * name (e.g. `20240131_120000`).
+     */
+    pub fn backup_path_for(&self, backup_id: &str) -> Result<PathBuf> {
+        if backup_id.is_empty()
+            || !backup_id
+                .chars()
+                .all(|c| c.is_ascii_digit() || c == '_')
+        {
+            return Err(anyhow::anyhow!("Invalid backup ID: {}", backup_id));
+        }
+        let mut backup_path = self.config_path.clone();
+        backup_path.set_extension(format!("backup_{}.toml", backup_id));
+        Ok(backup_path)
+    }
+    pub fn backup_id(backup_path: &std::path::Path) -> Option<String> {
+        let file_name = backup_path.file_name()?.to_str()?;
+        let (_, rest) = file_name.rsplit_once(".backup_")?;
+        rest.strip_suffix(".toml").map(str::to_string)
+    }
+    /**
+     * Reads the configuration file, or the backup with the given ID, as it
+     * is on disk. Returns `None` when it does not exist.
+     */
+    pub async fn read_stored_config(&self, backup_id: Option<&str>) -> Result<Option<String>> {
+        let path = match backup_id {
+            Some(backup_id) => self.backup_path_for(backup_id)?,
+            None => self.config_path.clone(),
+        };
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read {:?}: {}", path, e))?;
+        Ok(Some(content))
+    }
+    pub fn parse_instances(config_content: &str) -> Result<Vec<ProxyInstance>> {
+        let persistent_data: PersistentData = toml::from_str(config_content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse configuration: {}", e))?;
+        persistent_data
+            .instances
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect()
+    }
+    /**
+     * Dotted paths of the keys in a configuration that no setting reads,
+     * such as a misspelled `idle_timout_secs`, which parsing would
+     * otherwise drop without a trace.
+     */
+    pub fn unknown_fields(config_content: &str) -> Result<Vec<String>> {
+        let raw: toml::Value = toml::from_str(config_content)?;
+        let parsed: PersistentData = raw.clone().try_into()?;
+        let kept = toml::Value::try_from(&parsed)?;
+        let mut unknown = Vec::new();
+        collect_unknown(&raw, &raw, &kept, &mut Vec::new(), &mut unknown);
+        Ok(unknown)
+    }
+    pub fn stats_path(&self) -> PathBuf {
+        self.config_path.with_extension("stats.json")
+    }
+    pub async fn load_stats(&self) -> Result<BTreeMap<Uuid, CounterSnapshot>> {
+        let path = self.stats_path();
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = fs::read_to_string(&path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read stats file {:?}: {}", path, e))?;
+        let stats: PersistentStats = serde_json::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse stats file {:?}: {}", path, e))?;
+        Ok(stats.instances)
+    }
+    pub async fn save_stats(&self, instances: BTreeMap<Uuid, CounterSnapshot>) -> Result<()> {
+        let path = self.stats_path();
+        let stats = PersistentStats {
+            updated_at: chrono::Utc::now().to_rfc3339(),
+            instances,
+        };
+        let content = serde_json::to_string_pretty(&stats)?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write stats file {:?}: {}", tmp_path, e))?;
+        fs::rename(&tmp_path, &path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to replace stats file {:?}: {}", path, e))?;
+        debug!("Saved stats of {} instances", stats.instances.len());
+        Ok(())
+    }
+    pub async fn create_backup(&self) -> Result<PathBuf> {
+        let backup_path = self.get_backup_path().await;
+        let content = self.export_config().await?;
+        fs::write(&backup_path, content)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create backup: {}", e))?;
+        info!("Created backup at: {:?}", backup_path);
+        Ok(backup_path)
+    }
+    /**
+     * Backups of the configuration file, oldest first.
+     */
+    pub async fn list_backups(&self) -> Result<Vec<PathBuf>> {
+        let prefix = match self.config_path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(stem) => format!("{}.backup_", stem),
+            None => return Ok(Vec::new()),
+        };
+        let dir = match self.config_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(anyhow::anyhow!("Failed to list {:?}: {}", dir, e)),
+        };
+        let mut backups = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if let Some(file_name) = path.file_name().and_then(|name| name.to_str())
+                && file_name.starts_with(&prefix)
+                && Self::backup_id(&path).is_some_and(|id| self.backup_path_for(&id).is_ok())
+            {
+                backups.push(path);
+            }
+        }
+        backups.sort();
+        Ok(backups)
+    }
+    /**
+     * Deletes all but the `keep` most recent backups and returns the deleted
+     * paths.
+     */
+    pub async fn prune_backups(&self, keep: usize) -> Result<Vec<PathBuf>> {
+        let mut backups = self.list_backups().await?;
+        let excess = backups.len().saturating_sub(keep);
+        backups.truncate(excess);
+        for path in &backups {
+            fs::remove_file(path)
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to delete backup {:?}: {}", path, e))?;
+            debug!("Deleted old backup {:?}", path);
+        }
+        Ok(backups)
+    }
+}
+enum Step<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+/**
+ * Walks the parsed file next to what survives a parse and re-serialize
+ * round trip. A key missing from the latter is either unknown or a setting
+ * left at a value that is not written back (an empty list, the defaults);
+ * only an unknown key still parses whatever value it holds.
+ */
+fn collect_unknown<'a>(
+    root: &toml::Value,
+    raw: &'a toml::Value,
+    kept: &toml::Value,
+    path: &mut Vec<Step<'a>>,
+    unknown: &mut Vec<String>,
+) {
+    match (raw, kept) {
+        (toml::Value::Table(raw), toml::Value::Table(kept)) => {
+            for (key, value) in raw {
+                path.push(Step::Key(key));
+                match kept.get(key) {
+                    Some(kept) => collect_unknown(root, value, kept, path, unknown),
+                    None if accepts_any_value(root, path) => unknown.push(render_path(path)),
+                    None => {}
+                }
+                path.pop();
+            }
+        }
+        (toml::Value::Array(raw), toml::Value::Array(kept)) => {
+            for (index, (value, kept)) in raw.iter().zip(kept).enumerate() {
+                path.push(Step::Index(index));
+                collect_unknown(root, value, kept, path, unknown);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+fn accepts_any_value(root: &toml::Value, path: &[Step]) -> bool {
+    [
+        toml::Value::Boolean(true),
+        toml::Value::String(String::new()),
+    ]
+    .into_iter()
+    .all(|probe| {
+        let mut candidate = root.clone();
+        let slot = path
+            .iter()
+            .try_fold(&mut candidate, |value, step| match step {
+                Step::Key(key) => value.get_mut(*key),
+                Step::Index(index) => value.get_mut(*index),
+            });
+        let Some(slot) = slot else {
+            return false;
+        };
+        *slot = probe;
+        candidate.try_into::<PersistentData>().is_ok()
+    })
+}
+fn render_path(path: &[Step]) -> String {
+    let mut rendered = String::new();
+    for step in path {
+        match step {
+            Step::Key(key) if rendered.is_empty() => rendered.push_str(key),
+            Step::Key(key) => {
+                rendered.push('.');
+                rendered.push_str(key);
+            }
+            Step::Index(index) => rendered.push_str(&format!("[{}]", index)),
+        }
+    }
+    rendered
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{LogLevel, Protocol};
+    use crate::instance::CreateInstanceRequest;
+    use std::net::{IpAddr, Ipv4Addr};
+    #[tokio::test]
+    async fn test_storage_manager_save_and_load() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("test_config.toml");
+        let storage = StorageManager::new(config_path.clone());
+        let request = CreateInstanceRequest {
+            name: "Test Instance".to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            listen_port: 8080,
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100)),
+            dst_port: 80,
+            protocol: Protocol::Tcp,
+            auto_start: false,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        };
+        let instance = ProxyInstance::new(
+            request.name.clone(),
+            request.to_config(),
+            request.auto_start,
+        );
+        storage.add_instance(&instance).await.unwrap();
+        let loaded_instances = storage.load().await.unwrap();
+        assert_eq!(loaded_instances.len(), 1);
+        assert_eq!(loaded_instances[0].name, instance.name);
+        assert_eq!(loaded_instances[0].id, instance.id);
+    }
+    #[tokio::test]
+    async fn test_storage_manager_export_import() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("test_config.toml");
+        let storage = StorageManager::new(config_path.clone());
+        let request1 = CreateInstanceRequest {
+            name: "Instance 1".to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            listen_port: 8080,
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100)),
+            dst_port: 80,
+            protocol: Protocol::Tcp,
+            auto_start: true,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        };
+        let request2 = CreateInstanceRequest {
+            name: "Instance 2".to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            listen_port: 8081,
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 101)),
+            dst_port: 443,
+            protocol: Protocol::Udp,
+            auto_start: false,
+            allow_list: Some(vec![IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10))]),
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        };
+        let instance1 = ProxyInstance::new(
+            request1.name.clone(),
+            request1.to_config(),
+            request1.auto_start,
+        );
+        let instance2 = ProxyInstance::new(
+            request2.name.clone(),
+            request2.to_config(),
+            request2.auto_start,
+        );
+        storage.add_instance(&instance1).await.unwrap();
+        storage.add_instance(&instance2).await.unwrap();
+        let exported_config = storage.export_config().await.unwrap();
+        let config_path2 = temp_dir.path().join("test_config2.toml");
+        let storage2 = StorageManager::new(config_path2.clone());
+        storage2.import_config(&exported_config).await.unwrap();
+        let imported_instances = storage2.load().await.unwrap();
+        assert_eq!(imported_instances.len(), 2);
+        let names: Vec<String> = imported_instances.iter().map(|i| i.name.clone()).collect();
+        assert!(names.contains(&"Instance 1".to_string()));
+        assert!(names.contains(&"Instance 2".to_string()));
+    }
+    #[tokio::test]
+    async fn test_storage_manager_backup() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("test_config.toml");
+        let storage = StorageManager::new(config_path.clone());
+        let request = CreateInstanceRequest {
+            name: "Backup Test Instance".to_string(),
+            listen_ip: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            listen_port: 8080,
+            dst_ip: IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100)),
+            dst_port: 80,
+            protocol: Protocol::Tcp,
+            auto_start: false,
+            allow_list: None,
+            deny_list: None,
+            allow_hosts: Vec::new(),
+            deny_hosts: Vec::new(),
+            connect_timeout_secs: 30,
+            idle_timeout_secs: 300,
+            log_level: LogLevel::Info,
+            bind_device: None,
+            bind_nonlocal: false,
+            backends: Vec::new(),
+            wasm_filters: Vec::new(),
+            listen_backlog: None,
+            bind_src_ip: None,
+            bind_src_device: None,
+            max_inflight_bytes: None,
+            tls: None,
+            tunnel: None,
+            slow_log: None,
+            max_concurrent_handlers: None,
+            buffer_pool_size: None,
+            max_concurrent_buffers: None,
+            ip_cache_size: None,
+            ip_cache_ttl_secs: None,
+            propagate_unreachable: false,
+            log_limit: None,
+            mirror: None,
+            max_connection_lifetime_secs: None,
+            verify_destination_on_start: Default::default(),
+        };
+        let instance = ProxyInstance::new(
+            request.name.clone(),
+            request.to_config(),
+            request.auto_start,
+        );
+        storage.add_instance(&instance).await.unwrap();
+        let backup_path = storage.create_backup().await.unwrap();
+        assert!(backup_path.exists());
+        let backup_content = fs::read_to_string(&backup_path).await.unwrap();
+        assert!(backup_content.contains("Backup Test Instance"));
+    }
+    #[tokio::test]
+    async fn test_storage_manager_global_filter() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("test_config.toml");
+        let storage = StorageManager::new(config_path.clone());
+        let global_filter = GlobalFilterConfig {
+            allow: Vec::new(),
+            deny: vec!["203.0.113.0/24".parse().unwrap(), "198.51.100.7/32".parse().unwrap()],
+        };
+        storage.set_global_filter(global_filter.clone()).await.unwrap();
+        let reloaded = StorageManager::new(config_path);
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.global_filter().await, global_filter);
+    }
+}
@@ -0,0 +1,1252 @@
+use crate::backend::BackendSet;
+use crate::backpressure;
+use crate::buffer_pool::BufferPool;
+use crate::config::{Config, TunnelMode};
+use crate::connection_registry::ConnectionRegistry;
+use crate::events::{Event, EventKind};
+use crate::handoff::SocketKind;
+use crate::host_acl::HostAcl;
+use crate::happy_eyeballs;
+use crate::instance::{ErrorCategory, ErrorSlot, InstanceState, InstanceStatus};
+use crate::log_limit::{LogClass, LogLimiter};
+use crate::metrics::{InstanceMetrics, SlowLogEntry};
+use crate::middleware::{
+    CloseReason, ConnectionContext, ConnectionId, Direction, MiddlewareChain, Transport, Verdict,
+};
+use crate::tls::TlsTerminator;
+use crate::tunnel::TunnelClient;
+use anyhow::{Context, Result};
+use socket2::{SockRef, Socket};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+#[derive(Clone)]
+struct TcpConnectionHandler {
+    connection_id: ConnectionId,
+    config: Arc<Config>,
+    dst_addrs: Vec<SocketAddr>,
+    instance_id: Uuid,
+    instance: Option<InstanceState>,
+    buffer_pool: Arc<BufferPool>,
+    cancel_token: Arc<CancellationToken>,
+    middleware: MiddlewareChain,
+    tls: Option<Arc<TlsTerminator>>,
+    tunnel: Option<Arc<TunnelClient>>,
+    connections: Arc<ConnectionRegistry>,
+    log_limiter: Arc<LogLimiter>,
+}
+type BoxedReader = Box<dyn AsyncRead + Unpin + Send>;
+type BoxedWriter = Box<dyn AsyncWrite + Unpin + Send>;
+const AGENT_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const AGENT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+/**
+ * How often a relay direction adds the bytes it copied to the instance
+ * counters while the connection stays open. The rest is added when the
+ * direction ends.
+ */
+const METRICS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+const ACCEPT_MIN_BACKOFF: Duration = Duration::from_millis(10);
+const ACCEPT_MAX_BACKOFF: Duration = Duration::from_secs(1);
+/**
+ * Consecutive failed accepts (e.g. out of file descriptors) after which the
+ * instance is reported in `Error` status until an accept succeeds again.
+ */
+const ACCEPT_ERROR_THRESHOLD: u32 = 10;
+#[derive(Clone)]
+/**
+ * TCP proxy implementation for forwarding TCP connections.
+ *
+ * Handles TCP proxy operations including connection forwarding,
+ * traffic monitoring, and resource management.
+ */
+pub struct TcpProxy {
+    config: Arc<Config>,
+    instance_id: Uuid,
+    instances: crate::instance::InstanceManager,
+    ip_cache: Arc<crate::ip_cache::IpCache>,
+    host_acl: Arc<HostAcl>,
+    backends: Option<Arc<BackendSet>>,
+    middleware: MiddlewareChain,
+    connections: Arc<ConnectionRegistry>,
+    log_limiter: Arc<LogLimiter>,
+    state: Option<InstanceState>,
+    events: Option<broadcast::Sender<Event>>,
+}
+impl TcpProxy {
+    pub fn new(
+        config: Arc<Config>,
+        instance_id: Uuid,
+        instances: crate::instance::InstanceManager,
+    ) -> Self {
+        let backends = BackendSet::from_config(&config.proxy);
+        let idle_timeout = Duration::from_secs(config.proxy.idle_timeout_secs);
+        Self {
+            ip_cache: Arc::new(crate::ip_cache::IpCache::for_proxy(&config.proxy)),
+            host_acl: Arc::new(HostAcl::for_config(&config)),
+            connections: Arc::new(ConnectionRegistry::new(idle_timeout)),
+            log_limiter: Arc::new(LogLimiter::for_proxy(&config.proxy)),
+            config,
+            instance_id,
+            instances,
+            backends,
+            middleware: MiddlewareChain::default(),
+            state: None,
+            events: None,
+        }
+    }
+    pub fn with_middleware(mut self, middleware: MiddlewareChain) -> Self {
+        self.middleware = middleware;
+        self
+    }
+    /**
+     * Records into the given instance state instead of looking the instance
+     * up in the instance map when the proxy starts.
+     */
+    pub fn with_state(mut self, state: InstanceState) -> Self {
+        self.state = Some(state);
+        self
+    }
+    /**
+     * Announces the status changes caused by listener failures on `events`.
+     */
+    pub fn with_events(mut self, events: broadcast::Sender<Event>) -> Self {
+        self.events = Some(events);
+        self
+    }
+    /**
+     * Session metrics from the connections currently open.
+     */
+    pub fn get_session_metrics(&self) -> crate::metrics::SessionMetrics {
+        crate::metrics::SessionMetrics {
+            session_timeout_seconds: self.connections.idle_timeout().as_secs(),
+            cleanup_interval_seconds: 0,
+            active_sessions: self.connections.active_count(),
+            longest_idle_seconds: self.connections.longest_idle().as_secs(),
+        }
+    }
+    pub async fn run_with_token(&self, cancel_token: Arc<CancellationToken>) -> Result<()> {
+        if self
+            .config
+            .proxy
+            .tunnel
+            .as_ref()
+            .is_some_and(|tunnel| tunnel.mode == TunnelMode::Agent)
+        {
+            return self.run_agent(cancel_token).await;
+        }
+        let listen_addr =
+            SocketAddr::new(self.config.proxy.listen_ip, self.config.proxy.listen_port);
+        let listener = match crate::handoff::take_tcp_listener(listen_addr) {
+            Some(listener) => {
+                listener.set_nonblocking(true)?;
+                info!("Reusing inherited TCP listener on {}", listen_addr);
+                TcpListener::from_std(listener).context("Failed to adopt inherited TCP listener")?
+            }
+            None => crate::listener::bind_tcp_listener(listen_addr, &self.config.proxy)
+                .context("Failed to bind TCP listener")?,
+        };
+        #[cfg(unix)]
+        crate::handoff::register_listener(
+            SocketKind::Tcp,
+            listen_addr,
+            std::os::fd::AsRawFd::as_raw_fd(&listener),
+            Some(self.instance_id),
+        );
+        let tls = match self.config.proxy.tls {
+            Some(ref tls) => Some(Arc::new(
+                TlsTerminator::from_config(tls).context("Failed to load TLS configuration")?,
+            )),
+            None => None,
+        };
+        let instance = self.instance_state().await;
+        let metrics = instance.as_ref().map(|instance| instance.metrics.clone());
+        let last_error = instance.as_ref().map(|instance| instance.last_error.clone());
+        let tunnel = match self.config.proxy.tunnel {
+            Some(ref tunnel) if matches!(tunnel.mode, TunnelMode::Edge | TunnelMode::Gateway) => {
+                Some(Arc::new(
+                    TunnelClient::new(self.config.clone(), metrics.clone())
+                        .context("Failed to set up tunnel")?,
+                ))
+            }
+            _ => None,
+        };
+        if let (Some(agent_addr), Some(tunnel)) = (
+            self.config.proxy.tunnel.as_ref().and_then(|tunnel| tunnel.agent_addr),
+            tunnel.clone(),
+        ) {
+            let agent_listener = crate::listener::bind_tcp_listener(agent_addr, &self.config.proxy)
+                .context("Failed to bind tunnel agent listener")?;
+            info!("Accepting tunnel agents on {}", agent_addr);
+            tokio::spawn(Self::accept_agents(
+                agent_listener,
+                tunnel,
+                tls.clone(),
+                self.config.clone(),
+                metrics.clone(),
+                cancel_token.clone(),
+            ));
+        }
+        info!("TCP proxy listening on {}", listen_addr);
+        match &self.backends {
+            Some(backends) => {
+                backends
+                    .start_refresh(cancel_token.clone(), metrics.clone())
+                    .await;
+                info!("Balancing across {} backends", backends.backends().len());
+            }
+            None => info!(
+                "Forwarding to {}:{}",
+                self.config.proxy.dst_ip, self.config.proxy.dst_port
+            ),
+        }
+        self.host_acl
+            .start_refresh(self.ip_cache.clone(), cancel_token.clone())
+            .await;
+        let handler_limit = self
+            .config
+            .proxy
+            .max_concurrent_handlers
+            .map(|limit| backpressure::HandlerLimit::new(limit, metrics.clone()));
+        let buffer_pool = self.buffer_pool(metrics.as_ref());
+        let drain_token = crate::handoff::drain_token();
+        let mut accept_failures = 0u32;
+        if let Some(ref tls) = tls {
+            Self::set_certificate_expiry(metrics.as_deref(), tls);
+            let tls = tls.clone();
+            let metrics = metrics.clone();
+            let last_error = last_error.clone();
+            let cancel_token = cancel_token.clone();
+            tokio::spawn(async move {
+                let mut checks = tokio::time::interval(crate::tls::RELOAD_CHECK_INTERVAL);
+                loop {
+                    tokio::select! {
+                        _ = cancel_token.cancelled() => break,
+                        _ = checks.tick() => match tls.reload_if_changed() {
+                            Ok(true) => {
+                                info!("Reloaded TLS certificate, expires {:?}", tls.expires_at());
+                                Self::set_certificate_expiry(metrics.as_deref(), &tls);
+                            }
+                            Ok(false) => {}
+                            Err(e) => {
+                                warn!("Failed to reload TLS certificate, keeping the current one: {:#}", e);
+                                if let Some(ref last_error) = last_error {
+                                    last_error.set(ErrorCategory::Config, format!("TLS reload: {:#}", e));
+                                }
+                            }
+                        },
+                    }
+                }
+            });
+        }
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => {
+                    info!("TCP proxy shutdown signal received for instance {}", self.instance_id);
+                    break;
+                }
+                _ = drain_token.cancelled() => {
+                    info!("TCP proxy for instance {} stopped accepting, draining connections", self.instance_id);
+                    break;
+                }
+                accept_result = listener.accept() => {
+                    match accept_result {
+                        Ok((stream, peer_addr)) => {
+                            if cancel_token.is_cancelled() {
+                                break;
+                            }
+                            if accept_failures >= ACCEPT_ERROR_THRESHOLD {
+                                info!("TCP listener of instance {} recovered after {} failed accepts", self.instance_id, accept_failures);
+                                self.set_listener_failing(false).await;
+                            }
+                            accept_failures = 0;
+                            if crate::blocklist::global().check(&peer_addr.ip()) {
+                                debug!("Connection rejected from {}: IP on a blocklist feed", peer_addr);
+                                continue;
+                            }
+                            if !crate::global_filter::global().is_allowed(&peer_addr.ip()) {
+                                if let Some(suppressed) = self.log_limiter.admit(LogClass::Rejected) {
+                                    warn!("Connection rejected from {}: IP denied by the global filter{}", peer_addr, suppressed);
+                                }
+                                continue;
+                            }
+                            let resolved_hosts = self.host_acl.resolved();
+                            let ip_allowed = self.ip_cache.check_ip(&peer_addr.ip(), |ip| {
+                                self.config.is_ip_allowed_with(ip, &resolved_hosts)
+                            }).await;
+                            if !ip_allowed {
+                                if let Some(suppressed) = self.log_limiter.admit(LogClass::Rejected) {
+                                    warn!("Connection rejected from {}: IP not allowed{}", peer_addr, suppressed);
+                                }
+                                continue;
+                            }
+                            let Some(dst_addrs) = self.pick_destination(peer_addr, last_error.as_ref()) else {
+                                continue;
+                            };
+                            let permit = match handler_limit {
+                                Some(ref limit) => tokio::select! {
+                                    _ = cancel_token.cancelled() => break,
+                                    permit = limit.acquire() => Some(permit),
+                                },
+                                None => None,
+                            };
+                            let handler = self.connection_handler(dst_addrs, instance.clone(), &buffer_pool, &cancel_token, tls.clone(), tunnel.clone());
+                            let connection_id = handler.connection_id;
+                            let connection = Self::track_connection(
+                                Self::handle_connection_with_token(stream, peer_addr, handler),
+                                connection_id,
+                                peer_addr,
+                                metrics.clone(),
+                                last_error.clone(),
+                                self.log_limiter.clone(),
+                            );
+                            tokio::spawn(async move {
+                                connection.await;
+                                drop(permit);
+                            });
+                        }
+                        Err(e) => {
+                            if cancel_token.is_cancelled() {
+                                break;
+                            }
+                            accept_failures = accept_failures.saturating_add(1);
+                            if let Some(suppressed) = self.log_limiter.admit(LogClass::Listener) {
+                                error!("Failed to accept TCP connection: {}{}", e, suppressed);
+                            }
+                            if let Some(ref metrics) = metrics {
+                                metrics.accept_errors.fetch_add(1, Ordering::Relaxed);
+                            }
+                            if accept_failures == ACCEPT_ERROR_THRESHOLD {
+                                warn!("TCP listener of instance {} keeps failing, retrying with backoff", self.instance_id);
+                                if let Some(ref last_error) = last_error {
+                                    last_error.set(ErrorCategory::Listener, format!("Failed to accept connections: {}", e));
+                                }
+                                self.set_listener_failing(true).await;
+                            }
+                            tokio::select! {
+                                _ = cancel_token.cancelled() => break,
+                                _ = tokio::time::sleep(accept_backoff(accept_failures)) => {}
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        crate::handoff::unregister_listener(SocketKind::Tcp, listen_addr);
+        if let Some(ref metrics) = metrics {
+            metrics.certificate_expires_at.store(0, Ordering::Relaxed);
+        }
+        info!("TCP proxy stopped for instance {}", self.instance_id);
+        Ok(())
+    }
+    /**
+     * Moves a running instance to `Error` while its listener keeps failing
+     * and back to `Running` once it recovers. Stopping instances are left
+     * alone.
+     */
+    async fn set_listener_failing(&self, failing: bool) {
+        if let Some(instance) = self.instances.write().await.get_mut(&self.instance_id) {
+            let to = match (instance.status, failing) {
+                (InstanceStatus::Running, true) => InstanceStatus::Error,
+                (InstanceStatus::Error, false) => InstanceStatus::Running,
+                _ => return,
+            };
+            if let Ok(transition) = instance.transition(to)
+                && let Some(ref events) = self.events
+            {
+                let _ = events.send(Event::new(EventKind::InstanceStatusChanged {
+                    instance_id: self.instance_id,
+                    from: transition.from,
+                    to: transition.to,
+                }));
+            }
+        }
+    }
+    async fn instance_state(&self) -> Option<InstanceState> {
+        if let Some(ref state) = self.state {
+            return Some(state.clone());
+        }
+        self.instances
+            .read()
+            .await
+            .get(&self.instance_id)
+            .map(|instance| instance.state())
+    }
+    /**
+     * Upstream addresses for a new connection, or `None` when no backend is
+     * healthy.
+     */
+    fn pick_destination(
+        &self,
+        peer_addr: SocketAddr,
+        last_error: Option<&ErrorSlot>,
+    ) -> Option<Vec<SocketAddr>> {
+        match &self.backends {
+            Some(backends) => match backends.pick() {
+                Some(addr) => Some(backends.connect_candidates(addr)),
+                None => {
+                    if let Some(suppressed) = self.log_limiter.admit(LogClass::NoBackend) {
+                        warn!("Connection from {} dropped: no healthy backend{}", peer_addr, suppressed);
+                    }
+                    if let Some(last_error) = last_error {
+                        last_error.set(ErrorCategory::Upstream, "No healthy backend");
+                    }
+                    None
+                }
+            },
+            None => Some(vec![SocketAddr::new(
+                self.config.proxy.dst_ip,
+                self.config.proxy.dst_port,
+            )]),
+        }
+    }
+    /**
+     * Buffer pool of a run, sized by the instance's settings or the
+     * footprint and accounting its buffers to the instance.
+     */
+    fn buffer_pool(&self, metrics: Option<&Arc<InstanceMetrics>>) -> Arc<BufferPool> {
+        let size = crate::footprint::Footprint::current().buffer_pool_size();
+        let proxy = &self.config.proxy;
+        let pool = BufferPool::new(
+            proxy.buffer_pool_size.map_or(size, |size| size as usize),
+            proxy.max_concurrent_buffers.map_or(size, |limit| limit as usize),
+        );
+        Arc::new(match metrics {
+            Some(metrics) => pool.with_gauge(metrics.buffer_bytes.clone()),
+            None => pool,
+        })
+    }
+    fn connection_handler(
+        &self,
+        dst_addrs: Vec<SocketAddr>,
+        instance: Option<InstanceState>,
+        buffer_pool: &Arc<BufferPool>,
+        cancel_token: &Arc<CancellationToken>,
+        tls: Option<Arc<TlsTerminator>>,
+        tunnel: Option<Arc<TunnelClient>>,
+    ) -> TcpConnectionHandler {
+        TcpConnectionHandler {
+            connection_id: ConnectionId::next(),
+            config: self.config.clone(),
+            dst_addrs,
+            instance_id: self.instance_id,
+            instance,
+            buffer_pool: buffer_pool.clone(),
+            cancel_token: cancel_token.clone(),
+            middleware: self.middleware.clone(),
+            tls,
+            tunnel,
+            connections: self.connections.clone(),
+            log_limiter: self.log_limiter.clone(),
+        }
+    }
+    /**
+     * Runs a connection, counting it as active meanwhile and recording its
+     * error.
+     */
+    async fn track_connection(
+        connection: impl Future<Output = Result<()>>,
+        connection_id: ConnectionId,
+        peer_addr: SocketAddr,
+        metrics: Option<Arc<InstanceMetrics>>,
+        last_error: Option<ErrorSlot>,
+        log_limiter: Arc<LogLimiter>,
+    ) {
+        if let Some(ref metrics) = metrics {
+            metrics.connection_opened(Transport::Tcp);
+        }
+        let result = connection.await;
+        if let Some(ref metrics) = metrics {
+            metrics.connection_closed(Transport::Tcp);
+        }
+        if let Err(e) = result {
+            if let Some(suppressed) = log_limiter.admit(LogClass::Handler) {
+                error!(
+                    "[{}] Error handling connection from {}: {}{}",
+                    connection_id, peer_addr, e, suppressed
+                );
+            }
+            if let Some(ref last_error) = last_error {
+                last_error.set(
+                    ErrorCategory::Upstream,
+                    format!("Connection from {}: {}", peer_addr, e),
+                );
+            }
+        }
+    }
+    /**
+     * Runs a tunnel agent: keeps a link to the gateway open and forwards the
+     * streams the gateway opens on it to the destination, reconnecting with
+     * backoff while the gateway is unreachable.
+     */
+    async fn run_agent(&self, cancel_token: Arc<CancellationToken>) -> Result<()> {
+        let instance = self.instance_state().await;
+        let metrics = instance.as_ref().map(|instance| instance.metrics.clone());
+        let last_error = instance.as_ref().map(|instance| instance.last_error.clone());
+        let buffer_pool = self.buffer_pool(metrics.as_ref());
+        let tunnel = TunnelClient::new(self.config.clone(), metrics.clone())
+            .context("Failed to set up tunnel")?;
+        let gateway = tunnel.peer();
+        if let Some(backends) = &self.backends {
+            backends
+                .start_refresh(cancel_token.clone(), metrics.clone())
+                .await;
+        }
+        info!("Tunnel agent for instance {} connecting to {}", self.instance_id, gateway);
+        let mut backoff = AGENT_MIN_BACKOFF;
+        loop {
+            let dialed = tokio::select! {
+                _ = cancel_token.cancelled() => break,
+                dialed = tunnel.dial() => dialed,
+            };
+            match dialed {
+                Ok(mut link) => {
+                    info!("Tunnel agent connected to {}", gateway);
+                    backoff = AGENT_MIN_BACKOFF;
+                    loop {
+                        let next = tokio::select! {
+                            _ = cancel_token.cancelled() => break,
+                            next = crate::tunnel::next_stream(&mut link) => next,
+                        };
+                        let stream = match next {
+                            Some(Ok(stream)) => stream,
+                            Some(Err(e)) => {
+                                warn!("Tunnel link to {} failed: {:#}", gateway, e);
+                                break;
+                            }
+                            None => {
+                                warn!("Tunnel link to {} closed", gateway);
+                                break;
+                            }
+                        };
+                        let Some(dst_addrs) = self.pick_destination(gateway, last_error.as_ref()) else {
+                            continue;
+                        };
+                        let handler = self.connection_handler(dst_addrs, instance.clone(), &buffer_pool, &cancel_token, None, None);
+                        let connection_id = handler.connection_id;
+                        tokio::spawn(Self::track_connection(
+                            async move {
+                                let (reader, writer) = crate::tunnel::split(stream);
+                                Self::relay(Box::new(reader), Box::new(writer), None, gateway, handler)
+                                    .await
+                            },
+                            connection_id,
+                            gateway,
+                            metrics.clone(),
+                            last_error.clone(),
+                            self.log_limiter.clone(),
+                        ));
+                    }
+                }
+                Err(e) => {
+                    warn!("Tunnel agent failed to connect to {}: {:#}", gateway, e);
+                    if let Some(ref last_error) = last_error {
+                        last_error.set(ErrorCategory::Upstream, format!("Tunnel gateway: {:#}", e));
+                    }
+                }
+            }
+            tokio::select! {
+                _ = cancel_token.cancelled() => break,
+                _ = tokio::time::sleep(backoff) => {}
+            }
+            backoff = (backoff * 2).min(AGENT_MAX_BACKOFF);
+        }
+        info!("Tunnel agent stopped for instance {}", self.instance_id);
+        Ok(())
+    }
+    /**
+     * Accepts the links of tunnel agents for a gateway, encrypted through the
+     * instance's TLS settings when present.
+     */
+    async fn accept_agents(
+        listener: TcpListener,
+        tunnel: Arc<TunnelClient>,
+        tls: Option<Arc<TlsTerminator>>,
+        config: Arc<Config>,
+        metrics: Option<Arc<InstanceMetrics>>,
+        cancel_token: Arc<CancellationToken>,
+    ) {
+        let Some(tunnel_config) = config.proxy.tunnel.clone() else {
+            return;
+        };
+        let handshake_timeout = Duration::from_secs(config.proxy.connect_timeout_secs);
+        let mut accept_failures = 0u32;
+        loop {
+            let (stream, peer_addr) = tokio::select! {
+                _ = cancel_token.cancelled() => break,
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        error!("Failed to accept tunnel agent: {}", e);
+                        accept_failures = accept_failures.saturating_add(1);
+                        tokio::select! {
+                            _ = cancel_token.cancelled() => break,
+                            _ = tokio::time::sleep(accept_backoff(accept_failures)) => {}
+                        }
+                        continue;
+                    }
+                },
+            };
+            accept_failures = 0;
+            let tunnel = tunnel.clone();
+            let tls = tls.clone();
+            let tunnel_config = tunnel_config.clone();
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                let accepted = match tls {
+                    Some(tls) => match timeout(handshake_timeout, tls.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            crate::tunnel::accept_link(stream, &tunnel_config, handshake_timeout, metrics).await
+                        }
+                        Ok(Err(e)) => Err(e),
+                        Err(_) => Err(anyhow::anyhow!("TLS handshake timed out")),
+                    },
+                    None => crate::tunnel::accept_link(stream, &tunnel_config, handshake_timeout, metrics).await,
+                };
+                match accepted {
+                    Ok(link) => {
+                        info!("Tunnel agent {} connected", peer_addr);
+                        tunnel.attach(link, peer_addr).await;
+                    }
+                    Err(e) => warn!("Tunnel agent link from {} rejected: {:#}", peer_addr, e),
+                }
+            });
+        }
+    }
+    fn set_certificate_expiry(metrics: Option<&InstanceMetrics>, tls: &TlsTerminator) {
+        if let Some(metrics) = metrics {
+            let expires_at = tls
+                .expires_at()
+                .map_or(0, |expires_at| expires_at.timestamp());
+            metrics
+                .certificate_expires_at
+                .store(expires_at, Ordering::Relaxed);
+        }
+    }
+    async fn handle_connection_with_token(
+        client_stream: TcpStream,
+        peer_addr: SocketAddr,
+        handler: TcpConnectionHandler,
+    ) -> Result<()> {
+        let client_socket = match handler.config.proxy.max_inflight_bytes {
+            Some(_) => Some(SockRef::from(&client_stream).try_clone()?),
+            None => None,
+        };
+        let tunnel_exit = handler
+            .config
+            .proxy
+            .tunnel
+            .as_ref()
+            .is_some_and(|tunnel| tunnel.mode == TunnelMode::Exit);
+        let (client_reader, client_writer): (BoxedReader, BoxedWriter) = match handler.tls {
+            Some(ref tls) => {
+                let handshake_timeout =
+                    Duration::from_secs(handler.config.proxy.connect_timeout_secs);
+                match timeout(handshake_timeout, tls.accept(client_stream)).await {
+                    Ok(Ok(stream)) if tunnel_exit => {
+                        return Self::serve_tunnel(stream, peer_addr, handler).await;
+                    }
+                    Ok(Ok(stream)) => {
+                        let (reader, writer) = tokio::io::split(stream);
+                        (Box::new(reader), Box::new(writer))
+                    }
+                    Ok(Err(e)) => {
+                        if let Some(suppressed) = handler.log_limiter.admit(LogClass::Tls) {
+                            warn!(
+                                "[{}] TLS connection from {} rejected: {:#}{}",
+                                handler.connection_id, peer_addr, e, suppressed
+                            );
+                        }
+                        let reason = CloseReason::Error("tls");
+                        Self::record_disconnect(handler.instance.as_ref(), reason);
+                        return Ok(());
+                    }
+                    Err(_) => {
+                        if let Some(suppressed) = handler.log_limiter.admit(LogClass::Tls) {
+                            warn!(
+                                "[{}] TLS handshake with {} timed out{}",
+                                handler.connection_id, peer_addr, suppressed
+                            );
+                        }
+                        let reason = CloseReason::Error("tls_timeout");
+                        Self::record_disconnect(handler.instance.as_ref(), reason);
+                        return Ok(());
+                    }
+                }
+            }
+            None if tunnel_exit => {
+                return Self::serve_tunnel(client_stream, peer_addr, handler).await;
+            }
+            None => {
+                let (reader, writer) = client_stream.into_split();
+                (Box::new(reader), Box::new(writer))
+            }
+        };
+        Self::relay(client_reader, client_writer, client_socket, peer_addr, handler).await
+    }
+    /**
+     * Accepts a link from a tunnel edge and relays every stream opened on it
+     * like a client connection, until the link closes.
+     */
+    async fn serve_tunnel<S>(
+        stream: S,
+        peer_addr: SocketAddr,
+        handler: TcpConnectionHandler,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let Some(ref tunnel) = handler.config.proxy.tunnel else {
+            return Ok(());
+        };
+        let metrics = handler
+            .instance
+            .as_ref()
+            .map(|instance| instance.metrics.clone());
+        let handshake_timeout = Duration::from_secs(handler.config.proxy.connect_timeout_secs);
+        let mut link = match crate::tunnel::accept_link(stream, tunnel, handshake_timeout, metrics).await {
+            Ok(link) => link,
+            Err(e) => {
+                warn!("Tunnel link from {} rejected: {:#}", peer_addr, e);
+                return Ok(());
+            }
+        };
+        info!("Tunnel link from {} accepted", peer_addr);
+        loop {
+            let next = tokio::select! {
+                _ = handler.cancel_token.cancelled() => break,
+                next = crate::tunnel::next_stream(&mut link) => next,
+            };
+            match next {
+                Some(Ok(stream)) => {
+                    let mut handler = handler.clone();
+                    handler.connection_id = ConnectionId::next();
+                    tokio::spawn(async move {
+                        let connection_id = handler.connection_id;
+                        let (reader, writer) = crate::tunnel::split(stream);
+                        if let Err(e) =
+                            Self::relay(Box::new(reader), Box::new(writer), None, peer_addr, handler)
+                                .await
+                        {
+                            error!(
+                                "[{}] Error relaying tunnel stream from {}: {}",
+                                connection_id, peer_addr, e
+                            );
+                        }
+                    });
+                }
+                Some(Err(e)) => {
+                    warn!("Tunnel link from {} failed: {:#}", peer_addr, e);
+                    break;
+                }
+                None => break,
+            }
+        }
+        info!("Tunnel link from {} closed", peer_addr);
+        Ok(())
+    }
+    fn record_disconnect(instance: Option<&InstanceState>, reason: CloseReason) {
+        if let Some(instance) = instance {
+            instance.metrics.record_disconnect(Transport::Tcp, reason);
+        }
+    }
+    /**
+     * Adds a connection to the slow log of its instance when connecting or
+     * waiting for the first byte of the destination exceeded the configured
+     * thresholds.
+     */
+    fn log_if_slow(
+        config: &Config,
+        metrics: &InstanceMetrics,
+        ctx: &ConnectionContext,
+        connect_time: Duration,
+        first_byte: Option<Duration>,
+        error: Option<String>,
+    ) {
+        let Some(slow_log) = config.proxy.slow_log else {
+            return;
+        };
+        let exceeds = |elapsed: Duration, threshold: Option<u64>| {
+            threshold.is_some_and(|threshold| elapsed.as_millis() > u128::from(threshold))
+        };
+        let slow_first_byte =
+            first_byte.is_some_and(|first_byte| exceeds(first_byte, slow_log.first_byte_ms));
+        if !exceeds(connect_time, slow_log.connect_ms) && !slow_first_byte {
+            return;
+        }
+        let entry = SlowLogEntry {
+            id: 0,
+            connection_id: ctx.connection_id,
+            timestamp: chrono::Utc::now(),
+            client: ctx.peer_addr,
+            backend: ctx.upstream_addr,
+            connect_ms: connect_time.as_millis() as u64,
+            first_byte_ms: first_byte.map(|first_byte| first_byte.as_millis() as u64),
+            duration_ms: ctx.accepted_at.elapsed().as_millis() as u64,
+            error,
+        };
+        metrics.record_slow_connection(entry, slow_log.capacity);
+    }
+    async fn relay(
+        client_reader: BoxedReader,
+        client_writer: BoxedWriter,
+        client_socket: Option<Socket>,
+        peer_addr: SocketAddr,
+        handler: TcpConnectionHandler,
+    ) -> Result<()> {
+        let TcpConnectionHandler {
+            connection_id,
+            config,
+            dst_addrs,
+            instance_id,
+            instance,
+            buffer_pool,
+            cancel_token,
+            middleware,
+            tls: _,
+            tunnel,
+            connections,
+            log_limiter,
+        } = handler;
+        let dst_addr = dst_addrs[0];
+        let ctx = Arc::new(
+            ConnectionContext::new(
+                instance_id,
+                Transport::Tcp,
+                peer_addr,
+                SocketAddr::new(config.proxy.listen_ip, config.proxy.listen_port),
+                dst_addr,
+            )
+            .with_connection_id(connection_id),
+        );
+        if let Verdict::Reject(reason) = middleware.on_accept(&ctx).await {
+            debug!(
+                "[{}] Connection from {} rejected by middleware: {}",
+                ctx.connection_id, peer_addr, reason
+            );
+            middleware.on_close(&ctx).await;
+            if let Some(ref instance) = instance {
+                instance.metrics.record_close(&ctx, CloseReason::Rejected, 0, 0);
+            }
+            return Ok(());
+        }
+        let connect_timeout = Duration::from_secs(config.proxy.connect_timeout_secs);
+        debug!(
+            "[{}] New TCP connection from {} to {}",
+            ctx.connection_id, peer_addr, dst_addr
+        );
+        let max_inflight_bytes = config.proxy.max_inflight_bytes;
+        let connect_started = Instant::now();
+        let connected = match tunnel {
+            Some(ref tunnel) => timeout(connect_timeout, tunnel.open_stream())
+                .await
+                .map(|result| {
+                    result.map(|stream| {
+                        let (reader, writer) = crate::tunnel::split(stream);
+                        let reader: BoxedReader = Box::new(reader);
+                        let writer: BoxedWriter = Box::new(writer);
+                        (reader, writer, None)
+                    })
+                }),
+            None => {
+                let proxy_config = config.clone();
+                let connect = happy_eyeballs::connect(
+                    &dst_addrs,
+                    happy_eyeballs::CONNECTION_ATTEMPT_DELAY,
+                    move |addr| {
+                        let config = proxy_config.clone();
+                        async move { crate::listener::connect_upstream(addr, &config.proxy).await }
+                    },
+                );
+                timeout(connect_timeout, connect).await.map(|result| {
+                    result.and_then(|stream| {
+                        let socket = match max_inflight_bytes {
+                            Some(_) => Some(SockRef::from(&stream).try_clone()?),
+                            None => None,
+                        };
+                        let (reader, writer) = stream.into_split();
+                        let reader: BoxedReader = Box::new(reader);
+                        let writer: BoxedWriter = Box::new(writer);
+                        Ok((reader, writer, socket))
+                    })
+                })
+            }
+        };
+        let connect_time = connect_started.elapsed();
+        let (server_reader, server_writer, server_socket) = match connected {
+            Ok(Ok(connection)) => connection,
+            Ok(Err(e)) => {
+                warn!(
+                    "[{}] Failed to connect to destination server {} for client {}: {}",
+                    ctx.connection_id, dst_addr, peer_addr, e
+                );
+                if let Some(ref instance) = instance {
+                    instance.metrics.record_error(Transport::Tcp);
+                    instance.metrics.record_backend_error(dst_addr);
+                    let message = format!("Failed to connect to {}: {}", dst_addr, e);
+                    instance
+                        .metrics
+                        .record_close(&ctx, CloseReason::Error("connect"), 0, 0);
+                    Self::log_if_slow(
+                        &config,
+                        &instance.metrics,
+                        &ctx,
+                        connect_started.elapsed(),
+                        None,
+                        Some(message.clone()),
+                    );
+                    instance.last_error.set(ErrorCategory::Upstream, message);
+                }
+                middleware.on_close(&ctx).await;
+                return Ok(());
+            }
+            Err(_) => {
+                warn!(
+                    "[{}] Connection timeout to destination server {} for client {} after {}s",
+                    ctx.connection_id, dst_addr, peer_addr, config.proxy.connect_timeout_secs
+                );
+                if let Some(ref instance) = instance {
+                    instance.metrics.record_error(Transport::Tcp);
+                    instance.metrics.record_backend_error(dst_addr);
+                    let message = format!(
+                        "Connection to {} timed out after {}s",
+                        dst_addr, config.proxy.connect_timeout_secs
+                    );
+                    instance
+                        .metrics
+                        .record_close(&ctx, CloseReason::Error("connect_timeout"), 0, 0);
+                    Self::log_if_slow(
+                        &config,
+                        &instance.metrics,
+                        &ctx,
+                        connect_started.elapsed(),
+                        None,
+                        Some(message.clone()),
+                    );
+                    instance.last_error.set(ErrorCategory::Upstream, message);
+                }
+                middleware.on_close(&ctx).await;
+                return Ok(());
+            }
+        };
+        if let Verdict::Reject(reason) = middleware.on_connect(&ctx).await {
+            debug!(
+                "[{}] Connection from {} rejected by middleware: {}",
+                ctx.connection_id, peer_addr, reason
+            );
+            middleware.on_close(&ctx).await;
+            if let Some(ref instance) = instance {
+                instance.metrics.record_close(&ctx, CloseReason::Rejected, 0, 0);
+            }
+            return Ok(());
+        }
+        let backend = instance
+            .as_ref()
+            .and_then(|instance| instance.metrics.backend(dst_addr));
+        if let Some(ref backend) = backend {
+            backend.opened();
+        }
+        let idle_timeout_duration = connections.idle_timeout();
+        let idle_timeout_secs = config.proxy.idle_timeout_secs;
+        let registration = connections.register(ctx.connection_id, peer_addr);
+        let activity = registration.connection().clone();
+        let inspect_data = middleware.inspects_data(&ctx);
+        let connection_token = cancel_token.child_token();
+        let max_lifetime = config
+            .proxy
+            .max_connection_lifetime_secs
+            .map(Duration::from_secs);
+        let connected_at = Instant::now();
+        let first_byte = Arc::new(OnceLock::new());
+        let client_bytes = Arc::new(AtomicU64::new(0));
+        let server_bytes = Arc::new(AtomicU64::new(0));
+        let stall_metrics = match max_inflight_bytes {
+            Some(_) => instance.as_ref().map(|instance| instance.metrics.clone()),
+            None => None,
+        };
+        let client_to_server = {
+            let buffer_pool = buffer_pool.clone();
+            let instance_for_client = instance.clone();
+            let cancel_token_clone = connection_token.clone();
+            let idle_timeout = idle_timeout_duration;
+            let middleware = middleware.clone();
+            let ctx = ctx.clone();
+            let stall_metrics = stall_metrics.clone();
+            let client_bytes = client_bytes.clone();
+            let backend = backend.clone();
+            let activity = activity.clone();
+            let log_limiter = log_limiter.clone();
+            tokio::spawn(async move {
+                let mut buffer = buffer_pool.acquire(8192).await;
+                let mut reader = client_reader;
+                let mut writer = server_writer;
+                let mut unflushed_bytes = 0u64;
+                let mut flushed_at = Instant::now();
+                let mut packets_processed = 0u32;
+                let reason = loop {
+                    tokio::select! {
+                        _ = cancel_token_clone.cancelled() => {
+                            debug!("[{}] Client to server task cancelled for instance {}", ctx.connection_id, instance_id);
+                            break CloseReason::Cancelled;
+                        }
+                        read_result = timeout(idle_timeout.saturating_sub(activity.idle()), reader.read_buf(buffer.as_mut())) => {
+                            match read_result {
+                                Ok(Ok(0)) => break CloseReason::ClientEof,
+                                Ok(Ok(n)) => {
+                                    activity.touch();
+                                    if packets_processed.is_multiple_of(100) {
+                                        debug!("[{}] Read {} bytes from client", ctx.connection_id, n);
+                                    }
+                                    unflushed_bytes += n as u64;
+                                    client_bytes.fetch_add(n as u64, Ordering::Relaxed);
+                                    if flushed_at.elapsed() >= METRICS_FLUSH_INTERVAL {
+                                        if let Some(ref instance) = instance_for_client {
+                                            instance.metrics.record_received(Transport::Tcp, unflushed_bytes);
+                                        }
+                                        if let Some(ref backend) = backend {
+                                            backend.add_received(unflushed_bytes);
+                                        }
+                                        unflushed_bytes = 0;
+                                        flushed_at = Instant::now();
+                                    }
+                                    packets_processed += 1;
+                                    let written = if !inspect_data {
+                                        writer.write_all(&buffer[..n]).await
+                                    } else {
+                                        let mut data = buffer[..n].to_vec();
+                                        if let Verdict::Reject(reason) = middleware.on_data(&ctx, Direction::ClientToServer, &mut data).await {
+                                            debug!("[{}] Connection from {} closed by middleware: {}", ctx.connection_id, ctx.peer_addr, reason);
+                                            break CloseReason::Rejected;
+                                        }
+                                        writer.write_all(&data).await
+                                    };
+                                    if let Err(e) = written {
+                                        if let Some(suppressed) = log_limiter.admit(LogClass::Relay) {
+                                            error!("[{}] Failed to write to server: {}{}", ctx.connection_id, e, suppressed);
+                                        }
+                                        break CloseReason::Error("server_write");
+                                    }
+                                    if let (Some(watermark), Some(socket)) = (max_inflight_bytes, &server_socket) {
+                                        let drain = backpressure::wait_for_drain(
+                                            socket,
+                                            watermark,
+                                            idle_timeout,
+                                            stall_metrics.as_deref(),
+                                        );
+                                        tokio::select! {
+                                            _ = cancel_token_clone.cancelled() => break CloseReason::Cancelled,
+                                            drained = drain => if !drained {
+                                                debug!("[{}] Server did not drain its send queue within {}s", ctx.connection_id, idle_timeout_secs);
+                                                break CloseReason::Error("server_stalled");
+                                            }
+                                        }
+                                    }
+                                    buffer.clear();
+                                }
+                                Ok(Err(e)) => {
+                                    if let Some(suppressed) = log_limiter.admit(LogClass::Relay) {
+                                        error!("[{}] Failed to read from client: {}{}", ctx.connection_id, e, suppressed);
+                                    }
+                                    break CloseReason::Error("client_read");
+                                }
+                                Err(_) if activity.idle() < idle_timeout => continue,
+                                Err(_) => {
+                                    debug!("[{}] Client to server connection idle timeout after {}s", ctx.connection_id, idle_timeout_secs);
+                                    break CloseReason::IdleTimeout;
+                                }
+                            }
+                        }
+                    }
+                };
+                if unflushed_bytes > 0
+                    && let Some(instance) = instance_for_client
+                {
+                    instance.metrics.record_received(Transport::Tcp, unflushed_bytes);
+                }
+                if let Some(backend) = backend {
+                    backend.add_received(unflushed_bytes);
+                }
+                reason
+            })
+        };
+        let server_to_client = {
+            let buffer_pool = buffer_pool.clone();
+            let instance_for_server = instance.clone();
+            let cancel_token_clone = connection_token.clone();
+            let idle_timeout = idle_timeout_duration;
+            let middleware = middleware.clone();
+            let ctx = ctx.clone();
+            let stall_metrics = stall_metrics.clone();
+            let first_byte = first_byte.clone();
+            let server_bytes = server_bytes.clone();
+            let backend = backend.clone();
+            let activity = activity.clone();
+            let log_limiter = log_limiter.clone();
+            tokio::spawn(async move {
+                let mut buffer = buffer_pool.acquire(8192).await;
+                let mut reader = server_reader;
+                let mut writer = client_writer;
+                let mut total_bytes = 0u64;
+                let mut unflushed_bytes = 0u64;
+                let mut flushed_at = Instant::now();
+                let mut packets_processed = 0u32;
+                let reason = loop {
+                    tokio::select! {
+                        _ = cancel_token_clone.cancelled() => {
+                            debug!("[{}] Server to client task cancelled for instance {}", ctx.connection_id, instance_id);
+                            break CloseReason::Cancelled;
+                        }
+                        read_result = timeout(idle_timeout.saturating_sub(activity.idle()), reader.read_buf(buffer.as_mut())) => {
+                            match read_result {
+                                Ok(Ok(0)) => break CloseReason::ServerEof,
+                                Ok(Ok(n)) => {
+                                    activity.touch();
+                                    if packets_processed.is_multiple_of(100) {
+                                        debug!("[{}] Read {} bytes from server", ctx.connection_id, n);
+                                    }
+                                    if total_bytes == 0 {
+                                        first_byte.set(connected_at.elapsed()).ok();
+                                    }
+                                    total_bytes += n as u64;
+                                    unflushed_bytes += n as u64;
+                                    server_bytes.fetch_add(n as u64, Ordering::Relaxed);
+                                    if flushed_at.elapsed() >= METRICS_FLUSH_INTERVAL {
+                                        if let Some(ref instance) = instance_for_server {
+                                            instance.metrics.record_sent(Transport::Tcp, unflushed_bytes);
+                                        }
+                                        if let Some(ref backend) = backend {
+                                            backend.add_sent(unflushed_bytes);
+                                        }
+                                        unflushed_bytes = 0;
+                                        flushed_at = Instant::now();
+                                    }
+                                    packets_processed += 1;
+                                    let written = if !inspect_data {
+                                        writer.write_all(&buffer[..n]).await
+                                    } else {
+                                        let mut data = buffer[..n].to_vec();
+                                        if let Verdict::Reject(reason) = middleware.on_data(&ctx, Direction::ServerToClient, &mut data).await {
+                                            debug!("[{}] Connection from {} closed by middleware: {}", ctx.connection_id, ctx.peer_addr, reason);
+                                            break CloseReason::Rejected;
+                                        }
+                                        writer.write_all(&data).await
+                                    };
+                                    if let Err(e) = written {
+                                        if let Some(suppressed) = log_limiter.admit(LogClass::Relay) {
+                                            error!("[{}] Failed to write to client: {}{}", ctx.connection_id, e, suppressed);
+                                        }
+                                        break CloseReason::Error("client_write");
+                                    }
+                                    if let (Some(watermark), Some(socket)) = (max_inflight_bytes, &client_socket) {
+                                        let drain = backpressure::wait_for_drain(
+                                            socket,
+                                            watermark,
+                                            idle_timeout,
+                                            stall_metrics.as_deref(),
+                                        );
+                                        tokio::select! {
+                                            _ = cancel_token_clone.cancelled() => break CloseReason::Cancelled,
+                                            drained = drain => if !drained {
+                                                debug!("[{}] Client did not drain its send queue within {}s", ctx.connection_id, idle_timeout_secs);
+                                                break CloseReason::Error("client_stalled");
+                                            }
+                                        }
+                                    }
+                                    buffer.clear();
+                                }
+                                Ok(Err(e)) => {
+                                    if let Some(suppressed) = log_limiter.admit(LogClass::Relay) {
+                                        error!("[{}] Failed to read from server: {}{}", ctx.connection_id, e, suppressed);
+                                    }
+                                    break CloseReason::Error("server_read");
+                                }
+                                Err(_) if activity.idle() < idle_timeout => continue,
+                                Err(_) => {
+                                    debug!("[{}] Server to client connection idle timeout after {}s", ctx.connection_id, idle_timeout_secs);
+                                    break CloseReason::IdleTimeout;
+                                }
+                            }
+                        }
+                    }
+                };
+                if unflushed_bytes > 0
+                    && let Some(instance) = instance_for_server
+                {
+                    instance.metrics.record_sent(Transport::Tcp, unflushed_bytes);
+                }
+                if let Some(backend) = backend {
+                    backend.add_sent(unflushed_bytes);
+                }
+                reason
+            })
+        };
+        let reason = tokio::select! {
+            _ = cancel_token.cancelled() => {
+                debug!("[{}] Connection handler cancelled for instance {}", ctx.connection_id, instance_id);
+                CloseReason::Cancelled
+            }
+            _ = ctx.lifetime_expired(max_lifetime) => {
+                debug!("[{}] Connection reached its maximum lifetime", ctx.connection_id);
+                connection_token.cancel();
+                CloseReason::MaxLifetime
+            }
+            result = client_to_server => result.unwrap_or_else(|e| {
+                error!("[{}] Client to server task failed: {}", ctx.connection_id, e);
+                CloseReason::Error("task")
+            }),
+            result = server_to_client => result.unwrap_or_else(|e| {
+                error!("[{}] Server to client task failed: {}", ctx.connection_id, e);
+                CloseReason::Error("task")
+            }),
+        };
+        middleware.on_close(&ctx).await;
+        if let Some(ref backend) = backend {
+            backend.closed(matches!(reason, CloseReason::Error(_)));
+        }
+        if let Some(ref instance) = instance {
+            instance
+                .metrics
+                .record_duration(Transport::Tcp, ctx.accepted_at.elapsed());
+            instance.metrics.record_close(
+                &ctx,
+                reason,
+                client_bytes.load(Ordering::Relaxed),
+                server_bytes.load(Ordering::Relaxed),
+            );
+            Self::log_if_slow(
+                &config,
+                &instance.metrics,
+                &ctx,
+                connect_time,
+                first_byte.get().copied(),
+                None,
+            );
+        }
+        debug!(
+            "[{}] TCP connection from {} to {} closed after {:?}: {}",
+            ctx.connection_id,
+            peer_addr,
+            dst_addr,
+            ctx.accepted_at.elapsed(),
+            reason
+        );
+        Ok(())
+    }
+}
+/**
+ * Delay before retrying after the given number of consecutive failed
+ * accepts, doubling from `ACCEPT_MIN_BACKOFF` up to `ACCEPT_MAX_BACKOFF`.
+ */
+fn accept_backoff(failures: u32) -> Duration {
+    ACCEPT_MIN_BACKOFF
+        .saturating_mul(1 << failures.saturating_sub(1).min(16))
+        .min(ACCEPT_MAX_BACKOFF)
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, UdpSocket};
+use tracing::{debug, info};
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/**
+ * Behaviour of a test backend: echo servers send everything back, sinks
+ * discard it.
+ */
+pub enum TestServerKind {
+    TcpEcho,
+    TcpSink,
+    UdpEcho,
+    UdpSink,
+}
+impl std::fmt::Display for TestServerKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            TestServerKind::TcpEcho => "TCP echo",
+            TestServerKind::TcpSink => "TCP sink",
+            TestServerKind::UdpEcho => "UDP echo",
+            TestServerKind::UdpSink => "UDP sink",
+        })
+    }
+}
+enum Listener {
+    Tcp(TcpListener),
+    Udp(UdpSocket),
+}
+/**
+ * Mock backend for validating an instance end-to-end before pointing it at
+ * a real service.
+ */
+pub struct TestServer {
+    kind: TestServerKind,
+    listener: Listener,
+}
+impl TestServer {
+    pub async fn bind(kind: TestServerKind, addr: SocketAddr) -> Result<Self> {
+        let listener = match kind {
+            TestServerKind::TcpEcho | TestServerKind::TcpSink => Listener::Tcp(
+                TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("Failed to bind {} server on {}", kind, addr))?,
+            ),
+            TestServerKind::UdpEcho | TestServerKind::UdpSink => Listener::Udp(
+                UdpSocket::bind(addr)
+                    .await
+                    .with_context(|| format!("Failed to bind {} server on {}", kind, addr))?,
+            ),
+        };
+        Ok(Self { kind, listener })
+    }
+    pub fn kind(&self) -> TestServerKind {
+        self.kind
+    }
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        match self.listener {
+            Listener::Tcp(ref listener) => listener.local_addr(),
+            Listener::Udp(ref socket) => socket.local_addr(),
+        }
+    }
+    /**
+     * Serves clients until the returned future is dropped or the socket
+     * fails.
+     */
+    pub async fn serve(self) -> Result<()> {
+        info!("{} server listening on {}", self.kind, self.local_addr()?);
+        let echo = matches!(self.kind, TestServerKind::TcpEcho | TestServerKind::UdpEcho);
+        match self.listener {
+            Listener::Tcp(listener) => loop {
+                let (mut stream, peer_addr) = listener.accept().await?;
+                debug!("{} connection from {}", self.kind, peer_addr);
+                tokio::spawn(async move {
+                    let (mut reader, mut writer) = stream.split();
+                    let result = if echo {
+                        tokio::io::copy(&mut reader, &mut writer).await
+                    } else {
+                        tokio::io::copy(&mut reader, &mut tokio::io::sink()).await
+                    };
+                    if let Err(e) = result {
+                        debug!("Test connection from {} failed: {}", peer_addr, e);
+                    }
+                });
+            },
+            Listener::Udp(socket) => {
+                let mut buffer = vec![0u8; 65_535];
+                loop {
+                    let (len, peer_addr) = socket.recv_from(&mut buffer).await?;
+                    if echo && let Err(e) = socket.send_to(&buffer[..len], peer_addr).await {
+                        debug!("Failed to echo datagram to {}: {}", peer_addr, e);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use crate::config::TlsConfig;
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{RootCertStore, ServerConfig};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+/**
+ * How often the certificate, key and client CA files are checked for
+ * changes.
+ */
+pub const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/**
+ * Terminates TLS on accepted client connections.
+ *
+ * When a client CA is configured every client must present a certificate
+ * issued by it, and when `allowed_client_names` is not empty the
+ * certificate's common name or one of its DNS subject alternative names
+ * must also be on that list (compared case-insensitively).
+ *
+ * Clients asking for one of the `sni_certificates` names (SNI) get that
+ * certificate, all others the default one.
+ *
+ * `reload_if_changed` picks up replaced certificate, key or CA files;
+ * handshakes in progress keep the configuration they started with.
+ */
+pub struct TlsTerminator {
+    config: TlsConfig,
+    loaded: RwLock<LoadedCertificate>,
+    fingerprint: Mutex<Vec<Option<(SystemTime, u64)>>>,
+}
+struct LoadedCertificate {
+    acceptor: TlsAcceptor,
+    expires_at: Option<DateTime<Utc>>,
+}
+impl TlsTerminator {
+    pub fn from_config(config: &TlsConfig) -> Result<Self> {
+        let fingerprint = file_fingerprint(config);
+        Ok(Self {
+            config: config.clone(),
+            loaded: RwLock::new(load(config)?),
+            fingerprint: Mutex::new(fingerprint),
+        })
+    }
+    /**
+     * Expiry of the certificate served that expires first.
+     */
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.loaded
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .expires_at
+    }
+    /**
+     * Reloads the certificate when one of its files changed since the last
+     * check. Returns whether it was reloaded; on error the previous
+     * certificate stays in use and the same files are not retried until
+     * they change again.
+     */
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let fingerprint = file_fingerprint(&self.config);
+        {
+            let mut last = self.fingerprint.lock().unwrap_or_else(|e| e.into_inner());
+            if *last == fingerprint {
+                return Ok(false);
+            }
+            *last = fingerprint;
+        }
+        let loaded = load(&self.config)?;
+        *self.loaded.write().unwrap_or_else(|e| e.into_inner()) = loaded;
+        Ok(true)
+    }
+    pub async fn accept(&self, stream: TcpStream) -> Result<TlsStream<TcpStream>> {
+        let acceptor = self
+            .loaded
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .acceptor
+            .clone();
+        let stream = acceptor
+            .accept(stream)
+            .await
+            .context("TLS handshake failed")?;
+        if !self.config.allowed_client_names.is_empty() {
+            let names = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(client_names)
+                .unwrap_or_default();
+            let allowed = names.iter().any(|name| {
+                self.config
+                    .allowed_client_names
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(name))
+            });
+            if !allowed {
+                return Err(anyhow::anyhow!(
+                    "Client certificate name not allowed: {}",
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                ));
+            }
+        }
+        Ok(stream)
+    }
+}
+fn file_fingerprint(config: &TlsConfig) -> Vec<Option<(SystemTime, u64)>> {
+    [
+        Some(&config.cert_path),
+        Some(&config.key_path),
+        config.client_ca_path.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(
+        config
+            .sni_certificates
+            .iter()
+            .flat_map(|sni| [&sni.cert_path, &sni.key_path]),
+    )
+    .map(|path| {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    })
+    .collect()
+}
+/**
+ * Picks the certificate matching the SNI name of a client: an exact match
+ * first, then a `*.` wildcard entry for the parent domain, then the
+ * default certificate.
+ */
+#[derive(Debug)]
+struct CertificateResolver {
+    default: Arc<CertifiedKey>,
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+}
+impl CertificateResolver {
+    fn lookup(&self, server_name: &str) -> Option<&Arc<CertifiedKey>> {
+        let server_name = server_name.to_ascii_lowercase();
+        self.by_name.get(&server_name).or_else(|| {
+            let (_, parent) = server_name.split_once('.')?;
+            self.by_name.get(&format!("*.{}", parent))
+        })
+    }
+}
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let selected = client_hello
+            .server_name()
+            .and_then(|server_name| self.lookup(server_name));
+        Some(selected.unwrap_or(&self.default).clone())
+    }
+}
+fn load_certified_key(
+    cert_path: &str,
+    key_path: &str,
+    provider: &CryptoProvider,
+) -> Result<Arc<CertifiedKey>> {
+    let certs = read_certs(Path::new(cert_path))?;
+    let key_pem =
+        std::fs::read(key_path).with_context(|| format!("Failed to read TLS key {}", key_path))?;
+    let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut key_pem.as_slice())
+        .with_context(|| format!("Invalid TLS key {}", key_path))?
+        .ok_or_else(|| anyhow::anyhow!("No private key found in {}", key_path))?;
+    let certified_key = CertifiedKey::from_der(certs, key, provider)
+        .with_context(|| format!("TLS certificate {} does not match its key", cert_path))?;
+    Ok(Arc::new(certified_key))
+}
+fn load(config: &TlsConfig) -> Result<LoadedCertificate> {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let default = load_certified_key(&config.cert_path, &config.key_path, &provider)?;
+    let mut certificates = vec![default.clone()];
+    let mut by_name = HashMap::new();
+    for sni in &config.sni_certificates {
+        let certified_key = load_certified_key(&sni.cert_path, &sni.key_path, &provider)?;
+        for server_name in &sni.server_names {
+            by_name.insert(server_name.to_ascii_lowercase(), certified_key.clone());
+        }
+        certificates.push(certified_key);
+    }
+    let expires_at = certificates
+        .iter()
+        .filter_map(|certified_key| certified_key.cert.first().and_then(not_after))
+        .min();
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+    let builder = match config.client_ca_path {
+        Some(ref ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(Path::new(ca_path))? {
+                roots
+                    .add(cert)
+                    .context("Invalid certificate in client CA bundle")?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .context("Failed to build client certificate verifier")?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let server_config =
+        builder.with_cert_resolver(Arc::new(CertificateResolver { default, by_name }));
+    Ok(LoadedCertificate {
+        acceptor: TlsAcceptor::from(Arc::new(server_config)),
+        expires_at,
+    })
+}
+fn read_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let pem = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid certificate in {:?}", path))?;
+    if certs.is_empty() {
+        return Err(anyhow::anyhow!("No certificate found in {:?}", path));
+    }
+    Ok(certs)
+}
+/**
+ * Common names and DNS subject alternative names of a certificate.
+ */
+pub fn client_names(cert: &CertificateDer<'_>) -> Vec<String> {
+    let Ok(cert) = webpki::EndEntityCert::try_from(cert) else {
+        return Vec::new();
+    };
+    let mut names = common_names(cert.subject());
+    names.extend(cert.valid_dns_names().map(str::to_string));
+    names
+}
+/**
+ * End of the validity period of a DER encoded certificate.
+ */
+pub fn not_after(cert: &CertificateDer<'_>) -> Option<DateTime<Utc>> {
+    let (_, cert, _) = read_tlv(cert)?;
+    let (_, mut tbs, _) = read_tlv(cert)?;
+    if tbs.first() == Some(&0xa0) {
+        tbs = read_tlv(tbs)?.2;
+    }
+    for _ in 0..3 {
+        tbs = read_tlv(tbs)?.2;
+    }
+    let (_, validity, _) = read_tlv(tbs)?;
+    let (_, _, validity) = read_tlv(validity)?;
+    let (tag, time, _) = read_tlv(validity)?;
+    let time = std::str::from_utf8(time).ok()?;
+    let time = match tag {
+        0x17 if time.len() >= 2 => {
+            let century = if time[..2] >= *"50" { "19" } else { "20" };
+            format!("{}{}", century, time)
+        }
+        0x18 => time.to_string(),
+        _ => return None,
+    };
+    NaiveDateTime::parse_from_str(&time, "%Y%m%d%H%M%SZ")
+        .ok()
+        .map(|time| time.and_utc())
+}
+const COMMON_NAME_OID: &[u8] = &[0x55, 0x04, 0x03];
+/**
+ * Extracts the common name attributes from a DER encoded X.509 name
+ * (without its outer SEQUENCE).
+ */
+fn common_names(mut name: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    while let Some((_, mut set, rest)) = read_tlv(name) {
+        name = rest;
+        while let Some((_, attribute, rest)) = read_tlv(set) {
+            set = rest;
+            let Some((0x06, oid, value)) = read_tlv(attribute) else {
+                continue;
+            };
+            if oid != COMMON_NAME_OID {
+                continue;
+            }
+            if let Some((0x0c | 0x13 | 0x16, value, _)) = read_tlv(value)
+                && let Ok(value) = std::str::from_utf8(value)
+            {
+                names.push(value.to_string());
+            }
+        }
+    }
+    names
+}
+fn read_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, &byte| (len << 8) | byte as usize);
+        (len, &rest[count..])
+    };
+    (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+}
@@ -0,0 +1,222 @@
+use crate::geoip::GeoIpDb;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+/** Completed periods kept for the API. */
+const REPORT_HISTORY: usize = 24;
+/** Client IPs tracked per period; further clients are summed up as `other`. */
+const MAX_TRACKED_IPS: usize = 100_000;
+static TRAFFIC: OnceLock<TrafficAccounting> = OnceLock::new();
+/**
+ * Traffic accounting shared by all instances of the process.
+ */
+pub fn global() -> &'static TrafficAccounting {
+    TRAFFIC.get_or_init(TrafficAccounting::default)
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+/**
+ * Connections (or UDP sessions) closed and bytes transferred.
+ * `bytes_received` counts the bytes read from clients, `bytes_sent` those
+ * sent to them.
+ */
+pub struct TrafficCounters {
+    pub connections: u64,
+    pub bytes_received: u64,
+    pub bytes_sent: u64,
+}
+impl TrafficCounters {
+    fn add(&mut self, other: &TrafficCounters) {
+        self.connections += other.connections;
+        self.bytes_received = self.bytes_received.saturating_add(other.bytes_received);
+        self.bytes_sent = self.bytes_sent.saturating_add(other.bytes_sent);
+    }
+    fn total_bytes(&self) -> u64 {
+        self.bytes_received.saturating_add(self.bytes_sent)
+    }
+}
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    #[default]
+    Ip,
+    Country,
+}
+#[derive(Debug, Clone, Serialize)]
+pub struct TrafficGroup {
+    pub key: String,
+    #[serde(flatten)]
+    pub counters: TrafficCounters,
+}
+#[derive(Debug, Clone, Serialize)]
+/**
+ * Traffic of one period grouped by client IP or country, largest first.
+ * `ended_at` is unset for the period in progress.
+ */
+pub struct TrafficReport {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub total: TrafficCounters,
+    pub groups: Vec<TrafficGroup>,
+}
+#[derive(Debug, Clone)]
+struct Period {
+    started_at: DateTime<Utc>,
+    ended_at: Option<DateTime<Utc>>,
+    by_ip: HashMap<IpAddr, TrafficCounters>,
+    other: TrafficCounters,
+}
+impl Period {
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            ended_at: None,
+            by_ip: HashMap::new(),
+            other: TrafficCounters::default(),
+        }
+    }
+    fn report(&self, group_by: GroupBy, geoip: Option<&GeoIpDb>, limit: usize) -> TrafficReport {
+        let mut grouped: HashMap<String, TrafficCounters> = HashMap::new();
+        let mut total = self.other;
+        for (ip, counters) in &self.by_ip {
+            total.add(counters);
+            let key = match group_by {
+                GroupBy::Ip => ip.to_string(),
+                GroupBy::Country => geoip
+                    .and_then(|db| db.country(ip))
+                    .unwrap_or("unknown")
+                    .to_string(),
+            };
+            grouped.entry(key).or_default().add(counters);
+        }
+        if self.other.connections > 0 {
+            grouped
+                .entry("other".to_string())
+                .or_default()
+                .add(&self.other);
+        }
+        let mut groups: Vec<TrafficGroup> = grouped
+            .into_iter()
+            .map(|(key, counters)| TrafficGroup { key, counters })
+            .collect();
+        groups.sort_by(|a, b| {
+            b.counters
+                .total_bytes()
+                .cmp(&a.counters.total_bytes())
+                .then(b.counters.connections.cmp(&a.counters.connections))
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        groups.truncate(limit);
+        TrafficReport {
+            started_at: self.started_at,
+            ended_at: self.ended_at,
+            total,
+            groups,
+        }
+    }
+}
+/**
+ * Bytes and connections per client IP across all instances, collected in
+ * periods of `report_interval`. The last completed periods are kept and can
+ * be grouped per country when a GeoIP database is loaded.
+ */
+pub struct TrafficAccounting {
+    current: Mutex<Period>,
+    history: Mutex<VecDeque<Period>>,
+    geoip: RwLock<Option<Arc<GeoIpDb>>>,
+    report_interval: RwLock<Duration>,
+}
+impl Default for TrafficAccounting {
+    fn default() -> Self {
+        Self {
+            current: Mutex::new(Period::new()),
+            history: Mutex::new(VecDeque::new()),
+            geoip: RwLock::new(None),
+            report_interval: RwLock::new(Duration::ZERO),
+        }
+    }
+}
+impl TrafficAccounting {
+    pub fn set_geoip(&self, db: GeoIpDb) {
+        *self.geoip.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(db));
+    }
+    pub fn geoip(&self) -> Option<Arc<GeoIpDb>> {
+        self.geoip.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+    pub fn report_interval(&self) -> Duration {
+        *self
+            .report_interval
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+    /**
+     * Accounts a closed connection (or UDP session) of `ip`.
+     */
+    pub fn record(&self, ip: IpAddr, bytes_received: u64, bytes_sent: u64) {
+        let counters = TrafficCounters {
+            connections: 1,
+            bytes_received,
+            bytes_sent,
+        };
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        let ip = ip.to_canonical();
+        if let Some(tracked) = current.by_ip.get_mut(&ip) {
+            tracked.add(&counters);
+        } else if current.by_ip.len() < MAX_TRACKED_IPS {
+            current.by_ip.insert(ip, counters);
+        } else {
+            current.other.add(&counters);
+        }
+    }
+    /**
+     * Completes the current period and starts a new one.
+     */
+    pub fn rotate(&self) {
+        let mut period = {
+            let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+            std::mem::replace(&mut *current, Period::new())
+        };
+        period.ended_at = Some(Utc::now());
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        if history.len() == REPORT_HISTORY {
+            history.pop_front();
+        }
+        history.push_back(period);
+    }
+    /**
+     * Completes a period every `interval` in the background.
+     */
+    pub fn start(&'static self, interval: Duration) {
+        *self
+            .report_interval
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = interval;
+        tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.tick().await;
+            loop {
+                ticks.tick().await;
+                self.rotate();
+            }
+        });
+    }
+    /**
+     * Reports of the completed periods, oldest first, followed by the period
+     * in progress. Each lists its `limit` largest groups.
+     */
+    pub fn reports(&self, group_by: GroupBy, limit: usize) -> Vec<TrafficReport> {
+        let geoip = self.geoip();
+        let mut reports: Vec<TrafficReport> = self
+            .history
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|period| period.report(group_by, geoip.as_deref(), limit))
+            .collect();
+        let current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        reports.push(current.report(group_by, geoip.as_deref(), limit));
+        reports
+    }
+}
@@ -0,0 +1,635 @@
+use crate::config::{Config, TunnelCompression, TunnelConfig, TunnelMode};
+use crate::metrics::InstanceMetrics;
+use anyhow::{Context, Result};
+use rustls::pki_types::ServerName;
+use std::collections::VecDeque;
+use std::future::poll_fn;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::task::{Context as TaskContext, Poll, Wake, Waker, ready};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio::time::timeout;
+use tokio_rustls::TlsConnector;
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+use tracing::{debug, info, warn};
+use yamux::{Connection, Mode};
+/**
+ * Preamble sent by the dialing side before its token and requested
+ * compression, identifying the tunnel protocol and its version.
+ */
+const HELLO: &[u8; 4] = b"VPT1";
+const ACCEPTED: u8 = 0;
+const REJECTED: u8 = 1;
+const MAX_TOKEN_LEN: usize = 1024;
+/**
+ * Largest amount of link traffic compressed as one frame.
+ */
+const BLOCK_SIZE: usize = 64 * 1024;
+/**
+ * Set in a frame header when the payload is stored uncompressed because
+ * compression did not make it smaller.
+ */
+const STORED: u32 = 1 << 31;
+const ZSTD_LEVEL: i32 = 3;
+/**
+ * A multiplexed stream carrying one relayed connection.
+ */
+pub type TunnelStream = Compat<yamux::Stream>;
+/**
+ * Transport of a link dialed by an edge or agent, TLS-encrypted or not.
+ */
+pub type LinkStream = Box<dyn LinkIo>;
+pub trait LinkIo: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> LinkIo for T {}
+type StreamRequest = oneshot::Sender<yamux::Result<yamux::Stream>>;
+/**
+ * Side of a tunnel that opens streams: relays connections as yamux streams
+ * over a single link.
+ *
+ * An edge dials the exit on the first stream, authenticating with the
+ * shared token (and TLS-encrypted when configured), and dials again on
+ * demand after the link failed. A gateway never dials; it uses the link
+ * most recently handed over by `attach`. An agent only uses `dial` to reach
+ * its gateway.
+ */
+pub struct TunnelClient {
+    config: Arc<Config>,
+    metrics: Option<Arc<InstanceMetrics>>,
+    peer: SocketAddr,
+    tls: Option<(TlsConnector, ServerName<'static>)>,
+    link: Mutex<Option<mpsc::Sender<StreamRequest>>>,
+}
+impl TunnelClient {
+    pub fn new(config: Arc<Config>, metrics: Option<Arc<InstanceMetrics>>) -> Result<Self> {
+        let tunnel = tunnel_config(&config)?;
+        let peer = tunnel
+            .gateway_addr
+            .unwrap_or_else(|| SocketAddr::new(config.proxy.dst_ip, config.proxy.dst_port));
+        let tls = if tunnel.tls {
+            let client_config = match tunnel.ca_path {
+                Some(ref ca_path) => {
+                    crate::http_client::tls_config_with_ca(std::path::Path::new(ca_path))?
+                }
+                None => crate::http_client::default_tls_config(),
+            };
+            let server_name = match tunnel.server_name {
+                Some(ref server_name) => ServerName::try_from(server_name.clone())
+                    .with_context(|| format!("Invalid tunnel server name {}", server_name))?,
+                None => ServerName::IpAddress(peer.ip().into()),
+            };
+            Some((TlsConnector::from(client_config), server_name))
+        } else {
+            None
+        };
+        Ok(Self {
+            config,
+            metrics,
+            peer,
+            tls,
+            link: Mutex::new(None),
+        })
+    }
+    pub async fn open_stream(&self) -> io::Result<TunnelStream> {
+        let mut link = self.link.lock().await;
+        if let Some(ref requests) = *link {
+            match request_stream(requests).await {
+                Ok(stream) => return Ok(stream),
+                Err(e) => debug!("Tunnel link unusable, reconnecting: {}", e),
+            }
+        }
+        if tunnel_config(&self.config).map_err(io::Error::other)?.mode == TunnelMode::Gateway {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "No tunnel agent connected",
+            ));
+        }
+        let connection = self.dial().await.map_err(io::Error::other)?;
+        let requests = spawn_link(connection, self.peer);
+        info!("Tunnel link to {} established", self.peer);
+        let stream = request_stream(&requests).await;
+        *link = Some(requests);
+        stream
+    }
+    /**
+     * Connects to the tunnel peer, authenticates with the token and returns
+     * the yamux connection of the new link.
+     */
+    pub async fn dial(&self) -> Result<Connection<Compat<LinkStream>>> {
+        let peer = self.peer;
+        let tunnel = tunnel_config(&self.config)?;
+        let stream = crate::listener::connect_upstream(peer, &self.config.proxy)
+            .await
+            .with_context(|| format!("Failed to connect to tunnel peer {}", peer))?;
+        let mut stream: LinkStream = match self.tls {
+            Some((ref connector, ref server_name)) => Box::new(
+                connector
+                    .connect(server_name.clone(), stream)
+                    .await
+                    .with_context(|| format!("TLS handshake with tunnel peer {} failed", peer))?,
+            ),
+            None => Box::new(stream),
+        };
+        let compression = authenticate(&mut stream, tunnel).await?;
+        if compression != TunnelCompression::None {
+            info!("Tunnel link to {} compressed with {:?}", peer, compression);
+        }
+        Ok(Connection::new(
+            compress(stream, compression, self.metrics.clone()).compat(),
+            yamux::Config::default(),
+            Mode::Client,
+        ))
+    }
+    /**
+     * Makes an accepted agent link the one new streams are opened on,
+     * closing the previous one.
+     */
+    pub async fn attach<S>(&self, connection: Connection<Compat<S>>, peer: SocketAddr)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let requests = spawn_link(connection, peer);
+        *self.link.lock().await = Some(requests);
+    }
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+}
+fn tunnel_config(config: &Config) -> Result<&TunnelConfig> {
+    config
+        .proxy
+        .tunnel
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Instance has no tunnel configuration"))
+}
+async fn request_stream(requests: &mpsc::Sender<StreamRequest>) -> io::Result<TunnelStream> {
+    let (reply, stream) = oneshot::channel();
+    let closed = || io::Error::new(io::ErrorKind::BrokenPipe, "Tunnel link closed");
+    requests.send(reply).await.map_err(|_| closed())?;
+    match stream.await.map_err(|_| closed())? {
+        Ok(stream) => Ok(stream.compat()),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+/**
+ * Presents the token and requested compression, returning the compression
+ * the peer agreed to.
+ */
+async fn authenticate<S>(stream: &mut S, tunnel: &TunnelConfig) -> Result<TunnelCompression>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut hello = HELLO.to_vec();
+    hello.extend_from_slice(&(tunnel.token.len() as u16).to_be_bytes());
+    hello.extend_from_slice(tunnel.token.as_bytes());
+    hello.push(compression_code(tunnel.compression));
+    stream.write_all(&hello).await?;
+    stream.flush().await?;
+    match stream
+        .read_u8()
+        .await
+        .context("Tunnel peer closed the link")?
+    {
+        ACCEPTED => Ok(compression_from_code(stream.read_u8().await?)),
+        _ => Err(anyhow::anyhow!("Tunnel peer rejected the token")),
+    }
+}
+fn compression_code(compression: TunnelCompression) -> u8 {
+    match compression {
+        TunnelCompression::None => 0,
+        TunnelCompression::Lz4 => 1,
+        TunnelCompression::Zstd => 2,
+    }
+}
+fn compression_from_code(code: u8) -> TunnelCompression {
+    match code {
+        1 => TunnelCompression::Lz4,
+        2 => TunnelCompression::Zstd,
+        _ => TunnelCompression::None,
+    }
+}
+fn spawn_link<S>(connection: Connection<Compat<S>>, peer: SocketAddr) -> mpsc::Sender<StreamRequest>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (requests, pending) = mpsc::channel(64);
+    tokio::spawn(drive_link(connection, pending, peer));
+    requests
+}
+/**
+ * Runs the yamux connection of a link streams are opened on, opening a
+ * stream for every request until the link fails or is replaced.
+ */
+async fn drive_link<S>(
+    mut connection: Connection<Compat<S>>,
+    mut requests: mpsc::Receiver<StreamRequest>,
+    peer: SocketAddr,
+) where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut waiting: VecDeque<StreamRequest> = VecDeque::new();
+    let mut requests_open = true;
+    let result = poll_fn(|cx| {
+        while requests_open {
+            match requests.poll_recv(cx) {
+                Poll::Ready(Some(reply)) => waiting.push_back(reply),
+                Poll::Ready(None) => requests_open = false,
+                Poll::Pending => break,
+            }
+        }
+        while !waiting.is_empty() {
+            match connection.poll_new_outbound(cx) {
+                Poll::Ready(result) => {
+                    let failed = result.is_err();
+                    if let Some(reply) = waiting.pop_front() {
+                        let _ = reply.send(result);
+                    }
+                    if failed {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                Poll::Pending => break,
+            }
+        }
+        if !requests_open && waiting.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            match connection.poll_next_inbound(cx) {
+                Poll::Ready(Some(Ok(stream))) => {
+                    debug!("Ignoring stream {} opened by tunnel peer", stream);
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Err(e)),
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    })
+    .await;
+    match result {
+        Ok(()) => info!("Tunnel link to {} closed", peer),
+        Err(e) => warn!("Tunnel link to {} failed: {}", peer, e),
+    }
+}
+/**
+ * Accepting side of a tunnel link: checks the peer's token, agrees to the
+ * requested compression unless `tunnel.compression` is `none`, and returns
+ * the yamux connection. On an exit its inbound streams are the relayed
+ * connections; a gateway hands it to `TunnelClient::attach` instead.
+ */
+pub async fn accept_link<S>(
+    mut stream: S,
+    tunnel: &TunnelConfig,
+    handshake_timeout: Duration,
+    metrics: Option<Arc<InstanceMetrics>>,
+) -> Result<Connection<Compat<LinkStream>>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (presented, requested) = timeout(handshake_timeout, read_hello(&mut stream))
+        .await
+        .context("Tunnel handshake timed out")??;
+    if !tokens_match(&presented, tunnel.token.as_bytes()) {
+        let _ = stream.write_u8(REJECTED).await;
+        return Err(anyhow::anyhow!("Tunnel token rejected"));
+    }
+    let compression = match tunnel.compression {
+        TunnelCompression::None => TunnelCompression::None,
+        _ => requested,
+    };
+    stream
+        .write_all(&[ACCEPTED, compression_code(compression)])
+        .await?;
+    stream.flush().await?;
+    Ok(Connection::new(
+        compress(Box::new(stream), compression, metrics).compat(),
+        yamux::Config::default(),
+        Mode::Server,
+    ))
+}
+async fn read_hello<S: AsyncRead + Unpin>(stream: &mut S) -> Result<(Vec<u8>, TunnelCompression)> {
+    let mut magic = [0u8; 4];
+    stream.read_exact(&mut magic).await?;
+    if &magic != HELLO {
+        return Err(anyhow::anyhow!("Not a tunnel connection"));
+    }
+    let len = stream.read_u16().await? as usize;
+    if len > MAX_TOKEN_LEN {
+        return Err(anyhow::anyhow!("Tunnel token too long"));
+    }
+    let mut token = vec![0u8; len];
+    stream.read_exact(&mut token).await?;
+    let compression = compression_from_code(stream.read_u8().await?);
+    Ok((token, compression))
+}
+/**
+ * Compares tokens in time independent of where they differ.
+ */
+fn tokens_match(presented: &[u8], expected: &[u8]) -> bool {
+    presented.len() == expected.len()
+        && presented
+            .iter()
+            .zip(expected)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+/**
+ * Waits for the next stream the peer opens on a link. Returns `None` once
+ * the link is closed.
+ */
+pub async fn next_stream<S>(connection: &mut Connection<Compat<S>>) -> Option<Result<TunnelStream>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    poll_fn(|cx| connection.poll_next_inbound(cx))
+        .await
+        .map(|stream| stream.map(|stream| stream.compat()).map_err(Into::into))
+}
+/**
+ * Splits a tunnel stream into halves that can be polled independently.
+ *
+ * A yamux stream keeps one waker for reads and writes alike, so with
+ * `tokio::io::split` a half blocked on a full send queue can lose its
+ * wakeup to the other half and stall. These halves are both woken whenever
+ * the stream wakes either of them.
+ */
+pub fn split(stream: TunnelStream) -> (TunnelReadHalf, TunnelWriteHalf) {
+    let shared = Arc::new(SplitStream {
+        stream: std::sync::Mutex::new(stream),
+        wakers: Arc::new(SplitWakers::default()),
+    });
+    (TunnelReadHalf(shared.clone()), TunnelWriteHalf(shared))
+}
+pub struct TunnelReadHalf(Arc<SplitStream>);
+pub struct TunnelWriteHalf(Arc<SplitStream>);
+struct SplitStream {
+    stream: std::sync::Mutex<TunnelStream>,
+    wakers: Arc<SplitWakers>,
+}
+#[derive(Default)]
+struct SplitWakers {
+    read: std::sync::Mutex<Option<Waker>>,
+    write: std::sync::Mutex<Option<Waker>>,
+}
+impl Wake for SplitWakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+    fn wake_by_ref(self: &Arc<Self>) {
+        for slot in [&self.read, &self.write] {
+            if let Some(waker) = slot.lock().unwrap_or_else(|e| e.into_inner()).take() {
+                waker.wake();
+            }
+        }
+    }
+}
+impl SplitStream {
+    fn poll_with<T>(
+        &self,
+        slot: &std::sync::Mutex<Option<Waker>>,
+        cx: &mut TaskContext<'_>,
+        poll: impl FnOnce(Pin<&mut TunnelStream>, &mut TaskContext<'_>) -> Poll<T>,
+    ) -> Poll<T> {
+        *slot.lock().unwrap_or_else(|e| e.into_inner()) = Some(cx.waker().clone());
+        let waker = Waker::from(self.wakers.clone());
+        let mut stream = self.stream.lock().unwrap_or_else(|e| e.into_inner());
+        poll(Pin::new(&mut *stream), &mut TaskContext::from_waker(&waker))
+    }
+}
+impl AsyncRead for TunnelReadHalf {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let split = &self.0;
+        split.poll_with(&split.wakers.read, cx, |stream, cx| {
+            stream.poll_read(cx, buf)
+        })
+    }
+}
+impl AsyncWrite for TunnelWriteHalf {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let split = &self.0;
+        split.poll_with(&split.wakers.write, cx, |stream, cx| {
+            stream.poll_write(cx, buf)
+        })
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let split = &self.0;
+        split.poll_with(&split.wakers.write, cx, |stream, cx| stream.poll_flush(cx))
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let split = &self.0;
+        split.poll_with(&split.wakers.write, cx, |stream, cx| {
+            stream.poll_shutdown(cx)
+        })
+    }
+}
+fn compress(
+    stream: LinkStream,
+    compression: TunnelCompression,
+    metrics: Option<Arc<InstanceMetrics>>,
+) -> LinkStream {
+    match compression {
+        TunnelCompression::None => stream,
+        _ => Box::new(CompressedStream {
+            inner: stream,
+            compression,
+            metrics,
+            unsent: Vec::new(),
+            encoded: Vec::new(),
+            encoded_pos: 0,
+            received: Vec::new(),
+            decoded: Vec::new(),
+            decoded_pos: 0,
+        }),
+    }
+}
+/**
+ * Compresses link traffic in frames of up to `BLOCK_SIZE` bytes, each
+ * prefixed with its length as a big-endian u32 (with `STORED` set when the
+ * payload is uncompressed). Writes are collected until a flush or a full
+ * block, so small yamux frames share a compressed frame.
+ */
+struct CompressedStream {
+    inner: LinkStream,
+    compression: TunnelCompression,
+    metrics: Option<Arc<InstanceMetrics>>,
+    unsent: Vec<u8>,
+    encoded: Vec<u8>,
+    encoded_pos: usize,
+    received: Vec<u8>,
+    decoded: Vec<u8>,
+    decoded_pos: usize,
+}
+impl CompressedStream {
+    fn encode(&self, raw: &[u8]) -> io::Result<Vec<u8>> {
+        match self.compression {
+            TunnelCompression::Lz4 => Ok(lz4_flex::block::compress(raw)),
+            TunnelCompression::Zstd => zstd::bulk::compress(raw, ZSTD_LEVEL),
+            TunnelCompression::None => Ok(raw.to_vec()),
+        }
+    }
+    fn decode(&self, payload: &[u8]) -> io::Result<Vec<u8>> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        match self.compression {
+            TunnelCompression::Lz4 => {
+                let mut decoded = vec![0u8; BLOCK_SIZE];
+                let len = lz4_flex::block::decompress_into(payload, &mut decoded)
+                    .map_err(|e| invalid(e.to_string()))?;
+                decoded.truncate(len);
+                Ok(decoded)
+            }
+            TunnelCompression::Zstd => zstd::bulk::decompress(payload, BLOCK_SIZE),
+            TunnelCompression::None => Ok(payload.to_vec()),
+        }
+    }
+    /**
+     * Decodes the next complete frame in `received`, if any.
+     */
+    fn next_frame(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let Some(header) = self.received.first_chunk::<4>() else {
+            return Ok(None);
+        };
+        let header = u32::from_be_bytes(*header);
+        let len = (header & !STORED) as usize;
+        if len > 2 * BLOCK_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Tunnel frame too large",
+            ));
+        }
+        if self.received.len() < 4 + len {
+            return Ok(None);
+        }
+        let payload = &self.received[4..4 + len];
+        let decoded = if header & STORED != 0 {
+            payload.to_vec()
+        } else {
+            self.decode(payload)?
+        };
+        if let Some(ref metrics) = self.metrics {
+            metrics
+                .tunnel_bytes_uncompressed
+                .fetch_add(decoded.len() as u64, Ordering::Relaxed);
+            metrics
+                .tunnel_bytes_compressed
+                .fetch_add(4 + len as u64, Ordering::Relaxed);
+        }
+        self.received.drain(..4 + len);
+        Ok(Some(decoded))
+    }
+    /**
+     * Encodes the collected writes into a frame and writes it out.
+     */
+    fn poll_write_unsent(&mut self, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        ready!(self.poll_write_encoded(cx))?;
+        if self.unsent.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        let raw = std::mem::take(&mut self.unsent);
+        let compressed = self.encode(&raw)?;
+        let (header, payload) = if compressed.len() < raw.len() {
+            (compressed.len() as u32, compressed.as_slice())
+        } else {
+            (raw.len() as u32 | STORED, raw.as_slice())
+        };
+        self.encoded.clear();
+        self.encoded.extend_from_slice(&header.to_be_bytes());
+        self.encoded.extend_from_slice(payload);
+        self.encoded_pos = 0;
+        if let Some(ref metrics) = self.metrics {
+            metrics
+                .tunnel_bytes_uncompressed
+                .fetch_add(raw.len() as u64, Ordering::Relaxed);
+            metrics
+                .tunnel_bytes_compressed
+                .fetch_add(self.encoded.len() as u64, Ordering::Relaxed);
+        }
+        self.unsent = raw;
+        self.unsent.clear();
+        self.poll_write_encoded(cx)
+    }
+    fn poll_write_encoded(&mut self, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        while self.encoded_pos < self.encoded.len() {
+            let written = ready!(
+                Pin::new(&mut self.inner).poll_write(cx, &self.encoded[self.encoded_pos..])
+            )?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.encoded_pos += written;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+impl AsyncRead for CompressedStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if this.decoded_pos < this.decoded.len() {
+                let len = buf.remaining().min(this.decoded.len() - this.decoded_pos);
+                buf.put_slice(&this.decoded[this.decoded_pos..this.decoded_pos + len]);
+                this.decoded_pos += len;
+                return Poll::Ready(Ok(()));
+            }
+            if let Some(decoded) = this.next_frame()? {
+                this.decoded = decoded;
+                this.decoded_pos = 0;
+                continue;
+            }
+            let start = this.received.len();
+            this.received.resize(start + 16 * 1024, 0);
+            let mut read = ReadBuf::new(&mut this.received[start..]);
+            let result = Pin::new(&mut this.inner).poll_read(cx, &mut read);
+            let filled = read.filled().len();
+            this.received.truncate(start + filled);
+            ready!(result)?;
+            if filled == 0 {
+                return Poll::Ready(if this.received.is_empty() {
+                    Ok(())
+                } else {
+                    Err(io::ErrorKind::UnexpectedEof.into())
+                });
+            }
+        }
+    }
+}
+impl AsyncWrite for CompressedStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.unsent.len() >= BLOCK_SIZE {
+            ready!(this.poll_write_unsent(cx))?;
+        }
+        let len = buf.len().min(BLOCK_SIZE - this.unsent.len());
+        this.unsent.extend_from_slice(&buf[..len]);
+        Poll::Ready(Ok(len))
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_unsent(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_write_unsent(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,318 @@
+use crate::footprint::Footprint;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+/**
+ * Maximum number of datagrams moved per system call.
+ *
+ * The compact footprint lowers it, see `Footprint::udp_batch_size`.
+ */
+pub const BATCH_SIZE: usize = 32;
+const DATAGRAM_SIZE: usize = 65535;
+/**
+ * Receives datagrams in batches.
+ *
+ * On Linux a single `recvmmsg` call drains up to `BATCH_SIZE` datagrams,
+ * and UDP GRO is enabled where the kernel supports it, with coalesced
+ * datagrams split back into their original segments. Elsewhere every call
+ * returns a single datagram.
+ */
+pub struct BatchReceiver {
+    socket: Arc<UdpSocket>,
+    buffers: Vec<Vec<u8>>,
+    gro: bool,
+}
+impl BatchReceiver {
+    pub fn new(socket: Arc<UdpSocket>) -> Self {
+        let gro = sys::enable_gro(&socket);
+        debug!("UDP GRO {}", if gro { "enabled" } else { "unavailable" });
+        let batch_size = if sys::BATCHING {
+            Footprint::current().udp_batch_size()
+        } else {
+            1
+        };
+        Self {
+            socket,
+            buffers: vec![vec![0; DATAGRAM_SIZE]; batch_size],
+            gro,
+        }
+    }
+    /**
+     * Capacity of the receive buffers.
+     */
+    pub fn buffer_bytes(&self) -> u64 {
+        (self.buffers.len() * DATAGRAM_SIZE) as u64
+    }
+    pub async fn recv(&mut self) -> io::Result<Vec<(Vec<u8>, SocketAddr)>> {
+        sys::recv(&self.socket, &mut self.buffers, self.gro).await
+    }
+}
+/**
+ * Sends datagrams from many tasks through one socket in batches.
+ *
+ * Datagrams are queued and written by a background task with `sendmmsg` on
+ * Linux; consecutive equally sized datagrams to the same peer are coalesced
+ * with UDP GSO where the kernel supports it. The task ends once every clone
+ * of the sender is dropped.
+ */
+#[derive(Clone)]
+pub struct BatchSender {
+    queue: mpsc::Sender<(Vec<u8>, SocketAddr)>,
+}
+impl BatchSender {
+    pub fn new(socket: Arc<UdpSocket>) -> Self {
+        let footprint = Footprint::current();
+        let batch_size = footprint.udp_batch_size();
+        let (queue, mut pending) = mpsc::channel(footprint.udp_send_queue());
+        let mut gso = sys::gso_supported(&socket);
+        debug!("UDP GSO {}", if gso { "enabled" } else { "unavailable" });
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(batch_size);
+            while pending.recv_many(&mut batch, batch_size).await > 0 {
+                let mut sent = 0;
+                while sent < batch.len() {
+                    match sys::send(&socket, &batch[sent..], gso).await {
+                        Ok(count) => sent += count,
+                        Err(e) if gso && e.raw_os_error() == Some(sys::EIO) => {
+                            warn!("UDP GSO rejected by the network device, disabling it");
+                            gso = false;
+                        }
+                        Err(e) => {
+                            debug!("Failed to send UDP datagram to {}: {}", batch[sent].1, e);
+                            sent += 1;
+                        }
+                    }
+                }
+                batch.clear();
+            }
+        });
+        Self { queue }
+    }
+    pub async fn send_to(&self, data: Vec<u8>, addr: SocketAddr) -> io::Result<()> {
+        self.queue
+            .send((data, addr))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "UDP sender stopped"))
+    }
+}
+/**
+ * Number of leading datagrams that can go out as one GSO send: same peer,
+ * same size, except for a possibly shorter last one.
+ */
+fn gso_run(datagrams: &[(Vec<u8>, SocketAddr)]) -> usize {
+    let Some((first, addr)) = datagrams.first() else {
+        return 0;
+    };
+    let segment = first.len();
+    if segment == 0 {
+        return 1;
+    }
+    let mut total = 0;
+    let mut count = 0;
+    for (data, peer) in datagrams.iter().take(sys::MAX_GSO_SEGMENTS) {
+        if peer != addr || data.len() > segment || total + data.len() > DATAGRAM_SIZE - 8 {
+            break;
+        }
+        total += data.len();
+        count += 1;
+        if data.len() < segment {
+            break;
+        }
+    }
+    count.max(1)
+}
+#[cfg(target_os = "linux")]
+mod sys {
+    use super::gso_run;
+    use nix::sys::socket::{
+        ControlMessage, ControlMessageOwned, MsgFlags, MultiHeaders, SockaddrStorage, getsockopt,
+        recvmmsg, sendmmsg, sendmsg, setsockopt, sockopt,
+    };
+    use std::io::{self, IoSlice, IoSliceMut};
+    use std::net::SocketAddr;
+    use std::os::fd::{AsFd, AsRawFd};
+    use tokio::io::Interest;
+    use tokio::net::UdpSocket;
+    pub const BATCHING: bool = true;
+    pub const MAX_GSO_SEGMENTS: usize = 64;
+    pub const EIO: i32 = nix::libc::EIO;
+    pub fn enable_gro(socket: &UdpSocket) -> bool {
+        setsockopt(&socket.as_fd(), sockopt::UdpGroSegment, &true).is_ok()
+    }
+    pub fn gso_supported(socket: &UdpSocket) -> bool {
+        getsockopt(&socket.as_fd(), sockopt::UdpGsoSegment).is_ok()
+    }
+    fn to_socket_addr(addr: &SockaddrStorage) -> Option<SocketAddr> {
+        if let Some(addr) = addr.as_sockaddr_in() {
+            return Some(SocketAddr::V4((*addr).into()));
+        }
+        addr.as_sockaddr_in6()
+            .map(|addr| SocketAddr::V6((*addr).into()))
+    }
+    fn recv_batch(
+        socket: &UdpSocket,
+        buffers: &mut [Vec<u8>],
+        gro: bool,
+    ) -> io::Result<Vec<(Vec<u8>, SocketAddr)>> {
+        let received: Vec<(usize, Option<SocketAddr>, Option<usize>)> = {
+            let cmsg_buffer = gro.then(|| nix::cmsg_space!(i32));
+            let mut headers =
+                MultiHeaders::<SockaddrStorage>::preallocate(buffers.len(), cmsg_buffer);
+            let mut slices: Vec<[IoSliceMut; 1]> = buffers
+                .iter_mut()
+                .map(|buffer| [IoSliceMut::new(buffer)])
+                .collect();
+            recvmmsg(
+                socket.as_raw_fd(),
+                &mut headers,
+                slices.iter_mut(),
+                MsgFlags::MSG_DONTWAIT,
+                None,
+            )?
+            .map(|message| {
+                let segment = message.cmsgs().ok().and_then(|mut cmsgs| {
+                    cmsgs.find_map(|cmsg| match cmsg {
+                        ControlMessageOwned::UdpGroSegments(size) if size > 0 => {
+                            Some(size as usize)
+                        }
+                        _ => None,
+                    })
+                });
+                (
+                    message.bytes,
+                    message.address.as_ref().and_then(to_socket_addr),
+                    segment,
+                )
+            })
+            .collect()
+        };
+        let mut datagrams = Vec::with_capacity(received.len());
+        for ((len, addr, segment), buffer) in received.into_iter().zip(buffers.iter()) {
+            let Some(addr) = addr else {
+                continue;
+            };
+            let data = &buffer[..len];
+            match segment {
+                Some(segment) if segment < len => {
+                    datagrams.extend(data.chunks(segment).map(|chunk| (chunk.to_vec(), addr)));
+                }
+                _ => datagrams.push((data.to_vec(), addr)),
+            }
+        }
+        Ok(datagrams)
+    }
+    pub async fn recv(
+        socket: &UdpSocket,
+        buffers: &mut [Vec<u8>],
+        gro: bool,
+    ) -> io::Result<Vec<(Vec<u8>, SocketAddr)>> {
+        loop {
+            socket.readable().await?;
+            match socket.try_io(Interest::READABLE, || recv_batch(socket, buffers, gro)) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return result,
+            }
+        }
+    }
+    fn send_batch(
+        socket: &UdpSocket,
+        datagrams: &[(Vec<u8>, SocketAddr)],
+        gso: bool,
+    ) -> io::Result<usize> {
+        let fd = socket.as_raw_fd();
+        if gso {
+            let run = gso_run(datagrams);
+            if run > 1 {
+                let segment = datagrams[0].0.len() as u16;
+                let payload: Vec<u8> = datagrams[..run]
+                    .iter()
+                    .flat_map(|(data, _)| data.iter().copied())
+                    .collect();
+                let addr = SockaddrStorage::from(datagrams[0].1);
+                sendmsg(
+                    fd,
+                    &[IoSlice::new(&payload)],
+                    &[ControlMessage::UdpGsoSegments(&segment)],
+                    MsgFlags::MSG_DONTWAIT,
+                    Some(&addr),
+                )?;
+                return Ok(run);
+            }
+        }
+        let count = if gso {
+            (1..datagrams.len())
+                .find(|&start| gso_run(&datagrams[start..]) > 1)
+                .unwrap_or(datagrams.len())
+        } else {
+            datagrams.len()
+        };
+        let datagrams = &datagrams[..count];
+        let slices: Vec<[IoSlice; 1]> = datagrams
+            .iter()
+            .map(|(data, _)| [IoSlice::new(data)])
+            .collect();
+        let addrs: Vec<Option<SockaddrStorage>> = datagrams
+            .iter()
+            .map(|(_, addr)| Some(SockaddrStorage::from(*addr)))
+            .collect();
+        let mut headers = MultiHeaders::<SockaddrStorage>::preallocate(count, None);
+        let sent = sendmmsg(
+            fd,
+            &mut headers,
+            slices.iter(),
+            addrs,
+            [] as [ControlMessage; 0],
+            MsgFlags::MSG_DONTWAIT,
+        )?
+        .count();
+        Ok(sent)
+    }
+    pub async fn send(
+        socket: &UdpSocket,
+        datagrams: &[(Vec<u8>, SocketAddr)],
+        gso: bool,
+    ) -> io::Result<usize> {
+        loop {
+            socket.writable().await?;
+            match socket.try_io(Interest::WRITABLE, || send_batch(socket, datagrams, gso)) {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                result => return result,
+            }
+        }
+    }
+}
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+    use std::net::SocketAddr;
+    use tokio::net::UdpSocket;
+    pub const BATCHING: bool = false;
+    pub const MAX_GSO_SEGMENTS: usize = 1;
+    pub const EIO: i32 = 5;
+    pub fn enable_gro(_socket: &UdpSocket) -> bool {
+        false
+    }
+    pub fn gso_supported(_socket: &UdpSocket) -> bool {
+        false
+    }
+    pub async fn recv(
+        socket: &UdpSocket,
+        buffers: &mut [Vec<u8>],
+        _gro: bool,
+    ) -> io::Result<Vec<(Vec<u8>, SocketAddr)>> {
+        let (len, addr) = socket.recv_from(&mut buffers[0]).await?;
+        Ok(vec![(buffers[0][..len].to_vec(), addr)])
+    }
+    pub async fn send(
+        socket: &UdpSocket,
+        datagrams: &[(Vec<u8>, SocketAddr)],
+        _gso: bool,
+    ) -> io::Result<usize> {
+        let (data, addr) = &datagrams[0];
+        socket.send_to(data, addr).await?;
+        Ok(1)
+    }
+}
@@ -0,0 +1,545 @@
+use crate::backend::BackendSet;
+use crate::backpressure::HandlerLimit;
+use crate::buffer_pool::UdpSessionManager;
+use crate::config::Config;
+use crate::handoff::SocketKind;
+use crate::host_acl::HostAcl;
+use crate::instance::{ErrorCategory, InstanceState};
+use crate::log_limit::{LogClass, LogLimiter};
+use crate::metrics::InstanceMetrics;
+use crate::middleware::{
+    CloseReason, ConnectionContext, Direction, MiddlewareChain, Transport, Verdict,
+};
+use crate::udp_batch::{BatchReceiver, BatchSender};
+use anyhow::{Context, Result};
+use bytes::BytesMut;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::Interest;
+use tokio::net::UdpSocket;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+#[derive(Clone)]
+struct UdpPacketHandler {
+    sender: BatchSender,
+    config: Arc<Config>,
+    session_manager: Arc<UdpSessionManager>,
+    instance_id: Uuid,
+    instance: Option<InstanceState>,
+    cancel_token: Arc<CancellationToken>,
+    backends: Option<Arc<BackendSet>>,
+    middleware: MiddlewareChain,
+    log_limiter: Arc<LogLimiter>,
+}
+#[derive(Clone)]
+/**
+ * UDP proxy implementation for stateless UDP packet forwarding.
+ *
+ * Handles UDP proxy operations using session management to maintain
+ * context for stateless UDP communication with timeout handling.
+ */
+pub struct UdpProxy {
+    config: Arc<Config>,
+    session_manager: Arc<UdpSessionManager>,
+    instance_id: Uuid,
+    instances: crate::instance::InstanceManager,
+    ip_cache: Arc<crate::ip_cache::IpCache>,
+    host_acl: Arc<HostAcl>,
+    backends: Option<Arc<BackendSet>>,
+    middleware: MiddlewareChain,
+    log_limiter: Arc<LogLimiter>,
+    state: Option<InstanceState>,
+}
+impl UdpProxy {
+    pub fn new(
+        config: Arc<Config>,
+        instance_id: Uuid,
+        instances: crate::instance::InstanceManager,
+    ) -> Self {
+        let session_timeout = Duration::from_secs(config.proxy.idle_timeout_secs);
+        let cleanup_interval = Duration::from_secs(config.proxy.idle_timeout_secs.min(60));
+        let backends = BackendSet::from_config(&config.proxy);
+        Self {
+            ip_cache: Arc::new(crate::ip_cache::IpCache::for_proxy(&config.proxy)),
+            host_acl: Arc::new(HostAcl::for_config(&config)),
+            log_limiter: Arc::new(LogLimiter::for_proxy(&config.proxy)),
+            config,
+            session_manager: Arc::new(UdpSessionManager::new(
+                session_timeout,
+                cleanup_interval,
+            )),
+            instance_id,
+            instances,
+            backends,
+            middleware: MiddlewareChain::default(),
+            state: None,
+        }
+    }
+    pub fn with_middleware(mut self, middleware: MiddlewareChain) -> Self {
+        self.middleware = middleware;
+        self
+    }
+    /**
+     * Records into the given instance state instead of looking the instance
+     * up in the instance map when the proxy starts.
+     */
+    pub fn with_state(mut self, state: InstanceState) -> Self {
+        self.state = Some(state);
+        self
+    }
+    /**
+     * Get session metrics for monitoring.
+     */
+    pub async fn get_session_metrics(&self) -> crate::metrics::SessionMetrics {
+        crate::metrics::SessionMetrics {
+            session_timeout_seconds: self.session_manager.session_timeout().as_secs(),
+            cleanup_interval_seconds: self.session_manager.cleanup_interval().as_secs(),
+            active_sessions: self.session_manager.active_session_count().await,
+            longest_idle_seconds: self.session_manager.longest_idle().await.as_secs(),
+        }
+    }
+    pub async fn run_with_token(&self, cancel_token: Arc<CancellationToken>) -> Result<()> {
+        let listen_addr =
+            SocketAddr::new(self.config.proxy.listen_ip, self.config.proxy.listen_port);
+        let socket = Arc::new(match crate::handoff::take_udp_socket(listen_addr) {
+            Some(socket) => {
+                socket.set_nonblocking(true)?;
+                info!("Reusing inherited UDP socket on {}", listen_addr);
+                UdpSocket::from_std(socket).context("Failed to adopt inherited UDP socket")?
+            }
+            None => crate::listener::bind_udp_socket(listen_addr, &self.config.proxy)
+                .context("Failed to bind UDP socket")?,
+        });
+        #[cfg(unix)]
+        crate::handoff::register_listener(
+            SocketKind::Udp,
+            listen_addr,
+            std::os::fd::AsRawFd::as_raw_fd(&*socket),
+            Some(self.instance_id),
+        );
+        info!("UDP proxy listening on {}", listen_addr);
+        let mut receiver = BatchReceiver::new(socket.clone());
+        let sender = BatchSender::new(socket.clone());
+        let drain_token = crate::handoff::drain_token();
+        let instance = match self.state {
+            Some(ref state) => Some(state.clone()),
+            None => self
+                .instances
+                .read()
+                .await
+                .get(&self.instance_id)
+                .map(|instance| instance.state()),
+        };
+        let last_error = instance.as_ref().map(|instance| instance.last_error.clone());
+        let metrics = instance.as_ref().map(|instance| instance.metrics.clone());
+        if let Some(ref metrics) = metrics {
+            metrics
+                .buffer_bytes
+                .fetch_add(receiver.buffer_bytes(), Ordering::Relaxed);
+        }
+        match &self.backends {
+            Some(backends) => {
+                backends
+                    .start_refresh(cancel_token.clone(), metrics.clone())
+                    .await;
+                info!("Balancing across {} backends", backends.backends().len());
+            }
+            None => info!(
+                "Forwarding to {}:{}",
+                self.config.proxy.dst_ip, self.config.proxy.dst_port
+            ),
+        }
+        self.host_acl
+            .start_refresh(self.ip_cache.clone(), cancel_token.clone())
+            .await;
+        let handler_limit = self
+            .config
+            .proxy
+            .max_concurrent_handlers
+            .map(|limit| HandlerLimit::new(limit, metrics.clone()));
+        loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => {
+                    info!("UDP proxy shutdown signal received for instance {}", self.instance_id);
+                    break;
+                }
+                _ = drain_token.cancelled() => {
+                    info!("UDP proxy for instance {} stopped receiving, draining sessions", self.instance_id);
+                    break;
+                }
+                result = receiver.recv() => {
+                    match result {
+                        Ok(datagrams) => for (data, peer_addr) in datagrams {
+                            if crate::blocklist::global().check(&peer_addr.ip()) {
+                                debug!("UDP packet rejected from {}: IP on a blocklist feed", peer_addr);
+                                continue;
+                            }
+                            if !crate::global_filter::global().is_allowed(&peer_addr.ip()) {
+                                if let Some(suppressed) = self.log_limiter.admit(LogClass::Rejected) {
+                                    warn!("UDP packet rejected from {}: IP denied by the global filter{}", peer_addr, suppressed);
+                                }
+                                continue;
+                            }
+                            let resolved_hosts = self.host_acl.resolved();
+                            let ip_allowed = self.ip_cache.check_ip(&peer_addr.ip(), |ip| {
+                                self.config.is_ip_allowed_with(ip, &resolved_hosts)
+                            }).await;
+                            if !ip_allowed {
+                                if let Some(suppressed) = self.log_limiter.admit(LogClass::Rejected) {
+                                    warn!("UDP packet rejected from {}: IP not allowed{}", peer_addr, suppressed);
+                                }
+                                continue;
+                            }
+                            let handler = UdpPacketHandler {
+                                sender: sender.clone(),
+                                config: self.config.clone(),
+                                session_manager: self.session_manager.clone(),
+                                instance_id: self.instance_id,
+                                instance: instance.clone(),
+                                cancel_token: cancel_token.clone(),
+                                backends: self.backends.clone(),
+                                middleware: self.middleware.clone(),
+                                log_limiter: self.log_limiter.clone(),
+                            };
+                            let peer_addr_for_cleanup = peer_addr;
+                            let last_error = last_error.clone();
+                            let log_limiter = self.log_limiter.clone();
+                            let permit = match handler_limit {
+                                Some(ref limit) => tokio::select! {
+                                    _ = cancel_token.cancelled() => break,
+                                    permit = limit.acquire() => Some(permit),
+                                },
+                                None => None,
+                            };
+                            tokio::spawn(async move {
+                                let result = Self::handle_udp_packet_with_token(
+                                    data, peer_addr, handler
+                                ).await;
+                                if let Err(e) = result {
+                                    if let Some(suppressed) = log_limiter.admit(LogClass::Handler) {
+                                        error!("Error handling UDP packet from {}: {}{}", peer_addr_for_cleanup, e, suppressed);
+                                    }
+                                    if let Some(ref last_error) = last_error {
+                                        last_error.set(
+                                            ErrorCategory::Upstream,
+                                            format!("UDP packet from {}: {}", peer_addr_for_cleanup, e),
+                                        );
+                                    }
+                                }
+                                drop(permit);
+                            });
+                        },
+                        Err(e) => {
+                            if !cancel_token.is_cancelled()
+                                && let Some(suppressed) = self.log_limiter.admit(LogClass::Listener)
+                            {
+                                error!("Failed to receive UDP packet: {}{}", e, suppressed);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(ref metrics) = metrics {
+            metrics
+                .buffer_bytes
+                .fetch_sub(receiver.buffer_bytes(), Ordering::Relaxed);
+        }
+        crate::handoff::unregister_listener(SocketKind::Udp, listen_addr);
+        info!("UDP proxy stopped for instance {}", self.instance_id);
+        Ok(())
+    }
+    async fn handle_udp_packet_with_token(
+        mut data: Vec<u8>,
+        peer_addr: SocketAddr,
+        handler: UdpPacketHandler,
+    ) -> Result<()> {
+        let dst_addr = match &handler.backends {
+            Some(backends) => backends
+                .pick_sticky(&peer_addr)
+                .ok_or_else(|| anyhow::anyhow!("No healthy backend for UDP client {}", peer_addr))?,
+            None => SocketAddr::new(handler.config.proxy.dst_ip, handler.config.proxy.dst_port),
+        };
+        debug!(
+            "Received {} bytes from UDP client {}",
+            data.len(),
+            peer_addr
+        );
+        let (client_socket, ctx, session_bytes) = match handler
+            .session_manager
+            .get_or_create_session(peer_addr, || {
+                let socket = crate::listener::bind_upstream_udp_socket(dst_addr, &handler.config.proxy)?;
+                let ctx = ConnectionContext::new(
+                    handler.instance_id,
+                    Transport::Udp,
+                    peer_addr,
+                    SocketAddr::new(handler.config.proxy.listen_ip, handler.config.proxy.listen_port),
+                    dst_addr,
+                );
+                Ok((socket, ctx))
+            })
+            .await
+        {
+            Some((session, false)) => (session.client_socket, session.context, session.bytes_received),
+            Some((session, true)) => {
+                let ctx = session.context.clone();
+                let mut verdict = handler.middleware.on_accept(&ctx).await;
+                if verdict == Verdict::Continue {
+                    verdict = handler.middleware.on_connect(&ctx).await;
+                }
+                if let Verdict::Reject(reason) = verdict {
+                    debug!(
+                        "[{}] UDP session from {} rejected by middleware: {}",
+                        ctx.connection_id, peer_addr, reason
+                    );
+                    handler.middleware.on_close(&ctx).await;
+                    handler.session_manager.remove_session(&peer_addr).await;
+                    if let Some(ref instance) = handler.instance {
+                        instance.metrics.record_close(&ctx, CloseReason::Rejected, 0, 0);
+                    }
+                    return Ok(());
+                }
+                let response_handler = handler.clone();
+                let log_limiter = handler.log_limiter.clone();
+                let client_socket_clone = session.client_socket.clone();
+                let ctx_clone = ctx.clone();
+                let connection_id = ctx.connection_id;
+                let session_bytes = session.bytes_received.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = Self::handle_udp_responses_with_token(
+                        client_socket_clone,
+                        peer_addr,
+                        response_handler,
+                        ctx_clone,
+                        session_bytes,
+                    )
+                    .await
+                        && let Some(suppressed) = log_limiter.admit(LogClass::Handler)
+                    {
+                        error!(
+                            "[{}] Error handling UDP responses: {}{}",
+                            connection_id, e, suppressed
+                        );
+                    }
+                });
+                (session.client_socket, ctx, session.bytes_received)
+            }
+            None => {
+                return Err(anyhow::anyhow!(
+                    "Failed to create UDP session for {}",
+                    peer_addr
+                ));
+            }
+        };
+        if let Verdict::Reject(reason) = handler
+            .middleware
+            .on_data(&ctx, Direction::ClientToServer, &mut data)
+            .await
+        {
+            debug!(
+                "[{}] UDP datagram from {} dropped by middleware: {}",
+                ctx.connection_id, peer_addr, reason
+            );
+            return Ok(());
+        }
+        match client_socket.send(&data).await {
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                report_unreachable(
+                    &handler.config,
+                    handler.instance.as_ref().map(|instance| &*instance.metrics),
+                    &ctx,
+                );
+                handler.session_manager.remove_session(&peer_addr).await;
+                return Ok(());
+            }
+            result => {
+                result.context("Failed to send UDP packet to destination")?;
+            }
+        }
+        debug!(
+            "[{}] Forwarded {} bytes from {} to {}",
+            ctx.connection_id,
+            data.len(),
+            peer_addr,
+            dst_addr
+        );
+        let bytes_sent = data.len() as u64;
+        session_bytes.fetch_add(bytes_sent, Ordering::Relaxed);
+        if bytes_sent > 0
+            && let Some(ref instance) = handler.instance
+        {
+            instance.metrics.record_received(Transport::Udp, bytes_sent);
+        }
+        Ok(())
+    }
+    async fn handle_udp_responses_with_token(
+        client_socket: Arc<UdpSocket>,
+        peer_addr: SocketAddr,
+        handler: UdpPacketHandler,
+        ctx: Arc<ConnectionContext>,
+        session_bytes: Arc<std::sync::atomic::AtomicU64>,
+    ) -> Result<()> {
+        let UdpPacketHandler {
+            sender,
+            config,
+            session_manager,
+            instance_id,
+            instance,
+            cancel_token,
+            middleware,
+            log_limiter,
+            ..
+        } = handler;
+        let metrics = instance.map(|instance| instance.metrics);
+        if let Some(ref metrics) = metrics {
+            metrics.connection_opened(Transport::Udp);
+        }
+        let backend = metrics
+            .as_ref()
+            .and_then(|metrics| metrics.backend(ctx.upstream_addr));
+        if let Some(ref backend) = backend {
+            backend.opened();
+        }
+        let mut backend_received = 0u64;
+        let mut buffer = BytesMut::zeroed(65535);
+        let mut bytes_sent = 0u64;
+        let mut expiry_checks = tokio::time::interval(session_manager.cleanup_interval());
+        let max_lifetime = config
+            .proxy
+            .max_connection_lifetime_secs
+            .map(Duration::from_secs);
+        let reason = loop {
+            tokio::select! {
+                _ = cancel_token.cancelled() => {
+                    debug!("[{}] UDP response handler cancelled for instance {}", ctx.connection_id, instance_id);
+                    break CloseReason::Cancelled;
+                }
+                _ = ctx.lifetime_expired(max_lifetime) => {
+                    debug!("[{}] UDP session of {} reached its maximum lifetime", ctx.connection_id, peer_addr);
+                    break CloseReason::MaxLifetime;
+                }
+                _ = expiry_checks.tick() => {
+                    if let Some(ref backend) = backend {
+                        let received = session_bytes.load(Ordering::Relaxed);
+                        backend.add_received(received.saturating_sub(backend_received));
+                        backend_received = received;
+                    }
+                    if !session_manager.is_active(&peer_addr, &client_socket).await {
+                        break CloseReason::IdleTimeout;
+                    }
+                }
+                e = pending_error(&client_socket) => {
+                    if e.kind() == std::io::ErrorKind::ConnectionRefused {
+                        report_unreachable(&config, metrics.as_deref(), &ctx);
+                        break CloseReason::Error("server_unreachable");
+                    }
+                    debug!("[{}] UDP connection from {} failed: {}", ctx.connection_id, peer_addr, e);
+                    break CloseReason::Error("server_read");
+                }
+                result = client_socket.recv_from(&mut buffer) => {
+                    match result {
+                        Ok((len, _)) => {
+                            let mut data = buffer[..len].to_vec();
+                            if let Verdict::Reject(reason) = middleware.on_data(&ctx, Direction::ServerToClient, &mut data).await {
+                                debug!("[{}] UDP response to {} dropped by middleware: {}", ctx.connection_id, peer_addr, reason);
+                                continue;
+                            }
+                            if let Err(e) = sender.send_to(data, peer_addr).await {
+                                if let Some(suppressed) = log_limiter.admit(LogClass::Relay) {
+                                    error!("[{}] Failed to send UDP response to client {}: {}{}", ctx.connection_id, peer_addr, e, suppressed);
+                                }
+                                break CloseReason::Error("client_write");
+                            }
+                                      debug!("[{}] Forwarded {} bytes response to UDP client {}", ctx.connection_id, len, peer_addr);
+                              let bytes_received = len as u64;
+                            bytes_sent += bytes_received;
+                            if bytes_received > 0
+                                && let Some(ref metrics) = metrics
+                            {
+                                metrics.record_sent(Transport::Udp, bytes_received);
+                            }
+                            if let Some(ref backend) = backend {
+                                backend.add_sent(bytes_received);
+                            }
+                        }
+                        Err(e) => {
+                            debug!("[{}] UDP connection from {} closed: {}", ctx.connection_id, peer_addr, e);
+                            break CloseReason::Error("server_read");
+                        }
+                    }
+                }
+            }
+        };
+        middleware.on_close(&ctx).await;
+        if reason != CloseReason::IdleTimeout {
+            session_manager.remove_session(&peer_addr).await;
+        }
+        if let Some(ref backend) = backend {
+            backend.add_received(
+                session_bytes
+                    .load(Ordering::Relaxed)
+                    .saturating_sub(backend_received),
+            );
+            backend.closed(matches!(reason, CloseReason::Error(_)));
+        }
+        if let Some(ref metrics) = metrics {
+            metrics.connection_closed(Transport::Udp);
+            if let CloseReason::Error(_) = reason {
+                metrics.record_error(Transport::Udp);
+            }
+            metrics.record_duration(Transport::Udp, ctx.accepted_at.elapsed());
+            metrics.record_close(
+                &ctx,
+                reason,
+                session_bytes.load(Ordering::Relaxed),
+                bytes_sent,
+            );
+        }
+        debug!(
+            "[{}] UDP session of {} closed: {}",
+            ctx.connection_id, peer_addr, reason
+        );
+        Ok(())
+    }
+}
+/**
+ * Counts an ICMP port unreachable from the destination of a session and,
+ * with `propagate_unreachable`, passes it on to its client.
+ */
+fn report_unreachable(config: &Config, metrics: Option<&InstanceMetrics>, ctx: &ConnectionContext) {
+    let peer_addr = ctx.peer_addr;
+    debug!(
+        "[{}] UDP destination of {} is unreachable",
+        ctx.connection_id, peer_addr
+    );
+    if let Some(metrics) = metrics {
+        metrics.udp_unreachable.fetch_add(1, Ordering::Relaxed);
+    }
+    if config.proxy.propagate_unreachable {
+        let listen_addr = SocketAddr::new(config.proxy.listen_ip, config.proxy.listen_port);
+        if let Err(e) = crate::icmp::send_port_unreachable(peer_addr, listen_addr) {
+            warn!(
+                "[{}] Failed to report the unreachable destination to {}: {}",
+                ctx.connection_id, peer_addr, e
+            );
+        }
+    }
+}
+/**
+ * Waits for an error queued on `socket`, such as the ICMP port unreachable
+ * of its connected destination, which does not wake a pending receive.
+ */
+async fn pending_error(socket: &UdpSocket) -> std::io::Error {
+    let result = socket
+        .async_io(Interest::ERROR, || match socket.take_error() {
+            Ok(Some(e)) => Ok(e),
+            Ok(None) => Err(std::io::ErrorKind::WouldBlock.into()),
+            Err(e) => Ok(e),
+        })
+        .await;
+    result.unwrap_or_else(|e| e)
+}
@@ -0,0 +1,257 @@
+use crate::middleware::{
+    ConnectionContext, ConnectionId, ConnectionMiddleware, Direction, MiddlewareFuture, Verdict,
+};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, info, warn};
+use wasmtime::{Caller, Engine, Extern, Linker, Memory, Module, Store, TypedFunc};
+const DEFAULT_INSPECT_LIMIT: usize = 4096;
+const MAX_INSPECT_LIMIT: usize = 1024 * 1024;
+const FUEL_PER_CALL: u64 = 10_000_000;
+struct FilterState {
+    metadata: Vec<(String, String)>,
+}
+struct FilterSession {
+    store: Store<FilterState>,
+    memory: Memory,
+    on_data: TypedFunc<(i32, i32, i32, i32), i32>,
+    buffer_ptr: usize,
+    limit: usize,
+    seen: [usize; 2],
+}
+/**
+ * Connection middleware backed by a WebAssembly module.
+ *
+ * Each connection (or UDP session) gets its own module instance, so the
+ * guest can keep per-connection state in globals. The module sees the first
+ * `inspect_limit()` bytes of each direction and may rewrite or deny them.
+ *
+ * Guest exports: `memory`, `alloc(size) -> ptr`,
+ * `on_data(direction, ptr, len, capacity) -> i32` returning the new length
+ * or a negative value to deny, and optionally `on_accept() -> i32` (non-zero
+ * denies) and `inspect_limit() -> i32`. Direction 0 is client to server.
+ *
+ * Host imports (module `voidproxy`): `set_metadata(key_ptr, key_len,
+ * value_ptr, value_len)` attaches a key/value pair to the connection, logged
+ * when it closes, and `log(ptr, len)` writes a debug message.
+ */
+pub struct WasmFilter {
+    name: String,
+    engine: Engine,
+    module: Module,
+    linker: Linker<FilterState>,
+    sessions: Mutex<HashMap<ConnectionId, Arc<Mutex<FilterSession>>>>,
+}
+fn guest_str(caller: &mut Caller<'_, FilterState>, ptr: i32, len: i32) -> Result<String> {
+    let memory = match caller.get_export("memory") {
+        Some(Extern::Memory(memory)) => memory,
+        _ => anyhow::bail!("module does not export memory"),
+    };
+    let bytes = memory
+        .data(&caller)
+        .get(ptr as usize..(ptr as usize).saturating_add(len as usize))
+        .context("string out of bounds")?;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+impl WasmFilter {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, path)
+            .with_context(|| format!("Failed to load WASM filter {}", path.display()))?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap(
+            "voidproxy",
+            "set_metadata",
+            |mut caller: Caller<'_, FilterState>,
+             key_ptr: i32,
+             key_len: i32,
+             value_ptr: i32,
+             value_len: i32|
+             -> Result<()> {
+                let key = guest_str(&mut caller, key_ptr, key_len)?;
+                let value = guest_str(&mut caller, value_ptr, value_len)?;
+                caller.data_mut().metadata.push((key, value));
+                Ok(())
+            },
+        )?;
+        let log_name = name.clone();
+        linker.func_wrap(
+            "voidproxy",
+            "log",
+            move |mut caller: Caller<'_, FilterState>, ptr: i32, len: i32| -> Result<()> {
+                let message = guest_str(&mut caller, ptr, len)?;
+                debug!("WASM filter {}: {}", log_name, message);
+                Ok(())
+            },
+        )?;
+        Ok(Self {
+            name,
+            engine,
+            module,
+            linker,
+            sessions: Mutex::new(HashMap::new()),
+        })
+    }
+    fn instantiate(&self) -> Result<(FilterSession, Option<TypedFunc<(), i32>>)> {
+        let mut store = Store::new(
+            &self.engine,
+            FilterState {
+                metadata: Vec::new(),
+            },
+        );
+        store.set_fuel(FUEL_PER_CALL)?;
+        let instance = self.linker.instantiate(&mut store, &self.module)?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .context("module does not export memory")?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+        let on_data =
+            instance.get_typed_func::<(i32, i32, i32, i32), i32>(&mut store, "on_data")?;
+        let on_accept = instance
+            .get_typed_func::<(), i32>(&mut store, "on_accept")
+            .ok();
+        let limit = match instance.get_typed_func::<(), i32>(&mut store, "inspect_limit") {
+            Ok(inspect_limit) => {
+                (inspect_limit.call(&mut store, ())?.max(0) as usize).min(MAX_INSPECT_LIMIT)
+            }
+            Err(_) => DEFAULT_INSPECT_LIMIT,
+        };
+        let buffer_ptr = alloc.call(&mut store, limit as i32)? as usize;
+        if buffer_ptr.saturating_add(limit) > memory.data_size(&store) {
+            anyhow::bail!("alloc returned a buffer outside of memory");
+        }
+        let session = FilterSession {
+            store,
+            memory,
+            on_data,
+            buffer_ptr,
+            limit,
+            seen: [0, 0],
+        };
+        Ok((session, on_accept))
+    }
+    fn accept(&self, ctx: &ConnectionContext) -> Result<Verdict> {
+        let (mut session, on_accept) = self.instantiate()?;
+        if let Some(on_accept) = on_accept {
+            session.store.set_fuel(FUEL_PER_CALL)?;
+            if on_accept.call(&mut session.store, ())? != 0 {
+                return Ok(Verdict::Reject("denied by filter".to_string()));
+            }
+        }
+        self.sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(ctx.connection_id, Arc::new(Mutex::new(session)));
+        Ok(Verdict::Continue)
+    }
+    fn filter(
+        session: &mut FilterSession,
+        direction: Direction,
+        data: &mut Vec<u8>,
+    ) -> Result<Verdict> {
+        let index = match direction {
+            Direction::ClientToServer => 0,
+            Direction::ServerToClient => 1,
+        };
+        let remaining = session.limit.saturating_sub(session.seen[index]);
+        if remaining == 0 || data.is_empty() {
+            return Ok(Verdict::Continue);
+        }
+        let len = data.len().min(remaining);
+        session.seen[index] += len;
+        let start = session.buffer_ptr;
+        session.memory.data_mut(&mut session.store)[start..start + len]
+            .copy_from_slice(&data[..len]);
+        session.store.set_fuel(FUEL_PER_CALL)?;
+        let result = session.on_data.call(
+            &mut session.store,
+            (index as i32, start as i32, len as i32, session.limit as i32),
+        )?;
+        if result < 0 {
+            return Ok(Verdict::Reject("denied by filter".to_string()));
+        }
+        let new_len = result as usize;
+        if new_len > session.limit {
+            anyhow::bail!(
+                "filter returned {} bytes, capacity is {}",
+                new_len,
+                session.limit
+            );
+        }
+        let memory = session.memory.data(&session.store);
+        data.splice(..len, memory[start..start + new_len].iter().copied());
+        Ok(Verdict::Continue)
+    }
+}
+impl ConnectionMiddleware for WasmFilter {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn on_accept<'a>(&'a self, ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async move {
+            self.accept(ctx).unwrap_or_else(|e| {
+                warn!("WASM filter {} failed on accept: {:#}", self.name, e);
+                Verdict::Reject(format!("filter error: {}", e))
+            })
+        })
+    }
+    fn on_data<'a>(
+        &'a self,
+        ctx: &'a ConnectionContext,
+        direction: Direction,
+        data: &'a mut Vec<u8>,
+    ) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async move {
+            let session = self
+                .sessions
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(&ctx.connection_id)
+                .cloned();
+            let Some(session) = session else {
+                return Verdict::Continue;
+            };
+            let mut session = session.lock().unwrap_or_else(|e| e.into_inner());
+            Self::filter(&mut session, direction, data).unwrap_or_else(|e| {
+                warn!("WASM filter {} failed on data: {:#}", self.name, e);
+                Verdict::Reject(format!("filter error: {}", e))
+            })
+        })
+    }
+    fn on_close<'a>(&'a self, ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, ()> {
+        Box::pin(async move {
+            let session = self
+                .sessions
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&ctx.connection_id);
+            if let Some(session) = session {
+                let session = session.lock().unwrap_or_else(|e| e.into_inner());
+                for (key, value) in &session.store.data().metadata {
+                    info!(
+                        "WASM filter {} metadata for connection {} from {}: {}={}",
+                        self.name, ctx.connection_id, ctx.peer_addr, key, value
+                    );
+                }
+            }
+        })
+    }
+}
+/**
+ * Compiles the filters listed in an instance's `wasm_filters`, in order.
+ */
+pub fn load_wasm_filters(paths: &[String]) -> Result<Vec<Arc<dyn ConnectionMiddleware>>> {
+    paths
+        .iter()
+        .map(|path| Ok(Arc::new(WasmFilter::load(path)?) as Arc<dyn ConnectionMiddleware>))
+        .collect()
+}
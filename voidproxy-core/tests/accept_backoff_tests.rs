@@ -0,0 +1,63 @@
+#![cfg(unix)]
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::instance::InstanceStatus;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::storage::StorageManager;
+
+fn limit_open_files(limit: u64) {
+    unsafe {
+        let mut rlimit = std::mem::zeroed::<nix::libc::rlimit>();
+        assert_eq!(
+            nix::libc::getrlimit(nix::libc::RLIMIT_NOFILE, &mut rlimit),
+            0
+        );
+        rlimit.rlim_cur = limit;
+        assert_eq!(nix::libc::setrlimit(nix::libc::RLIMIT_NOFILE, &rlimit), 0);
+    }
+}
+
+#[tokio::test]
+async fn test_listener_backs_off_and_recovers_from_exhausted_descriptors() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let request = ProxyBuilder::new(
+        "127.0.0.1:18832".parse().unwrap(),
+        "127.0.0.1:9".parse().unwrap(),
+    )
+    .into_request();
+    let instance = service.create_instance(request).await.unwrap();
+    service.start_instance(instance.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    limit_open_files(256);
+    let mut files = Vec::new();
+    while let Ok(file) = std::fs::File::open("/dev/null") {
+        files.push(file);
+    }
+    files.pop();
+    let client = TcpStream::connect("127.0.0.1:18832").await.unwrap();
+    tokio::time::sleep(Duration::from_secs(5)).await;
+    let stats = service.get_instance_stats().await[&instance.id].clone();
+    assert!(
+        stats.accept_errors >= 10,
+        "{} accept errors",
+        stats.accept_errors
+    );
+    assert!(stats.accept_errors < 20, "accept loop did not back off");
+    let failing = service.get_instance(instance.id).await.unwrap();
+    assert_eq!(failing.status, InstanceStatus::Error);
+    assert!(failing.last_error.get().is_some());
+
+    files.clear();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let recovered = service.get_instance(instance.id).await.unwrap();
+    assert_eq!(recovered.status, InstanceStatus::Running);
+    drop(client);
+    assert!(service.stop_instance(instance.id).await.unwrap());
+    let stopped = service.get_instance(instance.id).await.unwrap();
+    assert_eq!(stopped.status, InstanceStatus::Stopped);
+}
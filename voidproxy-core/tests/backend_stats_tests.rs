@@ -0,0 +1,93 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::backend::Backend;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::metrics::InstanceMetrics;
+
+#[test]
+fn test_backend_health_changes_are_counted() {
+    let metrics = InstanceMetrics::new();
+    let first: SocketAddr = "10.0.0.1:80".parse().unwrap();
+    let second: SocketAddr = "10.0.0.2:80".parse().unwrap();
+    assert!(metrics.backend(first).is_none());
+
+    metrics.report_backends(&[Backend::new(first), Backend::new(second)]);
+    metrics.report_backends(&[Backend::new(first), Backend::new(second)]);
+    let stats = metrics.backend_stats();
+    assert_eq!(stats.len(), 2);
+    assert!(
+        stats
+            .iter()
+            .all(|backend| backend.healthy && backend.health_changes == 0)
+    );
+
+    let mut failing = Backend::new(first);
+    failing.healthy = false;
+    metrics.report_backends(&[failing]);
+    metrics.report_backends(&[Backend::new(first)]);
+    let stats = metrics.backend_stats();
+    assert_eq!((stats[0].addr, stats[0].healthy), (first, true));
+    assert_eq!(stats[0].health_changes, 2);
+    assert_eq!((stats[1].addr, stats[1].healthy), (second, false));
+    assert_eq!(stats[1].health_changes, 1);
+
+    metrics.record_backend_error(second);
+    metrics.record_backend_error("10.0.0.3:80".parse().unwrap());
+    assert_eq!(metrics.backend_stats()[1].traffic.errors, 1);
+    assert_eq!(metrics.backend_stats().len(), 2);
+}
+
+#[tokio::test]
+async fn test_traffic_is_counted_per_backend() {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let live = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = upstream.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 64];
+                while let Ok(len @ 1..) = stream.read(&mut buffer).await {
+                    if stream.write_all(&buffer[..len]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    let dead = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let proxy = ProxyBuilder::new("127.0.0.1:18840".parse().unwrap(), live)
+        .backends(vec![live.to_string(), dead.to_string()])
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for _ in 0..3 {
+        let mut stream = TcpStream::connect("127.0.0.1:18840").await.unwrap();
+        let _ = stream.write_all(b"hello").await;
+        let mut reply = [0u8; 5];
+        let _ = tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply)).await;
+    }
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let stats = proxy.metrics().backend_stats();
+    let live_stats = stats.iter().find(|backend| backend.addr == live).unwrap();
+    let dead_stats = stats.iter().find(|backend| backend.addr == dead).unwrap();
+    assert_eq!(live_stats.traffic.connections_total, 2);
+    assert_eq!(live_stats.traffic.connections_active, 0);
+    assert_eq!(
+        (
+            live_stats.traffic.bytes_received,
+            live_stats.traffic.bytes_sent
+        ),
+        (10, 10)
+    );
+    assert_eq!(dead_stats.traffic.connections_total, 0);
+    assert_eq!(dead_stats.traffic.errors, 1);
+    assert!(live_stats.healthy && dead_stats.healthy);
+    proxy.shutdown().await.unwrap();
+}
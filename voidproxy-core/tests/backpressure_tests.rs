@@ -0,0 +1,43 @@
+#![cfg(target_os = "linux")]
+use socket2::SockRef;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::backpressure::{unsent_bytes, wait_for_drain};
+use voidproxy_core::metrics::InstanceMetrics;
+
+#[tokio::test]
+async fn test_wait_for_drain_reports_stalled_connections() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut writer = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (mut reader, _) = listener.accept().await.unwrap();
+    let chunk = vec![0u8; 64 * 1024];
+    let mut written = 0;
+    while tokio::time::timeout(Duration::from_millis(100), writer.write_all(&chunk))
+        .await
+        .is_ok()
+    {
+        written += chunk.len();
+    }
+    let socket = SockRef::from(&writer);
+    assert!(unsent_bytes(&socket).unwrap() > 0);
+
+    let metrics = InstanceMetrics::new();
+    assert!(!wait_for_drain(&socket, 1024, Duration::from_millis(100), Some(&metrics)).await);
+    assert_eq!(metrics.stalls_total.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.connections_stalled.load(Ordering::Relaxed), 0);
+
+    let drain = tokio::spawn(async move {
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut read = 0;
+        while read < written {
+            read += reader.read(&mut buffer).await.unwrap();
+        }
+        reader
+    });
+    assert!(wait_for_drain(&socket, 1024, Duration::from_secs(5), Some(&metrics)).await);
+    drop(drain.await.unwrap());
+}
@@ -0,0 +1,92 @@
+use chrono::{DateTime, Utc, Weekday};
+use voidproxy_core::backup::{BackupFrequency, BackupSchedule};
+use voidproxy_core::storage::StorageManager;
+
+fn at(timestamp: &str) -> DateTime<Utc> {
+    timestamp.parse().unwrap()
+}
+
+#[test]
+fn test_backup_schedule_next_run() {
+    let mut schedule = BackupSchedule {
+        at: "03:30".to_string(),
+        ..Default::default()
+    };
+    assert_eq!(schedule.next_run(at("2024-05-01T12:00:00Z")), None);
+
+    schedule.frequency = BackupFrequency::Daily;
+    assert_eq!(
+        schedule.next_run(at("2024-05-01T01:00:00Z")),
+        Some(at("2024-05-01T03:30:00Z"))
+    );
+    assert_eq!(
+        schedule.next_run(at("2024-05-01T03:30:00Z")),
+        Some(at("2024-05-02T03:30:00Z"))
+    );
+
+    schedule.frequency = BackupFrequency::Weekly;
+    schedule.weekday = Weekday::Mon;
+    assert_eq!(
+        schedule.next_run(at("2024-05-01T12:00:00Z")),
+        Some(at("2024-05-06T03:30:00Z"))
+    );
+    assert_eq!(
+        schedule.next_run(at("2024-05-06T03:00:00Z")),
+        Some(at("2024-05-06T03:30:00Z"))
+    );
+}
+
+#[test]
+fn test_backup_schedule_is_validated() {
+    let schedule = BackupSchedule {
+        frequency: BackupFrequency::Daily,
+        at: "25:00".to_string(),
+        ..Default::default()
+    };
+    assert!(schedule.validate().is_err());
+    let schedule = BackupSchedule {
+        at: "23:59".to_string(),
+        ..schedule
+    };
+    assert!(schedule.validate().is_ok());
+}
+
+#[tokio::test]
+async fn test_backup_schedule_is_persisted_and_old_backups_pruned() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let config_path = temp_dir.path().join("instances.toml");
+    let storage = StorageManager::new(config_path.clone());
+    let schedule = BackupSchedule {
+        frequency: BackupFrequency::Weekly,
+        weekday: Weekday::Fri,
+        keep: 2,
+        ..Default::default()
+    };
+    storage.set_backup_schedule(schedule.clone()).await.unwrap();
+    let reloaded = StorageManager::new(config_path.clone());
+    reloaded.load().await.unwrap();
+    assert_eq!(reloaded.backup_schedule().await, schedule);
+
+    for id in ["20240101_000000", "20240102_000000", "20240103_000000"] {
+        let path = storage.backup_path_for(id).unwrap();
+        tokio::fs::write(path, "").await.unwrap();
+    }
+    tokio::fs::write(
+        temp_dir.path().join("other.backup_20240104_000000.toml"),
+        "",
+    )
+    .await
+    .unwrap();
+    let pruned = storage.prune_backups(2).await.unwrap();
+    assert_eq!(
+        pruned,
+        vec![storage.backup_path_for("20240101_000000").unwrap()]
+    );
+    assert_eq!(
+        storage.list_backups().await.unwrap(),
+        vec![
+            storage.backup_path_for("20240102_000000").unwrap(),
+            storage.backup_path_for("20240103_000000").unwrap(),
+        ]
+    );
+}
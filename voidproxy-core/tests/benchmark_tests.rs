@@ -0,0 +1,57 @@
+use voidproxy_core::benchmark::{self, BenchmarkOptions};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::Protocol;
+use voidproxy_core::middleware::MiddlewareChain;
+
+#[tokio::test]
+async fn test_benchmark_measures_both_protocols_on_loopback() {
+    let config = ProxyBuilder::new(
+        "127.0.0.1:18801".parse().unwrap(),
+        "192.0.2.1:9".parse().unwrap(),
+    )
+    .protocol(Protocol::Both)
+    .allow_list(vec!["10.0.0.1".parse().unwrap()])
+    .into_request()
+    .to_config();
+    let options = BenchmarkOptions {
+        duration_secs: 1,
+        payload_size: 1400,
+    };
+
+    let report = benchmark::run(&config, MiddlewareChain::default(), options)
+        .await
+        .unwrap();
+    for throughput in [report.tcp.unwrap(), report.udp.unwrap()] {
+        assert!(throughput.duration_secs >= 1.0);
+        assert!(throughput.bytes >= throughput.packets * 1400);
+        assert!(throughput.packets > 0);
+        assert!(throughput.megabytes_per_sec > 0.0);
+        assert!(throughput.packets_per_sec > 0.0);
+    }
+    assert!(
+        tokio::net::TcpStream::connect("127.0.0.1:18801")
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_benchmark_rejects_out_of_range_options() {
+    let config = ProxyBuilder::new(
+        "127.0.0.1:18802".parse().unwrap(),
+        "127.0.0.1:9".parse().unwrap(),
+    )
+    .into_request()
+    .to_config();
+    for (duration_secs, payload_size) in [(0, 1400), (31, 1400), (1, 0), (1, 70_000)] {
+        let options = BenchmarkOptions {
+            duration_secs,
+            payload_size,
+        };
+        assert!(
+            benchmark::run(&config, MiddlewareChain::default(), options)
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::blocklist::{self, Blocklist, parse_feed};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+async fn serve_feed(body: &'static str) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+    addr
+}
+
+#[test]
+fn test_parse_feed_formats() {
+    let networks = parse_feed(
+        "; Spamhaus DROP List\n\
+         1.10.16.0/20 ; SBL256894\n\
+         # FireHOL\n\
+         203.0.113.7\n\
+         2001:db8::/32\n\
+         not-an-address\n\
+         \n",
+    );
+    assert_eq!(networks.len(), 3);
+    let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+    assert!(networks.contains(&ip("1.10.20.1")));
+    assert!(!networks.contains(&ip("1.10.32.1")));
+    assert!(networks.contains(&ip("203.0.113.7")));
+    assert!(networks.contains(&ip("::ffff:203.0.113.7")));
+    assert!(!networks.contains(&ip("203.0.113.8")));
+    assert!(networks.contains(&ip("2001:db8:1::1")));
+}
+
+#[tokio::test]
+async fn test_feeds_refresh_and_report_status() {
+    let feed = serve_feed("198.51.100.0/24\n").await;
+    let unreachable = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let blocklist = Blocklist::default();
+    blocklist.subscribe(format!("http://{}/drop.txt", feed));
+    blocklist.subscribe(format!("http://{}/gone.txt", unreachable));
+    blocklist.refresh().await;
+
+    assert!(blocklist.check(&"198.51.100.20".parse().unwrap()));
+    assert!(!blocklist.check(&"198.51.101.20".parse().unwrap()));
+    let status = blocklist.status();
+    assert_eq!(status.entries, 1);
+    assert_eq!(status.blocked, 1);
+    assert_eq!(status.feeds.len(), 2);
+    assert_eq!(status.feeds[0].entries, 1);
+    assert!(status.feeds[0].last_updated.is_some());
+    assert!(status.feeds[0].last_error.is_none());
+    assert!(status.feeds[1].last_updated.is_none());
+    assert!(status.feeds[1].last_error.is_some());
+}
+
+#[tokio::test]
+async fn test_global_blocklist_rejects_connections() {
+    let echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let proxy = ProxyBuilder::new(
+        "127.0.0.1:18837".parse().unwrap(),
+        echo.local_addr().unwrap(),
+    )
+    .spawn()
+    .unwrap();
+    tokio::spawn(echo.serve());
+    let feed = serve_feed("127.0.0.1\n").await;
+    blocklist::global().subscribe(format!("http://{}/", feed));
+    blocklist::global().refresh().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18837").await.unwrap();
+    let _ = stream.write_all(b"ping").await;
+    let mut reply = [0u8; 4];
+    let read = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut reply))
+        .await
+        .unwrap();
+    assert!(matches!(read, Ok(0) | Err(_)));
+    assert!(blocklist::global().status().blocked >= 1);
+    proxy.shutdown().await.unwrap();
+}
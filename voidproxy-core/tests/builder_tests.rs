@@ -0,0 +1,54 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::builder::ProxyBuilder;
+
+#[tokio::test]
+async fn test_builder_forwards_tcp_traffic() {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = upstream.accept().await.unwrap();
+        let mut buffer = [0u8; 5];
+        stream.read_exact(&mut buffer).await.unwrap();
+        stream.write_all(&buffer).await.unwrap();
+    });
+
+    let proxy = ProxyBuilder::new("127.0.0.1:18740".parse().unwrap(), upstream_addr)
+        .name("embedded")
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = TcpStream::connect("127.0.0.1:18740").await.unwrap();
+    client.write_all(b"hello").await.unwrap();
+    let mut reply = [0u8; 5];
+    client.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"hello");
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(proxy.metrics().connections_total.load(Ordering::Relaxed), 1);
+    proxy.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_builder_reports_listener_errors() {
+    let occupied = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = ProxyBuilder::new(
+        occupied.local_addr().unwrap(),
+        "127.0.0.1:9".parse().unwrap(),
+    )
+    .spawn()
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(proxy.shutdown().await.is_err());
+    assert!(
+        ProxyBuilder::new("127.0.0.1:18741".parse().unwrap(), "127.0.0.1:80".parse().unwrap())
+            .connect_timeout(Duration::ZERO)
+            .spawn()
+            .is_err()
+    );
+}
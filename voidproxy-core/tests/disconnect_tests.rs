@@ -0,0 +1,156 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::Protocol;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::middleware::{CloseReason, Transport};
+use voidproxy_core::storage::StorageManager;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+#[test]
+fn test_close_reasons_render_as_labels() {
+    assert_eq!(CloseReason::IdleTimeout.to_string(), "idle_timeout");
+    assert_eq!(
+        CloseReason::Error("server_read").to_string(),
+        "error:server_read"
+    );
+    assert_eq!(
+        serde_json::to_string(&CloseReason::ClientEof).unwrap(),
+        "\"client_eof\""
+    );
+}
+
+#[tokio::test]
+async fn test_disconnects_are_counted_by_reason() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = upstream.accept().await {
+            tokio::spawn(async move {
+                let mut request = [0u8; 1];
+                if let Ok(1) = stream.read(&mut request).await
+                    && request[0] == b'q'
+                {
+                    return;
+                }
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            });
+        }
+    });
+    let udp_echo = TestServer::bind(TestServerKind::UdpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let udp_echo_addr = udp_echo.local_addr().unwrap();
+    tokio::spawn(udp_echo.serve());
+    let tcp = ProxyBuilder::new("127.0.0.1:18808".parse().unwrap(), upstream_addr)
+        .idle_timeout(Duration::from_secs(1))
+        .into_request();
+    let udp = ProxyBuilder::new("127.0.0.1:18809".parse().unwrap(), udp_echo_addr)
+        .protocol(Protocol::Udp)
+        .idle_timeout(Duration::from_secs(1))
+        .into_request();
+    let tcp = service.create_instance(tcp).await.unwrap();
+    let udp = service.create_instance(udp).await.unwrap();
+    service.start_instance(tcp.id).await.unwrap();
+    service.start_instance(udp.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut closed_by_server = TcpStream::connect("127.0.0.1:18808").await.unwrap();
+    closed_by_server.write_all(b"q").await.unwrap();
+    let mut idle = TcpStream::connect("127.0.0.1:18808").await.unwrap();
+    idle.write_all(b"w").await.unwrap();
+    let client = TcpStream::connect("127.0.0.1:18808").await.unwrap();
+    drop(client);
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(b"ping", "127.0.0.1:18809").await.unwrap();
+    let mut reply = [0u8; 4];
+    tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(3500)).await;
+
+    let stats = service.get_instance_stats().await;
+    let tcp_disconnects = &stats[&tcp.id].tcp_disconnects;
+    for reason in ["server_eof", "idle_timeout", "client_eof"] {
+        assert_eq!(tcp_disconnects.get(reason), Some(&1), "{}", reason);
+    }
+    assert_eq!(stats[&udp.id].udp_disconnects.get("idle_timeout"), Some(&1));
+
+    let history = service.connection_history(tcp.id).await.unwrap();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].reason, CloseReason::IdleTimeout);
+    assert_eq!(history[0].bytes_received, 1);
+    assert!(history[0].duration_ms >= 1000);
+    assert!(history.iter().all(|entry| entry.backend == upstream_addr));
+    let sessions = service.connection_history(udp.id).await.unwrap();
+    assert_eq!(sessions[0].transport, Transport::Udp);
+    assert_eq!((sessions[0].bytes_received, sessions[0].bytes_sent), (4, 4));
+    assert!(
+        service
+            .connection_history(uuid::Uuid::new_v4())
+            .await
+            .is_none()
+    );
+    service.stop_instance(tcp.id).await.unwrap();
+    service.stop_instance(udp.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connections_are_closed_at_max_lifetime() {
+    let tcp_echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let tcp_echo_addr = tcp_echo.local_addr().unwrap();
+    tokio::spawn(tcp_echo.serve());
+    let udp_echo = TestServer::bind(TestServerKind::UdpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let udp_echo_addr = udp_echo.local_addr().unwrap();
+    tokio::spawn(udp_echo.serve());
+    let tcp = ProxyBuilder::new("127.0.0.1:18843".parse().unwrap(), tcp_echo_addr)
+        .max_connection_lifetime(Duration::from_secs(1))
+        .spawn()
+        .unwrap();
+    let udp = ProxyBuilder::new("127.0.0.1:18844".parse().unwrap(), udp_echo_addr)
+        .protocol(Protocol::Udp)
+        .max_connection_lifetime(Duration::from_secs(1))
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18843").await.unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let mut reply = [0u8; 4];
+    let closed = tokio::time::timeout(Duration::from_secs(3), async {
+        loop {
+            if stream.write_all(b"ping").await.is_err() {
+                break;
+            }
+            match stream.read(&mut reply).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    })
+    .await;
+    assert!(closed.is_ok());
+    socket.send_to(b"ping", "127.0.0.1:18844").await.unwrap();
+    tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+
+    let tcp_stats = tcp.metrics().get_stats(None).await;
+    assert_eq!(tcp_stats.tcp_disconnects.get("max_lifetime"), Some(&1));
+    let udp_stats = udp.metrics().get_stats(None).await;
+    assert_eq!(udp_stats.udp_disconnects.get("max_lifetime"), Some(&1));
+    tcp.shutdown().await.unwrap();
+    udp.shutdown().await.unwrap();
+}
@@ -0,0 +1,32 @@
+#![cfg(unix)]
+use voidproxy_core::fd_limit;
+
+#[test]
+fn test_raise_limit_raises_up_to_the_hard_limit_and_never_lowers() {
+    let mut limit = unsafe { std::mem::zeroed::<nix::libc::rlimit>() };
+    assert_eq!(
+        unsafe { nix::libc::getrlimit(nix::libc::RLIMIT_NOFILE, &mut limit) },
+        0
+    );
+    limit.rlim_cur = 256.min(limit.rlim_max);
+    assert_eq!(
+        unsafe { nix::libc::setrlimit(nix::libc::RLIMIT_NOFILE, &limit) },
+        0
+    );
+    assert_eq!(fd_limit::current_limit(), Some(limit.rlim_cur as u64));
+
+    let raised = fd_limit::raise_limit(512).unwrap();
+    assert_eq!(raised, 512.min(limit.rlim_max as u64));
+    assert_eq!(fd_limit::raise_limit(128).unwrap(), raised);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_open_files_counts_the_descriptors_of_the_process() {
+    let before = fd_limit::open_files().unwrap();
+    let files: Vec<_> = (0..10)
+        .map(|_| std::fs::File::open("/dev/null").unwrap())
+        .collect();
+    assert!(fd_limit::open_files().unwrap() >= before + 10);
+    drop(files);
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::Protocol;
+use voidproxy_core::footprint::Footprint;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+#[tokio::test]
+async fn test_compact_footprint_proxies_on_a_current_thread_runtime() {
+    assert_eq!(Footprint::current(), Footprint::Standard);
+    Footprint::Compact.install().unwrap();
+    assert_eq!(Footprint::current(), Footprint::Compact);
+    assert!(Footprint::Standard.install().is_err());
+    assert!(Footprint::Compact.udp_batch_size() < Footprint::Standard.udp_batch_size());
+
+    let tcp_echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let udp_echo = TestServer::bind(TestServerKind::UdpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let tcp = ProxyBuilder::new(
+        "127.0.0.1:18812".parse().unwrap(),
+        tcp_echo.local_addr().unwrap(),
+    )
+    .spawn()
+    .unwrap();
+    let udp = ProxyBuilder::new(
+        "127.0.0.1:18813".parse().unwrap(),
+        udp_echo.local_addr().unwrap(),
+    )
+    .protocol(Protocol::Udp)
+    .spawn()
+    .unwrap();
+    tokio::spawn(tcp_echo.serve());
+    tokio::spawn(udp_echo.serve());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18812").await.unwrap();
+    stream.write_all(b"tiny").await.unwrap();
+    let mut reply = [0u8; 4];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&reply, b"tiny");
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    for _ in 0..3 {
+        socket.send_to(b"ping", "127.0.0.1:18813").await.unwrap();
+        let mut reply = [0u8; 4];
+        tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut reply))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&reply, b"ping");
+    }
+    tcp.shutdown().await.unwrap();
+    udp.shutdown().await.unwrap();
+}
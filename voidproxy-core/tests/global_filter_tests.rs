@@ -0,0 +1,64 @@
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::global_filter::{self, GlobalFilter, GlobalFilterConfig};
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+#[test]
+fn test_allow_and_deny_networks() {
+    let config: GlobalFilterConfig = serde_json::from_str(
+        r#"{"allow": ["10.0.0.0/8", "2001:db8::/32"], "deny": ["10.1.2.3", "10.9.0.0/16"]}"#,
+    )
+    .unwrap();
+    let filter = GlobalFilter::default();
+    let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+    assert!(filter.is_allowed(&ip("192.0.2.1")));
+    filter.set(config);
+    assert!(filter.is_allowed(&ip("10.1.2.4")));
+    assert!(filter.is_allowed(&ip("::ffff:10.1.2.4")));
+    assert!(filter.is_allowed(&ip("2001:db8::1")));
+    assert!(!filter.is_allowed(&ip("10.1.2.3")));
+    assert!(!filter.is_allowed(&ip("10.9.1.1")));
+    assert!(!filter.is_allowed(&ip("192.0.2.1")));
+    let status = filter.status();
+    assert_eq!(status.rejected, 3);
+    assert_eq!(status.config.deny[0].to_string(), "10.1.2.3/32");
+
+    assert!(serde_json::from_str::<GlobalFilterConfig>(r#"{"deny": ["10.0.0.0/33"]}"#).is_err());
+}
+
+#[tokio::test]
+async fn test_global_filter_rejects_connections_on_all_instances() {
+    let echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let proxy = ProxyBuilder::new(
+        "127.0.0.1:18838".parse().unwrap(),
+        echo.local_addr().unwrap(),
+    )
+    .spawn()
+    .unwrap();
+    tokio::spawn(echo.serve());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let round_trip = || async {
+        let mut stream = TcpStream::connect("127.0.0.1:18838").await.unwrap();
+        let _ = stream.write_all(b"ping").await;
+        let mut reply = [0u8; 4];
+        matches!(
+            tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply)).await,
+            Ok(Ok(_))
+        )
+    };
+    assert!(round_trip().await);
+    global_filter::global().set(GlobalFilterConfig {
+        allow: Vec::new(),
+        deny: vec!["127.0.0.0/8".parse().unwrap()],
+    });
+    assert!(!round_trip().await);
+    global_filter::global().set(GlobalFilterConfig::default());
+    assert!(round_trip().await);
+    proxy.shutdown().await.unwrap();
+}
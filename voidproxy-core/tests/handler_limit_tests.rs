@@ -0,0 +1,53 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+async fn echo(stream: &mut TcpStream, message: &[u8], wait: Duration) -> bool {
+    stream.write_all(message).await.unwrap();
+    let mut reply = vec![0u8; message.len()];
+    tokio::time::timeout(wait, stream.read_exact(&mut reply))
+        .await
+        .is_ok()
+}
+
+#[tokio::test]
+async fn test_connections_beyond_the_budget_wait_for_a_handler() {
+    let server = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let echo_addr = server.local_addr().unwrap();
+    tokio::spawn(server.serve());
+    let proxy = ProxyBuilder::new("127.0.0.1:18810".parse().unwrap(), echo_addr)
+        .max_concurrent_handlers(1)
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut first = TcpStream::connect("127.0.0.1:18810").await.unwrap();
+    assert!(echo(&mut first, b"one", Duration::from_secs(2)).await);
+    let mut second = TcpStream::connect("127.0.0.1:18810").await.unwrap();
+    assert!(!echo(&mut second, b"two", Duration::from_millis(300)).await);
+    assert_eq!(proxy.metrics().handler_waits.load(Ordering::Relaxed), 1);
+
+    drop(first);
+    let mut reply = [0u8; 3];
+    tokio::time::timeout(Duration::from_secs(2), second.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&reply, b"two");
+    proxy.shutdown().await.unwrap();
+
+    assert!(
+        ProxyBuilder::new(
+            "127.0.0.1:18811".parse().unwrap(),
+            "127.0.0.1:9".parse().unwrap()
+        )
+        .max_concurrent_handlers(0)
+        .spawn()
+        .is_err()
+    );
+}
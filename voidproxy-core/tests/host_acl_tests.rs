@@ -0,0 +1,40 @@
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+async fn echo_round_trip(addr: &str) -> bool {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    if stream.write_all(b"ping").await.is_err() {
+        return false;
+    }
+    let mut reply = [0u8; 4];
+    matches!(
+        tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply)).await,
+        Ok(Ok(_))
+    )
+}
+
+#[tokio::test]
+async fn test_filter_hostnames_match_their_resolved_addresses() {
+    let echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let echo_addr = echo.local_addr().unwrap();
+    tokio::spawn(echo.serve());
+    let allowed = ProxyBuilder::new("127.0.0.1:18835".parse().unwrap(), echo_addr)
+        .allow_hosts(vec!["localhost".to_string()])
+        .spawn()
+        .unwrap();
+    let denied = ProxyBuilder::new("127.0.0.1:18836".parse().unwrap(), echo_addr)
+        .deny_hosts(vec!["localhost".to_string()])
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    assert!(echo_round_trip("127.0.0.1:18835").await);
+    assert!(!echo_round_trip("127.0.0.1:18836").await);
+    allowed.shutdown().await.unwrap();
+    denied.shutdown().await.unwrap();
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::instance::{InstanceManager, ProxyInstance};
+use voidproxy_core::tcp_proxy::TcpProxy;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+#[tokio::test]
+async fn test_traffic_is_counted_while_the_instance_map_is_locked() {
+    let echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let config = ProxyBuilder::new(
+        "127.0.0.1:18830".parse().unwrap(),
+        echo.local_addr().unwrap(),
+    )
+    .into_request()
+    .to_config();
+    tokio::spawn(echo.serve());
+    let instance = ProxyInstance::new("locked-map".to_string(), config.clone(), false);
+    let (id, metrics) = (instance.id, instance.metrics.clone());
+    let instances: InstanceManager = Arc::new(RwLock::new(HashMap::from([(id, instance)])));
+    let proxy = TcpProxy::new(Arc::new(config), id, instances.clone());
+    let cancel_token = Arc::new(CancellationToken::new());
+    let token = cancel_token.clone();
+    tokio::spawn(async move { proxy.run_with_token(token).await });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let guard = instances.write().await;
+    let mut stream = TcpStream::connect("127.0.0.1:18830").await.unwrap();
+    stream.write_all(b"ping").await.unwrap();
+    let mut reply = [0u8; 4];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(metrics.bytes_received.load(Ordering::Relaxed), 4);
+    assert_eq!(metrics.bytes_sent.load(Ordering::Relaxed), 4);
+    assert_eq!(metrics.connections_total.load(Ordering::Relaxed), 1);
+    assert_eq!(metrics.connections_active.load(Ordering::Relaxed), 0);
+    drop(guard);
+    cancel_token.cancel();
+}
+
+#[tokio::test]
+async fn test_open_connections_flush_their_bytes_periodically() {
+    let echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let proxy = ProxyBuilder::new(
+        "127.0.0.1:18831".parse().unwrap(),
+        echo.local_addr().unwrap(),
+    )
+    .spawn()
+    .unwrap();
+    tokio::spawn(echo.serve());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18831").await.unwrap();
+    let mut reply = [0u8; 4];
+    for _ in 0..2 {
+        stream.write_all(b"ping").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+    }
+    stream.write_all(b"ping").await.unwrap();
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    let metrics = proxy.metrics();
+    assert!(metrics.bytes_received.load(Ordering::Relaxed) >= 8);
+    assert!(metrics.bytes_sent.load(Ordering::Relaxed) >= 8);
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(metrics.bytes_received.load(Ordering::Relaxed), 12);
+    assert_eq!(metrics.bytes_sent.load(Ordering::Relaxed), 12);
+    proxy.shutdown().await.unwrap();
+}
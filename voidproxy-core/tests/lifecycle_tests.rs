@@ -0,0 +1,37 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::instance::InstanceStatus;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::storage::StorageManager;
+
+#[tokio::test]
+async fn test_instances_start_and_stop_concurrently() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let mut ids = Vec::new();
+    for port in 18820..18830 {
+        let listen = format!("127.0.0.1:{}", port).parse().unwrap();
+        let request = ProxyBuilder::new(listen, "127.0.0.1:9".parse().unwrap())
+            .name(format!("forward-{}", port))
+            .into_request();
+        ids.push(service.create_instance(request).await.unwrap().id);
+    }
+
+    let results = service.start_instances(&ids).await;
+    assert!(results.iter().all(|result| matches!(result, Ok(true))));
+    for id in &ids {
+        let instance = service.get_instance(*id).await.unwrap();
+        assert_eq!(instance.status, InstanceStatus::Running);
+    }
+
+    let started = Instant::now();
+    let results = service.stop_instances(&ids).await;
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(results.iter().all(|result| matches!(result, Ok(true))));
+    for id in &ids {
+        let instance = service.get_instance(*id).await.unwrap();
+        assert_eq!(instance.status, InstanceStatus::Stopped);
+    }
+}
@@ -0,0 +1,68 @@
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::LogLimitConfig;
+use voidproxy_core::log_limit::{LogClass, LogLimiter, Suppressed};
+
+#[test]
+fn test_classes_are_limited_independently() {
+    let limiter = LogLimiter::new(LogLimitConfig {
+        per_sec: 2,
+        sample_every: 0,
+    });
+    assert_eq!(limiter.admit(LogClass::Rejected), Some(Suppressed(0)));
+    assert_eq!(limiter.admit(LogClass::Rejected), Some(Suppressed(0)));
+    for _ in 0..50 {
+        assert_eq!(limiter.admit(LogClass::Rejected), None);
+    }
+    assert_eq!(limiter.admit(LogClass::Relay), Some(Suppressed(0)));
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert_eq!(limiter.admit(LogClass::Rejected), Some(Suppressed(50)));
+    assert_eq!(limiter.admit(LogClass::Rejected), Some(Suppressed(0)));
+    assert_eq!(Suppressed(0).to_string(), "");
+    assert_eq!(
+        Suppressed(50).to_string(),
+        " (50 similar messages suppressed)"
+    );
+}
+
+#[test]
+fn test_sampling_beyond_the_limit() {
+    let limiter = LogLimiter::new(LogLimitConfig {
+        per_sec: 1,
+        sample_every: 10,
+    });
+    assert!(limiter.admit(LogClass::Handler).is_some());
+    let admitted: Vec<_> = (0..30)
+        .filter_map(|_| limiter.admit(LogClass::Handler))
+        .collect();
+    assert_eq!(admitted, vec![Suppressed(9); 3]);
+
+    let unlimited = LogLimiter::new(LogLimitConfig {
+        per_sec: 0,
+        sample_every: 0,
+    });
+    assert!((0..1000).all(|_| unlimited.admit(LogClass::Rejected) == Some(Suppressed(0))));
+}
+
+#[test]
+fn test_log_limit_config() {
+    let builder = || {
+        ProxyBuilder::new(
+            "127.0.0.1:8080".parse().unwrap(),
+            "127.0.0.1:8081".parse().unwrap(),
+        )
+    };
+    let config = builder().into_request().to_config();
+    assert_eq!(config.proxy.log_limit, None);
+    assert!(!toml::to_string(&config).unwrap().contains("log_limit"));
+    let config = builder()
+        .log_limit(LogLimitConfig {
+            per_sec: 0,
+            sample_every: 5,
+        })
+        .into_request()
+        .to_config();
+    assert!(config.validate().is_err());
+    let parsed: LogLimitConfig = toml::from_str("sample_every = 100").unwrap();
+    assert_eq!(parsed.per_sec, 10);
+    assert_eq!(parsed.sample_every, 100);
+}
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use voidproxy_core::buffer_pool::BufferPool;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::Protocol;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::metrics::{TCP_CONNECTION_BYTES, UDP_SESSION_BYTES};
+use voidproxy_core::storage::StorageManager;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+#[tokio::test]
+async fn test_pool_releases_its_bytes_from_the_gauge_when_dropped() {
+    let gauge = Arc::new(std::sync::atomic::AtomicU64::new(0));
+    let pool = BufferPool::new(1, 1).with_gauge(gauge.clone());
+    let buffer = pool.acquire(8192).await;
+    assert_eq!(pool.held_bytes(), 8192);
+    drop(buffer);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(gauge.load(std::sync::atomic::Ordering::Relaxed), 8192);
+    drop(pool);
+    assert_eq!(gauge.load(std::sync::atomic::Ordering::Relaxed), 0);
+}
+
+#[tokio::test]
+async fn test_memory_is_attributed_to_the_instance_holding_it() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let tcp_echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let udp_echo = TestServer::bind(TestServerKind::UdpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let tcp = ProxyBuilder::new(
+        "127.0.0.1:18814".parse().unwrap(),
+        tcp_echo.local_addr().unwrap(),
+    )
+    .into_request();
+    let udp = ProxyBuilder::new(
+        "127.0.0.1:18815".parse().unwrap(),
+        udp_echo.local_addr().unwrap(),
+    )
+    .protocol(Protocol::Udp)
+    .into_request();
+    tokio::spawn(tcp_echo.serve());
+    tokio::spawn(udp_echo.serve());
+    let tcp = service.create_instance(tcp).await.unwrap();
+    let udp = service.create_instance(udp).await.unwrap();
+    service.start_instance(tcp.id).await.unwrap();
+    service.start_instance(udp.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18814").await.unwrap();
+    stream.write_all(b"ping").await.unwrap();
+    let mut reply = [0u8; 4];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(b"ping", "127.0.0.1:18815").await.unwrap();
+    tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let stats = service.get_instance_stats().await;
+    let tcp_memory = stats[&tcp.id].memory;
+    assert_eq!(tcp_memory.connections, TCP_CONNECTION_BYTES);
+    assert_eq!(tcp_memory.buffers, 2 * 8192);
+    assert_eq!(tcp_memory.udp_sessions, 0);
+    let udp_memory = stats[&udp.id].memory;
+    assert_eq!(udp_memory.udp_sessions, UDP_SESSION_BYTES);
+    assert!(udp_memory.buffers >= 65535);
+    assert_eq!(
+        udp_memory.total,
+        udp_memory.buffers + udp_memory.udp_sessions + udp_memory.connections
+    );
+
+    drop(stream);
+    service.stop_instance(tcp.id).await.unwrap();
+    service.stop_instance(udp.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let stats = service.get_instance_stats().await;
+    assert_eq!(stats[&tcp.id].memory.total, 0);
+    assert_eq!(stats[&udp.id].memory.total, 0);
+}
+
+#[tokio::test]
+async fn test_instance_buffer_pool_size_overrides_the_footprint() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let tcp_echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let request = ProxyBuilder::new(
+        "127.0.0.1:18817".parse().unwrap(),
+        tcp_echo.local_addr().unwrap(),
+    )
+    .buffer_pool(0, 4)
+    .ip_cache(16, Duration::from_secs(5))
+    .into_request();
+    tokio::spawn(tcp_echo.serve());
+    let instance = service.create_instance(request).await.unwrap();
+    assert_eq!(instance.config.proxy.ip_cache_ttl_secs, Some(5));
+    service.start_instance(instance.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18817").await.unwrap();
+    stream.write_all(b"ping").await.unwrap();
+    let mut reply = [0u8; 4];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        service.get_instance_stats().await[&instance.id].memory.buffers,
+        2 * 8192
+    );
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(
+        service.get_instance_stats().await[&instance.id].memory.buffers,
+        0
+    );
+    service.stop_instance(instance.id).await.unwrap();
+}
@@ -0,0 +1,163 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::middleware::{
+    ConnectionContext, ConnectionId, ConnectionMiddleware, Direction, MiddlewareFuture, Verdict,
+};
+
+struct Uppercase {
+    closed: AtomicUsize,
+}
+
+impl ConnectionMiddleware for Uppercase {
+    fn name(&self) -> &str {
+        "uppercase"
+    }
+    fn on_data<'a>(
+        &'a self,
+        _ctx: &'a ConnectionContext,
+        direction: Direction,
+        data: &'a mut Vec<u8>,
+    ) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async move {
+            if direction == Direction::ClientToServer {
+                data.make_ascii_uppercase();
+            }
+            Verdict::Continue
+        })
+    }
+    fn on_close<'a>(&'a self, _ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, ()> {
+        Box::pin(async move {
+            self.closed.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+}
+
+#[derive(Default)]
+struct RecordIds {
+    ids: Mutex<Vec<ConnectionId>>,
+}
+
+impl ConnectionMiddleware for RecordIds {
+    fn name(&self) -> &str {
+        "record-ids"
+    }
+    fn on_close<'a>(&'a self, ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, ()> {
+        Box::pin(async move {
+            self.ids.lock().unwrap().push(ctx.connection_id);
+        })
+    }
+}
+
+struct RejectAll;
+
+impl ConnectionMiddleware for RejectAll {
+    fn name(&self) -> &str {
+        "reject-all"
+    }
+    fn on_accept<'a>(&'a self, _ctx: &'a ConnectionContext) -> MiddlewareFuture<'a, Verdict> {
+        Box::pin(async { Verdict::Reject("closed for maintenance".to_string()) })
+    }
+}
+
+async fn echo_upstream() -> std::net::SocketAddr {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = upstream.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 1024];
+                while let Ok(n) = stream.read(&mut buffer).await {
+                    if n == 0 || stream.write_all(&buffer[..n]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    upstream_addr
+}
+
+#[tokio::test]
+async fn test_middleware_rewrites_tcp_data() {
+    let upstream_addr = echo_upstream().await;
+    let middleware = Arc::new(Uppercase {
+        closed: AtomicUsize::new(0),
+    });
+    let proxy = ProxyBuilder::new("127.0.0.1:18750".parse().unwrap(), upstream_addr)
+        .middleware(middleware.clone())
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = TcpStream::connect("127.0.0.1:18750").await.unwrap();
+    client.write_all(b"hello").await.unwrap();
+    let mut reply = [0u8; 5];
+    client.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"HELLO");
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_eq!(middleware.closed.load(Ordering::SeqCst), 1);
+    proxy.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_middleware_rejects_tcp_connection() {
+    let upstream_addr = echo_upstream().await;
+    let proxy = ProxyBuilder::new("127.0.0.1:18751".parse().unwrap(), upstream_addr)
+        .middleware(Arc::new(RejectAll))
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = TcpStream::connect("127.0.0.1:18751").await.unwrap();
+    let _ = client.write_all(b"hello").await;
+    let mut reply = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(2), client.read_to_end(&mut reply)).await;
+    assert!(read.is_ok());
+    assert!(reply.is_empty());
+    proxy.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_connection_ids_match_history() {
+    let first = ConnectionId::next();
+    let second = ConnectionId::next();
+    assert_ne!(first, second);
+    assert_eq!(first.to_string().len(), 12);
+    assert_eq!(first.to_string().parse::<ConnectionId>().unwrap(), first);
+
+    let upstream_addr = echo_upstream().await;
+    let middleware = Arc::new(RecordIds::default());
+    let proxy = ProxyBuilder::new("127.0.0.1:18752".parse().unwrap(), upstream_addr)
+        .middleware(middleware.clone())
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for _ in 0..2 {
+        let mut client = TcpStream::connect("127.0.0.1:18752").await.unwrap();
+        client.write_all(b"ping").await.unwrap();
+        let mut reply = [0u8; 4];
+        client.read_exact(&mut reply).await.unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut seen = middleware.ids.lock().unwrap().clone();
+    let mut history: Vec<ConnectionId> = proxy
+        .metrics()
+        .connection_history()
+        .iter()
+        .map(|closed| closed.connection_id)
+        .collect();
+    seen.sort_by_key(|id| id.to_string());
+    history.sort_by_key(|id| id.to_string());
+    assert_eq!(seen.len(), 2);
+    assert_ne!(seen[0], seen[1]);
+    assert_eq!(seen, history);
+    proxy.shutdown().await.unwrap();
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::MirrorConfig;
+
+#[tokio::test]
+async fn test_mirror_samples_connections_within_hourly_cap() {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = upstream.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 64];
+                while let Ok(len @ 1..) = stream.read(&mut buffer).await {
+                    if stream.write_all(&buffer[..len]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    let mirror = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mirror_addr = mirror.local_addr().unwrap();
+    let mirrored = Arc::new(AtomicUsize::new(0));
+    tokio::spawn({
+        let mirrored = mirrored.clone();
+        async move {
+            while let Ok((mut stream, _)) = mirror.accept().await {
+                let mirrored = mirrored.clone();
+                tokio::spawn(async move {
+                    let mut buffer = [0u8; 64];
+                    while let Ok(len @ 1..) = stream.read(&mut buffer).await {
+                        mirrored.fetch_add(len, Ordering::Relaxed);
+                    }
+                });
+            }
+        }
+    });
+    let proxy = ProxyBuilder::new("127.0.0.1:18841".parse().unwrap(), upstream_addr)
+        .mirror(MirrorConfig {
+            addr: mirror_addr,
+            sample_percent: 50,
+            max_bytes_per_hour: Some(8),
+        })
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for _ in 0..4 {
+        let mut stream = TcpStream::connect("127.0.0.1:18841").await.unwrap();
+        stream.write_all(b"hello").await.unwrap();
+        let mut reply = [0u8; 5];
+        tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&reply, b"hello");
+    }
+    tokio::time::sleep(Duration::from_millis(300)).await;
+
+    let metrics = proxy.metrics();
+    assert_eq!(metrics.mirrored_connections.load(Ordering::Relaxed), 2);
+    assert_eq!(metrics.mirrored_bytes.load(Ordering::Relaxed), 5);
+    assert_eq!(metrics.mirror_dropped_bytes.load(Ordering::Relaxed), 5);
+    assert_eq!(mirrored.load(Ordering::Relaxed), 5);
+    proxy.shutdown().await.unwrap();
+}
+
+#[test]
+fn test_mirror_config_is_validated() {
+    let mirror = MirrorConfig {
+        addr: "127.0.0.1:18842".parse().unwrap(),
+        sample_percent: 0,
+        max_bytes_per_hour: None,
+    };
+    let builder = |mirror| {
+        ProxyBuilder::new(
+            "127.0.0.1:18842".parse().unwrap(),
+            "127.0.0.1:9".parse().unwrap(),
+        )
+        .mirror(mirror)
+    };
+    assert!(builder(mirror).spawn().is_err());
+    let mirror = MirrorConfig {
+        sample_percent: 100,
+        ..mirror
+    };
+    assert!(builder(mirror).spawn().is_err());
+}
@@ -0,0 +1,80 @@
+use std::sync::Arc;
+use tempfile::TempDir;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::instance::UpdateInstanceRequest;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::naming::{NameError, NamingPolicy};
+use voidproxy_core::storage::StorageManager;
+
+#[test]
+fn test_naming_policy_check() {
+    let policy = NamingPolicy {
+        unique: true,
+        pattern: Some("[a-z0-9-]+".to_string()),
+    };
+    assert!(policy.validate().is_ok());
+    assert_eq!(policy.check("web-1", ["web-2"]), Ok(()));
+    assert_eq!(
+        policy.check("web-1", ["web-1"]),
+        Err(NameError::Duplicate("web-1".to_string()))
+    );
+    assert!(matches!(
+        policy.check("Web 1", []),
+        Err(NameError::Pattern { .. })
+    ));
+    assert!(matches!(policy.check("web-1 extra!", []), Err(NameError::Pattern { .. })));
+    assert_eq!(NamingPolicy::default().check(" ", []), Err(NameError::Empty));
+
+    let relaxed = NamingPolicy {
+        unique: false,
+        pattern: None,
+    };
+    assert_eq!(relaxed.check("web", ["web"]), Ok(()));
+    let invalid = NamingPolicy {
+        unique: true,
+        pattern: Some("([a-z".to_string()),
+    };
+    assert!(invalid.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_service_enforces_naming_policy() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("instances.toml");
+    std::fs::write(
+        &config_path,
+        "instances = []\nversion = \"1.0\"\ncreated_at = \"2024-01-01T00:00:00Z\"\nupdated_at = \"2024-01-01T00:00:00Z\"\n\n[naming]\npattern = \"[a-z-]+\"\n",
+    )
+    .unwrap();
+    let storage = Arc::new(StorageManager::new(config_path));
+    storage.load().await.unwrap();
+    let service = InstanceService::with_storage(storage);
+    let request = |name: &str, port: u16| {
+        let mut request = ProxyBuilder::new(
+            format!("127.0.0.1:{}", port).parse().unwrap(),
+            "127.0.0.1:80".parse().unwrap(),
+        )
+        .name(name)
+        .into_request();
+        request.auto_start = false;
+        request
+    };
+
+    let web = service.create_instance(request("web", 18770)).await.unwrap();
+    let duplicate = service.create_instance(request("web", 18771)).await.unwrap_err();
+    assert_eq!(
+        duplicate.downcast_ref::<NameError>(),
+        Some(&NameError::Duplicate("web".to_string()))
+    );
+    assert!(service.create_instance(request("Web2", 18772)).await.is_err());
+    let api = service.create_instance(request("api", 18773)).await.unwrap();
+
+    let rename = |name: &str| -> UpdateInstanceRequest {
+        serde_json::from_value(serde_json::json!({ "name": name })).unwrap()
+    };
+    assert!(service.update_instance(api.id, rename("web")).await.is_err());
+    assert!(service.update_instance(web.id, rename("web")).await.unwrap().is_some());
+    service.update_instance(api.id, rename("api-v-two")).await.unwrap();
+    assert_eq!(service.get_instance_by_name("api-v-two").await.unwrap().id, api.id);
+    assert!(service.get_instance_by_name("api").await.is_none());
+}
@@ -0,0 +1,78 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::{DestinationCheck, Protocol};
+use voidproxy_core::instance::{ErrorCategory, InstanceStatus};
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::preflight::check_destination;
+use voidproxy_core::storage::StorageManager;
+
+async fn closed_port() -> SocketAddr {
+    TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_check_destination_probes_tcp_destinations() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let live = listener.local_addr().unwrap();
+    let dead = closed_port().await;
+    let listen = "127.0.0.1:18847".parse().unwrap();
+
+    let config = ProxyBuilder::new(listen, live).into_request().to_config();
+    assert!(check_destination(&config).await.is_ok());
+    let config = ProxyBuilder::new(listen, dead).into_request().to_config();
+    let error = check_destination(&config).await.unwrap_err();
+    assert!(error.to_string().contains(&dead.to_string()));
+    let config = ProxyBuilder::new(listen, dead)
+        .backends(vec![dead.to_string(), live.to_string()])
+        .into_request()
+        .to_config();
+    assert!(check_destination(&config).await.is_ok());
+    let config = ProxyBuilder::new(listen, dead)
+        .protocol(Protocol::Udp)
+        .into_request()
+        .to_config();
+    assert!(check_destination(&config).await.is_ok());
+}
+
+#[tokio::test]
+async fn test_start_follows_destination_check_policy() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let dead = closed_port().await;
+    let request = |port: u16, check| {
+        let mut request = ProxyBuilder::new(format!("127.0.0.1:{}", port).parse().unwrap(), dead)
+            .verify_destination_on_start(check)
+            .into_request();
+        request.auto_start = false;
+        request
+    };
+
+    let failing = service
+        .create_instance(request(18848, DestinationCheck::Fail))
+        .await
+        .unwrap();
+    assert!(service.start_instance(failing.id).await.is_err());
+    let failing = service.get_instance(failing.id).await.unwrap();
+    assert_eq!(failing.status, InstanceStatus::Stopped);
+    assert_eq!(
+        failing.last_error.get().unwrap().category,
+        ErrorCategory::Upstream
+    );
+
+    let warning = service
+        .create_instance(request(18849, DestinationCheck::Warn))
+        .await
+        .unwrap();
+    assert!(service.start_instance(warning.id).await.unwrap());
+    let warning = service.get_instance(warning.id).await.unwrap();
+    assert_eq!(warning.status, InstanceStatus::Running);
+    assert!(warning.last_error.get().is_some());
+    service.stop_instance(warning.id).await.unwrap();
+}
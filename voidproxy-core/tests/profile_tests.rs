@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::storage::StorageManager;
+
+fn config_file(default_port: u16, prod_port: u16) -> String {
+    format!(
+        r#"
+profiles = ["staging", "prod"]
+version = "1.0"
+created_at = "2024-01-01T00:00:00+00:00"
+updated_at = "2024-01-01T00:00:00+00:00"
+
+[[instances]]
+id = "5f0c6f3e-8a4b-4c1e-9d2a-1b3c4d5e6f70"
+name = "api"
+status = "stopped"
+created_at = "2024-01-01T00:00:00+00:00"
+auto_start = false
+
+[instances.config.proxy]
+listen_ip = "127.0.0.1"
+listen_port = 18770
+dst_ip = "127.0.0.1"
+dst_port = {default_port}
+protocol = "tcp"
+connect_timeout_secs = 5
+idle_timeout_secs = 60
+log_level = "info"
+
+[instances.config.profiles.prod]
+dst_port = {prod_port}
+"#
+    )
+}
+
+async fn greeter(greeting: &'static [u8]) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let _ = stream.write_all(greeting).await;
+        }
+    });
+    port
+}
+
+#[tokio::test]
+async fn test_profile_overrides_destination() {
+    let default_port = greeter(b"dev").await;
+    let prod_port = greeter(b"prd").await;
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("instances.toml");
+    std::fs::write(&config_path, config_file(default_port, prod_port)).unwrap();
+
+    let storage = Arc::new(StorageManager::new(config_path));
+    assert_eq!(
+        storage.declared_profiles().await.unwrap(),
+        vec!["staging".to_string(), "prod".to_string()]
+    );
+    let service =
+        InstanceService::with_storage(storage.clone()).with_profile(Some("prod".to_string()));
+    let instance = storage.load().await.unwrap().remove(0);
+    let id = instance.id;
+    service.restore_instance(instance).await.unwrap();
+    service.start_instance(id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = TcpStream::connect("127.0.0.1:18770").await.unwrap();
+    let mut reply = [0u8; 3];
+    client.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"prd");
+
+    let stored = service.get_instance(id).await.unwrap();
+    assert_eq!(stored.config.proxy.dst_port, default_port);
+    assert_eq!(
+        stored.config.for_profile(Some("staging")).proxy.dst_port,
+        default_port
+    );
+    service.stop_instance(id).await.unwrap();
+}
@@ -0,0 +1,95 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::replay::{RecordOptions, RecordedDirection, RecordingState, ReplayOptions};
+use voidproxy_core::storage::StorageManager;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+async fn exchange(message: &[u8]) -> Vec<u8> {
+    let mut stream = TcpStream::connect("127.0.0.1:18805").await.unwrap();
+    stream.write_all(message).await.unwrap();
+    let mut reply = vec![0u8; message.len()];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    reply
+}
+
+#[tokio::test]
+async fn test_recorded_connection_is_replayed_against_upstream() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let echo_addr = echo.local_addr().unwrap();
+    tokio::spawn(echo.serve());
+    let request = ProxyBuilder::new("127.0.0.1:18805".parse().unwrap(), echo_addr).into_request();
+    let instance = service.create_instance(request).await.unwrap();
+    service.start_instance(instance.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let options = RecordOptions {
+        max_bytes: 8,
+        peer_ip: None,
+    };
+    let armed = service
+        .record_connection(instance.id, options)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(armed.state, RecordingState::Armed);
+    assert_eq!(exchange(b"hello").await, b"hello");
+    assert_eq!(exchange(b"not recorded").await, b"not recorded");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let recording = service.recording(armed.id).unwrap();
+    assert_eq!(recording.state, RecordingState::Complete);
+    assert_eq!(recording.upstream_addr, Some(echo_addr));
+    assert_eq!(
+        recording.bytes_in(RecordedDirection::ClientToServer),
+        b"hello"
+    );
+    assert_eq!(
+        recording.bytes_in(RecordedDirection::ServerToClient),
+        b"hel"
+    );
+    assert!(recording.truncated);
+    assert!(service.recordings()[0].chunks.is_empty());
+
+    let replay_options = ReplayOptions {
+        preserve_timing: true,
+        timeout_secs: 1,
+    };
+    let report = service
+        .replay_recording(armed.id, None, replay_options)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(report.target, echo_addr);
+    assert_eq!(report.bytes_sent, 5);
+    assert_eq!(report.response, b"hello");
+    assert!(!report.closed_by_server);
+    assert!(!report.matches_recording);
+
+    assert!(
+        service
+            .record_connection(instance.id, RecordOptions::default())
+            .await
+            .unwrap()
+            .is_some()
+    );
+    assert!(
+        service
+            .replay_recording(uuid::Uuid::new_v4(), None, ReplayOptions::default())
+            .await
+            .unwrap()
+            .is_none()
+    );
+    service.stop_instance(instance.id).await.unwrap();
+}
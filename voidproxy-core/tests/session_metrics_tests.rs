@@ -0,0 +1,69 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::storage::StorageManager;
+
+#[tokio::test]
+async fn test_tcp_session_metrics_track_open_connections() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = upstream.accept().await {
+            tokio::spawn(async move {
+                for _ in 0..6 {
+                    if stream.write_all(b"tick").await.is_err() {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(400)).await;
+                }
+                let mut buffer = [0u8; 16];
+                let _ = stream.read(&mut buffer).await;
+            });
+        }
+    });
+    let request = ProxyBuilder::new("127.0.0.1:18819".parse().unwrap(), upstream_addr)
+        .idle_timeout(Duration::from_secs(1))
+        .into_request();
+    let instance = service.create_instance(request).await.unwrap();
+    service.start_instance(instance.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18819").await.unwrap();
+    let mut received = Vec::new();
+    let mut buffer = [0u8; 64];
+    while received.len() < 24 {
+        let n = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(n > 0, "connection closed while the server was sending");
+        received.extend_from_slice(&buffer[..n]);
+    }
+    let metrics = service
+        .get_instance_session_metrics(&instance.id)
+        .await
+        .unwrap();
+    assert_eq!(metrics.active_sessions, 1);
+    assert_eq!(metrics.session_timeout_seconds, 1);
+    assert_eq!(metrics.cleanup_interval_seconds, 0);
+    assert_eq!(metrics.longest_idle_seconds, 0);
+
+    let n = tokio::time::timeout(Duration::from_secs(3), stream.read(&mut buffer))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(n, 0);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let metrics = service
+        .get_instance_session_metrics(&instance.id)
+        .await
+        .unwrap();
+    assert_eq!(metrics.active_sessions, 0);
+    service.stop_instance(instance.id).await.unwrap();
+}
@@ -0,0 +1,130 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::{Protocol, SlowLogConfig};
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::storage::StorageManager;
+
+/**
+ * Upstream answering each request byte with `ok`, after 300ms when the byte
+ * is `s`.
+ */
+async fn spawn_upstream() -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut request = [0u8; 1];
+                while let Ok(1) = stream.read(&mut request).await {
+                    if request[0] == b's' {
+                        tokio::time::sleep(Duration::from_millis(300)).await;
+                    }
+                    if stream.write_all(b"ok").await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+async fn exchange(request: &[u8]) {
+    let mut stream = TcpStream::connect("127.0.0.1:18806").await.unwrap();
+    stream.write_all(request).await.unwrap();
+    let mut reply = [0u8; 2];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_slow_first_bytes_are_logged_up_to_capacity() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let upstream = spawn_upstream().await;
+    let request = ProxyBuilder::new("127.0.0.1:18806".parse().unwrap(), upstream)
+        .slow_log(SlowLogConfig {
+            connect_ms: None,
+            first_byte_ms: Some(200),
+            capacity: 2,
+        })
+        .into_request();
+    let instance = service.create_instance(request).await.unwrap();
+    service.start_instance(instance.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    exchange(b"f").await;
+    for _ in 0..3 {
+        exchange(b"s").await;
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let entries = service.slow_log(instance.id).await.unwrap();
+    assert_eq!(
+        entries.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+        [3, 2]
+    );
+    for entry in &entries {
+        assert_eq!(entry.backend, upstream);
+        assert!(entry.client.ip().is_loopback());
+        assert!(entry.first_byte_ms.unwrap() >= 200);
+        assert!(entry.duration_ms >= entry.first_byte_ms.unwrap());
+        assert!(entry.error.is_none());
+    }
+
+    assert_eq!(service.reset_slow_log(instance.id).await, Some(2));
+    assert!(service.slow_log(instance.id).await.unwrap().is_empty());
+    assert!(service.slow_log(uuid::Uuid::new_v4()).await.is_none());
+    service.stop_instance(instance.id).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_slow_log_requires_tcp_and_a_threshold() {
+    let builder = || {
+        ProxyBuilder::new(
+            "127.0.0.1:18807".parse().unwrap(),
+            "127.0.0.1:9".parse().unwrap(),
+        )
+    };
+    let without_threshold = SlowLogConfig {
+        connect_ms: None,
+        first_byte_ms: None,
+        capacity: 128,
+    };
+    let connect_only = SlowLogConfig {
+        connect_ms: Some(50),
+        ..without_threshold
+    };
+    assert!(builder().slow_log(without_threshold).spawn().is_err());
+    assert!(
+        builder()
+            .slow_log(SlowLogConfig {
+                capacity: 0,
+                ..connect_only
+            })
+            .spawn()
+            .is_err()
+    );
+    assert!(
+        builder()
+            .protocol(Protocol::Udp)
+            .slow_log(connect_only)
+            .spawn()
+            .is_err()
+    );
+    assert!(
+        builder()
+            .slow_log(connect_only)
+            .into_request()
+            .to_config()
+            .validate()
+            .is_ok()
+    );
+}
@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use tempfile::TempDir;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::storage::StorageManager;
+
+const CONFIG: &str = r#"
+version = "1.0"
+created_at = "2024-01-01T00:00:00+00:00"
+updated_at = "2024-01-01T00:00:00+00:00"
+
+[[instances]]
+id = "7a1d2c3b-4e5f-4a6b-8c7d-9e0f1a2b3c4d"
+name = "billing"
+status = "stopped"
+created_at = "2024-01-01T00:00:00+00:00"
+auto_start = false
+
+[instances.config.proxy]
+listen_ip = "127.0.0.1"
+listen_port = 18780
+dst_ip = "127.0.0.1"
+dst_port = 80
+protocol = "tcp"
+connect_timeout_secs = 5
+idle_timeout_secs = 60
+log_level = "info"
+"#;
+
+async fn load_service(config_path: &std::path::Path) -> InstanceService {
+    let storage = Arc::new(StorageManager::new(config_path.to_path_buf()));
+    let service = InstanceService::with_storage(storage.clone());
+    for instance in storage.load().await.unwrap() {
+        service.restore_instance(instance).await.unwrap();
+    }
+    service
+}
+
+#[tokio::test]
+async fn test_lifetime_counters_survive_restart() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_path = temp_dir.path().join("instances.toml");
+    std::fs::write(&config_path, CONFIG).unwrap();
+
+    let service = load_service(&config_path).await;
+    let instance = service.get_instances().await.remove(0);
+    instance.metrics.add_bytes_sent(u64::MAX - 10);
+    instance.metrics.add_bytes_received(2048);
+    instance
+        .metrics
+        .connections_total
+        .fetch_add(3, std::sync::atomic::Ordering::Relaxed);
+    service.persist_stats().await.unwrap();
+    assert!(temp_dir.path().join("instances.stats.json").exists());
+
+    let restarted = load_service(&config_path).await;
+    let restored = restarted.get_instances().await.remove(0);
+    restored.metrics.add_bytes_received(1024);
+    assert_eq!(restarted.restore_stats().await.unwrap(), 1);
+    let snapshot = restored.metrics.snapshot();
+    assert_eq!(snapshot.bytes_sent, u64::MAX - 10);
+    assert_eq!(snapshot.bytes_received, 3072);
+    assert_eq!(snapshot.connections_total, 3);
+    assert_eq!(snapshot.errors, 0);
+}
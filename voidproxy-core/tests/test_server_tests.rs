@@ -0,0 +1,78 @@
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::Protocol;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+
+async fn spawn(kind: TestServerKind) -> std::net::SocketAddr {
+    let server = TestServer::bind(kind, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(server.serve());
+    addr
+}
+
+#[tokio::test]
+async fn test_echo_servers_validate_an_instance_end_to_end() {
+    let tcp_echo = spawn(TestServerKind::TcpEcho).await;
+    let udp_echo = spawn(TestServerKind::UdpEcho).await;
+    let tcp_proxy = ProxyBuilder::new("127.0.0.1:18803".parse().unwrap(), tcp_echo)
+        .spawn()
+        .unwrap();
+    let udp_proxy = ProxyBuilder::new("127.0.0.1:18804".parse().unwrap(), udp_echo)
+        .protocol(Protocol::Udp)
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18803").await.unwrap();
+    stream.write_all(b"ping").await.unwrap();
+    let mut reply = [0u8; 4];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(&reply, b"ping");
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect("127.0.0.1:18804").await.unwrap();
+    for message in [b"one", b"two"] {
+        socket.send(message).await.unwrap();
+        let mut reply = [0u8; 16];
+        let len = tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut reply))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&reply[..len], message);
+    }
+
+    tcp_proxy.shutdown().await.unwrap();
+    udp_proxy.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_sink_servers_discard_traffic() {
+    let tcp_sink = spawn(TestServerKind::TcpSink).await;
+    let udp_sink = spawn(TestServerKind::UdpSink).await;
+
+    let mut stream = TcpStream::connect(tcp_sink).await.unwrap();
+    stream.write_all(&[0u8; 100_000]).await.unwrap();
+    stream.shutdown().await.unwrap();
+    let mut reply = Vec::new();
+    tokio::time::timeout(Duration::from_secs(2), stream.read_to_end(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(reply.is_empty());
+
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(b"dropped", udp_sink).await.unwrap();
+    let mut reply = [0u8; 16];
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), socket.recv(&mut reply))
+            .await
+            .is_err()
+    );
+}
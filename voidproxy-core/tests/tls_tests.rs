@@ -0,0 +1,280 @@
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, Issuer, KeyPair};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, RootCertStore};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsConnector;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::{SniCertificate, TlsConfig};
+use voidproxy_core::tls::{TlsTerminator, client_names};
+
+struct Pki {
+    ca: Issuer<'static, KeyPair>,
+    ca_pem: String,
+}
+
+impl Pki {
+    fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "VoidProxy Test CA");
+        let key = KeyPair::generate().unwrap();
+        let ca_pem = params.self_signed(&key).unwrap().pem();
+        Self {
+            ca: Issuer::new(params, key),
+            ca_pem,
+        }
+    }
+
+    fn issue(&self, common_name: &str, dns_names: &[&str]) -> (String, String) {
+        self.issue_with(common_name, dns_names, |_| {})
+    }
+
+    fn issue_with(
+        &self,
+        common_name: &str,
+        dns_names: &[&str],
+        customize: impl FnOnce(&mut CertificateParams),
+    ) -> (String, String) {
+        let names: Vec<String> = dns_names.iter().map(|name| name.to_string()).collect();
+        let mut params = CertificateParams::new(names).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        customize(&mut params);
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.ca).unwrap();
+        (cert.pem(), key.serialize_pem())
+    }
+
+    fn tls_config(&self, dir: &Path, allowed_client_names: &[&str]) -> TlsConfig {
+        let (cert, key) = self.issue("localhost", &["localhost"]);
+        std::fs::write(dir.join("server.pem"), cert).unwrap();
+        std::fs::write(dir.join("server.key"), key).unwrap();
+        std::fs::write(dir.join("ca.pem"), &self.ca_pem).unwrap();
+        TlsConfig {
+            cert_path: dir.join("server.pem").to_string_lossy().into_owned(),
+            key_path: dir.join("server.key").to_string_lossy().into_owned(),
+            client_ca_path: Some(dir.join("ca.pem").to_string_lossy().into_owned()),
+            allowed_client_names: allowed_client_names
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            sni_certificates: Vec::new(),
+        }
+    }
+
+    fn client_config(&self, identity: Option<(String, String)>) -> ClientConfig {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_slice(self.ca_pem.as_bytes()).unwrap())
+            .unwrap();
+        let builder =
+            ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_root_certificates(roots);
+        match identity {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(
+                    vec![CertificateDer::from_pem_slice(cert.as_bytes()).unwrap()],
+                    PrivateKeyDer::from_pem_slice(key.as_bytes()).unwrap(),
+                )
+                .unwrap(),
+            None => builder.with_no_client_auth(),
+        }
+    }
+}
+
+async fn handshake(terminator: &TlsTerminator, client_config: ClientConfig) -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = tokio::spawn(async move {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let connector = TlsConnector::from(Arc::new(client_config));
+        let server_name = ServerName::try_from("localhost").unwrap();
+        if let Ok(mut stream) = connector.connect(server_name, stream).await {
+            let _ = stream.read(&mut [0u8; 1]).await;
+        }
+    });
+    let (stream, _) = listener.accept().await.unwrap();
+    let result = terminator.accept(stream).await.map(drop);
+    client.abort();
+    result
+}
+
+#[tokio::test]
+async fn test_client_certificates_are_checked_against_ca_and_names() {
+    let dir = tempfile::tempdir().unwrap();
+    let pki = Pki::new();
+    let terminator =
+        TlsTerminator::from_config(&pki.tls_config(dir.path(), &["billing.internal"])).unwrap();
+
+    let by_common_name = pki.issue("billing.internal", &[]);
+    assert!(
+        handshake(&terminator, pki.client_config(Some(by_common_name)))
+            .await
+            .is_ok()
+    );
+    let by_san = pki.issue("billing", &["BILLING.internal"]);
+    assert!(
+        handshake(&terminator, pki.client_config(Some(by_san)))
+            .await
+            .is_ok()
+    );
+
+    let other_client = pki.issue("reports.internal", &["reports.internal"]);
+    let error = handshake(&terminator, pki.client_config(Some(other_client)))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("reports.internal"));
+    assert!(
+        handshake(&terminator, pki.client_config(None))
+            .await
+            .is_err()
+    );
+    let foreign_ca = Pki::new().issue("billing.internal", &[]);
+    assert!(
+        handshake(&terminator, pki.client_config(Some(foreign_ca)))
+            .await
+            .is_err()
+    );
+}
+
+#[test]
+fn test_client_names_include_common_name_and_dns_sans() {
+    let (cert, _) = Pki::new().issue("client-1", &["a.example.com", "b.example.com"]);
+    let cert = CertificateDer::from_pem_slice(cert.as_bytes()).unwrap();
+    assert_eq!(
+        client_names(&cert),
+        vec!["client-1", "a.example.com", "b.example.com"]
+    );
+}
+
+#[test]
+fn test_certificate_is_reloaded_when_its_files_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let pki = Pki::new();
+    let config = pki.tls_config(dir.path(), &[]);
+    let terminator = TlsTerminator::from_config(&config).unwrap();
+    assert_eq!(
+        terminator.expires_at().unwrap().to_rfc3339(),
+        "4096-01-01T00:00:00+00:00"
+    );
+    assert!(!terminator.reload_if_changed().unwrap());
+
+    let (cert, key) = pki.issue_with("localhost", &["localhost"], |params| {
+        params.not_after = rcgen::date_time_ymd(2031, 6, 1);
+    });
+    std::fs::write(&config.cert_path, cert).unwrap();
+    std::fs::write(&config.key_path, key).unwrap();
+    assert!(terminator.reload_if_changed().unwrap());
+    assert_eq!(
+        terminator.expires_at().unwrap().to_rfc3339(),
+        "2031-06-01T00:00:00+00:00"
+    );
+
+    std::fs::write(&config.key_path, "not a key").unwrap();
+    assert!(terminator.reload_if_changed().is_err());
+    assert!(!terminator.reload_if_changed().unwrap());
+    assert_eq!(
+        terminator.expires_at().unwrap().to_rfc3339(),
+        "2031-06-01T00:00:00+00:00"
+    );
+}
+
+#[tokio::test]
+async fn test_certificate_is_selected_by_server_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let pki = Pki::new();
+    let mut config = pki.tls_config(dir.path(), &[]);
+    config.client_ca_path = None;
+    for (name, server_name) in [("api", "api.example.com"), ("apps", "*.apps.example.com")] {
+        let (cert, key) = pki.issue(name, &[server_name]);
+        let cert_path = dir.path().join(format!("{}.pem", name));
+        let key_path = dir.path().join(format!("{}.key", name));
+        std::fs::write(&cert_path, cert).unwrap();
+        std::fs::write(&key_path, key).unwrap();
+        config.sni_certificates.push(SniCertificate {
+            server_names: vec![server_name.to_string()],
+            cert_path: cert_path.to_string_lossy().into_owned(),
+            key_path: key_path.to_string_lossy().into_owned(),
+        });
+    }
+    let terminator = Arc::new(TlsTerminator::from_config(&config).unwrap());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let terminator = terminator.clone();
+            tokio::spawn(async move {
+                let _ = terminator.accept(stream).await;
+            });
+        }
+    });
+
+    for (server_name, served) in [
+        ("API.example.com", "api"),
+        ("web.apps.example.com", "apps"),
+        ("localhost", "localhost"),
+    ] {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let connector = TlsConnector::from(Arc::new(pki.client_config(None)));
+        let client = connector
+            .connect(ServerName::try_from(server_name).unwrap(), stream)
+            .await
+            .unwrap();
+        let certs = client.get_ref().1.peer_certificates().unwrap();
+        assert_eq!(client_names(&certs[0])[0], served);
+    }
+}
+
+#[tokio::test]
+async fn test_proxy_terminates_tls_for_allowed_clients() {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = upstream.accept().await.unwrap();
+        let mut buffer = [0u8; 5];
+        stream.read_exact(&mut buffer).await.unwrap();
+        stream.write_all(&buffer).await.unwrap();
+    });
+
+    let dir = tempfile::tempdir().unwrap();
+    let pki = Pki::new();
+    let proxy = ProxyBuilder::new("127.0.0.1:18760".parse().unwrap(), upstream_addr)
+        .tls(pki.tls_config(dir.path(), &["billing.internal"]))
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let stream = TcpStream::connect("127.0.0.1:18760").await.unwrap();
+    let connector = TlsConnector::from(Arc::new(
+        pki.client_config(Some(pki.issue("billing.internal", &[]))),
+    ));
+    let mut client = connector
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+    client.write_all(b"hello").await.unwrap();
+    let mut reply = [0u8; 5];
+    client.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"hello");
+    drop(client);
+    assert_eq!(
+        proxy
+            .metrics()
+            .certificate_expires_at
+            .load(Ordering::Relaxed),
+        rcgen::date_time_ymd(4096, 1, 1).unix_timestamp()
+    );
+    proxy.shutdown().await.unwrap();
+}
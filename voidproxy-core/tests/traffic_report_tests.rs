@@ -0,0 +1,95 @@
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::geoip::GeoIpDb;
+use voidproxy_core::test_server::{TestServer, TestServerKind};
+use voidproxy_core::traffic_report::{self, GroupBy, TrafficAccounting};
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_geoip_ranges() {
+    let db = GeoIpDb::parse(
+        "1.0.0.0,1.0.0.255,AU\n\
+         \"16777472\",\"16778239\",\"CN\",\"China\"\n\
+         2001:db8::,2001:db8:ffff:ffff:ffff:ffff:ffff:ffff,de\n\
+         10.0.0.0,10.255.255.255,-\n",
+    )
+    .unwrap();
+    assert_eq!(db.len(), 3);
+    assert_eq!(db.country(&ip("1.0.0.7")), Some("AU"));
+    assert_eq!(db.country(&ip("::ffff:1.0.0.7")), Some("AU"));
+    assert_eq!(db.country(&ip("1.0.1.1")), Some("CN"));
+    assert_eq!(db.country(&ip("2001:db8::1")), Some("DE"));
+    assert_eq!(db.country(&ip("10.0.0.1")), None);
+    assert_eq!(db.country(&ip("1.0.4.0")), None);
+    assert!(GeoIpDb::parse("ip_from,ip_to,country\n1.0.0.0,bogus,AU\n").is_err());
+}
+
+#[test]
+fn test_reports_group_by_ip_and_country() {
+    let traffic = TrafficAccounting::default();
+    traffic.set_geoip(GeoIpDb::parse("1.0.0.0,1.0.0.255,AU\n").unwrap());
+    traffic.record(ip("1.0.0.1"), 100, 1000);
+    traffic.record(ip("1.0.0.1"), 50, 0);
+    traffic.record(ip("1.0.0.2"), 10, 10);
+    traffic.rotate();
+    traffic.record(ip("192.0.2.1"), 5, 5);
+
+    let reports = traffic.reports(GroupBy::Ip, 10);
+    assert_eq!(reports.len(), 2);
+    assert!(reports[0].ended_at.is_some());
+    assert!(reports[1].ended_at.is_none());
+    assert_eq!(reports[0].total.connections, 3);
+    assert_eq!(reports[0].groups[0].key, "1.0.0.1");
+    assert_eq!(reports[0].groups[0].counters.connections, 2);
+    assert_eq!(reports[0].groups[0].counters.bytes_received, 150);
+    assert_eq!(reports[0].groups[1].key, "1.0.0.2");
+
+    let reports = traffic.reports(GroupBy::Country, 10);
+    assert_eq!(reports[0].groups.len(), 1);
+    assert_eq!(reports[0].groups[0].key, "AU");
+    assert_eq!(reports[0].groups[0].counters.bytes_sent, 1010);
+    assert_eq!(reports[1].groups[0].key, "unknown");
+
+    assert_eq!(traffic.reports(GroupBy::Ip, 1)[0].groups.len(), 1);
+}
+
+#[tokio::test]
+async fn test_closed_connections_are_accounted() {
+    let echo = TestServer::bind(TestServerKind::TcpEcho, "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let proxy = ProxyBuilder::new(
+        "127.0.0.1:18839".parse().unwrap(),
+        echo.local_addr().unwrap(),
+    )
+    .spawn()
+    .unwrap();
+    tokio::spawn(echo.serve());
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18839").await.unwrap();
+    stream.write_all(b"ping").await.unwrap();
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await.unwrap();
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let reports = traffic_report::global().reports(GroupBy::Ip, 10);
+    let localhost = reports
+        .last()
+        .unwrap()
+        .groups
+        .iter()
+        .find(|group| group.key == "127.0.0.1")
+        .unwrap();
+    assert_eq!(localhost.counters.connections, 1);
+    assert_eq!(localhost.counters.bytes_received, 4);
+    assert_eq!(localhost.counters.bytes_sent, 4);
+    proxy.shutdown().await.unwrap();
+}
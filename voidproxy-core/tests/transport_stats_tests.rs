@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::Protocol;
+use voidproxy_core::instance_manager::InstanceService;
+use voidproxy_core::storage::StorageManager;
+
+#[tokio::test]
+async fn test_both_instance_reports_tcp_and_udp_separately() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let storage = Arc::new(StorageManager::new(temp_dir.path().join("instances.toml")));
+    let service = InstanceService::with_storage(storage);
+    let tcp_upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = tcp_upstream.local_addr().unwrap();
+    let udp_upstream = UdpSocket::bind(upstream_addr).await.unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = tcp_upstream.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 64];
+                while let Ok(len @ 1..) = stream.read(&mut buffer).await {
+                    if stream.write_all(&buffer[..len]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    tokio::spawn(async move {
+        let mut buffer = [0u8; 64];
+        while let Ok((len, peer)) = udp_upstream.recv_from(&mut buffer).await {
+            let _ = udp_upstream.send_to(&buffer[..len], peer).await;
+        }
+    });
+    let request = ProxyBuilder::new("127.0.0.1:18818".parse().unwrap(), upstream_addr)
+        .protocol(Protocol::Both)
+        .into_request();
+    let instance = service.create_instance(request).await.unwrap();
+    service.start_instance(instance.id).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut stream = TcpStream::connect("127.0.0.1:18818").await.unwrap();
+    stream.write_all(b"tcp-payload").await.unwrap();
+    let mut reply = [0u8; 11];
+    tokio::time::timeout(Duration::from_secs(2), stream.read_exact(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.send_to(b"udp", "127.0.0.1:18818").await.unwrap();
+    let mut reply = [0u8; 3];
+    tokio::time::timeout(Duration::from_secs(2), socket.recv(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    let stats = service.get_instance_stats().await[&instance.id].clone();
+    assert_eq!(stats.tcp.connections_active, 1);
+    assert_eq!(stats.udp.connections_active, 1);
+    assert_eq!((stats.udp.bytes_received, stats.udp.bytes_sent), (3, 3));
+
+    drop(stream);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let stats = service.get_instance_stats().await[&instance.id].clone();
+    assert_eq!((stats.tcp.bytes_received, stats.tcp.bytes_sent), (11, 11));
+    assert_eq!(stats.bytes_received, 14);
+    assert_eq!((stats.tcp.connections_active, stats.connections_total), (0, 1));
+    assert_eq!(stats.udp.connections_total, 1);
+    service.stop_instance(instance.id).await.unwrap();
+}
@@ -0,0 +1,208 @@
+use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::{TunnelCompression, TunnelConfig, TunnelMode};
+
+fn tunnel(mode: TunnelMode, token: &str) -> TunnelConfig {
+    TunnelConfig {
+        mode,
+        token: token.to_string(),
+        tls: false,
+        ca_path: None,
+        server_name: None,
+        gateway_addr: None,
+        agent_addr: None,
+        compression: TunnelCompression::None,
+    }
+}
+
+async fn echo_upstream() -> SocketAddr {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = upstream.accept().await.unwrap();
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = stream.split();
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+    addr
+}
+
+async fn round_trip(addr: &str, message: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut client = TcpStream::connect(addr).await?;
+    let (mut reader, mut writer) = client.split();
+    let mut reply = vec![0u8; message.len()];
+    tokio::time::timeout(Duration::from_secs(2), async {
+        tokio::try_join!(writer.write_all(message), reader.read_exact(&mut reply))
+    })
+    .await??;
+    Ok(reply)
+}
+
+#[tokio::test]
+async fn test_edge_relays_connections_through_exit() {
+    let upstream_addr = echo_upstream().await;
+    let exit = ProxyBuilder::new("127.0.0.1:18790".parse().unwrap(), upstream_addr)
+        .tunnel(tunnel(TunnelMode::Exit, "s3cret"))
+        .spawn()
+        .unwrap();
+    let edge = ProxyBuilder::new(
+        "127.0.0.1:18791".parse().unwrap(),
+        "127.0.0.1:18790".parse().unwrap(),
+    )
+    .tunnel(tunnel(TunnelMode::Edge, "s3cret"))
+    .spawn()
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let (first, second) = tokio::join!(
+        round_trip("127.0.0.1:18791", b"first"),
+        round_trip("127.0.0.1:18791", b"second")
+    );
+    assert_eq!(first.unwrap(), b"first");
+    assert_eq!(second.unwrap(), b"second");
+    assert_eq!(
+        round_trip("127.0.0.1:18791", b"third").await.unwrap(),
+        b"third"
+    );
+
+    edge.shutdown().await.unwrap();
+    exit.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_exit_rejects_wrong_token() {
+    let upstream_addr = echo_upstream().await;
+    let exit = ProxyBuilder::new("127.0.0.1:18792".parse().unwrap(), upstream_addr)
+        .tunnel(tunnel(TunnelMode::Exit, "s3cret"))
+        .spawn()
+        .unwrap();
+    let edge = ProxyBuilder::new(
+        "127.0.0.1:18793".parse().unwrap(),
+        "127.0.0.1:18792".parse().unwrap(),
+    )
+    .tunnel(tunnel(TunnelMode::Edge, "wrong"))
+    .spawn()
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(round_trip("127.0.0.1:18793", b"hello").await.is_err());
+    assert!(round_trip("127.0.0.1:18792", b"hello").await.is_err());
+
+    edge.shutdown().await.unwrap();
+    exit.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_gateway_relays_clients_to_agent_behind_nat() {
+    let upstream_addr = echo_upstream().await;
+    let gateway = ProxyBuilder::new(
+        "127.0.0.1:18794".parse().unwrap(),
+        "127.0.0.1:1".parse().unwrap(),
+    )
+    .tunnel(TunnelConfig {
+        agent_addr: Some("127.0.0.1:18795".parse().unwrap()),
+        ..tunnel(TunnelMode::Gateway, "s3cret")
+    })
+    .deny_list(vec!["127.0.0.2".parse().unwrap()])
+    .spawn()
+    .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(round_trip("127.0.0.1:18794", b"early").await.is_err());
+
+    let agent = ProxyBuilder::new("127.0.0.1:18796".parse().unwrap(), upstream_addr)
+        .tunnel(TunnelConfig {
+            gateway_addr: Some("127.0.0.1:18795".parse().unwrap()),
+            ..tunnel(TunnelMode::Agent, "s3cret")
+        })
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let (first, second) = tokio::join!(
+        round_trip("127.0.0.1:18794", b"first"),
+        round_trip("127.0.0.1:18794", b"second")
+    );
+    assert_eq!(first.unwrap(), b"first");
+    assert_eq!(second.unwrap(), b"second");
+    assert!(TcpStream::connect("127.0.0.1:18796").await.is_err());
+
+    let socket = tokio::net::TcpSocket::new_v4().unwrap();
+    socket.bind("127.0.0.2:0".parse().unwrap()).unwrap();
+    let mut denied = socket
+        .connect("127.0.0.1:18794".parse().unwrap())
+        .await
+        .unwrap();
+    denied.write_all(b"denied").await.unwrap();
+    let mut reply = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(2), denied.read_to_end(&mut reply)).await;
+    assert!(read.unwrap().is_err() || reply.is_empty());
+
+    assert_eq!(
+        gateway.metrics().connections_total.load(Ordering::Relaxed),
+        3
+    );
+    assert_eq!(agent.metrics().connections_total.load(Ordering::Relaxed), 2);
+
+    agent.shutdown().await.unwrap();
+    gateway.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_links_are_compressed_when_both_ends_allow_it() {
+    let upstream_addr = echo_upstream().await;
+    let message = b"GET /api/orders HTTP/1.1\r\nHost: example.com\r\n\r\n".repeat(4000);
+    for (port, exit_compression, compressed) in [
+        (18797, TunnelCompression::Zstd, true),
+        (18799, TunnelCompression::None, false),
+    ] {
+        let exit_addr = format!("127.0.0.1:{}", port);
+        let edge_addr = format!("127.0.0.1:{}", port + 1);
+        let exit = ProxyBuilder::new(exit_addr.parse().unwrap(), upstream_addr)
+            .tunnel(TunnelConfig {
+                compression: exit_compression,
+                ..tunnel(TunnelMode::Exit, "s3cret")
+            })
+            .spawn()
+            .unwrap();
+        let edge = ProxyBuilder::new(edge_addr.parse().unwrap(), exit_addr.parse().unwrap())
+            .tunnel(TunnelConfig {
+                compression: TunnelCompression::Lz4,
+                ..tunnel(TunnelMode::Edge, "s3cret")
+            })
+            .spawn()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(round_trip(&edge_addr, &message).await.unwrap(), message);
+        let uncompressed = edge
+            .metrics()
+            .tunnel_bytes_uncompressed
+            .load(Ordering::Relaxed);
+        let on_wire = edge
+            .metrics()
+            .tunnel_bytes_compressed
+            .load(Ordering::Relaxed);
+        if compressed {
+            assert!(uncompressed > 2 * message.len() as u64);
+            assert!(on_wire * 10 < uncompressed);
+            assert!(
+                exit.metrics()
+                    .tunnel_bytes_compressed
+                    .load(Ordering::Relaxed)
+                    > 0
+            );
+        } else {
+            assert_eq!((uncompressed, on_wire), (0, 0));
+        }
+
+        edge.shutdown().await.unwrap();
+        exit.shutdown().await.unwrap();
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use voidproxy_core::udp_batch::{BatchReceiver, BatchSender};
+
+async fn bind() -> Arc<UdpSocket> {
+    Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap())
+}
+
+#[tokio::test]
+async fn test_batch_receiver_returns_every_datagram() {
+    let socket = bind().await;
+    let addr = socket.local_addr().unwrap();
+    let mut receiver = BatchReceiver::new(socket);
+    let client = bind().await;
+    for index in 0..10u8 {
+        client.send_to(&[index; 100], addr).await.unwrap();
+    }
+
+    let mut received = Vec::new();
+    while received.len() < 10 {
+        let datagrams = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        received.extend(datagrams);
+    }
+    assert_eq!(received.len(), 10);
+    for (index, (data, peer)) in received.iter().enumerate() {
+        assert_eq!(*peer, client.local_addr().unwrap());
+        assert_eq!(data, &vec![index as u8; 100]);
+    }
+}
+
+#[tokio::test]
+async fn test_batch_sender_delivers_datagrams_separately() {
+    let sender = BatchSender::new(bind().await);
+    let peer = bind().await;
+    let peer_addr = peer.local_addr().unwrap();
+    for index in 0..20u8 {
+        let len = if index == 19 { 50 } else { 1200 };
+        sender.send_to(vec![index; len], peer_addr).await.unwrap();
+    }
+
+    let mut seen = HashSet::new();
+    let mut buffer = vec![0; 65535];
+    while seen.len() < 20 {
+        let (len, _) = tokio::time::timeout(Duration::from_secs(5), peer.recv_from(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        let index = buffer[0];
+        assert!(buffer[..len].iter().all(|byte| *byte == index));
+        assert_eq!(len, if index == 19 { 50 } else { 1200 });
+        seen.insert(index);
+    }
+}
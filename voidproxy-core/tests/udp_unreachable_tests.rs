@@ -0,0 +1,66 @@
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use voidproxy_core::builder::ProxyBuilder;
+use voidproxy_core::config::Protocol;
+
+async fn closed_udp_port() -> std::net::SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.local_addr().unwrap()
+}
+
+#[tokio::test]
+async fn test_unreachable_destination_is_counted() {
+    let proxy = ProxyBuilder::new("127.0.0.1:18833".parse().unwrap(), closed_udp_port().await)
+        .protocol(Protocol::Udp)
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    client.connect("127.0.0.1:18833").await.unwrap();
+    client.send(b"ping").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let metrics = proxy.metrics();
+    assert_eq!(metrics.udp_unreachable.load(Ordering::Relaxed), 1);
+    let mut buffer = [0u8; 16];
+    assert!(
+        tokio::time::timeout(Duration::from_millis(200), client.recv(&mut buffer))
+            .await
+            .is_err()
+    );
+
+    client.send(b"ping").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(metrics.udp_unreachable.load(Ordering::Relaxed), 2);
+    proxy.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_unreachable_destination_is_propagated_to_the_client() {
+    if socket2::Socket::new(
+        socket2::Domain::IPV4,
+        socket2::Type::RAW,
+        Some(socket2::Protocol::ICMPV4),
+    )
+    .is_err()
+    {
+        eprintln!("Skipping: raw sockets are not permitted");
+        return;
+    }
+    let proxy = ProxyBuilder::new("127.0.0.1:18834".parse().unwrap(), closed_udp_port().await)
+        .protocol(Protocol::Udp)
+        .propagate_unreachable(true)
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    client.connect("127.0.0.1:18834").await.unwrap();
+    client.send(b"ping").await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let error = client.take_error().unwrap().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::ConnectionRefused);
+    assert_eq!(proxy.metrics().udp_unreachable.load(Ordering::Relaxed), 1);
+    proxy.shutdown().await.unwrap();
+}
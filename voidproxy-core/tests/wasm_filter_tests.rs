@@ -0,0 +1,92 @@
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use voidproxy_core::builder::ProxyBuilder;
+
+const FILTER: &str = r#"
+(module
+  (import "voidproxy" "set_metadata" (func $set_metadata (param i32 i32 i32 i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "verdict")
+  (data (i32.const 16) "upper")
+  (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+  (func (export "inspect_limit") (result i32) (i32.const 4))
+  (func (export "on_data") (param $direction i32) (param $ptr i32) (param $len i32) (param $cap i32) (result i32)
+    (local $i i32)
+    (if (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 33))
+      (then (return (i32.const -1))))
+    (if (i32.eqz (local.get $direction))
+      (then
+        (loop $next
+          (if (i32.lt_u (local.get $i) (local.get $len))
+            (then
+              (i32.store8
+                (i32.add (local.get $ptr) (local.get $i))
+                (i32.and (i32.load8_u (i32.add (local.get $ptr) (local.get $i))) (i32.const 223)))
+              (local.set $i (i32.add (local.get $i) (i32.const 1)))
+              (br $next))))
+        (call $set_metadata (i32.const 0) (i32.const 7) (i32.const 16) (i32.const 5))))
+    (local.get $len)))
+"#;
+
+async fn echo_upstream() -> std::net::SocketAddr {
+    let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = upstream.accept().await {
+            tokio::spawn(async move {
+                let mut buffer = [0u8; 1024];
+                while let Ok(n) = stream.read(&mut buffer).await {
+                    if n == 0 || stream.write_all(&buffer[..n]).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    upstream_addr
+}
+
+#[tokio::test]
+async fn test_wasm_filter_rewrites_and_denies() {
+    let temp_dir = TempDir::new().unwrap();
+    let filter_path = temp_dir.path().join("upper.wat");
+    std::fs::write(&filter_path, FILTER).unwrap();
+    let upstream_addr = echo_upstream().await;
+    let proxy = ProxyBuilder::new("127.0.0.1:18760".parse().unwrap(), upstream_addr)
+        .wasm_filter(filter_path.to_string_lossy())
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = TcpStream::connect("127.0.0.1:18760").await.unwrap();
+    client.write_all(b"hello").await.unwrap();
+    let mut reply = [0u8; 5];
+    client.read_exact(&mut reply).await.unwrap();
+    assert_eq!(&reply, b"HELLo");
+    drop(client);
+
+    let mut client = TcpStream::connect("127.0.0.1:18760").await.unwrap();
+    client.write_all(b"!deny").await.unwrap();
+    let mut reply = Vec::new();
+    tokio::time::timeout(Duration::from_secs(2), client.read_to_end(&mut reply))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(reply.is_empty());
+    proxy.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_missing_wasm_filter_is_rejected() {
+    assert!(
+        ProxyBuilder::new(
+            "127.0.0.1:18761".parse().unwrap(),
+            "127.0.0.1:80".parse().unwrap()
+        )
+        .wasm_filter("/nonexistent/filter.wasm")
+        .spawn()
+        .is_err()
+    );
+}